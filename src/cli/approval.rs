@@ -0,0 +1,104 @@
+//! Interactive approval prompt for tool calls
+//!
+//! Implements the planner's `ApprovalHandler` with an inquire menu that shows
+//! the command or diff a tool call is about to apply. Progress lines printed
+//! through `print_or_hold` while the menu is open are shown once it closes.
+
+use crate::planer::approval::{ApprovalHandler, ApprovalRequest, ApprovalResponse};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+use inquire::Select;
+use std::sync::Mutex;
+
+const ALLOW_ONCE: &str = "Allow once";
+const ALLOW_SESSION: &str = "Allow for this session";
+const DENY: &str = "Deny";
+
+/// Maximum preview lines shown before the prompt
+const MAX_PREVIEW_LINES: usize = 60;
+
+/// Lines held back while a prompt owns the terminal (`None` when no prompt is open)
+static HELD_OUTPUT: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Print a line, or hold it until the open approval prompt closes
+pub fn print_or_hold(line: String) {
+    let mut held = HELD_OUTPUT.lock().unwrap_or_else(|e| e.into_inner());
+    match held.as_mut() {
+        Some(lines) => lines.push(line),
+        None => println!("\r\x1B[K{}", line),
+    }
+}
+
+/// Approval handler that asks the user in the terminal
+#[derive(Debug, Default)]
+pub struct CliApprovalHandler;
+
+impl CliApprovalHandler {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn print_request(request: &ApprovalRequest) {
+        println!(
+            "\r\x1B[K🔐 Approval required: {} {}",
+            request.tool, request.target
+        );
+        if !request.operation.trim().is_empty() {
+            println!("\r\x1B[K   {}", request.operation.trim());
+        }
+
+        let lines: Vec<&str> = request.preview.lines().collect();
+        for line in lines.iter().take(MAX_PREVIEW_LINES) {
+            let color = if line.starts_with("+++") || line.starts_with("---") {
+                "\x1B[1m"
+            } else if line.starts_with('+') {
+                "\x1B[32m"
+            } else if line.starts_with('-') {
+                "\x1B[31m"
+            } else if line.starts_with("@@") {
+                "\x1B[36m"
+            } else {
+                ""
+            };
+            println!("\r\x1B[K   {}{}\x1B[0m", color, line);
+        }
+        if lines.len() > MAX_PREVIEW_LINES {
+            println!(
+                "\r\x1B[K   ... {} more lines",
+                lines.len() - MAX_PREVIEW_LINES
+            );
+        }
+    }
+}
+
+impl ApprovalHandler for CliApprovalHandler {
+    fn request_approval(&self, request: &ApprovalRequest) -> ApprovalResponse {
+        *HELD_OUTPUT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Vec::new());
+
+        // inquire needs cooked mode; restore the caller's terminal mode afterwards
+        let was_raw = is_raw_mode_enabled().unwrap_or(false);
+        if was_raw {
+            let _ = disable_raw_mode();
+        }
+
+        Self::print_request(request);
+        let selection = Select::new("Run this tool call?", vec![ALLOW_ONCE, ALLOW_SESSION, DENY])
+            .with_help_message("Enter to select, Esc to deny")
+            .prompt();
+
+        if was_raw {
+            let _ = enable_raw_mode();
+        }
+
+        let mut held = HELD_OUTPUT.lock().unwrap_or_else(|e| e.into_inner());
+        for line in held.take().unwrap_or_default() {
+            println!("\r\x1B[K{}", line);
+        }
+        drop(held);
+
+        match selection {
+            Ok(ALLOW_ONCE) => ApprovalResponse::AllowOnce,
+            Ok(ALLOW_SESSION) => ApprovalResponse::AllowForSession,
+            _ => ApprovalResponse::Deny,
+        }
+    }
+}
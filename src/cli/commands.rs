@@ -20,6 +20,12 @@ pub enum CliCommand {
     Theme,
     KeyBinds,
     Workdir,
+    Undo,
+    Rollback,
+    Changes,
+    Plans,
+    Plan,
+    Resume,
 }
 
 impl CliCommand {
@@ -38,6 +44,12 @@ impl CliCommand {
             "theme" | "themes" => Some(Self::Theme),
             "keybinds" | "keys" | "bindings" => Some(Self::KeyBinds),
             "workdir" | "wd" | "workspace" => Some(Self::Workdir),
+            "undo" => Some(Self::Undo),
+            "rollback" => Some(Self::Rollback),
+            "changes" | "changed" => Some(Self::Changes),
+            "plans" => Some(Self::Plans),
+            "plan" => Some(Self::Plan),
+            "resume" => Some(Self::Resume),
             _ => None,
         }
     }
@@ -57,6 +69,12 @@ impl CliCommand {
             Self::Theme => "Change color theme",
             Self::KeyBinds => "View and edit key bindings",
             Self::Workdir => "Set or view the current working directory for operations",
+            Self::Undo => "Undo the file changes made by the last executed task",
+            Self::Rollback => "Roll back all file changes made by a plan",
+            Self::Changes => "Show files changed per plan",
+            Self::Plans => "List saved plans and their progress",
+            Self::Plan => "Export the current plan as Markdown, Mermaid, DOT or JSON",
+            Self::Resume => "Continue an interrupted or failed plan",
         }
     }
     
@@ -75,6 +93,12 @@ impl CliCommand {
             Self::Theme => "/theme [theme_name]",
             Self::KeyBinds => "/keybinds [show|edit]",
             Self::Workdir => "/workdir [path|show]",
+            Self::Undo => "/undo [plan_id]",
+            Self::Rollback => "/rollback <plan_id>",
+            Self::Changes => "/changes [plan_id]",
+            Self::Plans => "/plans",
+            Self::Plan => "/plan export <format> [file]",
+            Self::Resume => "/resume <plan_id>",
         }
     }
     
@@ -89,6 +113,12 @@ impl CliCommand {
             Self::Quit => CommandCategory::Control,
             Self::Theme => CommandCategory::Display,
             Self::Workdir => CommandCategory::Navigation,
+            Self::Undo
+            | Self::Rollback
+            | Self::Changes
+            | Self::Plans
+            | Self::Plan
+            | Self::Resume => CommandCategory::Planning,
         }
     }
     
@@ -111,6 +141,12 @@ impl CliCommand {
             Self::Theme,
             Self::KeyBinds,
             Self::Workdir,
+            Self::Undo,
+            Self::Rollback,
+            Self::Changes,
+            Self::Plans,
+            Self::Plan,
+            Self::Resume,
             Self::Quit,
         ]
     }
@@ -134,6 +170,7 @@ impl CliCommand {
             CommandCategory::Display,
             CommandCategory::Settings,
             CommandCategory::Session,
+            CommandCategory::Planning,
             CommandCategory::Control,
         ];
         
@@ -206,6 +243,33 @@ impl CliCommand {
                     "  • Custom key bindings".to_string(),
                 ]);
             }
+            Self::Undo | Self::Rollback | Self::Changes => {
+                help.extend(vec![
+                    "".to_string(),
+                    "Files are checkpointed before a plan first modifies them:".to_string(),
+                    "  /changes          - List changed files for every plan".to_string(),
+                    "  /undo             - Revert the last task of the latest plan".to_string(),
+                    "  /rollback <plan>  - Restore every file a plan touched".to_string(),
+                ]);
+            }
+            Self::Plans | Self::Resume => {
+                help.extend(vec![
+                    "".to_string(),
+                    "Plan state is saved after every task:".to_string(),
+                    "  /plans            - List saved plans and their progress".to_string(),
+                    "  /resume <plan>    - Re-run unfinished tasks, keeping completed ones".to_string(),
+                ]);
+            }
+            Self::Plan => {
+                help.extend(vec![
+                    "".to_string(),
+                    "Export the current plan, or the last saved one:".to_string(),
+                    "  /plan export markdown          - Print a Markdown summary".to_string(),
+                    "  /plan export mermaid plan.mmd  - Write a Mermaid flowchart".to_string(),
+                    "  /plan export dot plan.dot      - Write a Graphviz graph".to_string(),
+                    "  /plan export json plan.json    - Write the full plan".to_string(),
+                ]);
+            }
             _ => {}
         }
         
@@ -228,6 +292,12 @@ impl fmt::Display for CliCommand {
             Self::Theme => "Theme",
             Self::KeyBinds => "KeyBinds",
             Self::Workdir => "Workdir",
+            Self::Undo => "Undo",
+            Self::Rollback => "Rollback",
+            Self::Changes => "Changes",
+            Self::Plans => "Plans",
+            Self::Plan => "Plan",
+            Self::Resume => "Resume",
         };
         write!(f, "{}", name)
     }
@@ -241,6 +311,7 @@ pub enum CommandCategory {
     Display,
     Settings,
     Session,
+    Planning,
     Control,
 }
 
@@ -252,6 +323,7 @@ impl fmt::Display for CommandCategory {
             Self::Display => "Display & UI",
             Self::Settings => "Settings & Configuration",
             Self::Session => "Session Management",
+            Self::Planning => "Planning & Execution",
             Self::Control => "Application Control",
         };
         write!(f, "{}", name)
@@ -342,6 +414,20 @@ impl CommandParser {
                     return Err("Help command accepts at most one argument".to_string());
                 }
             }
+            CliCommand::Rollback if args.len() != 1 => {
+                return Err("Rollback command requires exactly one plan ID".to_string());
+            }
+            CliCommand::Resume if args.len() != 1 => {
+                return Err("Resume command requires exactly one plan ID".to_string());
+            }
+            CliCommand::Plan => {
+                let is_export = args.first().map(String::as_str) == Some("export");
+                if !is_export || !(2..=3).contains(&args.len()) {
+                    return Err(
+                        "Usage: /plan export <markdown|mermaid|dot|json> [file]".to_string(),
+                    );
+                }
+            }
             _ => {} // Most commands are flexible with arguments
         }
         
@@ -378,6 +464,7 @@ mod tests {
         assert_eq!(CliCommand::Help.category(), CommandCategory::Help);
         assert_eq!(CliCommand::Theme.category(), CommandCategory::Display);
         assert_eq!(CliCommand::Quit.category(), CommandCategory::Control);
+        assert_eq!(CliCommand::Rollback.category(), CommandCategory::Planning);
     }
     
     #[test]
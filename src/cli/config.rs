@@ -3,9 +3,14 @@
 //! This module handles configuration settings, theme management,
 //! color schemes, and OpenRouter model configuration for the CLI prompter.
 
+use crate::mcp::McpServerConfig;
+use crate::planer::approval::{ApprovalPolicy, PolicyDecision, PolicyRule};
+use crate::planer::scheduler::DEFAULT_MAX_WORKERS;
+use crate::planer::task_planner::DEFAULT_MAX_REPLANS;
 use crossterm::style::Color;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 
 /// OpenRouter model configuration with tiered model selection
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,8 +32,82 @@ impl Default for OpenRouterConfig {
     }
 }
 
+/// Git integration settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitConfig {
+    /// Commit each successfully completed plan on its own branch
+    pub auto_commit: bool,
+    /// Prefix for auto-commit branches (`<prefix><plan_id>`)
+    pub branch_prefix: String,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            auto_commit: false,
+            branch_prefix: "kai/".to_string(),
+        }
+    }
+}
+
+impl GitConfig {
+    /// Branch prefix to use when auto-commit is enabled
+    pub fn auto_commit_prefix(&self) -> Option<String> {
+        self.auto_commit.then(|| self.branch_prefix.clone())
+    }
+}
+
+/// Approval policy settings for tool calls made by plans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    /// Ask before running tools the policy does not allow outright
+    pub enabled: bool,
+    /// Rules checked in order before the built-in read-only allow list
+    pub rules: Vec<PolicyRule>,
+    /// Decision for tool calls no rule matches
+    pub default_decision: PolicyDecision,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: vec![
+                PolicyRule::new("bash", "cargo check*", PolicyDecision::Allow),
+                PolicyRule::new("bash", "cargo build*", PolicyDecision::Allow),
+                PolicyRule::new("bash", "cargo test*", PolicyDecision::Allow),
+                PolicyRule::new("build_diagnostics", "*", PolicyDecision::Allow),
+                PolicyRule::new("run_tests", "*", PolicyDecision::Allow),
+                PolicyRule::new("bash", "rm *", PolicyDecision::Ask),
+            ],
+            default_decision: PolicyDecision::Ask,
+        }
+    }
+}
+
+impl ApprovalConfig {
+    /// Build the policy, or `None` when approvals are disabled
+    pub fn policy(&self) -> Option<ApprovalPolicy> {
+        self.enabled.then(|| {
+            let mut policy = ApprovalPolicy::default().with_rules(self.rules.clone());
+            policy.default_decision = self.default_decision;
+            policy
+        })
+    }
+}
+
+/// External MCP tool servers launched at startup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpConfig {
+    pub servers: Vec<McpServerConfig>,
+}
+
+/// File the configuration is loaded from, relative to the directory KAI starts in
+pub const CONFIG_FILE: &str = "kai.json";
+
 /// Configuration for the CLI prompter
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CliConfig {
     pub frame_color: String,
     pub text_color: String,
@@ -39,6 +118,18 @@ pub struct CliConfig {
     pub custom_keybindings: HashMap<String, String>,
     pub theme_name: String,
     pub openrouter: OpenRouterConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    #[serde(default)]
+    pub approval: ApprovalConfig,
+    #[serde(default)]
+    pub mcp: McpConfig,
+    /// How many independent plan tasks may run at the same time
+    pub max_parallel_tasks: usize,
+    /// How often a plan may be revised after a task fails (0 disables replanning)
+    pub max_replans: usize,
+    /// Show new plans for review and editing before any task runs
+    pub review_plans: bool,
 }
 
 impl Default for CliConfig {
@@ -53,6 +144,12 @@ impl Default for CliConfig {
             custom_keybindings: HashMap::new(),
             theme_name: "default".to_string(),
             openrouter: OpenRouterConfig::default(),
+            git: GitConfig::default(),
+            approval: ApprovalConfig::default(),
+            mcp: McpConfig::default(),
+            max_parallel_tasks: DEFAULT_MAX_WORKERS,
+            max_replans: DEFAULT_MAX_REPLANS,
+            review_plans: true,
         }
     }
 }
@@ -63,6 +160,26 @@ impl CliConfig {
         Self::default()
     }
 
+    /// Load a configuration file; settings it leaves out keep their defaults
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid configuration in '{}': {}", path.display(), e))
+    }
+
+    /// Load `path` if it exists, falling back to the defaults (with a warning
+    /// when the file is invalid)
+    pub fn load_or_default(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+        Self::load(path).unwrap_or_else(|e| {
+            eprintln!("WARNING: {} - using default configuration", e);
+            Self::default()
+        })
+    }
+
     /// Apply a theme by name
     pub fn apply_theme(&mut self, theme_name: &str) {
         match theme_name {
@@ -190,7 +307,7 @@ impl CliConfig {
 
     /// Get configuration summary for display
     pub fn get_summary(&self) -> Vec<String> {
-        vec![
+        let mut summary = vec![
             "⚙️  Configuration".to_string(),
             "".to_string(),
             "🎨 Display Settings".to_string(),
@@ -203,15 +320,47 @@ impl CliConfig {
             format!("  File Browser Prefix: {}", self.file_browser_prefix),
             format!("  Auto Save History: {}", self.auto_save_history),
             format!("  Max History Size: {}", self.max_history_size),
+            format!("  Parallel Plan Tasks: {}", self.max_parallel_tasks),
+            format!("  Max Replans: {}", self.max_replans),
+            format!("  Review Plans: {}", self.review_plans),
             "".to_string(),
             "🤖 OpenRouter Models".to_string(),
             format!("  Tier 1 (Simple): {}", self.openrouter.simple_model),
             format!("  Tier 2 (MidRange): {}", self.openrouter.midrange_model),
             format!("  Tier 3 (Advanced): {}", self.openrouter.advanced_model),
             format!("  Tier 4 (Critical): {}", self.openrouter.critical_model),
+            "".to_string(),
+            "🌿 Git".to_string(),
+            format!("  Auto Commit Plans: {}", self.git.auto_commit),
+            format!("  Branch Prefix: {}", self.git.branch_prefix),
+            "".to_string(),
+            "🔐 Tool Approval".to_string(),
+            format!("  Enabled: {}", self.approval.enabled),
+            format!("  Default Decision: {:?}", self.approval.default_decision),
+        ];
+        summary.extend(
+            self.approval
+                .rules
+                .iter()
+                .map(|rule| format!("  Rule: {}", rule)),
+        );
+        summary.extend(vec!["".to_string(), "🔌 MCP Servers".to_string()]);
+        if self.mcp.servers.is_empty() {
+            summary.push("  None configured".to_string());
+        }
+        summary.extend(self.mcp.servers.iter().map(|server| {
+            format!(
+                "  {}: {} {}",
+                server.name,
+                server.command,
+                server.args.join(" ")
+            )
+        }));
+        summary.extend(vec![
             "".to_string(),
             "Press any key to continue...".to_string(),
-        ]
+        ]);
+        summary
     }
 }
 
@@ -289,4 +438,26 @@ mod tests {
         assert_eq!(tiers[3].0, "Tier 4 - Critical");
         assert_eq!(tiers[3].1, "openai/gpt-4o-mini");
     }
+
+    #[test]
+    fn test_load_partial_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            r#"{"theme_name": "dark", "mcp": {"servers": [{"name": "docs", "command": "docs-mcp", "args": ["--stdio"]}]}}"#,
+        )
+        .unwrap();
+
+        let config = CliConfig::load(&path).unwrap();
+        assert_eq!(config.theme_name, "dark");
+        assert_eq!(config.command_prefix, '/');
+        assert_eq!(config.mcp.servers[0].name, "docs");
+        assert_eq!(config.mcp.servers[0].args, vec!["--stdio"]);
+        assert!(config.approval.enabled);
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(CliConfig::load(&path).is_err());
+        assert_eq!(CliConfig::load_or_default(&path).theme_name, "default");
+    }
 }
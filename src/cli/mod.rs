@@ -6,25 +6,30 @@
 //!
 //! ## Module Structure
 //!
+//! - `approval` - Interactive approval prompt for tool calls
 //! - `config` - Configuration and theme management
 //! - `editor` - Multi-line text editor with cursor management
 //! - `history` - Command history with search capabilities
+//! - `review` - Interactive plan review before execution
 //! - `commands` - Command definitions and parsing
 //! - `file_browser` - Interactive file system navigation
 //! - `utils` - Common utilities and helper functions
 //! - `prompter` - Main CLI prompter orchestration
 
+pub mod approval;
 pub mod commands;
 pub mod config;
 pub mod editor;
 pub mod file_browser;
 pub mod history;
 pub mod prompter;
+pub mod review;
 pub mod utils;
 
 // Re-export main types for convenience
+pub use approval::CliApprovalHandler;
 pub use commands::{CliCommand, CommandParser, CommandResult};
-pub use config::CliConfig;
+pub use config::{ApprovalConfig, CliConfig, GitConfig, McpConfig};
 pub use editor::{CursorDirection, TextEditor};
 pub use file_browser::{FileBrowser, FileEntry, SelectionResult};
 pub use history::CommandHistory;
@@ -4,6 +4,7 @@
 //! with simple text-based input/output, similar to regular terminal applications.
 
 use std::io::{self, Write};
+use std::sync::Arc;
 
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
@@ -12,17 +13,25 @@ use crossterm::{
 use inquire::{InquireError, Select};
 
 use super::{
+    approval::{self, CliApprovalHandler},
     commands::{CliCommand, CommandParser, CommandResult},
-    config::CliConfig,
+    config::{CliConfig, CONFIG_FILE},
     file_browser::{FileBrowser, SelectionResult},
     history::CommandHistory,
+    review,
 };
 use crate::context::context_data_store::ContextDataStore;
 use crate::context::Context;
+use crate::mcp::McpManager;
 use crate::planer::{
-    plan::Plan,
-    queue::{QueueRequest, QueueResponse},
-    task::{Task, TaskExecution, TaskStatus},
+    approval::ApprovalGate,
+    checkpoint::{CheckpointStore, RestoreReport},
+    export::{export_plan, PlanExportFormat},
+    journal::PlanJournal,
+    plan::{Plan, TaskResult},
+    queue::QueueResponse,
+    scheduler::ProgressEvent,
+    task::{Task, TaskExecution},
     Planner,
 };
 use indicatif::{ProgressBar, ProgressStyle};
@@ -54,6 +63,133 @@ impl MessageType {
     }
 }
 
+/// Live per-task progress lines while a plan runs; sub-plan events are
+/// indented and name the task path, e.g. `Task 3 › 1` for sub-task 1 of task 3
+fn print_task_progress(event: &ProgressEvent) {
+    let mut path = Vec::new();
+    let mut event = event;
+    while let ProgressEvent::SubPlan {
+        task_id,
+        event: inner,
+    } = event
+    {
+        path.push(task_id.to_string());
+        event = inner;
+    }
+    let (message_type, line) = task_progress_line(event);
+    // Held back while an approval prompt is open
+    if path.is_empty() {
+        approval::print_or_hold(format!("{} {}", message_type.prefix(), line));
+    } else {
+        approval::print_or_hold(format!(
+            "{}{} ↳ Task {} › {}",
+            "   ".repeat(path.len() - 1),
+            message_type.prefix(),
+            path.join(" › "),
+            line
+        ));
+    }
+}
+
+fn task_progress_line(event: &ProgressEvent) -> (MessageType, String) {
+    match event {
+        ProgressEvent::Started {
+            task_id,
+            title,
+            running,
+        } => (
+            MessageType::Info,
+            format!("▶ Task {}: {} ({} running)", task_id, title, running),
+        ),
+        ProgressEvent::Finished {
+            task_id,
+            title,
+            success,
+            elapsed,
+            running,
+        } => (
+            if *success {
+                MessageType::Success
+            } else {
+                MessageType::Error
+            },
+            format!(
+                "{} Task {}: {} ({:.1}s, {} still running)",
+                if *success { "✅" } else { "❌" },
+                task_id,
+                title,
+                elapsed.as_secs_f32(),
+                running
+            ),
+        ),
+        ProgressEvent::Decomposed {
+            task_id,
+            title,
+            sub_tasks,
+        } => (
+            MessageType::Planning,
+            format!("🧬 Task {}: {} split into {} sub-tasks", task_id, title, sub_tasks),
+        ),
+        ProgressEvent::Retrying {
+            task_id,
+            title,
+            attempt,
+            max_retries,
+            delay,
+        } => (
+            MessageType::Warning,
+            format!(
+                "🔄 Task {}: {} failed, retry {}/{} in {:.1}s",
+                task_id,
+                title,
+                attempt,
+                max_retries,
+                delay.as_secs_f32()
+            ),
+        ),
+        ProgressEvent::Replanned {
+            task_id,
+            title,
+            action,
+            reason,
+            new_tasks,
+            dropped,
+        } => {
+            let mut line = format!("🔁 Task {}: {} revised ({})", task_id, title, action);
+            if !new_tasks.is_empty() {
+                line.push_str(&format!(", added tasks {:?}", new_tasks));
+            }
+            if !dropped.is_empty() {
+                line.push_str(&format!(", dropped tasks {:?}", dropped));
+            }
+            if !reason.is_empty() {
+                line.push_str(&format!(": {}", reason));
+            }
+            (MessageType::Planning, line)
+        }
+        ProgressEvent::ReplanFailed {
+            task_id,
+            title,
+            error,
+        } => (
+            MessageType::Error,
+            format!("🔁 Could not revise plan after task {} ({}): {}", task_id, title, error),
+        ),
+        ProgressEvent::SubPlanExpanded {
+            task_id,
+            title,
+            tasks,
+        } => (
+            MessageType::Planning,
+            format!(
+                "🗺️ Task {}: {} planned as a sub-plan of {} tasks",
+                task_id, title, tasks
+            ),
+        ),
+        ProgressEvent::SubPlan { event, .. } => task_progress_line(event),
+    }
+}
+
 /// Simple terminal CLI prompter
 pub struct CliPrompter {
     config: CliConfig,
@@ -70,9 +206,9 @@ pub struct CliPrompter {
 impl CliPrompter {
     /// Create a new simple CLI prompter instance
     pub fn new() -> io::Result<Self> {
-        let config = CliConfig::default();
-        let history = CommandHistory::new(config.max_history_size);
         let current_dir = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("/"));
+        let config = CliConfig::load_or_default(&current_dir.join(CONFIG_FILE));
+        let history = CommandHistory::new(config.max_history_size);
 
         // Default workdir is "workdir" relative to current directory
         let workdir = current_dir.join("workdir");
@@ -111,7 +247,19 @@ impl CliPrompter {
     pub fn with_planner(planner: Planner) -> io::Result<Self> {
         let mut prompter = Self::new()?;
         // Configure planner with the prompter's working directory
-        let planner_with_workdir = planner.with_workdir(&prompter.workdir);
+        let planner_with_workdir = planner
+            .with_workdir(&prompter.workdir)
+            .with_auto_commit(prompter.config.git.auto_commit_prefix())
+            .with_max_workers(prompter.config.max_parallel_tasks)
+            .with_max_replans(prompter.config.max_replans)
+            .with_progress(Arc::new(print_task_progress));
+        let planner_with_workdir = match prompter.config.approval.policy() {
+            Some(policy) => planner_with_workdir.with_approval(Arc::new(ApprovalGate::new(
+                policy,
+                Arc::new(CliApprovalHandler::new()),
+            ))),
+            None => planner_with_workdir,
+        };
         prompter.planner = Some(planner_with_workdir);
         Ok(prompter)
     }
@@ -145,6 +293,26 @@ impl CliPrompter {
         Ok(())
     }
 
+    /// Launch the MCP servers from the configuration and hand their tools to the planner
+    pub async fn start_mcp_servers(&mut self) {
+        if self.config.mcp.servers.is_empty() {
+            return;
+        }
+        let (manager, errors) = McpManager::start(&self.config.mcp.servers).await;
+        for error in errors {
+            self.print_warning(&error);
+        }
+        for server in manager.servers() {
+            self.print_info(&format!(
+                "MCP server '{}' connected - {} tools, {} resources",
+                server.name(),
+                server.tools.len(),
+                server.resources.len()
+            ));
+        }
+        self.planner = self.planner.take().map(|planner| planner.with_mcp(manager));
+    }
+
     /// Set planner after creation
     pub fn set_planner(&mut self, planner: Planner) {
         self.planner = Some(planner);
@@ -257,6 +425,68 @@ impl CliPrompter {
         self.print_message(MessageType::Planning, content);
     }
 
+    /// Print the output of a finished plan task, or the details of its failure
+    fn print_task_result(&self, task: &Task, response: &TaskResult) {
+        if response.success {
+            if response.tool_result.trim().is_empty() {
+                return;
+            }
+            self.print_system(&format!("📋 Task Result: {}", task.title));
+            // Parse and format the JSON result if possible
+            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&response.tool_result) {
+                match &parsed {
+                    serde_json::Value::Object(obj) if obj.contains_key("content") => {
+                        if let Some(content) = obj["content"].as_str() {
+                            for line in content.lines() {
+                                self.print_info(&format!("  {}", line));
+                            }
+                        }
+                    }
+                    _ => {
+                        let formatted = serde_json::to_string_pretty(&parsed)
+                            .unwrap_or_else(|_| response.tool_result.clone());
+                        for line in formatted.lines() {
+                            self.print_info(&format!("  {}", line));
+                        }
+                    }
+                }
+            } else {
+                // Raw content
+                for line in response.tool_result.lines() {
+                    self.print_info(&format!("  {}", line));
+                }
+            }
+        } else {
+            // Display detailed failure information
+            self.print_error(&format!("❌ Task failed: {}", task.title));
+            if let TaskExecution::ToolCall(tool_call) = &task.execution {
+                self.print_error(&format!("🔧 Tool: {}", tool_call.tool));
+                self.print_error(&format!("🎯 Target: {}", tool_call.target));
+            }
+            self.print_system("📋 Error Details:");
+
+            // Parse and format the JSON error if possible
+            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&response.tool_result) {
+                if let serde_json::Value::Object(obj) = &parsed {
+                    if let Some(error_msg) = obj.get("error").and_then(|v| v.as_str()) {
+                        self.print_error(&format!("  {}", error_msg));
+                    } else {
+                        let formatted = serde_json::to_string_pretty(&parsed)
+                            .unwrap_or_else(|_| response.tool_result.clone());
+                        for line in formatted.lines() {
+                            self.print_error(&format!("  {}", line));
+                        }
+                    }
+                }
+            } else {
+                // Raw error content
+                for line in response.tool_result.lines() {
+                    self.print_error(&format!("  {}", line));
+                }
+            }
+        }
+    }
+
     /// Show welcome screen
     async fn show_welcome(&self) -> io::Result<()> {
         println!("\r");
@@ -551,148 +781,16 @@ impl CliPrompter {
 
                     // --- Main Execution Loop ---
                     let plan_id = planner.task_planner.active_plans.last().unwrap().id.clone();
-                    while planner.task_planner.execution_queue.has_pending_requests() {
-                        if let Some(request) = planner.task_planner.execution_queue.pop_request() {
-                            if let QueueRequest::TaskExecution { task, .. } = request {
-                                self.print_info(&format!("Executing task: {}...", task.title));
-                                let current_plan = planner
-                                    .task_planner
-                                    .active_plans
-                                    .iter()
-                                    .find(|p| p.id == plan_id)
-                                    .unwrap();
-                                let response = planner
-                                    .execute_task_with_context(&task, &self.context, &current_plan)
-                                    .await
-                                    .unwrap();
-
-                                if response
-                                    .llm_processed_result
-                                    .contains("Decomposition needed")
-                                {
-                                    // Task needs to be decomposed
-                                    let sub_tasks =
-                                        planner.task_planner.decompose_task(&task).await.unwrap();
-                                    planner
-                                        .task_planner
-                                        .replace_task_with_subtasks(&plan_id, task.id, sub_tasks)
-                                        .unwrap();
-                                } else if response.success {
-                                    // Task was executed successfully
-                                    let current_plan = planner
-                                        .task_planner
-                                        .active_plans
-                                        .iter_mut()
-                                        .find(|p| p.id == plan_id)
-                                        .unwrap();
-                                    if let Some(t) = current_plan.find_task_by_id(task.id) {
-                                        t.set_status(TaskStatus::Completed);
-                                    }
-
-                                    // Display the task result
-                                    self.print_success(&format!(
-                                        "✅ Task completed: {}",
-                                        task.title
-                                    ));
-                                    if !response.tool_result.trim().is_empty() {
-                                        self.print_system("📋 Task Result:");
-                                        // Parse and format the JSON result if possible
-                                        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(
-                                            &response.tool_result,
-                                        ) {
-                                            match &parsed {
-                                                serde_json::Value::Object(obj)
-                                                    if obj.contains_key("content") =>
-                                                {
-                                                    if let Some(content) = obj["content"].as_str() {
-                                                        for line in content.lines() {
-                                                            self.print_info(&format!("  {}", line));
-                                                        }
-                                                    }
-                                                }
-                                                _ => {
-                                                    let formatted =
-                                                        serde_json::to_string_pretty(&parsed)
-                                                            .unwrap_or_else(|_| {
-                                                                response.tool_result.clone()
-                                                            });
-                                                    for line in formatted.lines() {
-                                                        self.print_info(&format!("  {}", line));
-                                                    }
-                                                }
-                                            }
-                                        } else {
-                                            // Raw content
-                                            for line in response.tool_result.lines() {
-                                                self.print_info(&format!("  {}", line));
-                                            }
-                                        }
-                                    }
-                                } else {
-                                    // Task failed
-                                    let current_plan = planner
-                                        .task_planner
-                                        .active_plans
-                                        .iter_mut()
-                                        .find(|p| p.id == plan_id)
-                                        .unwrap();
-                                    if let Some(t) = current_plan.find_task_by_id(task.id) {
-                                        t.set_status(TaskStatus::Failed);
-                                    }
-
-                                    // Display detailed failure information
-                                    self.print_error(&format!("❌ Task failed: {}", task.title));
-                                    if let TaskExecution::ToolCall(tool_call) = &task.execution {
-                                        self.print_error(&format!("🔧 Tool: {}", tool_call.tool));
-                                        self.print_error(&format!(
-                                            "🎯 Target: {}",
-                                            tool_call.target
-                                        ));
-                                    }
-                                    self.print_system("📋 Error Details:");
-
-                                    // Parse and format the JSON error if possible
-                                    if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(
-                                        &response.tool_result,
-                                    ) {
-                                        if let serde_json::Value::Object(obj) = &parsed {
-                                            if let Some(error_msg) =
-                                                obj.get("error").and_then(|v| v.as_str())
-                                            {
-                                                self.print_error(&format!("  {}", error_msg));
-                                            } else {
-                                                let formatted =
-                                                    serde_json::to_string_pretty(&parsed)
-                                                        .unwrap_or_else(|_| {
-                                                            response.tool_result.clone()
-                                                        });
-                                                for line in formatted.lines() {
-                                                    self.print_error(&format!("  {}", line));
-                                                }
-                                            }
-                                        }
-                                    } else {
-                                        // Raw error content
-                                        for line in response.tool_result.lines() {
-                                            self.print_error(&format!("  {}", line));
-                                        }
-                                    }
-                                    break; // Stop execution on failure
-                                }
-
-                                // Refresh the queue with newly available tasks
-                                let current_plan = planner
-                                    .task_planner
-                                    .active_plans
-                                    .iter()
-                                    .find(|p| p.id == plan_id)
-                                    .unwrap();
-                                planner
-                                    .task_planner
-                                    .execution_queue
-                                    .push_plan_tasks(current_plan);
-                            }
-                        }
+                    if self.config.review_plans
+                        && !review::review_plan(&mut planner, &plan_id, &self.context).await
+                    {
+                        planner
+                            .task_planner
+                            .active_plans
+                            .retain(|plan| plan.id != plan_id);
+                        self.print_info("Plan cancelled; none of its tasks ran");
+                    } else {
+                        self.execute_plan(&mut planner, &plan_id).await;
                     }
                 }
                 Err(error) => {
@@ -717,6 +815,45 @@ impl CliPrompter {
         Ok(())
     }
 
+    /// Run an active plan to the end, print each task's result, then stop its
+    /// background processes and auto-commit its changes
+    async fn execute_plan(&mut self, planner: &mut Planner, plan_id: &str) {
+        match planner.run_plan_tasks(plan_id, &self.context).await {
+            Ok(results) => {
+                if let Some(plan) = planner
+                    .task_planner
+                    .active_plans
+                    .iter()
+                    .find(|p| p.id == plan_id)
+                {
+                    for result in &results {
+                        if let Some(task) =
+                            plan.get_all_tasks().into_iter().find(|t| t.id == result.task_id)
+                        {
+                            self.print_task_result(task, result);
+                        }
+                    }
+                }
+            }
+            Err(e) => self.print_error(&format!("Plan execution failed: {}", e)),
+        }
+
+        let stopped = planner.stop_plan_processes(plan_id).await;
+        if stopped > 0 {
+            self.print_info(&format!("Stopped {} background process(es)", stopped));
+        }
+
+        if let Some(commit) = planner
+            .task_planner
+            .active_plans
+            .iter()
+            .find(|p| p.id == plan_id)
+            .and_then(|plan| planner.commit_plan_changes(plan))
+        {
+            self.print_commit_result(&commit);
+        }
+    }
+
     /// Execute a CLI command
     async fn execute_command(&mut self, command: CliCommand, _args: Vec<String>) -> io::Result<()> {
         let result = match command {
@@ -781,6 +918,113 @@ impl CliPrompter {
                     }
                 }
             }
+            CliCommand::Undo => {
+                let store = CheckpointStore::for_workdir(&self.workdir);
+                match store.undo_last_task(_args.first().map(|s| s.as_str())) {
+                    Ok(report) => {
+                        self.print_restore_report("Undo", &report);
+                        CommandResult::Success("Last task undone".to_string())
+                    }
+                    Err(e) => CommandResult::Error(format!("Undo failed: {}", e)),
+                }
+            }
+            CliCommand::Rollback => match CommandParser::validate_args(&command, &_args) {
+                Ok(()) => {
+                    let store = CheckpointStore::for_workdir(&self.workdir);
+                    match store.rollback_plan(&_args[0]) {
+                        Ok(report) => {
+                            self.print_restore_report("Rollback", &report);
+                            CommandResult::Success("Plan rolled back".to_string())
+                        }
+                        Err(e) => CommandResult::Error(format!("Rollback failed: {}", e)),
+                    }
+                }
+                Err(e) => CommandResult::Error(e),
+            },
+            CliCommand::Changes => {
+                let store = CheckpointStore::for_workdir(&self.workdir);
+                let plan_ids = match _args.first() {
+                    Some(plan_id) => vec![plan_id.clone()],
+                    None => store.list_plans(),
+                };
+                self.print_system("=== Changed Files ===");
+                if plan_ids.is_empty() {
+                    self.print_info("No plan has modified any files yet");
+                }
+                for plan_id in plan_ids {
+                    match store.changed_files(&plan_id) {
+                        Ok(changes) => {
+                            self.print_info(&format!("📋 {} ({} files)", plan_id, changes.len()));
+                            for change in changes {
+                                self.print_info(&format!(
+                                    "  {:<9} {} (tasks {:?})",
+                                    format!("{:?}", change.change),
+                                    change.path.display(),
+                                    change.task_ids
+                                ));
+                            }
+                        }
+                        Err(e) => self.print_error(&format!("{}: {}", plan_id, e)),
+                    }
+                }
+                CommandResult::Success("Changes displayed".to_string())
+            }
+            CliCommand::Plans => {
+                let journal = PlanJournal::for_workdir(&self.workdir);
+                let plans = journal.list();
+                self.print_system("=== Saved Plans ===");
+                if plans.is_empty() {
+                    self.print_info("No plans have been run in this working directory yet");
+                }
+                for plan in plans {
+                    let state = if plan.is_complete() {
+                        "complete"
+                    } else if plan.failed > 0 {
+                        "failed"
+                    } else {
+                        "interrupted"
+                    };
+                    self.print_info(&format!(
+                        "📋 {}  {}  [{}/{} tasks, {}]  {}",
+                        plan.id,
+                        plan.title,
+                        plan.completed,
+                        plan.total,
+                        state,
+                        plan.updated_at.format("%Y-%m-%d %H:%M")
+                    ));
+                }
+                CommandResult::Success("Plans displayed".to_string())
+            }
+            CliCommand::Resume => match CommandParser::validate_args(&command, &_args) {
+                Ok(()) => match self.planner.take() {
+                    Some(mut planner) => {
+                        let resumed = planner.resume_plan(&_args[0]).map(|plan| {
+                            let (completed, total) = plan.get_progress();
+                            (plan.id.clone(), completed, total)
+                        });
+                        let result = match resumed {
+                            Ok((plan_id, completed, total)) => {
+                                self.print_system(&format!(
+                                    "Resuming {} ({}/{} tasks already complete)",
+                                    plan_id, completed, total
+                                ));
+                                self.execute_plan(&mut planner, &plan_id).await;
+                                CommandResult::Success("Plan resumed".to_string())
+                            }
+                            Err(e) => CommandResult::Error(format!("Resume failed: {}", e)),
+                        };
+                        self.planner = Some(planner);
+                        result
+                    }
+                    None => CommandResult::Error("No AI planner available".to_string()),
+                },
+                Err(e) => CommandResult::Error(e),
+            },
+            CliCommand::Plan => match CommandParser::validate_args(&command, &_args) {
+                Ok(()) => self.export_plan(&_args[1], _args.get(2).map(|s| s.as_str())),
+                Err(e) => CommandResult::Error(e),
+            },
             _ => {
                 self.print_warning(&format!(
                     "Command '{:?}' not available in simple mode",
@@ -801,6 +1045,102 @@ impl CliPrompter {
         Ok(())
     }
 
+    /// Export the current plan, or the last journaled one, to `file` or the terminal
+    fn export_plan(&self, format: &str, file: Option<&str>) -> CommandResult {
+        let format: PlanExportFormat = match format.parse() {
+            Ok(format) => format,
+            Err(e) => return CommandResult::Error(e),
+        };
+        let active = self
+            .planner
+            .as_ref()
+            .and_then(|planner| planner.task_planner.active_plans.last().cloned());
+        let plan = match active {
+            Some(plan) => plan,
+            None => {
+                let journal = PlanJournal::for_workdir(&self.workdir);
+                let Some(latest) = journal.list().into_iter().next() else {
+                    return CommandResult::Error("No plan to export yet".to_string());
+                };
+                match journal.load(&latest.id) {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        return CommandResult::Error(format!("Failed to load {}: {}", latest.id, e))
+                    }
+                }
+            }
+        };
+        let output = match export_plan(&plan, format) {
+            Ok(output) => output,
+            Err(e) => return CommandResult::Error(format!("Export failed: {}", e)),
+        };
+
+        match file {
+            Some(file) => {
+                let path = self.workdir.join(file);
+                match std::fs::write(&path, output) {
+                    Ok(()) => {
+                        self.print_success(&format!(
+                            "Exported plan {} as {} to {}",
+                            plan.id,
+                            format.name(),
+                            path.display()
+                        ));
+                        CommandResult::Success("Plan exported".to_string())
+                    }
+                    Err(e) => CommandResult::Error(format!(
+                        "Failed to write {}: {}",
+                        path.display(),
+                        e
+                    )),
+                }
+            }
+            None => {
+                // Without a prefix, so the output can be copied as-is
+                for line in output.lines() {
+                    println!("\r\x1B[K{}", line);
+                }
+                CommandResult::Success("Plan exported".to_string())
+            }
+        }
+    }
+
+    /// Print the outcome of a plan's auto-commit
+    fn print_commit_result(&self, result: &crate::tools::file_system::ToolResult) {
+        let data = result.data.clone().unwrap_or_default();
+        if !result.success {
+            self.print_error(&format!(
+                "Auto-commit failed: {}",
+                result.error.as_deref().unwrap_or("unknown error")
+            ));
+        } else if let Some(hash) = data["commit"].as_str() {
+            self.print_success(&format!(
+                "📦 Committed plan changes on branch {} ({})",
+                data["branch"].as_str().unwrap_or("?"),
+                &hash[..hash.len().min(10)]
+            ));
+        } else {
+            self.print_info("Auto-commit: no changes to commit");
+        }
+    }
+
+    /// Print the outcome of an undo or rollback
+    fn print_restore_report(&self, action: &str, report: &RestoreReport) {
+        self.print_system(&format!(
+            "=== {} of {} (tasks {:?}) ===",
+            action, report.plan_id, report.task_ids
+        ));
+        for path in &report.restored {
+            self.print_success(&format!("Restored {}", path.display()));
+        }
+        for path in &report.removed {
+            self.print_success(&format!("Removed {}", path.display()));
+        }
+        for error in &report.errors {
+            self.print_error(error);
+        }
+    }
+
     /// Show interactive command menu with inquire auto-complete
     async fn show_command_menu(&mut self) -> io::Result<()> {
         let commands = CliCommand::get_command_menu();
@@ -0,0 +1,311 @@
+//! Interactive plan review
+//!
+//! Shows a new plan before any of its tools run and lets the user approve it,
+//! drop, move or edit tasks, add a task of their own, or send feedback to the
+//! planner for a revised plan. Tasks are edited as JSON in `$VISUAL` or
+//! `$EDITOR`.
+
+use crate::context::Context;
+use crate::planer::{Plan, PlanEdit, Planner, RevisedTask, TaskStatus};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+use indicatif::{ProgressBar, ProgressStyle};
+use inquire::{Confirm, MultiSelect, Select, Text};
+use std::io;
+use std::process::Command;
+
+const APPROVE: &str = "✅ Approve and run";
+const EDIT: &str = "✏️  Edit a task";
+const DROP: &str = "🗑️  Drop tasks";
+const MOVE: &str = "↕️  Move a task";
+const ADD: &str = "➕ Add a task";
+const REVISE: &str = "💬 Ask the planner to revise";
+const CANCEL: &str = "❌ Cancel";
+
+const AT_END: &str = "At the end of the plan";
+
+/// Review the active plan `plan_id`; returns whether the user approved it
+pub async fn review_plan(planner: &mut Planner, plan_id: &str, context: &Context) -> bool {
+    // inquire and the editor need cooked mode; restore the caller's mode afterwards
+    let was_raw = is_raw_mode_enabled().unwrap_or(false);
+    if was_raw {
+        let _ = disable_raw_mode();
+    }
+
+    let approved = loop {
+        let choice = Select::new(
+            "Review the plan before it runs:",
+            vec![APPROVE, EDIT, DROP, MOVE, ADD, REVISE, CANCEL],
+        )
+        .with_help_message("Enter to select, Esc to cancel the plan")
+        .prompt();
+
+        let changed = match choice {
+            Ok(APPROVE) => break true,
+            Ok(EDIT) => edit_task(planner, plan_id),
+            Ok(DROP) => drop_tasks(planner, plan_id),
+            Ok(MOVE) => move_task(planner, plan_id),
+            Ok(ADD) => add_task(planner, plan_id),
+            Ok(REVISE) => revise_plan(planner, plan_id, context).await,
+            _ => break false,
+        };
+        match changed {
+            Ok(true) => {
+                if let Ok(plan) = find_plan(planner, plan_id) {
+                    print_plan(plan);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => println!("\r\x1B[K❌ {}", e),
+        }
+    };
+
+    if was_raw {
+        let _ = enable_raw_mode();
+    }
+    approved
+}
+
+fn find_plan<'a>(planner: &'a Planner, plan_id: &str) -> Result<&'a Plan, String> {
+    planner
+        .task_planner
+        .active_plans
+        .iter()
+        .find(|p| p.id == plan_id)
+        .ok_or_else(|| format!("Plan '{}' not found", plan_id))
+}
+
+fn apply(planner: &mut Planner, plan_id: &str, edit: PlanEdit) -> Result<usize, String> {
+    planner
+        .task_planner
+        .active_plans
+        .iter_mut()
+        .find(|p| p.id == plan_id)
+        .ok_or_else(|| format!("Plan '{}' not found", plan_id))?
+        .apply_edit(edit)
+}
+
+fn print_plan(plan: &Plan) {
+    for line in plan.to_string().lines() {
+        println!("\r\x1B[K{}", line);
+    }
+}
+
+/// One line per task for selection menus
+fn task_label(plan: &Plan, task_id: usize) -> String {
+    plan.get_all_tasks()
+        .into_iter()
+        .find(|task| task.id == task_id)
+        .map(|task| format!("{}: {}", task.id, task.title))
+        .unwrap_or_default()
+}
+
+/// Let the user pick one of `task_ids`; `None` when cancelled
+fn select_task(plan: &Plan, message: &str, task_ids: &[usize]) -> Option<usize> {
+    let labels: Vec<String> = task_ids.iter().map(|&id| task_label(plan, id)).collect();
+    let choice = Select::new(message, labels.clone()).prompt().ok()?;
+    labels
+        .iter()
+        .position(|label| *label == choice)
+        .map(|index| task_ids[index])
+}
+
+fn pending_tasks(plan: &Plan) -> Vec<usize> {
+    plan.get_all_tasks()
+        .into_iter()
+        .filter(|task| task.status == TaskStatus::Pending)
+        .map(|task| task.id)
+        .collect()
+}
+
+fn edit_task(planner: &mut Planner, plan_id: &str) -> Result<bool, String> {
+    let plan = find_plan(planner, plan_id)?;
+    let Some(task_id) = select_task(plan, "Task to edit:", &pending_tasks(plan)) else {
+        return Ok(false);
+    };
+    let Some(task) = plan.get_all_tasks().into_iter().find(|t| t.id == task_id) else {
+        return Ok(false);
+    };
+    let draft = RevisedTask::from_task(task);
+    let Some(task) = edit_draft(&draft)? else {
+        return Ok(false);
+    };
+    apply(planner, plan_id, PlanEdit::Replace { task_id, task })?;
+    Ok(true)
+}
+
+fn add_task(planner: &mut Planner, plan_id: &str) -> Result<bool, String> {
+    let plan = find_plan(planner, plan_id)?;
+    let phases: Vec<String> = plan.phases.iter().map(|phase| phase.name.clone()).collect();
+    let Ok(phase) = Select::new("Add the task to phase:", phases).prompt() else {
+        return Ok(false);
+    };
+    let template = RevisedTask {
+        id: plan.next_task_id,
+        title: "New task".to_string(),
+        tool: "bash".to_string(),
+        target: String::new(),
+        operation: String::new(),
+        content: String::new(),
+        dependencies: Vec::new(),
+        success_criteria: None,
+        policy: Default::default(),
+    };
+    let Some(task) = edit_draft(&template)? else {
+        return Ok(false);
+    };
+    let task_id = apply(
+        planner,
+        plan_id,
+        PlanEdit::Add {
+            task,
+            phase: Some(phase),
+        },
+    )?;
+    println!("\r\x1B[K➕ Added task {}", task_id);
+    Ok(true)
+}
+
+fn drop_tasks(planner: &mut Planner, plan_id: &str) -> Result<bool, String> {
+    let plan = find_plan(planner, plan_id)?;
+    let task_ids = pending_tasks(plan);
+    let labels: Vec<String> = task_ids.iter().map(|&id| task_label(plan, id)).collect();
+    let Ok(chosen) = MultiSelect::new("Tasks to drop:", labels.clone())
+        .with_help_message("Space to select, Enter to confirm")
+        .prompt()
+    else {
+        return Ok(false);
+    };
+    let dropped: Vec<usize> = labels
+        .iter()
+        .zip(&task_ids)
+        .filter(|(label, _)| chosen.contains(label))
+        .map(|(_, &id)| id)
+        .collect();
+    // Tasks that depend on a dropped task are dropped with it
+    let mut skipped = Vec::new();
+    for &task_id in &dropped {
+        if skipped.contains(&task_id) {
+            continue;
+        }
+        let dependents = find_plan(planner, plan_id)?.dependents_of(task_id);
+        apply(planner, plan_id, PlanEdit::Drop(task_id))?;
+        let also: Vec<String> = dependents
+            .iter()
+            .filter(|id| !dropped.contains(id))
+            .map(|id| id.to_string())
+            .collect();
+        if !also.is_empty() {
+            println!(
+                "\r\x1B[K🗑️  Also dropped tasks {}, which depend on task {}",
+                also.join(", "),
+                task_id
+            );
+        }
+        skipped.extend(dependents);
+    }
+    Ok(!dropped.is_empty())
+}
+
+fn move_task(planner: &mut Planner, plan_id: &str) -> Result<bool, String> {
+    let plan = find_plan(planner, plan_id)?;
+    let task_ids: Vec<usize> = plan.get_all_tasks().iter().map(|task| task.id).collect();
+    let Some(task_id) = select_task(plan, "Task to move:", &task_ids) else {
+        return Ok(false);
+    };
+    let mut targets: Vec<String> = task_ids
+        .iter()
+        .filter(|&&id| id != task_id)
+        .map(|&id| format!("Before {}", task_label(plan, id)))
+        .collect();
+    targets.push(AT_END.to_string());
+    let Ok(target) = Select::new("Move it to:", targets.clone()).prompt() else {
+        return Ok(false);
+    };
+    let before = task_ids
+        .iter()
+        .filter(|&&id| id != task_id)
+        .zip(&targets)
+        .find(|(_, label)| **label == target)
+        .map(|(&id, _)| id);
+    apply(planner, plan_id, PlanEdit::Move { task_id, before })?;
+    Ok(true)
+}
+
+async fn revise_plan(
+    planner: &mut Planner,
+    plan_id: &str,
+    context: &Context,
+) -> Result<bool, String> {
+    let Ok(feedback) = Text::new("What should the planner change?").prompt() else {
+        return Ok(false);
+    };
+    if feedback.trim().is_empty() {
+        return Ok(false);
+    }
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.blue} {msg}")
+            .unwrap(),
+    );
+    pb.set_message("Revising the plan...");
+    pb.enable_steady_tick(std::time::Duration::from_millis(120));
+    let revised = planner
+        .revise_plan_with_feedback(plan_id, &feedback, context)
+        .await;
+    pb.finish_and_clear();
+
+    revised.map(|_| true)
+}
+
+/// Edit a task definition as JSON until it parses or the user gives up;
+/// `None` when nothing changed or the edit was abandoned
+fn edit_draft(draft: &RevisedTask) -> Result<Option<RevisedTask>, String> {
+    let original = serde_json::to_string_pretty(draft).map_err(|e| e.to_string())?;
+    let mut text = original.clone();
+    loop {
+        text = edit_in_editor(&text, "json").map_err(|e| format!("Editor failed: {}", e))?;
+        if text.trim() == original.trim() {
+            return Ok(None);
+        }
+        match serde_json::from_str::<RevisedTask>(&text) {
+            Ok(task) => return Ok(Some(task)),
+            Err(e) => {
+                println!("\r\x1B[K❌ Invalid task JSON: {}", e);
+                let again = Confirm::new("Edit again?").with_default(true).prompt();
+                if !matches!(again, Ok(true)) {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// Open `text` in `$VISUAL` or `$EDITOR` (`vi` otherwise) and return the saved text
+fn edit_in_editor(text: &str, extension: &str) -> io::Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let path = std::env::temp_dir().join(format!("kai-task-{}.{}", std::process::id(), extension));
+    std::fs::write(&path, text)?;
+
+    // Through the shell so editors configured with arguments ("code --wait") work
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    if !status?.success() {
+        return Err(io::Error::other(format!(
+            "'{}' exited with an error",
+            editor
+        )));
+    }
+    edited
+}
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use crate::context::harvesters::{Harvester, HarvesterConfig, ModuleInfo, FileInfo};
 use crate::context::story::{Story, ResponseMetadata};
 use crate::llm::OpenRouterClient;
+use crate::tools::symbols;
 
 /// Enhanced context object that manages contextual information with file tracking and updates
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -237,6 +238,7 @@ impl Context {
             size: metadata.len(),
             description: None,
             last_modified,
+            symbols: symbols::outline_file(file_path).unwrap_or_default(),
         })
     }
     
@@ -1,11 +1,12 @@
 use crate::cli::config::OpenRouterConfig;
 use crate::llm::OpenRouterClient;
+use crate::tools::symbols::{self, Symbol};
+use crate::tools::walk;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
 
 /// File information collected by the harvester
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +17,9 @@ pub struct FileInfo {
     pub size: u64,
     pub description: Option<String>,
     pub last_modified: Option<DateTime<Utc>>,
+    /// Parsed definitions, for languages the symbol parser supports
+    #[serde(default)]
+    pub symbols: Vec<Symbol>,
 }
 
 /// Module information for higher-level architecture analysis
@@ -36,6 +40,8 @@ pub struct HarvesterConfig {
     pub include_extensions: HashSet<String>,
     pub max_file_size_mb: u64,
     pub openrouter_model: String,
+    /// Skip files excluded by `.gitignore`, `.ignore` and `.kaiignore`
+    pub respect_ignore_files: bool,
 }
 
 impl Default for HarvesterConfig {
@@ -111,6 +117,7 @@ impl Default for HarvesterConfig {
             include_extensions,
             max_file_size_mb: 5, // 5MB max file size
             openrouter_model: OpenRouterConfig::default().midrange_model,
+            respect_ignore_files: true,
         }
     }
 }
@@ -158,10 +165,10 @@ impl Harvester {
     }
 
     /// Check if a path should be excluded based on the exclusion patterns
-    fn should_exclude_path(&self, path: &Path) -> bool {
+    fn should_exclude_path(exclude_patterns: &HashSet<String>, path: &Path) -> bool {
         for component in path.components() {
             if let Some(name) = component.as_os_str().to_str() {
-                if self.config.exclude_patterns.contains(name) {
+                if exclude_patterns.contains(name) {
                     return true;
                 }
             }
@@ -195,10 +202,15 @@ impl Harvester {
     pub fn discover_files(&self) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>> {
         let mut files = Vec::new();
 
-        for entry in WalkDir::new(&self.config.root_path)
-            .into_iter()
-            .filter_entry(|e| !self.should_exclude_path(e.path()))
-        {
+        let root = self.config.root_path.clone();
+        let exclude_patterns = self.config.exclude_patterns.clone();
+        let mut walker = walk::walker(&root, self.config.respect_ignore_files);
+        walker.filter_entry(move |e| {
+            let relative = e.path().strip_prefix(&root).unwrap_or(e.path());
+            e.file_name() != ".git" && !Self::should_exclude_path(&exclude_patterns, relative)
+        });
+
+        for entry in walker.build() {
             let entry = entry?;
             let path = entry.path();
 
@@ -234,6 +246,7 @@ impl Harvester {
                     size: metadata.len(),
                     description: None,
                     last_modified,
+                    symbols: symbols::outline_file(path).unwrap_or_default(),
                 });
             }
         }
@@ -300,6 +313,9 @@ impl Harvester {
 - **Size**: {} bytes
 - **Language**: {}
 
+## Parsed Structure
+{}
+
 ## Analysis Requirements
 Provide a comprehensive but concise analysis covering:
 
@@ -351,6 +367,11 @@ Source code:
             file_info.extension.as_deref().unwrap_or("none"),
             file_info.size,
             detect_language_from_extension(file_info.extension.as_deref().unwrap_or("")),
+            if file_info.symbols.is_empty() {
+                "(not available for this language)".to_string()
+            } else {
+                symbols::format_outline(&file_info.symbols)
+            },
             file_info.relative_path.display(),
             content
         );
@@ -702,10 +723,14 @@ mod tests {
     fn test_exclusion_patterns() {
         let harvester = Harvester::with_defaults();
 
-        assert!(harvester.should_exclude_path(Path::new("target/debug")));
-        assert!(harvester.should_exclude_path(Path::new("node_modules/package")));
-        assert!(harvester.should_exclude_path(Path::new("src/.git/config")));
-        assert!(!harvester.should_exclude_path(Path::new("src/main.rs")));
+        let excluded = |path: &str| {
+            Harvester::should_exclude_path(&harvester.config.exclude_patterns, Path::new(path))
+        };
+
+        assert!(excluded("target/debug"));
+        assert!(excluded("node_modules/package"));
+        assert!(excluded("src/.git/config"));
+        assert!(!excluded("src/main.rs"));
     }
 
     #[test]
@@ -749,6 +774,35 @@ mod tests {
             .any(|f| f.relative_path == Path::new("Cargo.toml")));
     }
 
+    #[test]
+    fn test_file_discovery_respects_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+
+        fs::create_dir_all(temp_path.join("src/generated")).unwrap();
+        fs::write(temp_path.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_path.join("src/generated/api.rs"), "").unwrap();
+        fs::write(temp_path.join("notes.md"), "").unwrap();
+        fs::write(temp_path.join(".gitignore"), "notes.md\n").unwrap();
+        fs::write(temp_path.join(".kaiignore"), "src/generated/\n").unwrap();
+
+        let config = HarvesterConfig {
+            root_path: temp_path.clone(),
+            ..Default::default()
+        };
+        let files = Harvester::new(config).discover_files().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].relative_path, Path::new("src/main.rs"));
+        assert_eq!(files[0].symbols[0].name, "main");
+
+        let config = HarvesterConfig {
+            root_path: temp_path,
+            respect_ignore_files: false,
+            ..Default::default()
+        };
+        assert_eq!(Harvester::new(config).discover_files().unwrap().len(), 3);
+    }
+
     #[tokio::test]
     async fn test_context_file_optimization() {
         let temp_dir = TempDir::new().unwrap();
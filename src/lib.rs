@@ -41,12 +41,24 @@
 //! 6. **grep_files** - Search text in files using regular expressions
 //! 7. **search_replace** - Find and replace text across multiple files
 //! 8. **find_files** - Find files by name patterns and types
+//! 9. **git_status**, **git_diff**, **git_log**, **git_show** - Structured repository inspection
+//! 10. **git_branch**, **git_switch**, **git_add**, **git_commit** - Branch and commit operations
+//! 11. **start_process**, **read_process_output**, **wait_for_output**, **send_process_input**,
+//!     **stop_process**, **list_processes** - Background processes owned by a plan
+//! 12. **http_request** - HTTP requests to local services (loopback only by default)
+//! 13. **build_diagnostics** - Structured compiler diagnostics (cargo, tsc, pytest)
+//! 14. **run_tests** - Per-test outcomes for cargo test, pytest, jest and go test
+//! 15. **outline**, **find_symbol** - Definitions and spans in Rust, Python, TypeScript and Go sources
+//! 16. **mcp__<server>__<tool>**, **mcp_list_resources**, **mcp_read_resource** - Tools and resources from configured MCP servers
 //!
 //! All tools support wildcard patterns (*, **, ?) and provide comprehensive error handling.
+//! Tools that walk the tree skip paths excluded by `.gitignore`, `.ignore` and `.kaiignore`
+//! unless called with `no_ignore`.
 
 pub mod cli;
 pub mod context;
 pub mod llm;
+pub mod mcp;
 pub mod planer;
 pub mod prompts;
 pub mod session;
@@ -2,6 +2,7 @@ use crate::tools::get_all_tools;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::sync::{Arc, RwLock};
 
 /// OpenRouter API client for handling prompts
 #[derive(Debug, Clone)]
@@ -9,6 +10,8 @@ pub struct OpenRouterClient {
     client: Client,
     api_key: String,
     base_url: String,
+    /// Tools from outside the built-in registry (e.g. MCP servers), shared between clones
+    additional_tools: Arc<RwLock<Vec<serde_json::Value>>>,
 }
 
 /// Request structure for OpenRouter API
@@ -25,7 +28,7 @@ pub struct ChatRequest {
 }
 
 /// Message structure for chat requests
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
@@ -60,6 +63,14 @@ impl OpenRouterClient {
             client: Client::new(),
             api_key,
             base_url: "https://openrouter.ai/api/v1".to_string(),
+            additional_tools: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Replace the tool definitions sent alongside the built-in tools
+    pub fn set_additional_tools(&self, tools: Vec<serde_json::Value>) {
+        if let Ok(mut additional) = self.additional_tools.write() {
+            *additional = tools;
         }
     }
 
@@ -110,10 +121,13 @@ impl OpenRouterClient {
     ) -> Result<ChatResponse, Box<dyn Error>> {
         // Get file system tools and convert to JSON format for OpenRouter
         let all_tools = get_all_tools();
-        let tools_json: Vec<serde_json::Value> = all_tools
+        let mut tools_json: Vec<serde_json::Value> = all_tools
             .iter()
             .map(|tool| serde_json::to_value(tool).unwrap())
             .collect();
+        if let Ok(additional) = self.additional_tools.read() {
+            tools_json.extend(additional.iter().cloned());
+        }
 
         let request = ChatRequest {
             model: model.to_string(),
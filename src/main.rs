@@ -2,17 +2,33 @@
 //!
 //! This is the main entry point for the KAI application, which starts the
 //! enhanced CLI prompter for interactive usage with AI planning capabilities.
+//! With `--mcp-server` it instead serves KAI's tools and planner over MCP (stdio).
+//! `--dry-run` reports what writes, deletes and shell commands would do
+//! without performing them.
 
 use std::env;
 use std::io::{self, Write};
 use std::process;
 use std::sync::Arc;
+use KAI::cli::config::{CliConfig, CONFIG_FILE};
 use KAI::cli::CliPrompter;
+use KAI::context::{Context, ContextDataStore};
 use KAI::llm::OpenRouterClient;
-use KAI::planer::Planner;
+use KAI::mcp::server::{protocol_stdout, NonInteractiveApproval};
+use KAI::mcp::{McpManager, McpToolServer};
+use KAI::planer::{ApprovalGate, Planner, TaskExecutor};
 
 #[tokio::main]
 async fn main() {
+    let dry_run = env::args().skip(1).any(|arg| arg == "--dry-run");
+    if env::args().skip(1).any(|arg| arg == "--mcp-server") {
+        if let Err(e) = run_mcp_server(dry_run).await {
+            eprintln!("ERROR: KAI MCP server failed: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     // Initialize OpenRouter client from environment variable
     let openrouter_client = match initialize_openrouter_client() {
         Ok(client) => {
@@ -33,7 +49,7 @@ async fn main() {
     };
 
     // Initialize and run the application
-    match run_kai_application(openrouter_client).await {
+    match run_kai_application(openrouter_client, dry_run).await {
         Ok(_) => {
             println!("\nThanks for using 🦀 KAI! Goodbye!");
         }
@@ -63,6 +79,68 @@ fn initialize_openrouter_client() -> Result<Arc<OpenRouterClient>, String> {
     Ok(Arc::new(client))
 }
 
+/// Serve KAI's tools over MCP on stdin/stdout. `plan_and_execute` is only
+/// offered when an OpenRouter API key is configured.
+async fn run_mcp_server(dry_run: bool) -> io::Result<()> {
+    // Claim stdout before anything else can print to it
+    let stdout = protocol_stdout()?;
+
+    let current_dir = env::current_dir()?;
+    let config = CliConfig::load_or_default(&current_dir.join(CONFIG_FILE));
+    let workdir = current_dir.join("workdir");
+    std::fs::create_dir_all(&workdir)?;
+
+    let (mcp, errors) = McpManager::start(&config.mcp.servers).await;
+    for error in errors {
+        eprintln!("WARNING: {}", error);
+    }
+    let gate = config
+        .approval
+        .policy()
+        .map(|policy| Arc::new(ApprovalGate::new(policy, Arc::new(NonInteractiveApproval))));
+
+    let mut executor = TaskExecutor::new()
+        .with_workdir(&workdir)
+        .with_dry_run(dry_run)
+        .with_mcp(mcp.clone());
+    if let Some(gate) = &gate {
+        executor = executor.with_approval(gate.clone());
+    }
+    let mut server = McpToolServer::new(executor);
+
+    match initialize_openrouter_client() {
+        Ok(client) => {
+            let mut planner = Planner::with_llm_client(client.clone())
+                .with_workdir(&workdir)
+                .with_workdir_confinement(true)
+                .with_dry_run(dry_run)
+                .with_max_workers(config.max_parallel_tasks)
+                .with_max_replans(config.max_replans)
+                .with_auto_commit(config.git.auto_commit_prefix())
+                .with_mcp(mcp);
+            if let Some(gate) = gate {
+                planner = planner.with_approval(gate);
+            }
+
+            let mut context = Context::new_with_root(workdir.clone());
+            let data_store = ContextDataStore::new(workdir.clone());
+            if let Err(e) = context
+                .update(&data_store, Some((*client).clone()), false)
+                .await
+            {
+                eprintln!("WARNING: Context initialization failed: {}", e);
+            }
+            server = server.with_planner(planner).with_context(context);
+        }
+        Err(e) => eprintln!("WARNING: {}; plan_and_execute is disabled", e),
+    }
+
+    eprintln!("KAI MCP server ready (workdir: {})", workdir.display());
+    server
+        .serve(tokio::io::BufReader::new(tokio::io::stdin()), stdout)
+        .await
+}
+
 fn print_banner() {
     println!("╭─────────────────────────────────────────────────╮");
     println!("│  KAI - Enhanced AI-Powered CLI Assistant        │");
@@ -78,11 +156,14 @@ fn print_banner() {
     std::thread::sleep(std::time::Duration::from_millis(1000));
 }
 
-async fn run_kai_application(openrouter_client: Option<Arc<OpenRouterClient>>) -> io::Result<()> {
+async fn run_kai_application(
+    openrouter_client: Option<Arc<OpenRouterClient>>,
+    dry_run: bool,
+) -> io::Result<()> {
     // Initialize the planner with LLM client
     let mut prompter = if let Some(client) = openrouter_client {
         println!("AI Planning system initialized with OpenRouter");
-        let planner = Planner::with_llm_client(client);
+        let planner = Planner::with_llm_client(client).with_dry_run(dry_run);
 
         // Create prompter with planner
         match CliPrompter::with_planner(planner) {
@@ -96,6 +177,8 @@ async fn run_kai_application(openrouter_client: Option<Arc<OpenRouterClient>>) -
                     eprintln!("Continuing without context integration...");
                 }
 
+                p.start_mcp_servers().await;
+
                 p
             }
             Err(e) => {
@@ -0,0 +1,597 @@
+//! Model Context Protocol client
+//!
+//! Launches configured MCP servers as child processes and speaks JSON-RPC 2.0
+//! over their stdin/stdout, one message per line. Each server's tools are
+//! exposed as `mcp__<server>__<tool>` so they can sit next to the built-in
+//! tools in the registry sent to the LLM and be routed back by name.
+
+use super::{error_response, jsonrpc_request, MCP_PROTOCOL_VERSION};
+use crate::tools::file_system::ToolResult;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+/// Prefix of namespaced MCP tool names (`mcp__<server>__<tool>`)
+pub const TOOL_PREFIX: &str = "mcp__";
+/// Built-in tool listing the resources of connected servers
+pub const LIST_RESOURCES_TOOL: &str = "mcp_list_resources";
+/// Built-in tool reading one resource by URI
+pub const READ_RESOURCE_TOOL: &str = "mcp_read_resource";
+
+/// How long a single request may take before the server is considered stuck
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An MCP server to launch, as configured in `mcp.servers`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// Short name used in tool names; must not contain `__`
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+}
+
+/// A tool offered by an MCP server
+#[derive(Debug, Clone, Serialize)]
+pub struct McpTool {
+    pub server: String,
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
+
+impl McpTool {
+    /// Name under which the tool is offered to the LLM
+    pub fn qualified_name(&self) -> String {
+        format!("{}{}__{}", TOOL_PREFIX, self.server, self.name)
+    }
+
+    /// OpenRouter-compatible function definition
+    pub fn definition(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.qualified_name(),
+                "description": format!("[MCP {}] {}", self.server, self.description),
+                "parameters": self.input_schema,
+            }
+        })
+    }
+}
+
+/// A resource offered by an MCP server
+#[derive(Debug, Clone, Serialize)]
+pub struct McpResource {
+    pub server: String,
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+struct Connection {
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+}
+
+/// A running MCP server and what it offers
+pub struct McpServer {
+    config: McpServerConfig,
+    // Held so the child is killed when the server is dropped
+    _child: Child,
+    connection: Mutex<Connection>,
+    pub server_info: Value,
+    pub tools: Vec<McpTool>,
+    pub resources: Vec<McpResource>,
+}
+
+impl std::fmt::Debug for McpServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpServer")
+            .field("name", &self.config.name)
+            .field("tools", &self.tools.len())
+            .field("resources", &self.resources.len())
+            .finish()
+    }
+}
+
+impl McpServer {
+    /// Launch the server, perform the initialize handshake and list its
+    /// tools and resources
+    pub async fn start(config: McpServerConfig) -> Result<Self, String> {
+        if config.name.is_empty() || config.name.contains("__") {
+            return Err(format!(
+                "Invalid MCP server name '{}': must be non-empty and not contain '__'",
+                config.name
+            ));
+        }
+
+        let mut command = Command::new(&config.command);
+        command
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(cwd) = &config.cwd {
+            command.current_dir(cwd);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to start MCP server '{}': {}", config.name, e))?;
+        let stdin = child.stdin.take().ok_or("MCP server stdin unavailable")?;
+        let stdout = child.stdout.take().ok_or("MCP server stdout unavailable")?;
+
+        let mut server = Self {
+            config,
+            _child: child,
+            connection: Mutex::new(Connection {
+                stdin,
+                stdout: BufReader::new(stdout).lines(),
+                next_id: 1,
+            }),
+            server_info: Value::Null,
+            tools: Vec::new(),
+            resources: Vec::new(),
+        };
+
+        let init = server
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": MCP_PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "KAI", "version": env!("CARGO_PKG_VERSION") },
+                }),
+            )
+            .await?;
+        server
+            .notify("notifications/initialized", json!({}))
+            .await?;
+        server.server_info = init["serverInfo"].clone();
+
+        let capabilities = &init["capabilities"];
+        if capabilities.get("tools").is_some() {
+            server.tools = server.list_tools().await?;
+        }
+        if capabilities.get("resources").is_some() {
+            server.resources = server.list_resources().await?;
+        }
+        Ok(server)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn write_message(connection: &mut Connection, message: &Value) -> Result<(), String> {
+        let mut line = message.to_string();
+        line.push('\n');
+        connection
+            .stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to MCP server: {}", e))?;
+        connection
+            .stdin
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to write to MCP server: {}", e))
+    }
+
+    /// Send a request and wait for its response. Notifications are skipped
+    /// and server-initiated pings are answered while waiting.
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let mut connection = self.connection.lock().await;
+        let id = connection.next_id;
+        connection.next_id += 1;
+        Self::write_message(&mut connection, &jsonrpc_request(id, method, params)).await?;
+
+        let name = self.config.name.clone();
+        let wait = async {
+            loop {
+                let line = connection
+                    .stdout
+                    .next_line()
+                    .await
+                    .map_err(|e| format!("Failed to read from MCP server '{}': {}", name, e))?
+                    .ok_or_else(|| format!("MCP server '{}' closed its output", name))?;
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+
+                if let Some(request_method) = message["method"].as_str() {
+                    if let Some(request_id) = message.get("id") {
+                        let reply = if request_method == "ping" {
+                            json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                        } else {
+                            error_response(request_id.clone(), -32601, "Method not found")
+                        };
+                        Self::write_message(&mut connection, &reply).await?;
+                    }
+                    continue;
+                }
+                if message["id"].as_u64() != Some(id) {
+                    continue;
+                }
+                if let Some(error) = message.get("error") {
+                    return Err(format!(
+                        "MCP server '{}' returned an error for {}: {}",
+                        name,
+                        method,
+                        error["message"].as_str().unwrap_or("unknown error")
+                    ));
+                }
+                return Ok(message["result"].clone());
+            }
+        };
+        tokio::time::timeout(REQUEST_TIMEOUT, wait)
+            .await
+            .map_err(|_| format!("MCP server '{}' timed out on {}", self.config.name, method))?
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        let mut connection = self.connection.lock().await;
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        Self::write_message(&mut connection, &message).await
+    }
+
+    /// Follow `nextCursor` pagination of a list method
+    async fn list_all(&self, method: &str, key: &str) -> Result<Vec<Value>, String> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request(method, params).await?;
+            if let Some(page) = result[key].as_array() {
+                items.extend(page.iter().cloned());
+            }
+            match result["nextCursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => return Ok(items),
+            }
+        }
+    }
+
+    async fn list_tools(&self) -> Result<Vec<McpTool>, String> {
+        Ok(self
+            .list_all("tools/list", "tools")
+            .await?
+            .into_iter()
+            .filter_map(|tool| {
+                Some(McpTool {
+                    server: self.config.name.clone(),
+                    name: tool["name"].as_str()?.to_string(),
+                    description: tool["description"].as_str().unwrap_or_default().to_string(),
+                    input_schema: match &tool["inputSchema"] {
+                        Value::Null => json!({ "type": "object", "properties": {} }),
+                        schema => schema.clone(),
+                    },
+                })
+            })
+            .collect())
+    }
+
+    async fn list_resources(&self) -> Result<Vec<McpResource>, String> {
+        Ok(self
+            .list_all("resources/list", "resources")
+            .await?
+            .into_iter()
+            .filter_map(|resource| {
+                Some(McpResource {
+                    server: self.config.name.clone(),
+                    uri: resource["uri"].as_str()?.to_string(),
+                    name: resource["name"].as_str().unwrap_or_default().to_string(),
+                    description: resource["description"].as_str().map(str::to_string),
+                    mime_type: resource["mimeType"].as_str().map(str::to_string),
+                })
+            })
+            .collect())
+    }
+
+    /// Call one of this server's tools with JSON arguments
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+        let arguments = match arguments {
+            Value::Null => json!({}),
+            other => other,
+        };
+        self.request(
+            "tools/call",
+            json!({ "name": name, "arguments": arguments }),
+        )
+        .await
+    }
+
+    /// Read a resource by URI
+    pub async fn read_resource(&self, uri: &str) -> Result<Value, String> {
+        self.request("resources/read", json!({ "uri": uri })).await
+    }
+}
+
+/// Text parts of an MCP `content` array, joined by newlines
+fn content_text(content: &Value) -> String {
+    content
+        .as_array()
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+fn error_result(message: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        data: None,
+        error: Some(message),
+    }
+}
+
+/// All connected MCP servers (cheap to clone; clones share the servers)
+#[derive(Debug, Clone, Default)]
+pub struct McpManager {
+    servers: Arc<Vec<McpServer>>,
+}
+
+impl McpManager {
+    /// Start every configured server. Servers that fail to start are left
+    /// out and reported in the returned error list.
+    pub async fn start(configs: &[McpServerConfig]) -> (Self, Vec<String>) {
+        let mut servers = Vec::new();
+        let mut errors = Vec::new();
+        for config in configs {
+            if servers.iter().any(|s: &McpServer| s.name() == config.name) {
+                errors.push(format!("Duplicate MCP server name '{}'", config.name));
+                continue;
+            }
+            match McpServer::start(config.clone()).await {
+                Ok(server) => servers.push(server),
+                Err(e) => errors.push(e),
+            }
+        }
+        (
+            Self {
+                servers: Arc::new(servers),
+            },
+            errors,
+        )
+    }
+
+    pub fn servers(&self) -> &[McpServer] {
+        &self.servers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    fn server(&self, name: &str) -> Option<&McpServer> {
+        self.servers.iter().find(|s| s.name() == name)
+    }
+
+    /// Function definitions for every MCP tool, plus the resource tools when
+    /// any server offers resources
+    pub fn tool_definitions(&self) -> Vec<Value> {
+        let mut definitions: Vec<Value> = self
+            .servers
+            .iter()
+            .flat_map(|s| s.tools.iter().map(McpTool::definition))
+            .collect();
+        if self.servers.iter().any(|s| !s.resources.is_empty()) {
+            definitions.push(json!({
+                "type": "function",
+                "function": {
+                    "name": LIST_RESOURCES_TOOL,
+                    "description": "List the resources (documents, tables, ...) offered by connected MCP servers.",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {
+                            "server": { "type": "string", "description": "Only list this server's resources." }
+                        }
+                    }
+                }
+            }));
+            definitions.push(json!({
+                "type": "function",
+                "function": {
+                    "name": READ_RESOURCE_TOOL,
+                    "description": "Read an MCP resource by URI.",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {
+                            "uri": { "type": "string", "description": "Resource URI from mcp_list_resources." }
+                        },
+                        "required": ["uri"]
+                    }
+                }
+            }));
+        }
+        definitions
+    }
+
+    /// Whether a tool name is routed to MCP
+    pub fn handles(tool: &str) -> bool {
+        tool.starts_with(TOOL_PREFIX) || tool == LIST_RESOURCES_TOOL || tool == READ_RESOURCE_TOOL
+    }
+
+    /// Call a namespaced tool (`mcp__<server>__<tool>`)
+    pub async fn call_tool(&self, qualified_name: &str, arguments: Value) -> ToolResult {
+        let Some((server_name, tool_name)) = qualified_name
+            .strip_prefix(TOOL_PREFIX)
+            .and_then(|rest| rest.split_once("__"))
+        else {
+            return error_result(format!(
+                "Invalid MCP tool name '{}': expected {}<server>__<tool>",
+                qualified_name, TOOL_PREFIX
+            ));
+        };
+        let Some(server) = self.server(server_name) else {
+            return error_result(format!("Unknown MCP server '{}'", server_name));
+        };
+        if !server.tools.iter().any(|t| t.name == tool_name) {
+            return error_result(format!(
+                "MCP server '{}' has no tool '{}'",
+                server_name, tool_name
+            ));
+        }
+
+        match server.call_tool(tool_name, arguments).await {
+            Ok(result) => {
+                let is_error = result["isError"].as_bool().unwrap_or(false);
+                let text = content_text(&result["content"]);
+                ToolResult {
+                    success: !is_error,
+                    error: is_error.then(|| text.clone()),
+                    data: Some(json!({
+                        "server": server_name,
+                        "tool": tool_name,
+                        "text": text,
+                        "content": result["content"],
+                        "structured_content": result.get("structuredContent"),
+                    })),
+                }
+            }
+            Err(e) => error_result(e),
+        }
+    }
+
+    /// Resources of every server, or of one server
+    pub fn list_resources(&self, server: Option<&str>) -> ToolResult {
+        if let Some(name) = server {
+            if self.server(name).is_none() {
+                return error_result(format!("Unknown MCP server '{}'", name));
+            }
+        }
+        let resources: Vec<&McpResource> = self
+            .servers
+            .iter()
+            .filter(|s| server.is_none_or(|name| s.name() == name))
+            .flat_map(|s| s.resources.iter())
+            .collect();
+        ToolResult {
+            success: true,
+            data: Some(json!({ "resources": resources, "count": resources.len() })),
+            error: None,
+        }
+    }
+
+    /// Read a resource from the server that listed it
+    pub async fn read_resource(&self, uri: &str) -> ToolResult {
+        let Some(server) = self
+            .servers
+            .iter()
+            .find(|s| s.resources.iter().any(|r| r.uri == uri))
+        else {
+            return error_result(format!("No MCP server offers resource '{}'", uri));
+        };
+        match server.read_resource(uri).await {
+            Ok(result) => ToolResult {
+                success: true,
+                data: Some(json!({
+                    "server": server.name(),
+                    "uri": uri,
+                    "text": content_text(&result["contents"]),
+                    "contents": result["contents"],
+                })),
+                error: None,
+            },
+            Err(e) => error_result(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::task::ToolCall;
+    use crate::planer::task_executor::TaskExecutor;
+
+    fn fixture_config() -> McpServerConfig {
+        McpServerConfig {
+            name: "fixture".to_string(),
+            command: "python3".to_string(),
+            args: vec![format!(
+                "{}/tests/fixtures/mcp_server.py",
+                env!("CARGO_MANIFEST_DIR")
+            )],
+            env: HashMap::new(),
+            cwd: None,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs python3 to run the fixture MCP server"]
+    async fn test_stdio_server_tools_and_resources() {
+        let config = fixture_config();
+        let (manager, errors) = McpManager::start(&[config]).await;
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let names: Vec<String> = manager
+            .tool_definitions()
+            .iter()
+            .map(|d| d["function"]["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "mcp__fixture__echo",
+                "mcp__fixture__fail",
+                LIST_RESOURCES_TOOL,
+                READ_RESOURCE_TOOL
+            ]
+        );
+
+        let result = manager
+            .call_tool("mcp__fixture__echo", json!({ "text": "hello" }))
+            .await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.data.unwrap()["text"], "echo: hello");
+
+        let failed = manager.call_tool("mcp__fixture__fail", json!({})).await;
+        assert!(!failed.success);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+        assert!(
+            !manager
+                .call_tool("mcp__other__echo", json!({}))
+                .await
+                .success
+        );
+
+        let resources = manager.list_resources(None).data.unwrap();
+        assert_eq!(resources["resources"][0]["uri"], "docs://readme");
+        let read = manager.read_resource("docs://readme").await;
+        assert_eq!(read.data.unwrap()["text"], "# Fixture docs");
+
+        // Plan tasks reach the server through the executor's tool dispatch
+        let executor = TaskExecutor::new().with_mcp(manager);
+        let output = executor
+            .dispatch_tool(&ToolCall {
+                tool: "mcp__fixture__echo".to_string(),
+                target: String::new(),
+                operation: "Echo".to_string(),
+                content: r#"{"text": "from a plan"}"#.to_string(),
+            })
+            .await;
+        let output: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["data"]["text"], "echo: from a plan");
+    }
+}
@@ -0,0 +1,26 @@
+//! Model Context Protocol (MCP) support
+//!
+//! `client` connects to external MCP tool servers over stdio and merges
+//! their tools into KAI's tool registry; `server` exposes KAI's own tools and
+//! planner to other MCP clients (`KAI --mcp-server`).
+
+pub mod client;
+pub mod server;
+
+pub use client::{McpManager, McpServerConfig, McpTool};
+pub use server::McpToolServer;
+
+use serde_json::{json, Value};
+
+/// Protocol revision spoken by KAI
+pub const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// A JSON-RPC 2.0 request
+pub fn jsonrpc_request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+/// A JSON-RPC 2.0 error response
+pub fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
@@ -0,0 +1,439 @@
+//! MCP server mode (`KAI --mcp-server`)
+//!
+//! Serves KAI's file system tools, the shell tool and a `plan_and_execute`
+//! tool over stdio, so editors and other agents can drive KAI. Every call goes
+//! through the same `TaskExecutor` path as plan tasks: file paths are confined
+//! to the working directory and the approval policy applies. Calls the policy
+//! would ask about are refused, since stdin carries the protocol.
+
+use crate::context::Context;
+use crate::mcp::{error_response, MCP_PROTOCOL_VERSION};
+use crate::planer::approval::{
+    canonical_tool_name, ApprovalHandler, ApprovalRequest, ApprovalResponse, PolicyDecision,
+};
+use crate::planer::task::ToolCall;
+use crate::planer::{Planner, TaskExecutor};
+use crate::tools::{exec, file_system};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Name of the tool that plans and runs a request end to end
+pub const PLAN_AND_EXECUTE_TOOL: &str = "plan_and_execute";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Refuses tool calls that would need an interactive approval prompt
+pub struct NonInteractiveApproval;
+
+impl ApprovalHandler for NonInteractiveApproval {
+    fn request_approval(&self, request: &ApprovalRequest) -> ApprovalResponse {
+        eprintln!(
+            "KAI MCP server: refused {} '{}' (needs approval; add an allow rule to permit it)",
+            request.tool, request.target
+        );
+        ApprovalResponse::Deny
+    }
+}
+
+/// Answers MCP requests with KAI's tools and planner
+pub struct McpToolServer {
+    executor: TaskExecutor,
+    planner: Option<Planner>,
+    context: Context,
+}
+
+impl McpToolServer {
+    /// Serve the executor's tools; `plan_and_execute` needs `with_planner`
+    pub fn new(executor: TaskExecutor) -> Self {
+        let context = Context::new_with_root(executor.workdir.clone());
+        Self {
+            executor: executor.with_workdir_confinement(true),
+            planner: None,
+            context,
+        }
+    }
+
+    /// Offer `plan_and_execute`, backed by this planner
+    pub fn with_planner(mut self, planner: Planner) -> Self {
+        self.planner = Some(planner);
+        self
+    }
+
+    /// Project context handed to the planner
+    pub fn with_context(mut self, context: Context) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// MCP tool descriptors for `tools/list`
+    pub fn tools(&self) -> Vec<Value> {
+        let mut tools: Vec<Value> = file_system::get_file_system_tools()
+            .into_iter()
+            .chain(std::iter::once(exec::get_shell_tool()))
+            .map(|tool| {
+                let mut properties = tool.function.parameters.properties;
+                // The executor always overwrites; appending is not exposed
+                if tool.function.name == "write_file" {
+                    if let Some(map) = properties.as_object_mut() {
+                        map.remove("append");
+                    }
+                }
+                json!({
+                    "name": tool.function.name,
+                    "description": tool.function.description,
+                    "inputSchema": {
+                        "type": tool.function.parameters.param_type,
+                        "properties": properties,
+                        "required": tool.function.parameters.required,
+                    }
+                })
+            })
+            .collect();
+        if self.planner.is_some() {
+            tools.push(json!({
+                "name": PLAN_AND_EXECUTE_TOOL,
+                "description": "Plan a request with KAI's planner and execute the plan in the working directory. Returns the plan and each task's outcome.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "request": {
+                            "type": "string",
+                            "description": "What to do, in natural language."
+                        }
+                    },
+                    "required": ["request"]
+                }
+            }));
+        }
+        tools
+    }
+
+    /// Translate MCP tool arguments into the executor's `ToolCall` form
+    fn tool_call(name: &str, arguments: &Value) -> Result<ToolCall, String> {
+        let string_arg = |key: &str| -> Result<String, String> {
+            arguments[key]
+                .as_str()
+                .map(|s| s.to_string())
+                .ok_or_else(|| format!("'{}' requires a string argument '{}'", name, key))
+        };
+        let target = match name {
+            "read_file" | "write_file" | "list_directory" | "create_path" => string_arg("path")?,
+            "delete_path" | "grep_files" => string_arg("pattern")?,
+            "search_replace" => string_arg("file_pattern")?,
+            "find_files" => string_arg("name_pattern")?,
+            "run_shell" => string_arg("command")?,
+            _ => return Err(format!("Unknown tool: {}", name)),
+        };
+        let content = match name {
+            "write_file" => string_arg("content")?,
+            "run_shell" => String::new(),
+            _ => arguments.to_string(),
+        };
+        Ok(ToolCall {
+            tool: name.to_string(),
+            target,
+            operation: format!("MCP call to {}", name),
+            content,
+        })
+    }
+
+    fn text_result(text: String, is_error: bool) -> Value {
+        json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+    }
+
+    /// Handle `tools/call`, returning an MCP `CallToolResult`
+    pub async fn call_tool(&mut self, name: &str, arguments: &Value) -> Value {
+        if name == PLAN_AND_EXECUTE_TOOL {
+            return match arguments["request"]
+                .as_str()
+                .filter(|r| !r.trim().is_empty())
+            {
+                Some(request) => self.plan_and_execute(request).await,
+                None => Self::text_result(
+                    "plan_and_execute requires a non-empty 'request'".to_string(),
+                    true,
+                ),
+            };
+        }
+
+        let tool_call = match Self::tool_call(name, arguments) {
+            Ok(tool_call) => tool_call,
+            Err(e) => return Self::text_result(e, true),
+        };
+
+        // Paths outside the working directory are refused by the dispatch itself
+        let confined = self.executor.confine_to_workdir
+            && self.executor.path_outside_workdir(&tool_call).is_some();
+        if let Some(gate) = self.executor.approval.as_ref().filter(|_| !confined) {
+            let tool = canonical_tool_name(&tool_call.tool);
+            let (decision, _) = gate.policy().evaluate(tool, tool_call.target.trim());
+            if decision == PolicyDecision::Ask {
+                return Self::text_result(
+                    format!(
+                        "{} '{}' requires approval, which MCP server mode cannot ask for. Add an allow rule to the approval policy to permit it.",
+                        tool, tool_call.target
+                    ),
+                    true,
+                );
+            }
+        }
+
+        let output = self
+            .executor
+            .dispatch_tool_for_task("", 0, &tool_call)
+            .await;
+        let is_error = serde_json::from_str::<Value>(&output)
+            .map(|result| result["success"] == false)
+            .unwrap_or(true);
+        Self::text_result(output, is_error)
+    }
+
+    /// Create a plan for the request and run it to completion or first failure
+    async fn plan_and_execute(&mut self, request: &str) -> Value {
+        let Some(planner) = self.planner.as_mut() else {
+            return Self::text_result(
+                "plan_and_execute is unavailable: no LLM client is configured".to_string(),
+                true,
+            );
+        };
+
+        self.context.add_user_prompt(request.to_string());
+        let plan_text = match planner
+            .create_and_execute_advanced_plan_with_context(request, &self.context)
+            .await
+        {
+            Ok(text) => text,
+            Err(e) => return Self::text_result(format!("Planning failed: {}", e), true),
+        };
+        let Some(plan_id) = planner
+            .task_planner
+            .active_plans
+            .last()
+            .map(|p| p.id.clone())
+        else {
+            return Self::text_result("Planning produced no plan".to_string(), true);
+        };
+
+        let results = match planner.execute_queued_plan(&plan_id, &self.context).await {
+            Ok(results) => results,
+            Err(e) => return Self::text_result(format!("Execution failed: {}", e), true),
+        };
+        let plan = planner
+            .task_planner
+            .active_plans
+            .iter()
+            .find(|p| p.id == plan_id);
+        let title = |id: usize| {
+            plan.and_then(|p| p.get_all_tasks().into_iter().find(|t| t.id == id))
+                .map(|t| t.title.clone())
+                .unwrap_or_default()
+        };
+
+        let failed = results.iter().any(|r| !r.success);
+        let complete = plan.is_some_and(|p| p.is_complete());
+        let mut lines = vec![plan_text, String::new()];
+        for result in &results {
+            lines.push(format!(
+                "{} Task {}: {}",
+                if result.success { "✅" } else { "❌" },
+                result.task_id,
+                title(result.task_id)
+            ));
+            lines.push(result.tool_result.clone());
+        }
+        self.context.add_response(lines.join("\n"), None);
+
+        let tasks: Vec<Value> = results
+            .iter()
+            .map(|r| {
+                json!({
+                    "id": r.task_id,
+                    "title": title(r.task_id),
+                    "success": r.success,
+                    "tool_result": r.tool_result,
+                    "summary": r.llm_processed_result,
+                })
+            })
+            .collect();
+        json!({
+            "content": [{ "type": "text", "text": lines.join("\n") }],
+            "structuredContent": { "plan_id": plan_id, "complete": complete, "tasks": tasks },
+            "isError": failed,
+        })
+    }
+
+    /// Handle one JSON-RPC message; notifications get no response
+    pub async fn handle_message(&mut self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message["method"].as_str() else {
+            return id.map(|id| error_response(id, INVALID_REQUEST, "Missing method"));
+        };
+        // Notifications (no id) need no answer
+        let id = id?;
+        let params = &message["params"];
+
+        let result = match method {
+            "initialize" => json!({
+                "protocolVersion": MCP_PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "KAI", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": self.tools() }),
+            "tools/call" => {
+                let Some(name) = params["name"].as_str() else {
+                    return Some(error_response(id, INVALID_PARAMS, "Missing tool name"));
+                };
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                self.call_tool(name, &arguments).await
+            }
+            _ => {
+                return Some(error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("Method not found: {}", method),
+                ))
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Serve newline-delimited JSON-RPC until the reader closes
+    pub async fn serve<R, W>(&mut self, reader: R, mut writer: W) -> std::io::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle_message(message).await,
+                Err(e) => Some(error_response(
+                    Value::Null,
+                    PARSE_ERROR,
+                    &format!("Parse error: {}", e),
+                )),
+            };
+            if let Some(response) = response {
+                writer.write_all(response.to_string().as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Claim stdout for protocol messages. Anything else printed to stdout
+/// afterwards (progress and debug output) goes to stderr instead, so it cannot
+/// corrupt the JSON-RPC stream. Call this before any other output.
+pub fn protocol_stdout() -> std::io::Result<Box<dyn AsyncWrite + Unpin + Send>> {
+    #[cfg(unix)]
+    {
+        Ok(Box::new(tokio::fs::File::from_std(
+            redirect_stdout_to_stderr()?,
+        )))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(Box::new(tokio::io::stdout()))
+    }
+}
+
+/// Point file descriptor 1 at stderr and return a handle to the original stdout
+#[cfg(unix)]
+fn redirect_stdout_to_stderr() -> std::io::Result<std::fs::File> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: dup/dup2 on the process's standard descriptors; the duplicated
+    // descriptor is owned by the returned File.
+    unsafe {
+        let original = libc::dup(libc::STDOUT_FILENO);
+        if original < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            libc::close(original);
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(std::fs::File::from_raw_fd(original))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::approval::{ApprovalGate, ApprovalPolicy, PolicyRule};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_serves_workdir_confined_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = ApprovalPolicy::default().with_rules(vec![
+            PolicyRule::new("write_file", "*", PolicyDecision::Allow),
+            PolicyRule::new("bash", "echo *", PolicyDecision::Allow),
+        ]);
+        let executor = TaskExecutor::new()
+            .with_workdir(dir.path())
+            .with_approval(Arc::new(ApprovalGate::new(
+                policy,
+                Arc::new(NonInteractiveApproval),
+            )));
+        let mut server = McpToolServer::new(executor);
+
+        let requests = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {
+                "name": "write_file", "arguments": {"path": "notes.txt", "content": "hello"}}}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {
+                "name": "read_file", "arguments": {"path": "../outside.txt"}}}),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": {
+                "name": "run_shell", "arguments": {"command": "echo hi"}}}),
+            json!({"jsonrpc": "2.0", "id": 6, "method": "tools/call", "params": {
+                "name": "run_shell", "arguments": {"command": "rm notes.txt"}}}),
+            json!({"jsonrpc": "2.0", "id": 7, "method": "resources/list"}),
+        ];
+        let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).await.unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 7);
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "KAI");
+
+        let tools = responses[1]["result"]["tools"].as_array().unwrap();
+        let names: Vec<&str> = tools.iter().filter_map(|t| t["name"].as_str()).collect();
+        assert!(names.contains(&"read_file") && names.contains(&"run_shell"));
+        assert!(!names.contains(&PLAN_AND_EXECUTE_TOOL));
+
+        assert_eq!(responses[2]["result"]["isError"], false);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("notes.txt")).unwrap(),
+            "hello"
+        );
+
+        assert_eq!(responses[3]["result"]["isError"], true);
+        let refused = responses[3]["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        assert!(refused.contains("outside the working directory"));
+
+        assert_eq!(responses[4]["result"]["isError"], false);
+        assert_eq!(responses[5]["result"]["isError"], true);
+        assert!(dir.path().join("notes.txt").exists());
+
+        assert_eq!(responses[6]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,466 @@
+//! Approval policy for tool calls
+//!
+//! Every tool call a plan wants to run is matched against an ordered list of
+//! rules (tool glob + target glob → allow / ask / deny). The first matching
+//! rule wins; shell commands are split into their `&&`, `||`, `;` and `|`
+//! segments and the strictest segment decision applies. Commands with
+//! substitutions or redirections and paths that climb out of the working
+//! directory are never allowed without asking. "Ask" decisions are
+//! delegated to an `ApprovalHandler` (the CLI prompt), which can remember an
+//! approval for the rest of the session. Concurrent tasks are asked one at a
+//! time, and the handler runs on a blocking thread.
+
+use crate::planer::task::ToolCall;
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// What to do with a tool call matched by a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyDecision {
+    Allow,
+    Ask,
+    Deny,
+}
+
+/// A single policy rule; `tool` and `pattern` are glob patterns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyRule {
+    /// Tool name pattern, e.g. `bash`, `write_file`, `git_*` or `*`
+    pub tool: String,
+    /// Target pattern: a path glob for file tools, a command glob for `bash`
+    pub pattern: String,
+    pub decision: PolicyDecision,
+}
+
+impl PolicyRule {
+    pub fn new(tool: &str, pattern: &str, decision: PolicyDecision) -> Self {
+        Self {
+            tool: tool.to_string(),
+            pattern: pattern.to_string(),
+            decision,
+        }
+    }
+
+    fn matches(&self, tool: &str, target: &str) -> bool {
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+        let glob_matches = |pattern: &str, value: &str| {
+            Pattern::new(pattern)
+                .map(|p| p.matches_with(value, options))
+                .unwrap_or(pattern == value)
+        };
+        glob_matches(&self.tool, tool) && glob_matches(&self.pattern, target)
+    }
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {} '{}'", self.decision, self.tool, self.pattern)
+    }
+}
+
+/// Ordered rule list with a fallback decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    pub rules: Vec<PolicyRule>,
+    pub default_decision: PolicyDecision,
+}
+
+/// Tools that only inspect the workspace and never modify it
+pub const READ_ONLY_TOOLS: &[&str] = &[
+    "read_file",
+    "list_directory",
+    "find_files",
+    "grep_files",
+    "outline",
+    "find_symbol",
+    "git_status",
+    "git_diff",
+    "git_log",
+    "git_show",
+    "read_process_output",
+    "wait_for_output",
+    "list_processes",
+    "mcp_list_resources",
+    "mcp_read_resource",
+];
+
+impl Default for ApprovalPolicy {
+    /// Read-only tools run freely, everything else asks first
+    fn default() -> Self {
+        // Stopping plan processes and probing local servers are safe as well
+        let allowed = READ_ONLY_TOOLS
+            .iter()
+            .chain(&["stop_process", "http_request"]);
+        Self {
+            rules: allowed
+                .map(|tool| PolicyRule::new(tool, "*", PolicyDecision::Allow))
+                .collect(),
+            default_decision: PolicyDecision::Ask,
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    /// Policy that allows every tool call
+    pub fn allow_all() -> Self {
+        Self {
+            rules: Vec::new(),
+            default_decision: PolicyDecision::Allow,
+        }
+    }
+
+    /// Insert rules ahead of the existing ones so they take precedence
+    pub fn with_rules(mut self, rules: Vec<PolicyRule>) -> Self {
+        self.rules.splice(0..0, rules);
+        self
+    }
+
+    /// Decide what to do with a call of `tool` on `target`
+    pub fn evaluate(&self, tool: &str, target: &str) -> (PolicyDecision, Option<&PolicyRule>) {
+        let tool = canonical_tool_name(tool);
+        if tool == "bash" {
+            // The strictest segment of a compound command decides
+            let (decision, rule) = split_shell_command(target)
+                .iter()
+                .map(|segment| self.evaluate_single(tool, segment))
+                .max_by_key(|(decision, _)| *decision)
+                .unwrap_or((self.default_decision, None));
+            // A substitution or redirection can run or write anything
+            if decision == PolicyDecision::Allow && has_substitution_or_redirect(target) {
+                return (PolicyDecision::Ask, None);
+            }
+            return (decision, rule);
+        }
+        let path = normalize_path(target);
+        let (decision, rule) = self.evaluate_single(tool, &path);
+        if decision == PolicyDecision::Allow && Path::new(&path).starts_with("..") {
+            return (PolicyDecision::Ask, None);
+        }
+        (decision, rule)
+    }
+
+    fn evaluate_single(&self, tool: &str, target: &str) -> (PolicyDecision, Option<&PolicyRule>) {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(tool, target))
+            .map(|rule| (rule.decision, Some(rule)))
+            .unwrap_or((self.default_decision, None))
+    }
+}
+
+/// Map tool aliases accepted by the executor onto their canonical names
+pub fn canonical_tool_name(tool: &str) -> &str {
+    match tool {
+        "write" => "write_file",
+        "read" => "read_file",
+        "ls" => "list_directory",
+        "run_shell" => "bash",
+        other => other,
+    }
+}
+
+/// Resolve `.` and `..` lexically, so `src/../../etc/passwd` becomes
+/// `../etc/passwd` and no longer matches `src/**`
+fn normalize_path(target: &str) -> String {
+    let mut normalized = PathBuf::new();
+    for component in Path::new(target.trim()).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized.to_string_lossy().to_string()
+}
+
+/// Whether a command line substitutes commands (`$(...)`, backticks) or
+/// redirects input or output
+fn has_substitution_or_redirect(command: &str) -> bool {
+    command.contains("$(") || command.contains(['`', '>', '<'])
+}
+
+/// Split a shell command line into the individual commands it runs
+fn split_shell_command(command: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' | '\n' => segments.push(std::mem::take(&mut current)),
+            '&' | '|' => {
+                if chars.peek() == Some(&c) {
+                    chars.next();
+                }
+                segments.push(std::mem::take(&mut current));
+            }
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+    segments
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// A tool call waiting for the user's decision
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub tool: String,
+    pub target: String,
+    pub operation: String,
+    /// Command line or diff of what the call would change
+    pub preview: String,
+}
+
+/// The user's answer to an approval prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalResponse {
+    AllowOnce,
+    AllowForSession,
+    Deny,
+}
+
+/// Asks the user about tool calls the policy marks as `Ask`; may block
+pub trait ApprovalHandler: Send + Sync {
+    fn request_approval(&self, request: &ApprovalRequest) -> ApprovalResponse;
+}
+
+/// Policy plus handler plus the session's "always allow" memory
+pub struct ApprovalGate {
+    policy: ApprovalPolicy,
+    handler: Arc<dyn ApprovalHandler>,
+    /// Held while the handler is asking, so only one prompt is open at a time
+    prompt: tokio::sync::Mutex<()>,
+    session_allowed: Mutex<HashSet<(String, String)>>,
+}
+
+impl fmt::Debug for ApprovalGate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ApprovalGate")
+            .field("policy", &self.policy)
+            .field("session_allowed", &self.session_allowed)
+            .finish()
+    }
+}
+
+impl ApprovalGate {
+    pub fn new(policy: ApprovalPolicy, handler: Arc<dyn ApprovalHandler>) -> Self {
+        Self {
+            policy,
+            handler,
+            prompt: tokio::sync::Mutex::new(()),
+            session_allowed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn policy(&self) -> &ApprovalPolicy {
+        &self.policy
+    }
+
+    /// Check whether a tool call may run, prompting if the policy says so.
+    ///
+    /// `preview` is only rendered when the user actually has to be asked.
+    pub async fn check(
+        &self,
+        tool_call: &ToolCall,
+        preview: impl FnOnce() -> String,
+    ) -> Result<(), String> {
+        let tool = canonical_tool_name(&tool_call.tool).to_string();
+        let target = tool_call.target.trim().to_string();
+        let (decision, rule) = self.policy.evaluate(&tool, &target);
+
+        match decision {
+            PolicyDecision::Allow => Ok(()),
+            PolicyDecision::Deny => Err(match rule {
+                Some(rule) => format!(
+                    "Denied by approval policy: {} '{}' matches rule {}",
+                    tool, target, rule
+                ),
+                None => format!("Denied by approval policy: {} '{}'", tool, target),
+            }),
+            PolicyDecision::Ask => {
+                let key = (tool.clone(), target.clone());
+                if self.session_allowed.lock().unwrap().contains(&key) {
+                    return Ok(());
+                }
+                // Wait for any open prompt; it may have allowed this call for the session
+                let _prompt = self.prompt.lock().await;
+                if self.session_allowed.lock().unwrap().contains(&key) {
+                    return Ok(());
+                }
+
+                let request = ApprovalRequest {
+                    tool,
+                    target,
+                    operation: tool_call.operation.clone(),
+                    preview: preview(),
+                };
+                let handler = self.handler.clone();
+                let asked = request.clone();
+                let response =
+                    tokio::task::spawn_blocking(move || handler.request_approval(&asked))
+                        .await
+                        .unwrap_or(ApprovalResponse::Deny);
+                match response {
+                    ApprovalResponse::AllowOnce => Ok(()),
+                    ApprovalResponse::AllowForSession => {
+                        self.session_allowed.lock().unwrap().insert(key);
+                        Ok(())
+                    }
+                    ApprovalResponse::Deny => Err(format!(
+                        "Denied by user: {} '{}'",
+                        request.tool, request.target
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingHandler {
+        response: ApprovalResponse,
+        calls: AtomicUsize,
+    }
+
+    impl ApprovalHandler for CountingHandler {
+        fn request_approval(&self, _request: &ApprovalRequest) -> ApprovalResponse {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.response
+        }
+    }
+
+    fn call(tool: &str, target: &str) -> ToolCall {
+        ToolCall {
+            tool: tool.to_string(),
+            target: target.to_string(),
+            operation: String::new(),
+            content: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_policy_rules() {
+        let policy = ApprovalPolicy::default().with_rules(vec![
+            PolicyRule::new("bash", "cargo test*", PolicyDecision::Allow),
+            PolicyRule::new("bash", "rm *", PolicyDecision::Ask),
+            PolicyRule::new("write_file", "src/**", PolicyDecision::Allow),
+            PolicyRule::new("write_file", "*", PolicyDecision::Deny),
+        ]);
+
+        assert_eq!(policy.evaluate("read_file", "x").0, PolicyDecision::Allow);
+        assert_eq!(
+            policy.evaluate("bash", "cargo test --lib").0,
+            PolicyDecision::Allow
+        );
+        assert_eq!(
+            policy
+                .evaluate("run_shell", "cargo test && rm -rf target")
+                .0,
+            PolicyDecision::Ask
+        );
+        assert_eq!(
+            policy.evaluate("write", "./src/main.rs").0,
+            PolicyDecision::Allow
+        );
+        assert_eq!(
+            policy.evaluate("write_file", "README.md").0,
+            PolicyDecision::Deny
+        );
+        assert_eq!(
+            policy.evaluate("delete_path", "a.txt").0,
+            PolicyDecision::Ask
+        );
+    }
+
+    #[test]
+    fn test_policy_bypasses_ask() {
+        let policy = ApprovalPolicy::default().with_rules(vec![
+            PolicyRule::new("bash", "cargo test*", PolicyDecision::Allow),
+            PolicyRule::new("write_file", "src/**", PolicyDecision::Allow),
+        ]);
+
+        assert_eq!(
+            policy.evaluate("write_file", "src/lib/../main.rs").0,
+            PolicyDecision::Allow
+        );
+        assert_eq!(
+            policy.evaluate("write_file", "src/../../etc/passwd").0,
+            PolicyDecision::Ask
+        );
+        assert_eq!(
+            policy.evaluate("write_file", "./src/../Cargo.toml").0,
+            PolicyDecision::Ask
+        );
+
+        for command in [
+            "cargo test $(rm -rf ~)",
+            "cargo test `rm -rf ~`",
+            "cargo test > src/main.rs",
+            "cargo test < input.txt",
+        ] {
+            assert_eq!(
+                policy.evaluate("bash", command).0,
+                PolicyDecision::Ask,
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gate_session_memory() {
+        let handler = Arc::new(CountingHandler {
+            response: ApprovalResponse::AllowForSession,
+            calls: AtomicUsize::new(0),
+        });
+        let gate = ApprovalGate::new(ApprovalPolicy::default(), handler.clone());
+
+        assert!(gate.check(&call("bash", "make"), String::new).await.is_ok());
+        assert!(gate.check(&call("bash", "make"), String::new).await.is_ok());
+        assert_eq!(handler.calls.load(Ordering::SeqCst), 1);
+
+        // Concurrent calls wait for the open prompt instead of asking again
+        let gate = ApprovalGate::new(ApprovalPolicy::default(), handler.clone());
+        let make = call("bash", "make install");
+        let (first, second) = tokio::join!(
+            gate.check(&make, String::new),
+            gate.check(&make, String::new)
+        );
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(handler.calls.load(Ordering::SeqCst), 2);
+
+        let deny = ApprovalGate::new(
+            ApprovalPolicy::default(),
+            Arc::new(CountingHandler {
+                response: ApprovalResponse::Deny,
+                calls: AtomicUsize::new(0),
+            }),
+        );
+        assert!(deny
+            .check(&call("delete_path", "a.txt"), String::new)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use walkdir::WalkDir;
+
+/// Name of the manifest file kept inside every plan checkpoint directory
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Serializes manifest updates from concurrently running tasks
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// A snapshot of a single file (or directory) taken before a task modified it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    /// Task that was about to modify the path
+    pub task_id: usize,
+    /// Absolute path of the original file
+    pub path: PathBuf,
+    /// Whether the path existed before the modification
+    pub existed: bool,
+    /// Whether the path was a directory
+    pub is_dir: bool,
+    /// Name of the stored copy inside the checkpoint directory
+    pub snapshot_file: Option<String>,
+    /// Snapshot timestamp
+    pub taken_at: DateTime<Utc>,
+}
+
+/// All snapshots recorded for one plan, in the order they were taken
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlanCheckpoint {
+    pub plan_id: String,
+    pub snapshots: Vec<FileSnapshot>,
+}
+
+/// How a file changed since the plan first touched it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FileChange {
+    Added,
+    Modified,
+    Deleted,
+    Unchanged,
+}
+
+/// Summary entry for a file touched by a plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedFile {
+    pub path: PathBuf,
+    pub change: FileChange,
+    pub task_ids: Vec<usize>,
+}
+
+/// Result of restoring snapshots (undo or rollback)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub plan_id: String,
+    pub task_ids: Vec<usize>,
+    pub restored: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+/// On-disk store of per-plan file checkpoints.
+///
+/// Every file a plan touches is copied before its first modification by each
+/// task, so the last task can be undone and a whole plan can be rolled back
+/// even in directories that are not under version control.
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    root: PathBuf,
+}
+
+impl CheckpointStore {
+    /// Create a store rooted at the given directory
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Create the default store for a working directory (`<workdir>/.context/checkpoints`)
+    pub fn for_workdir<P: AsRef<Path>>(workdir: P) -> Self {
+        Self::new(workdir.as_ref().join(".context").join("checkpoints"))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn plan_dir(&self, plan_id: &str) -> PathBuf {
+        self.root.join(plan_id)
+    }
+
+    /// Load the checkpoint manifest of a plan (empty if none exists yet)
+    pub fn load(&self, plan_id: &str) -> io::Result<PlanCheckpoint> {
+        let manifest_path = self.plan_dir(plan_id).join(MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return Ok(PlanCheckpoint {
+                plan_id: plan_id.to_string(),
+                snapshots: Vec::new(),
+            });
+        }
+        let contents = fs::read_to_string(manifest_path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn save(&self, checkpoint: &PlanCheckpoint) -> io::Result<()> {
+        let dir = self.plan_dir(&checkpoint.plan_id);
+        fs::create_dir_all(&dir)?;
+        let json = serde_json::to_string_pretty(checkpoint)?;
+        fs::write(dir.join(MANIFEST_FILE), json)
+    }
+
+    /// Snapshot a path before `task_id` modifies it.
+    ///
+    /// Directories are snapshotted recursively. Paths already captured for the
+    /// same task are skipped. Returns the number of new snapshots taken.
+    pub fn snapshot(&self, plan_id: &str, task_id: usize, path: &Path) -> io::Result<usize> {
+        let _guard = MANIFEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut checkpoint = self.load(plan_id)?;
+        let mut taken = 0;
+
+        let mut paths = vec![path.to_path_buf()];
+        if path.is_dir() {
+            paths.extend(
+                WalkDir::new(path)
+                    .min_depth(1)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path().to_path_buf()),
+            );
+        }
+
+        for path in paths {
+            let already_taken = checkpoint
+                .snapshots
+                .iter()
+                .any(|s| s.task_id == task_id && s.path == path);
+            if already_taken {
+                continue;
+            }
+
+            let index = checkpoint.snapshots.len();
+            let snapshot_file = if path.is_file() {
+                let name = format!("{:05}_task{}.snap", index, task_id);
+                fs::create_dir_all(self.plan_dir(plan_id))?;
+                fs::copy(&path, self.plan_dir(plan_id).join(&name))?;
+                Some(name)
+            } else {
+                None
+            };
+
+            checkpoint.snapshots.push(FileSnapshot {
+                task_id,
+                existed: path.exists(),
+                is_dir: path.is_dir(),
+                path,
+                snapshot_file,
+                taken_at: Utc::now(),
+            });
+            taken += 1;
+        }
+
+        if taken > 0 {
+            self.save(&checkpoint)?;
+        }
+        Ok(taken)
+    }
+
+    /// List plan IDs that have checkpoints, most recently modified first
+    pub fn list_plans(&self) -> Vec<String> {
+        let mut plans: Vec<(std::time::SystemTime, String)> = match fs::read_dir(&self.root) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().join(MANIFEST_FILE).exists())
+                .map(|e| {
+                    let modified = e
+                        .path()
+                        .join(MANIFEST_FILE)
+                        .metadata()
+                        .and_then(|m| m.modified())
+                        .unwrap_or(std::time::UNIX_EPOCH);
+                    (modified, e.file_name().to_string_lossy().to_string())
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        plans.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        plans.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Summarize the files a plan changed, comparing the first snapshot of
+    /// each path with its current state on disk
+    pub fn changed_files(&self, plan_id: &str) -> io::Result<Vec<ChangedFile>> {
+        let checkpoint = self.load(plan_id)?;
+        let mut changes: Vec<ChangedFile> = Vec::new();
+
+        for snapshot in &checkpoint.snapshots {
+            if let Some(existing) = changes.iter_mut().find(|c| c.path == snapshot.path) {
+                if !existing.task_ids.contains(&snapshot.task_id) {
+                    existing.task_ids.push(snapshot.task_id);
+                }
+                continue;
+            }
+            if snapshot.is_dir {
+                continue;
+            }
+
+            let change = match (snapshot.existed, snapshot.path.exists()) {
+                (false, true) => FileChange::Added,
+                (true, false) => FileChange::Deleted,
+                (false, false) => FileChange::Unchanged,
+                (true, true) => {
+                    let original = snapshot
+                        .snapshot_file
+                        .as_ref()
+                        .and_then(|name| fs::read(self.plan_dir(plan_id).join(name)).ok());
+                    let current = fs::read(&snapshot.path).ok();
+                    if original == current {
+                        FileChange::Unchanged
+                    } else {
+                        FileChange::Modified
+                    }
+                }
+            };
+
+            changes.push(ChangedFile {
+                path: snapshot.path.clone(),
+                change,
+                task_ids: vec![snapshot.task_id],
+            });
+        }
+
+        Ok(changes)
+    }
+
+    /// Undo the most recent task that modified files in the given plan
+    /// (or in the most recently checkpointed plan when `plan_id` is `None`)
+    pub fn undo_last_task(&self, plan_id: Option<&str>) -> Result<RestoreReport, String> {
+        let plan_id = match plan_id {
+            Some(id) => id.to_string(),
+            None => self
+                .list_plans()
+                .into_iter()
+                .next()
+                .ok_or_else(|| "No checkpoints available to undo".to_string())?,
+        };
+
+        let mut checkpoint = self
+            .load(&plan_id)
+            .map_err(|e| format!("Failed to load checkpoints for '{}': {}", plan_id, e))?;
+        let last_task = checkpoint
+            .snapshots
+            .last()
+            .map(|s| s.task_id)
+            .ok_or_else(|| format!("Plan '{}' has no recorded changes", plan_id))?;
+
+        let (to_restore, remaining): (Vec<_>, Vec<_>) = checkpoint
+            .snapshots
+            .drain(..)
+            .partition(|s| s.task_id == last_task);
+        checkpoint.snapshots = remaining;
+
+        let mut report = self.restore(&plan_id, &to_restore);
+        report.task_ids = vec![last_task];
+
+        for snapshot in &to_restore {
+            if let Some(name) = &snapshot.snapshot_file {
+                let _ = fs::remove_file(self.plan_dir(&plan_id).join(name));
+            }
+        }
+        self.save(&checkpoint)
+            .map_err(|e| format!("Failed to update checkpoints for '{}': {}", plan_id, e))?;
+
+        Ok(report)
+    }
+
+    /// Roll back every change a plan made and discard its checkpoints
+    pub fn rollback_plan(&self, plan_id: &str) -> Result<RestoreReport, String> {
+        let checkpoint = self
+            .load(plan_id)
+            .map_err(|e| format!("Failed to load checkpoints for '{}': {}", plan_id, e))?;
+        if checkpoint.snapshots.is_empty() {
+            return Err(format!("Plan '{}' has no recorded changes", plan_id));
+        }
+
+        // Only the earliest snapshot of each path reflects the pre-plan state
+        let mut earliest: Vec<FileSnapshot> = Vec::new();
+        for snapshot in &checkpoint.snapshots {
+            if !earliest.iter().any(|s| s.path == snapshot.path) {
+                earliest.push(snapshot.clone());
+            }
+        }
+
+        let mut report = self.restore(plan_id, &earliest);
+        report.task_ids = checkpoint.snapshots.iter().map(|s| s.task_id).collect();
+        report.task_ids.sort_unstable();
+        report.task_ids.dedup();
+
+        if report.errors.is_empty() {
+            let _ = fs::remove_dir_all(self.plan_dir(plan_id));
+        }
+        Ok(report)
+    }
+
+    /// Restore snapshots in reverse order so nested paths are handled before their parents
+    fn restore(&self, plan_id: &str, snapshots: &[FileSnapshot]) -> RestoreReport {
+        let mut report = RestoreReport {
+            plan_id: plan_id.to_string(),
+            ..Default::default()
+        };
+
+        // Recreate directories first, then files, then remove paths that did not exist
+        for snapshot in snapshots.iter().filter(|s| s.existed && s.is_dir) {
+            if let Err(e) = fs::create_dir_all(&snapshot.path) {
+                report.errors.push(format!(
+                    "Failed to recreate '{}': {}",
+                    snapshot.path.display(),
+                    e
+                ));
+            }
+        }
+
+        for snapshot in snapshots.iter().filter(|s| s.existed && !s.is_dir) {
+            let Some(name) = &snapshot.snapshot_file else {
+                continue;
+            };
+            if snapshot.path.is_dir() {
+                let _ = fs::remove_dir_all(&snapshot.path);
+            }
+            if let Some(parent) = snapshot.path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            match fs::copy(self.plan_dir(plan_id).join(name), &snapshot.path) {
+                Ok(_) => report.restored.push(snapshot.path.clone()),
+                Err(e) => report.errors.push(format!(
+                    "Failed to restore '{}': {}",
+                    snapshot.path.display(),
+                    e
+                )),
+            }
+        }
+
+        for snapshot in snapshots.iter().rev().filter(|s| !s.existed) {
+            let result = if snapshot.path.is_dir() {
+                // Only remove directories the plan created once they are empty again
+                fs::remove_dir(&snapshot.path)
+            } else if snapshot.path.exists() {
+                fs::remove_file(&snapshot.path)
+            } else {
+                continue;
+            };
+            match result {
+                Ok(_) => report.removed.push(snapshot.path.clone()),
+                Err(e) => report.errors.push(format!(
+                    "Failed to remove '{}': {}",
+                    snapshot.path.display(),
+                    e
+                )),
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rollback_restores_original_state() {
+        let dir = tempdir().unwrap();
+        let store = CheckpointStore::for_workdir(dir.path());
+        let existing = dir.path().join("main.rs");
+        let created = dir.path().join("new.rs");
+        let notes = dir.path().join("notes.md");
+        fs::write(&existing, "original").unwrap();
+
+        store.snapshot("plan_a", 1, &existing).unwrap();
+        fs::write(&existing, "changed by task 1").unwrap();
+        store.snapshot("plan_a", 2, &existing).unwrap();
+        store.snapshot("plan_a", 2, &created).unwrap();
+        fs::write(&existing, "changed by task 2").unwrap();
+        fs::write(&created, "brand new").unwrap();
+        // Task 1 touches another file after task 2 ran
+        store.snapshot("plan_a", 1, &notes).unwrap();
+        fs::write(&notes, "notes").unwrap();
+
+        let changes = store.changed_files("plan_a").unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].change, FileChange::Modified);
+        assert_eq!(changes[0].task_ids, vec![1, 2]);
+        assert_eq!(changes[1].change, FileChange::Added);
+
+        let report = store.rollback_plan("plan_a").unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(report.task_ids, vec![1, 2]);
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "original");
+        assert!(!created.exists());
+        assert!(!notes.exists());
+        assert!(store.list_plans().is_empty());
+    }
+
+    #[test]
+    fn test_undo_last_task_only() {
+        let dir = tempdir().unwrap();
+        let store = CheckpointStore::for_workdir(dir.path());
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "v1").unwrap();
+
+        store.snapshot("plan_b", 1, &file).unwrap();
+        fs::write(&file, "v2").unwrap();
+        store.snapshot("plan_b", 2, &file).unwrap();
+        fs::remove_file(&file).unwrap();
+
+        let report = store.undo_last_task(None).unwrap();
+        assert_eq!(report.task_ids, vec![2]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "v2");
+
+        let report = store.undo_last_task(Some("plan_b")).unwrap();
+        assert_eq!(report.task_ids, vec![1]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "v1");
+
+        assert!(store.undo_last_task(Some("plan_b")).is_err());
+    }
+}
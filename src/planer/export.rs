@@ -0,0 +1,450 @@
+//! Plan export
+//!
+//! Renders a plan for use outside the terminal: Markdown for PR descriptions
+//! and tickets, a Mermaid flowchart or Graphviz DOT graph of the task
+//! dependencies colored by status, or the plan's JSON as saved in the
+//! journal. Phases become sections or clusters; sub-plan tasks list their own
+//! tasks.
+
+use crate::planer::plan::Plan;
+use crate::planer::task::{Task, TaskExecution, TaskStatus};
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Output format of `export_plan`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanExportFormat {
+    Markdown,
+    Mermaid,
+    Dot,
+    Json,
+}
+
+impl PlanExportFormat {
+    pub fn all() -> [Self; 4] {
+        [Self::Markdown, Self::Mermaid, Self::Dot, Self::Json]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Mermaid => "mermaid",
+            Self::Dot => "dot",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for PlanExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "mermaid" | "mmd" => Ok(Self::Mermaid),
+            "dot" | "graphviz" | "gv" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "Unknown export format '{}' (expected one of: {})",
+                other,
+                Self::all().map(|format| format.name()).join(", ")
+            )),
+        }
+    }
+}
+
+/// Render `plan` in `format`
+pub fn export_plan(plan: &Plan, format: PlanExportFormat) -> Result<String, String> {
+    match format {
+        PlanExportFormat::Markdown => Ok(to_markdown(plan)),
+        PlanExportFormat::Mermaid => Ok(to_mermaid(plan)),
+        PlanExportFormat::Dot => Ok(to_dot(plan)),
+        PlanExportFormat::Json => serde_json::to_string_pretty(plan).map_err(|e| e.to_string()),
+    }
+}
+
+fn status_label(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Pending => "⏳ Pending",
+        TaskStatus::InProgress => "🔄 In Progress",
+        TaskStatus::Completed => "✅ Completed",
+        TaskStatus::Failed => "❌ Failed",
+        TaskStatus::Decomposed => "🧬 Decomposed",
+        TaskStatus::Skipped => "⏭️ Skipped",
+    }
+}
+
+/// Class name plus fill and stroke colors for the graph formats
+fn status_style(status: &TaskStatus) -> (&'static str, &'static str, &'static str) {
+    match status {
+        TaskStatus::Pending => ("pending", "#eeeeee", "#9e9e9e"),
+        TaskStatus::InProgress => ("inProgress", "#bbdefb", "#1976d2"),
+        TaskStatus::Completed => ("completed", "#c8e6c9", "#388e3c"),
+        TaskStatus::Failed => ("failed", "#ffcdd2", "#d32f2f"),
+        TaskStatus::Decomposed => ("decomposed", "#e1bee7", "#7b1fa2"),
+        TaskStatus::Skipped => ("skipped", "#fafafa", "#bdbdbd"),
+    }
+}
+
+const STATUSES: [TaskStatus; 6] = [
+    TaskStatus::Pending,
+    TaskStatus::InProgress,
+    TaskStatus::Completed,
+    TaskStatus::Failed,
+    TaskStatus::Decomposed,
+    TaskStatus::Skipped,
+];
+
+/// Tool name and target, or the goal of a sub-plan
+fn task_action(task: &Task) -> (&str, &str) {
+    match &task.execution {
+        TaskExecution::ToolCall(tool_call) => (tool_call.tool.as_str(), tool_call.target.as_str()),
+        TaskExecution::SubPlan(plan) => (crate::planer::task::SUB_PLAN_TOOL, plan.title.as_str()),
+    }
+}
+
+/// First line of `text`, shortened to `max_chars`
+fn short(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    let line = text.lines().next().unwrap_or_default().trim_end();
+    if line.chars().count() > max_chars || text.lines().nth(1).is_some() {
+        let cut: String = line.chars().take(max_chars.saturating_sub(1)).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+fn to_markdown(plan: &Plan) -> String {
+    let mut out = String::new();
+    let (completed, total) = plan.get_progress();
+    let _ = writeln!(out, "# {}\n", plan.title);
+    if !plan.overview.trim().is_empty() {
+        let _ = writeln!(out, "{}\n", plan.overview.trim());
+    }
+    let _ = writeln!(out, "**Progress:** {}/{} tasks done\n", completed, total);
+
+    for phase in &plan.phases {
+        let _ = writeln!(out, "## {} {}\n", phase.emoji, phase.name);
+        if phase.tasks.is_empty() {
+            let _ = writeln!(out, "_No tasks_\n");
+            continue;
+        }
+        let _ = writeln!(out, "| # | Task | Tool | Target | Depends on | Status |");
+        let _ = writeln!(out, "|---|------|------|--------|------------|--------|");
+        for task in &phase.tasks {
+            let (tool, target) = task_action(task);
+            let target = short(target, 60);
+            let _ = writeln!(
+                out,
+                "| {} | {} | `{}` | {} | {} | {} |",
+                task.id,
+                markdown_cell(&task.title),
+                tool,
+                if target.is_empty() {
+                    String::new()
+                } else {
+                    format!("`{}`", markdown_cell(&target).replace('`', "'"))
+                },
+                dependency_list(task),
+                status_label(&task.status)
+            );
+        }
+        out.push('\n');
+
+        for task in &phase.tasks {
+            if let TaskExecution::SubPlan(sub_plan) = &task.execution {
+                let _ = writeln!(out, "**Task {} sub-plan:** {}\n", task.id, sub_plan.title);
+                let tasks = sub_plan.get_all_tasks();
+                if tasks.is_empty() {
+                    let _ = writeln!(out, "_Planned when the task runs_\n");
+                    continue;
+                }
+                for sub_task in tasks {
+                    let _ = writeln!(
+                        out,
+                        "- [{}] {}. {} ({})",
+                        if sub_task.status.is_done() { "x" } else { " " },
+                        sub_task.id,
+                        sub_task.title,
+                        status_label(&sub_task.status)
+                    );
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out.trim_end().to_string() + "\n"
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn dependency_list(task: &Task) -> String {
+    task.dependencies
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Node ID of task `id`, prefixed by the IDs of the sub-plan tasks it sits in
+fn node_id(prefix: &str, id: usize) -> String {
+    format!("{}t{}", prefix, id)
+}
+
+fn to_mermaid(plan: &Plan) -> String {
+    let mut out = String::from("flowchart TD\n");
+    let mut classes: Vec<(String, &'static str)> = Vec::new();
+    mermaid_plan(&mut out, &mut classes, plan, "", 1);
+
+    for status in &STATUSES {
+        let (class, fill, stroke) = status_style(status);
+        let _ = writeln!(
+            out,
+            "    classDef {} fill:{},stroke:{}",
+            class, fill, stroke
+        );
+    }
+    for status in &STATUSES {
+        let (class, _, _) = status_style(status);
+        let nodes: Vec<&str> = classes
+            .iter()
+            .filter(|(_, c)| *c == class)
+            .map(|(node, _)| node.as_str())
+            .collect();
+        if !nodes.is_empty() {
+            let _ = writeln!(out, "    class {} {}", nodes.join(","), class);
+        }
+    }
+    out
+}
+
+fn mermaid_plan(
+    out: &mut String,
+    classes: &mut Vec<(String, &'static str)>,
+    plan: &Plan,
+    prefix: &str,
+    depth: usize,
+) {
+    let indent = "    ".repeat(depth);
+    for (index, phase) in plan.phases.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{}subgraph {}p{}[\"{}\"]",
+            indent,
+            prefix,
+            index + 1,
+            mermaid_text(&format!("{} {}", phase.emoji, phase.name))
+        );
+        for task in &phase.tasks {
+            let node = node_id(prefix, task.id);
+            let label = mermaid_text(&format!("{}. {}", task.id, short(&task.title, 60)));
+            classes.push((node.clone(), status_style(&task.status).0));
+            match &task.execution {
+                TaskExecution::SubPlan(sub_plan) if !sub_plan.get_all_tasks().is_empty() => {
+                    let _ = writeln!(out, "{}    subgraph {}[\"{}\"]", indent, node, label);
+                    mermaid_plan(out, classes, sub_plan, &format!("{}_", node), depth + 2);
+                    let _ = writeln!(out, "{}    end", indent);
+                }
+                TaskExecution::SubPlan(_) => {
+                    let _ = writeln!(out, "{}    {}[[\"{}\"]]", indent, node, label);
+                }
+                TaskExecution::ToolCall(_) => {
+                    let _ = writeln!(out, "{}    {}[\"{}\"]", indent, node, label);
+                }
+            }
+        }
+        let _ = writeln!(out, "{}end", indent);
+    }
+    for task in plan.get_all_tasks() {
+        for dep in &task.dependencies {
+            let _ = writeln!(
+                out,
+                "{}{} --> {}",
+                indent,
+                node_id(prefix, *dep),
+                node_id(prefix, task.id)
+            );
+        }
+    }
+}
+
+/// Mermaid labels are quoted; quotes and brackets are written as entities
+fn mermaid_text(text: &str) -> String {
+    text.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+fn to_dot(plan: &Plan) -> String {
+    let mut out = String::from("digraph plan {\n");
+    let _ = writeln!(out, "    label=\"{}\";", dot_text(&plan.title));
+    let _ = writeln!(out, "    labelloc=t;");
+    let _ = writeln!(out, "    rankdir=TB;");
+    let _ = writeln!(
+        out,
+        "    node [shape=box, style=\"rounded,filled\", fontname=\"Helvetica\"];"
+    );
+    dot_plan(&mut out, plan, "", 1);
+    out.push_str("}\n");
+    out
+}
+
+fn dot_plan(out: &mut String, plan: &Plan, prefix: &str, depth: usize) {
+    let indent = "    ".repeat(depth);
+    for (index, phase) in plan.phases.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{}subgraph cluster_{}p{} {{",
+            indent,
+            prefix,
+            index + 1
+        );
+        let _ = writeln!(
+            out,
+            "{}    label=\"{}\";",
+            indent,
+            dot_text(&format!("{} {}", phase.emoji, phase.name))
+        );
+        for task in &phase.tasks {
+            let node = node_id(prefix, task.id);
+            let label = dot_text(&format!("{}. {}", task.id, short(&task.title, 60)));
+            let (_, fill, stroke) = status_style(&task.status);
+            match &task.execution {
+                TaskExecution::SubPlan(sub_plan) if !sub_plan.get_all_tasks().is_empty() => {
+                    let _ = writeln!(out, "{}    subgraph cluster_{} {{", indent, node);
+                    let _ = writeln!(out, "{}        label=\"{}\";", indent, label);
+                    let _ = writeln!(
+                        out,
+                        "{}        style=filled; fillcolor=\"{}\"; color=\"{}\";",
+                        indent, fill, stroke
+                    );
+                    dot_plan(out, sub_plan, &format!("{}_", node), depth + 2);
+                    let _ = writeln!(out, "{}    }}", indent);
+                }
+                execution => {
+                    let shape = match execution {
+                        TaskExecution::SubPlan(_) => ", shape=component",
+                        TaskExecution::ToolCall(_) => "",
+                    };
+                    let _ = writeln!(
+                        out,
+                        "{}    {} [label=\"{}\", fillcolor=\"{}\", color=\"{}\"{}];",
+                        indent, node, label, fill, stroke, shape
+                    );
+                }
+            }
+        }
+        let _ = writeln!(out, "{}}}", indent);
+    }
+    for task in plan.get_all_tasks() {
+        for dep in &task.dependencies {
+            let _ = writeln!(
+                out,
+                "{}{} -> {};",
+                indent,
+                dot_edge_end(plan, prefix, *dep),
+                dot_edge_end(plan, prefix, task.id)
+            );
+        }
+    }
+}
+
+/// Expanded sub-plans are clusters, which DOT edges cannot point at; their
+/// edges attach to the sub-plan's first task instead
+fn dot_edge_end(plan: &Plan, prefix: &str, task_id: usize) -> String {
+    let node = node_id(prefix, task_id);
+    let task = plan
+        .get_all_tasks()
+        .into_iter()
+        .find(|task| task.id == task_id);
+    match task.map(|task| &task.execution) {
+        Some(TaskExecution::SubPlan(sub_plan)) => match sub_plan.get_all_tasks().first() {
+            Some(first) => dot_edge_end(sub_plan, &format!("{}_", node), first.id),
+            None => node,
+        },
+        _ => node,
+    }
+}
+
+fn dot_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::plan::Phase;
+
+    fn plan() -> Plan {
+        let mut plan = Plan::new(
+            "Add \"login\" page".to_string(),
+            "Build the page".to_string(),
+        );
+        let mut phase = Phase::new("Build".to_string(), "🔧".to_string());
+        let mut read = Task::new_tool_task(
+            1,
+            "Read the router".to_string(),
+            "read_file".to_string(),
+            "src/router.rs".to_string(),
+            String::new(),
+            String::new(),
+        );
+        read.set_status(TaskStatus::Completed);
+        phase.add_task(read);
+        let mut sub_plan = Plan::new("Write the form".to_string(), String::new());
+        let mut form = Phase::new("Form".to_string(), "📝".to_string());
+        form.add_task(Task::new_tool_task(
+            1,
+            "Write form | fields".to_string(),
+            "write_file".to_string(),
+            "src/form.rs".to_string(),
+            String::new(),
+            String::new(),
+        ));
+        sub_plan.add_phase(form);
+        phase.add_task(
+            Task::new_sub_plan_task(2, "Form".to_string(), sub_plan).with_dependencies(vec![1]),
+        );
+        plan.add_phase(phase);
+        plan
+    }
+
+    #[test]
+    fn test_export_plan() {
+        let plan = plan();
+
+        let markdown = export_plan(&plan, PlanExportFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# Add \"login\" page\n"));
+        assert!(markdown
+            .contains("| 1 | Read the router | `read_file` | `src/router.rs` |  | ✅ Completed |"));
+        assert!(markdown.contains("| 2 | Form | `sub_plan` | `Write the form` | 1 | ⏳ Pending |"));
+        assert!(markdown.contains("- [ ] 1. Write form | fields (⏳ Pending)"));
+
+        let mermaid = export_plan(&plan, PlanExportFormat::Mermaid).unwrap();
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("t1[\"1. Read the router\"]"));
+        assert!(mermaid.contains("subgraph t2[\"2. Form\"]"));
+        assert!(mermaid.contains("t2_t1[\"1. Write form | fields\"]"));
+        assert!(mermaid.contains("t1 --> t2"));
+        assert!(mermaid.contains("class t1 completed"));
+        assert!(mermaid.contains("class t2,t2_t1 pending"));
+
+        let dot = export_plan(&plan, PlanExportFormat::Dot).unwrap();
+        assert!(dot.contains("label=\"Add \\\"login\\\" page\";"));
+        assert!(dot.contains("t1 [label=\"1. Read the router\", fillcolor=\"#c8e6c9\""));
+        assert!(dot.contains("subgraph cluster_t2 {"));
+        // Edges into an expanded sub-plan point at its first task
+        assert!(dot.contains("t1 -> t2_t1;"));
+
+        let json = export_plan(&plan, PlanExportFormat::Json).unwrap();
+        let parsed: Plan = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.get_all_tasks().len(), 2);
+
+        assert_eq!("MD".parse(), Ok(PlanExportFormat::Markdown));
+        assert_eq!("graphviz".parse(), Ok(PlanExportFormat::Dot));
+        assert!("pdf".parse::<PlanExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Plan journal
+//!
+//! Plans are written to `<workdir>/.context/plans/<plan_id>.json` whenever one
+//! of their tasks changes state, so a plan interrupted by a crash or a quit can
+//! be listed and resumed after a restart.
+
+use crate::planer::plan::Plan;
+use crate::planer::task::TaskStatus;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Overview of a journaled plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanSummary {
+    pub id: String,
+    pub title: String,
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PlanSummary {
+    pub fn is_complete(&self) -> bool {
+        self.completed == self.total
+    }
+}
+
+/// On-disk store of plan state
+#[derive(Debug, Clone)]
+pub struct PlanJournal {
+    root: PathBuf,
+}
+
+impl PlanJournal {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The journal for a working directory (`<workdir>/.context/plans`)
+    pub fn for_workdir<P: AsRef<Path>>(workdir: P) -> Self {
+        Self::new(workdir.as_ref().join(".context").join("plans"))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn plan_path(&self, plan_id: &str) -> PathBuf {
+        self.root.join(format!("{}.json", plan_id))
+    }
+
+    /// Write the plan's current state, replacing the previous entry atomically
+    pub fn save(&self, plan: &Plan) -> io::Result<()> {
+        if plan.id.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot journal a plan without an ID",
+            ));
+        }
+        fs::create_dir_all(&self.root)?;
+        let json = serde_json::to_string_pretty(plan)?;
+        let tmp = self.root.join(format!(".{}.json.tmp", plan.id));
+        fs::write(&tmp, json)?;
+        fs::rename(tmp, self.plan_path(&plan.id))
+    }
+
+    pub fn load(&self, plan_id: &str) -> io::Result<Plan> {
+        let contents = fs::read_to_string(self.plan_path(plan_id))
+            .map_err(|e| io::Error::new(e.kind(), format!("No saved plan '{}': {}", plan_id, e)))?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Summaries of all journaled plans, most recently updated first
+    pub fn list(&self) -> Vec<PlanSummary> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+        let mut plans: Vec<PlanSummary> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let plan: Plan = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+                let tasks = plan.get_all_tasks();
+                Some(PlanSummary {
+                    id: plan.id.clone(),
+                    title: plan.title.clone(),
+                    completed: tasks.iter().filter(|t| t.status.is_done()).count(),
+                    failed: tasks
+                        .iter()
+                        .filter(|t| t.status == TaskStatus::Failed)
+                        .count(),
+                    total: tasks.len(),
+                    updated_at: plan.plan_context.execution_metadata.last_activity,
+                })
+            })
+            .collect();
+        plans.sort_by_key(|plan| std::cmp::Reverse(plan.updated_at));
+        plans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::plan::Phase;
+    use crate::planer::task::Task;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_load_and_resume() {
+        let dir = tempdir().unwrap();
+        let journal = PlanJournal::for_workdir(dir.path());
+
+        let mut plan = Plan::new("Refactor".to_string(), "Split module".to_string());
+        plan.set_id("plan_test".to_string());
+        let mut phase = Phase::new("Work".to_string(), "🔧".to_string());
+        for (title, status) in [
+            ("Read", TaskStatus::Completed),
+            ("Write", TaskStatus::InProgress),
+            ("Test", TaskStatus::Failed),
+            ("Commit", TaskStatus::Pending),
+        ] {
+            let id = plan.generate_task_id();
+            let mut task = Task::new_tool_task(
+                id,
+                title.to_string(),
+                "bash".to_string(),
+                "true".to_string(),
+                String::new(),
+                String::new(),
+            );
+            task.set_status(status);
+            phase.add_task(task);
+        }
+        plan.add_phase(phase);
+        journal.save(&plan).unwrap();
+
+        let summaries = journal.list();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(
+            (
+                summaries[0].completed,
+                summaries[0].failed,
+                summaries[0].total
+            ),
+            (1, 1, 4)
+        );
+
+        let mut loaded = journal.load("plan_test").unwrap();
+        assert_eq!(loaded.reset_unfinished_tasks(), 2);
+        let statuses: Vec<TaskStatus> = loaded
+            .get_all_tasks()
+            .iter()
+            .map(|t| t.status.clone())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TaskStatus::Completed,
+                TaskStatus::Pending,
+                TaskStatus::Pending,
+                TaskStatus::Pending
+            ]
+        );
+        assert!(journal.load("missing").is_err());
+    }
+}
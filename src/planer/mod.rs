@@ -4,24 +4,45 @@
 //! This module provides core functionality for task planning, execution queue management,
 //! and basic LLM integration without unnecessary complexity.
 
+pub mod approval;
+pub mod checkpoint;
+pub mod export;
+pub mod journal;
 pub mod plan;
 pub mod queue;
+pub mod review;
+pub mod revision;
+pub mod scheduler;
 
 pub mod task;
 pub mod task_executor;
 pub mod task_planner;
 pub mod task_processor;
+pub mod template;
+pub mod validator;
 
 // Re-export main types for convenience
+pub use approval::{ApprovalGate, ApprovalHandler, ApprovalPolicy, PolicyDecision, PolicyRule};
+pub use checkpoint::{ChangedFile, CheckpointStore, FileChange, RestoreReport};
+pub use export::{export_plan, PlanExportFormat};
+pub use journal::{PlanJournal, PlanSummary};
 pub use plan::{ExecutionMetadata, Phase, Plan, PlanContext, TaskResult};
 pub use queue::{ExecutionQueue, QueueRequest, QueueResponse};
+pub use review::PlanEdit;
+pub use revision::{PlanRevision, RevisedTask, RevisionAction};
+pub use scheduler::{PlanScheduler, ProgressCallback, ProgressEvent, ResourceClaim, TaskOutcome};
 pub use task::{Task, TaskStatus};
 pub use task_executor::TaskExecutor;
 pub use task_planner::TaskPlanner;
 pub use task_processor::{TaskExecutionContext, TaskProcessor};
+pub use validator::{PlanIssue, PlanValidator, ValidationReport};
 
 use crate::llm::OpenRouterClient;
+use crate::mcp::McpManager;
+use crate::planer::task::TaskExecution;
+use std::future::Future;
 use std::path::Path;
+use std::pin::Pin;
 use std::sync::Arc;
 
 /// Main planner facade that combines all components with LLM-powered task processing
@@ -62,6 +83,92 @@ impl Planner {
         self
     }
 
+    /// Require approval (per `ApprovalPolicy`) before tools run
+    pub fn with_approval(mut self, gate: Arc<ApprovalGate>) -> Self {
+        if let Some(processor) = self.task_processor.as_mut() {
+            processor.task_executor = processor.task_executor.clone().with_approval(gate);
+        }
+        self
+    }
+
+    /// Refuse file tool paths outside the working directory
+    pub fn with_workdir_confinement(mut self, confined: bool) -> Self {
+        if let Some(processor) = self.task_processor.as_mut() {
+            processor.task_executor = processor
+                .task_executor
+                .clone()
+                .with_workdir_confinement(confined);
+        }
+        self
+    }
+
+    /// Simulate mutating tools so a plan's effects can be reviewed without applying them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        if let Some(processor) = self.task_processor.as_mut() {
+            processor.task_executor = processor.task_executor.clone().with_dry_run(dry_run);
+        }
+        self
+    }
+
+    /// Let the LLM revise a plan up to `max_replans` times after tasks fail
+    pub fn with_max_replans(mut self, max_replans: usize) -> Self {
+        self.task_planner = self.task_planner.with_max_replans(max_replans);
+        self
+    }
+
+    /// Run up to `max_workers` independent plan tasks at the same time
+    pub fn with_max_workers(mut self, max_workers: usize) -> Self {
+        if let Some(processor) = self.task_processor.as_mut() {
+            processor.task_executor = processor.task_executor.clone().with_max_workers(max_workers);
+        }
+        self
+    }
+
+    /// Report when plan tasks start and finish
+    pub fn with_progress(mut self, progress: ProgressCallback) -> Self {
+        if let Some(processor) = self.task_processor.as_mut() {
+            processor.task_executor = processor.task_executor.clone().with_progress(progress);
+        }
+        self
+    }
+
+    /// Commit the changes of successful plans on a `<prefix><plan_id>` branch
+    pub fn with_auto_commit(mut self, branch_prefix: Option<String>) -> Self {
+        if let Some(processor) = self.task_processor.as_mut() {
+            processor.task_executor = processor
+                .task_executor
+                .clone()
+                .with_auto_commit(branch_prefix);
+        }
+        self
+    }
+
+    /// Route plan tool calls to connected MCP servers and offer their tools to the LLM
+    pub fn with_mcp(mut self, mcp: McpManager) -> Self {
+        if let Some(client) = self.task_planner.get_llm_client() {
+            client.set_additional_tools(mcp.tool_definitions());
+        }
+        if let Some(processor) = self.task_processor.as_mut() {
+            processor.task_executor = processor.task_executor.clone().with_mcp(mcp);
+        }
+        self
+    }
+
+    /// Auto-commit a finished plan's changes, if enabled (see `TaskExecutor::commit_plan_changes`)
+    pub fn commit_plan_changes(&self, plan: &Plan) -> Option<crate::tools::file_system::ToolResult> {
+        self.task_processor
+            .as_ref()
+            .and_then(|processor| processor.task_executor.commit_plan_changes(plan))
+    }
+
+    /// Kill the background processes a plan started, returning how many were stopped
+    pub async fn stop_plan_processes(&self, plan_id: &str) -> usize {
+        match &self.task_processor {
+            Some(processor) => processor.task_executor.processes.stop_plan(plan_id).await,
+            None => 0,
+        }
+    }
+
     /// Execute a task with full context awareness using LLM processing
     pub async fn execute_task_with_context(
         &self,
@@ -69,40 +176,13 @@ impl Planner {
         main_context: &crate::context::Context,
         plan: &Plan,
     ) -> Result<TaskResult, String> {
-        if let Some(processor) = &self.task_processor {
-            // Gather dependency results
-            let dependency_results = task
-                .dependencies
-                .iter()
-                .filter_map(|&dep_id| plan.get_task_result(dep_id))
-                .map(|result| (result.task_id, result.clone()))
-                .collect();
-
-            let execution_context = TaskExecutionContext {
-                main_context: main_context.clone(),
-                plan_context: plan.plan_context.clone(),
-                dependency_results,
-                current_task: task.clone(),
-            };
-
-            processor
-                .execute_task_with_context(task, execution_context)
-                .await
-        } else {
-            // Fallback to basic execution without LLM processing
-            let task_executor = TaskExecutor::new();
-            let response = task_executor
-                .execute_task(task, main_context, &plan.plan_context)
-                .await;
-            Ok(TaskResult {
-                task_id: task.id,
-                tool_result: response.tool_result,
-                llm_processed_result: "No LLM processing available".to_string(),
-                extracted_variables: std::collections::HashMap::new(),
-                success: response.success,
-                executed_at: chrono::Utc::now(),
-            })
-        }
+        execute_in_plan_context(
+            self.task_processor.as_ref(),
+            task,
+            main_context,
+            &plan.plan_context,
+        )
+        .await
     }
 
     /// Create a sub-plan with inherited context from parent plan
@@ -115,50 +195,204 @@ impl Planner {
         Plan::with_parent_context(title, overview, Some(parent_plan.plan_context.clone()))
     }
 
-    /// Execute an entire plan with context-aware processing
+    /// Execute an entire plan with context-aware processing.
+    ///
+    /// Tasks run through `PlanScheduler`, so their `TaskPolicy` applies.
     pub async fn execute_plan_with_context(
         &mut self,
         plan: &mut Plan,
         main_context: &crate::context::Context,
     ) -> Result<Vec<TaskResult>, String> {
+        let results = self
+            .schedule_plan(plan, Arc::new(main_context.clone()))
+            .await;
+
+        self.stop_plan_processes(&plan.id).await;
+        self.commit_plan_changes(plan);
+
+        Ok(results)
+    }
+
+    /// Run `plan` on the scheduler with this planner's processor and LLM
+    async fn schedule_plan(
+        &self,
+        plan: &mut Plan,
+        main_context: Arc<crate::context::Context>,
+    ) -> Vec<TaskResult> {
+        let executor = self
+            .task_processor
+            .as_ref()
+            .map(|processor| processor.task_executor.clone())
+            .unwrap_or_default();
+        let processor = self.task_processor.clone();
+        let decomposer = Arc::new(self.task_planner.llm_planner());
+
+        executor
+            .plan_scheduler()
+            .run(
+                plan,
+                |task| executor.resource_claims(task),
+                |task, plan_context| {
+                    run_scheduled_task(
+                        processor.clone(),
+                        decomposer.clone(),
+                        main_context.clone(),
+                        task,
+                        plan_context,
+                    )
+                },
+            )
+            .await
+    }
+
+    /// Run an active plan's tasks until none is ready or a task fails.
+    ///
+    /// Independent tasks run concurrently (see `PlanScheduler`); tasks the LLM
+    /// flags for decomposition are replaced by their sub-tasks. When a task
+    /// fails the LLM may revise the rest of the plan, up to the planner's
+    /// `max_replans` times, and the run continues. Returns the results ordered
+    /// by task ID. Background processes keep running and nothing is
+    /// committed; see `execute_queued_plan` for that.
+    pub async fn run_plan_tasks(
+        &mut self,
+        plan_id: &str,
+        main_context: &crate::context::Context,
+    ) -> Result<Vec<TaskResult>, String> {
+        let index = self
+            .task_planner
+            .active_plans
+            .iter()
+            .position(|p| p.id == plan_id)
+            .ok_or_else(|| format!("Plan '{}' not found", plan_id))?;
+        // The scheduler works off the plan's dependency graph, not the queue
+        self.task_planner.execution_queue.clear_all();
+        let mut plan = self.task_planner.active_plans.remove(index);
+
+        let main_context = Arc::new(main_context.clone());
+        let scheduler = self
+            .task_processor
+            .as_ref()
+            .map(|processor| processor.task_executor.plan_scheduler())
+            .unwrap_or_default();
         let mut results = Vec::new();
+        let mut replans = 0;
+        loop {
+            results.extend(self.schedule_plan(&mut plan, main_context.clone()).await);
 
-        for i in 0..plan.phases.len() {
-            let phase = &plan.phases[i].clone();
-            for task in &phase.tasks {
-                // Check dependencies are satisfied
-                let deps_satisfied = task
-                    .dependencies
-                    .iter()
-                    .all(|&dep_id| plan.get_task_result(dep_id).map_or(false, |r| r.success));
-
-                if !deps_satisfied {
-                    continue;
-                }
+            let Some((task_id, title)) = plan
+                .get_all_tasks()
+                .into_iter()
+                .find(|task| task.status == TaskStatus::Failed)
+                .map(|task| (task.id, task.title.clone()))
+            else {
+                break;
+            };
+            if replans >= self.task_planner.max_replans() {
+                break;
+            }
+            replans += 1;
 
-                match self
-                    .execute_task_with_context(task, main_context, plan)
-                    .await
-                {
-                    Ok(result) => {
-                        plan.add_task_result(result.clone());
-                        results.push(result);
-                    }
-                    Err(e) => {
-                        let error_result = TaskResult {
-                            task_id: task.id,
-                            tool_result: format!("Execution failed: {}", e),
-                            llm_processed_result: format!("Task failed with error: {}", e),
-                            extracted_variables: std::collections::HashMap::new(),
-                            success: false,
-                            executed_at: chrono::Utc::now(),
-                        };
-                        plan.add_task_result(error_result.clone());
-                        results.push(error_result);
-                    }
+            let revised = match self.task_planner.revise_plan(&plan, task_id).await {
+                Ok(revision) => plan
+                    .apply_revision(task_id, &revision)
+                    .map(|new_tasks| (revision, new_tasks)),
+                Err(e) => Err(e),
+            };
+            match revised {
+                Ok((revision, new_tasks)) => scheduler.emit(ProgressEvent::Replanned {
+                    task_id,
+                    title,
+                    action: revision.action,
+                    reason: revision.reason,
+                    new_tasks,
+                    dropped: revision.drop,
+                }),
+                Err(error) => {
+                    scheduler.emit(ProgressEvent::ReplanFailed {
+                        task_id,
+                        title,
+                        error,
+                    });
+                    break;
                 }
             }
         }
+        results.sort_by_key(|result| result.task_id);
+
+        self.task_planner.active_plans.insert(index, plan);
+        Ok(results)
+    }
+
+    /// Let the LLM rework an active plan that has not run yet, replacing it
+    pub async fn revise_plan_with_feedback(
+        &mut self,
+        plan_id: &str,
+        feedback: &str,
+        main_context: &crate::context::Context,
+    ) -> Result<&Plan, String> {
+        let index = self
+            .task_planner
+            .active_plans
+            .iter()
+            .position(|p| p.id == plan_id)
+            .ok_or_else(|| format!("Plan '{}' not found", plan_id))?;
+        let revised = self
+            .task_planner
+            .revise_plan_with_feedback(
+                &self.task_planner.active_plans[index],
+                feedback,
+                main_context,
+            )
+            .await?;
+        self.task_planner.active_plans[index] = revised;
+        Ok(&self.task_planner.active_plans[index])
+    }
+
+    /// Journal of plans run in the working directory, if journaling is enabled
+    pub fn plan_journal(&self) -> Option<PlanJournal> {
+        self.task_processor
+            .as_ref()
+            .and_then(|processor| processor.task_executor.plan_journal())
+    }
+
+    /// Load a journaled plan so `run_plan_tasks` continues it.
+    ///
+    /// Completed tasks keep their results; tasks that were running or had
+    /// failed are reset to pending. Replaces an active plan with the same ID.
+    pub fn resume_plan(&mut self, plan_id: &str) -> Result<&Plan, String> {
+        let journal = self
+            .plan_journal()
+            .ok_or_else(|| "Plan journaling is disabled".to_string())?;
+        let mut plan = journal.load(plan_id).map_err(|e| e.to_string())?;
+        if plan.is_complete() {
+            return Err(format!("Plan '{}' is already complete", plan_id));
+        }
+        plan.reset_unfinished_tasks();
+
+        let active_plans = &mut self.task_planner.active_plans;
+        active_plans.retain(|p| p.id != plan.id);
+        active_plans.push(plan);
+        Ok(active_plans.last().unwrap())
+    }
+
+    /// Run an active plan's tasks (see `run_plan_tasks`), then stop its
+    /// background processes and, if enabled, commit its changes.
+    pub async fn execute_queued_plan(
+        &mut self,
+        plan_id: &str,
+        main_context: &crate::context::Context,
+    ) -> Result<Vec<TaskResult>, String> {
+        let results = self.run_plan_tasks(plan_id, main_context).await?;
+
+        self.stop_plan_processes(plan_id).await;
+        if let Some(plan) = self
+            .task_planner
+            .active_plans
+            .iter()
+            .find(|p| p.id == plan_id)
+        {
+            self.commit_plan_changes(plan);
+        }
 
         Ok(results)
     }
@@ -202,6 +436,129 @@ impl Planner {
     }
 }
 
+/// Run a task against a snapshot of its plan's context, with LLM processing
+/// when a processor is available
+async fn execute_in_plan_context(
+    processor: Option<&TaskProcessor>,
+    task: &Task,
+    main_context: &crate::context::Context,
+    plan_context: &PlanContext,
+) -> Result<TaskResult, String> {
+    if let Some(processor) = processor {
+        // Gather dependency results
+        let dependency_results = task
+            .dependencies
+            .iter()
+            .filter_map(|&dep_id| plan_context.get_task_result(dep_id))
+            .map(|result| (result.task_id, result.clone()))
+            .collect();
+
+        let execution_context = TaskExecutionContext {
+            main_context: main_context.clone(),
+            plan_context: plan_context.clone(),
+            dependency_results,
+            current_task: task.clone(),
+        };
+
+        processor
+            .execute_task_with_context(task, execution_context)
+            .await
+    } else {
+        // Fallback to basic execution without LLM processing
+        let task_executor = TaskExecutor::new();
+        let response = task_executor
+            .execute_task(task, main_context, plan_context)
+            .await;
+        Ok(TaskResult {
+            task_id: task.id,
+            tool_result: response.tool_result,
+            llm_processed_result: "No LLM processing available".to_string(),
+            extracted_variables: response.extracted_variables,
+            success: response.success,
+            executed_at: chrono::Utc::now(),
+        })
+    }
+}
+
+/// One task of `Planner::run_plan_tasks`, run on its own tokio task
+async fn run_scheduled_task(
+    processor: Option<TaskProcessor>,
+    decomposer: Arc<TaskPlanner>,
+    main_context: Arc<crate::context::Context>,
+    task: Task,
+    plan_context: PlanContext,
+) -> TaskOutcome {
+    if let TaskExecution::SubPlan(_) = &task.execution {
+        return run_sub_plan_task(processor, decomposer, main_context, task, plan_context).await;
+    }
+
+    let result = execute_in_plan_context(processor.as_ref(), &task, &main_context, &plan_context)
+        .await
+        .unwrap_or_else(|e| TaskResult::failed(task.id, &e));
+
+    if result.llm_processed_result.contains("Decomposition needed") {
+        return match decomposer.decompose_task(&task).await {
+            Ok(sub_tasks) => TaskOutcome::Decomposed(sub_tasks),
+            Err(e) => TaskOutcome::Finished(TaskResult::failed(task.id, &e)),
+        };
+    }
+    TaskOutcome::Finished(result)
+}
+
+/// Run a sub-plan task, planning its tasks first if that hasn't happened
+/// yet. Its tasks get the same LLM processing as the parent plan's.
+fn run_sub_plan_task(
+    processor: Option<TaskProcessor>,
+    decomposer: Arc<TaskPlanner>,
+    main_context: Arc<crate::context::Context>,
+    task: Task,
+    plan_context: PlanContext,
+) -> Pin<Box<dyn Future<Output = TaskOutcome> + Send>> {
+    // Boxed because sub-plans run their tasks through `run_scheduled_task`
+    Box::pin(async move {
+        let TaskExecution::SubPlan(sub_plan) = &task.execution else {
+            return TaskOutcome::Finished(TaskResult::failed(task.id, "Task is not a sub-plan"));
+        };
+        let executor = processor
+            .as_ref()
+            .map(|processor| processor.task_executor.clone())
+            .unwrap_or_default();
+
+        let sub_plan = if sub_plan.get_all_tasks().is_empty() {
+            match decomposer
+                .expand_sub_plan(&task, &main_context, &plan_context)
+                .await
+            {
+                Ok(sub_plan) => {
+                    executor
+                        .plan_scheduler()
+                        .emit(ProgressEvent::SubPlanExpanded {
+                            task_id: task.id,
+                            title: task.title.clone(),
+                            tasks: sub_plan.get_all_tasks().len(),
+                        });
+                    sub_plan
+                }
+                Err(e) => return TaskOutcome::Finished(TaskResult::failed(task.id, &e)),
+            }
+        } else {
+            sub_plan.clone()
+        };
+
+        executor
+            .execute_sub_plan(&task, sub_plan, &plan_context, |sub_task, context| {
+                Box::pin(run_scheduled_task(
+                    processor.clone(),
+                    decomposer.clone(),
+                    main_context.clone(),
+                    sub_task,
+                    context,
+                )) as Pin<Box<dyn Future<Output = TaskOutcome> + Send>>
+            })
+            .await
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
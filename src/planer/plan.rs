@@ -1,4 +1,8 @@
-use crate::planer::task::{Task, TaskStatus};
+use crate::planer::review::PlanEdit;
+use crate::planer::revision::{PlanRevision, RevisedTask};
+use crate::planer::task::{Task, TaskExecution, TaskStatus};
+use crate::planer::template;
+use crate::planer::validator::PlanValidator;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -30,21 +34,20 @@ impl Phase {
             .collect()
     }
 
+    /// All tasks done (decomposed and skipped tasks count; others replace them)
     pub fn is_complete(&self) -> bool {
-        self.tasks
-            .iter()
-            .all(|task| task.status == TaskStatus::Completed)
+        self.tasks.iter().all(|task| task.status.is_done())
     }
 }
 
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// Plan-specific temporary context that accumulates during execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlanContext {
-    /// Accumulated results from executed tasks, keyed by task ID
-    pub task_results: HashMap<usize, TaskResult>,
+    /// Accumulated results from executed tasks, ordered by task ID
+    pub task_results: BTreeMap<usize, TaskResult>,
     /// Plan-scoped variables and state
     pub plan_variables: HashMap<String, String>,
     /// Parent plan context for sub-plans (allows inheritance)
@@ -72,9 +75,106 @@ pub struct TaskResult {
     pub executed_at: DateTime<Utc>,
 }
 
+impl TaskResult {
+    /// Whether a tool's JSON `ToolResult` output reports success (shell tools
+    /// set it from the exit code). Output that isn't a `ToolResult` fails.
+    pub fn tool_output_succeeded(tool_result: &str) -> bool {
+        serde_json::from_str::<serde_json::Value>(tool_result)
+            .is_ok_and(|value| value["success"].as_bool() == Some(true))
+    }
+
+    /// Result for a task that could not run or whose execution errored
+    pub fn failed(task_id: usize, error: &str) -> Self {
+        Self {
+            task_id,
+            tool_result: format!("Execution failed: {}", error),
+            llm_processed_result: format!("Task failed with error: {}", error),
+            extracted_variables: HashMap::new(),
+            success: false,
+            executed_at: Utc::now(),
+        }
+    }
+
+    /// Result of a sub-plan task: a `ToolResult`-style list of the sub-plan's
+    /// tasks and their output data, carrying the sub-plan's variables up to
+    /// the parent plan
+    pub fn from_sub_plan(task_id: usize, sub_plan: &Plan) -> Self {
+        let results = &sub_plan.plan_context.task_results;
+        let tasks: Vec<serde_json::Value> = sub_plan
+            .get_all_tasks()
+            .into_iter()
+            .map(|task| {
+                let output = results.get(&task.id).map(|result| {
+                    match serde_json::from_str::<serde_json::Value>(&result.tool_result) {
+                        Ok(value) => match value.get("data").filter(|data| !data.is_null()) {
+                            Some(data) => data.clone(),
+                            None => value,
+                        },
+                        Err(_) => serde_json::Value::String(result.tool_result.clone()),
+                    }
+                });
+                serde_json::json!({
+                    "id": task.id,
+                    "title": task.title,
+                    "status": format!("{:?}", task.status),
+                    "output": output,
+                })
+            })
+            .collect();
+
+        let success = sub_plan.is_complete();
+        let mut tool_result = serde_json::json!({
+            "success": success,
+            "data": {"plan": sub_plan.title, "tasks": tasks},
+        });
+        if !success {
+            let failed: Vec<String> = sub_plan
+                .get_all_tasks()
+                .into_iter()
+                .filter(|task| task.status == TaskStatus::Failed)
+                .map(|task| format!("task {} ({})", task.id, task.title))
+                .collect();
+            tool_result["error"] = serde_json::Value::String(if failed.is_empty() {
+                format!("Sub-plan '{}' did not finish", sub_plan.title)
+            } else {
+                format!(
+                    "Sub-plan '{}' failed at {}",
+                    sub_plan.title,
+                    failed.join(", ")
+                )
+            });
+        }
+
+        let (completed, total) = sub_plan.get_progress();
+        let summaries: String = results
+            .values()
+            .map(|result| {
+                format!(
+                    "\n- Task {}: {}",
+                    result.task_id, result.llm_processed_result
+                )
+            })
+            .collect();
+        Self {
+            task_id,
+            tool_result: tool_result.to_string(),
+            llm_processed_result: format!(
+                "Sub-plan '{}': {}/{} tasks completed{}",
+                sub_plan.title, completed, total, summaries
+            ),
+            extracted_variables: sub_plan.plan_context.plan_variables.clone(),
+            success,
+            executed_at: Utc::now(),
+        }
+    }
+}
+
 /// Metadata about plan execution state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionMetadata {
+    /// ID of the plan this context belongs to
+    #[serde(default)]
+    pub plan_id: String,
     /// Total tasks in plan
     pub total_tasks: usize,
     /// Completed tasks count
@@ -92,11 +192,12 @@ impl PlanContext {
     pub fn new(parent: Option<PlanContext>) -> Self {
         let now = Utc::now();
         Self {
-            task_results: HashMap::new(),
+            task_results: BTreeMap::new(),
             plan_variables: HashMap::new(),
             parent_context: parent.map(Box::new),
             created_at: now,
             execution_metadata: ExecutionMetadata {
+                plan_id: String::new(),
                 total_tasks: 0,
                 completed_tasks: 0,
                 current_phase: None,
@@ -121,8 +222,8 @@ impl PlanContext {
     }
 
     /// Get all available task results including from parent contexts
-    pub fn get_all_available_results(&self) -> HashMap<usize, &TaskResult> {
-        let mut results = HashMap::new();
+    pub fn get_all_available_results(&self) -> BTreeMap<usize, &TaskResult> {
+        let mut results = BTreeMap::new();
 
         // Add parent results first (can be overridden by current level)
         if let Some(parent) = &self.parent_context {
@@ -137,6 +238,13 @@ impl PlanContext {
         results
     }
 
+    /// How many plans this context's plan is nested in (0 for a top-level plan)
+    pub fn depth(&self) -> usize {
+        self.parent_context
+            .as_ref()
+            .map_or(0, |parent| parent.depth() + 1)
+    }
+
     /// Set plan variable
     pub fn set_variable(&mut self, key: String, value: String) {
         self.plan_variables.insert(key, value);
@@ -218,6 +326,10 @@ impl PlanContext {
     }
 }
 
+/// Task IDs of a sub-plan run by task N start at `N * SUB_PLAN_ID_STRIDE + 1`,
+/// so they never collide with the parent plan's
+pub const SUB_PLAN_ID_STRIDE: usize = 1000;
+
 /// Core plan structure organizing tasks into phases with temporary execution context
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Plan {
@@ -254,6 +366,61 @@ impl Plan {
         }
     }
 
+    /// Run this plan as a sub-plan of the plan owning `parent`: the parent's
+    /// results and variables become visible to it, and its processes and
+    /// checkpoints belong to the parent plan
+    pub fn attach_to_parent(&mut self, parent: &PlanContext) {
+        self.plan_context.parent_context = Some(Box::new(parent.clone()));
+        self.plan_context.execution_metadata.plan_id = parent.execution_metadata.plan_id.clone();
+    }
+
+    /// Drop the parent context snapshot, e.g. before a finished sub-plan is
+    /// stored in its parent's task
+    pub fn detach_from_parent(&mut self) {
+        self.plan_context.parent_context = None;
+    }
+
+    /// Number this sub-plan's tasks after `task_id`, the parent task running it.
+    ///
+    /// Sub-plans share their parent's plan ID, so checkpoints, spill files and
+    /// `{{task.N}}` references need task IDs the parent doesn't use. Task 1 of
+    /// task 3's sub-plan becomes 3001; dependencies, references and earlier
+    /// results follow. Already numbered plans are left alone.
+    pub fn number_as_sub_plan_of(&mut self, task_id: usize) {
+        let base = task_id * SUB_PLAN_ID_STRIDE;
+        if self.next_task_id > base {
+            return;
+        }
+        let renumber = |id: usize| (id < self.next_task_id).then_some(base + id);
+        for task in self
+            .phases
+            .iter_mut()
+            .flat_map(|phase| phase.tasks.iter_mut())
+        {
+            task.id += base;
+            for dep in &mut task.dependencies {
+                *dep = renumber(*dep).unwrap_or(*dep);
+            }
+            if let TaskExecution::ToolCall(tool_call) = &mut task.execution {
+                tool_call.target = template::renumber_task_references(&tool_call.target, renumber);
+                tool_call.content =
+                    template::renumber_task_references(&tool_call.content, renumber);
+            }
+        }
+        let results = std::mem::take(&mut self.plan_context.task_results);
+        for (id, mut result) in results {
+            result.task_id = base + id;
+            self.plan_context.task_results.insert(base + id, result);
+        }
+        self.next_task_id += base;
+    }
+
+    /// Set the plan ID, keeping the plan context in sync
+    pub fn set_id(&mut self, id: String) {
+        self.plan_context.execution_metadata.plan_id = id.clone();
+        self.id = id;
+    }
+
     /// Add task result to plan context
     pub fn add_task_result(&mut self, result: TaskResult) {
         self.plan_context.add_task_result(result);
@@ -323,10 +490,11 @@ impl Plan {
             .collect()
     }
 
+    /// Tasks whose dependents may run: completed, decomposed or skipped
     pub fn get_completed_task_ids(&self) -> Vec<usize> {
         self.get_all_tasks()
             .iter()
-            .filter(|task| task.status == TaskStatus::Completed)
+            .filter(|task| task.status.is_done())
             .map(|task| task.id)
             .collect()
     }
@@ -349,6 +517,424 @@ impl Plan {
         self.phases.iter().all(|phase| phase.is_complete())
     }
 
+    /// Replace a task with sub-tasks run one after another in its place.
+    ///
+    /// The first sub-task inherits the original dependencies and tasks that
+    /// depended on the original depend on all sub-tasks. Sub-tasks keep their
+    /// policy and success criteria; those without a policy take the original
+    /// task's. Returns the new IDs.
+    pub fn replace_task_with_subtasks(
+        &mut self,
+        original_task_id: usize,
+        sub_tasks: Vec<Task>,
+    ) -> Result<Vec<usize>, String> {
+        let task_to_decompose = self
+            .find_task_by_id(original_task_id)
+            .ok_or_else(|| format!("Task with ID {} not found in plan", original_task_id))?;
+        let original_dependencies = task_to_decompose.dependencies.clone();
+        let original_policy = task_to_decompose.policy.clone();
+        task_to_decompose.set_status(TaskStatus::Decomposed);
+
+        let mut new_task_ids = Vec::new();
+        let mut last_task_id = original_task_id;
+
+        for (i, sub_task_template) in sub_tasks.into_iter().enumerate() {
+            let new_task_id = self.generate_task_id();
+            let mut new_dependencies = sub_task_template.dependencies;
+
+            if i == 0 {
+                new_dependencies.extend(original_dependencies.clone());
+            } else {
+                new_dependencies.push(last_task_id);
+            }
+            new_dependencies.sort();
+            new_dependencies.dedup();
+
+            let policy = if sub_task_template.policy.is_default() {
+                original_policy.clone()
+            } else {
+                sub_task_template.policy
+            };
+            let new_task = match sub_task_template.execution {
+                TaskExecution::ToolCall(tool_call) => Task::new_planned_task(
+                    new_task_id,
+                    sub_task_template.title,
+                    tool_call.tool,
+                    tool_call.target,
+                    tool_call.operation,
+                    tool_call.content,
+                ),
+                TaskExecution::SubPlan(sub_plan) => {
+                    Task::new_sub_plan_task(new_task_id, sub_task_template.title, sub_plan)
+                }
+            }
+            .with_dependencies(new_dependencies)
+            .with_policy(policy)
+            .with_success_criteria(sub_task_template.success_criteria);
+
+            self.add_task_to_phase(&new_task, None)?;
+            new_task_ids.push(new_task_id);
+            last_task_id = new_task_id;
+        }
+
+        for task in self.get_all_tasks_mut() {
+            if task.dependencies.contains(&original_task_id) {
+                task.dependencies.retain(|&dep| dep != original_task_id);
+                task.dependencies.extend(new_task_ids.clone());
+                task.dependencies.sort();
+                task.dependencies.dedup();
+            }
+        }
+        Ok(new_task_ids)
+    }
+
+    /// Splice a revision in place of a failed task.
+    ///
+    /// The failed task and dropped tasks are marked skipped. Revision tasks
+    /// get fresh IDs in the failed task's phase, and tasks that depended on
+    /// the failed task depend on all of them instead. Returns the new IDs.
+    pub fn apply_revision(
+        &mut self,
+        failed_task_id: usize,
+        revision: &PlanRevision,
+    ) -> Result<Vec<usize>, String> {
+        let failed = self
+            .get_all_tasks()
+            .into_iter()
+            .find(|task| task.id == failed_task_id)
+            .ok_or_else(|| format!("Task with ID {} not found in plan", failed_task_id))?;
+        if failed.status != TaskStatus::Failed {
+            return Err(format!("Task {} has not failed", failed_task_id));
+        }
+        let phase_name = self
+            .phases
+            .iter()
+            .find(|phase| phase.tasks.iter().any(|task| task.id == failed_task_id))
+            .map(|phase| phase.name.clone());
+
+        let mut removed = HashSet::from([failed_task_id]);
+        for &id in &revision.drop {
+            match self.get_all_tasks().into_iter().find(|task| task.id == id) {
+                Some(task) if task.status == TaskStatus::Pending => {
+                    removed.insert(id);
+                }
+                Some(_) => return Err(format!("Task {} is not pending and cannot be dropped", id)),
+                None => return Err(format!("Task with ID {} not found in plan", id)),
+            }
+        }
+
+        // Revision tasks may only wait on tasks that don't wait on the ones being
+        // replaced, otherwise rewiring the dependents would create a cycle
+        let mut downstream = removed.clone();
+        loop {
+            let before = downstream.len();
+            for task in self.get_all_tasks() {
+                if task.dependencies.iter().any(|dep| downstream.contains(dep)) {
+                    downstream.insert(task.id);
+                }
+            }
+            if downstream.len() == before {
+                break;
+            }
+        }
+
+        let first_id = self.next_task_id;
+        let id_map: HashMap<usize, usize> = revision
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(i, task)| (task.id, first_id + i))
+            .collect();
+        let mut new_tasks = Vec::new();
+        for (i, revised) in revision.tasks.iter().enumerate() {
+            let new_id = first_id + i;
+            let renumber = |text: &str| {
+                template::renumber_task_references(text, |id| id_map.get(&id).copied())
+            };
+            let (target, content) = (renumber(&revised.target), renumber(&revised.content));
+            let mut wanted = revised.dependencies.clone();
+            wanted.extend(template::task_references(&revised.target));
+            wanted.extend(template::task_references(&revised.content));
+
+            let mut dependencies = Vec::new();
+            for dep in &wanted {
+                let resolved = match id_map.get(dep) {
+                    Some(&mapped) if mapped < new_id => mapped,
+                    Some(_) => {
+                        return Err(format!(
+                            "Revised task {} depends on task {} which comes after it",
+                            revised.id, dep
+                        ))
+                    }
+                    None if removed.contains(dep) => continue,
+                    None if downstream.contains(dep) => {
+                        return Err(format!(
+                            "Revised task {} depends on task {} which waits on the failed task",
+                            revised.id, dep
+                        ))
+                    }
+                    None if self.get_all_tasks().iter().any(|task| task.id == *dep) => *dep,
+                    None => continue,
+                };
+                dependencies.push(resolved);
+            }
+            dependencies.sort();
+            dependencies.dedup();
+            new_tasks.push(
+                Task::new_planned_task(
+                    new_id,
+                    revised.title.clone(),
+                    revised.tool.clone(),
+                    target,
+                    revised.operation.clone(),
+                    content,
+                )
+                .with_dependencies(dependencies)
+                .with_policy(revised.policy.clone())
+                .with_success_criteria(revised.success_criteria.clone()),
+            );
+        }
+
+        let new_task_ids: Vec<usize> = new_tasks.iter().map(|task| task.id).collect();
+        for task in &new_tasks {
+            self.add_task_to_phase(task, phase_name.as_deref())?;
+        }
+        self.next_task_id = first_id + new_tasks.len();
+
+        for task in self.get_all_tasks_mut() {
+            if removed.contains(&task.id) {
+                task.set_status(TaskStatus::Skipped);
+            }
+            if task.dependencies.contains(&failed_task_id) {
+                task.dependencies.extend(new_task_ids.iter().copied());
+            }
+            task.dependencies.retain(|dep| !removed.contains(dep));
+            task.dependencies.sort();
+            task.dependencies.dedup();
+        }
+        self.update_task_count();
+        Ok(new_task_ids)
+    }
+
+    /// Apply a change made while reviewing the plan (see `PlanEdit`).
+    ///
+    /// Only pending tasks can be dropped or edited. Edited and added tasks
+    /// must use a known tool and depend only on other tasks of the plan; tasks
+    /// their target or content references (`{{task.N...}}`) become
+    /// dependencies. Dropping a task also drops the tasks that depend on it
+    /// (see `dependents_of`), since they would run without its results.
+    /// Returns the ID of the changed or added task.
+    pub fn apply_edit(&mut self, edit: PlanEdit) -> Result<usize, String> {
+        match edit {
+            PlanEdit::Drop(task_id) => {
+                let dependents = self.dependents_of(task_id);
+                self.pending_task_mut(task_id)?
+                    .set_status(TaskStatus::Skipped);
+                for id in dependents {
+                    if let Some(task) = self.find_task_by_id(id) {
+                        task.set_status(TaskStatus::Skipped);
+                    }
+                }
+                Ok(task_id)
+            }
+            PlanEdit::Move { task_id, before } => {
+                self.move_task(task_id, before)?;
+                Ok(task_id)
+            }
+            PlanEdit::Replace { task_id, task } => {
+                self.pending_task_mut(task_id)?;
+                let task = self.task_from_draft(task_id, &task)?;
+                if self.depends_on(&task.dependencies, task_id) {
+                    return Err(format!(
+                        "Task {} would depend on a task that waits for it",
+                        task_id
+                    ));
+                }
+                if let Some(existing) = self.find_task_by_id(task_id) {
+                    *existing = task;
+                }
+                Ok(task_id)
+            }
+            PlanEdit::Add { task, phase } => {
+                let task = self.task_from_draft(self.next_task_id, &task)?;
+                self.add_task_to_phase(&task, phase.as_deref())?;
+                self.next_task_id += 1;
+                self.update_task_count();
+                Ok(task.id)
+            }
+        }
+    }
+
+    /// Pending tasks that wait on `task_id`, directly or through other tasks
+    pub fn dependents_of(&self, task_id: usize) -> Vec<usize> {
+        let tasks = self.get_all_tasks();
+        let mut waiting = HashSet::from([task_id]);
+        loop {
+            let before = waiting.len();
+            for task in &tasks {
+                if task.dependencies.iter().any(|dep| waiting.contains(dep)) {
+                    waiting.insert(task.id);
+                }
+            }
+            if waiting.len() == before {
+                break;
+            }
+        }
+        let mut dependents: Vec<usize> = tasks
+            .iter()
+            .filter(|task| task.id != task_id && task.status == TaskStatus::Pending)
+            .map(|task| task.id)
+            .filter(|id| waiting.contains(id))
+            .collect();
+        dependents.sort_unstable();
+        dependents
+    }
+
+    fn pending_task_mut(&mut self, task_id: usize) -> Result<&mut Task, String> {
+        let task = self
+            .find_task_by_id(task_id)
+            .ok_or_else(|| format!("Task with ID {} not found in plan", task_id))?;
+        if task.status != TaskStatus::Pending {
+            return Err(format!(
+                "Task {} is not pending and cannot be changed",
+                task_id
+            ));
+        }
+        Ok(task)
+    }
+
+    /// Move a task before another one, or to the end of the last phase
+    fn move_task(&mut self, task_id: usize, before: Option<usize>) -> Result<(), String> {
+        let tasks = self.get_all_tasks();
+        let moved = tasks
+            .iter()
+            .find(|task| task.id == task_id)
+            .ok_or_else(|| format!("Task with ID {} not found in plan", task_id))?;
+        let order: Vec<usize> = tasks
+            .iter()
+            .map(|task| task.id)
+            .filter(|&id| id != task_id)
+            .collect();
+        let position = match before {
+            Some(before) => order
+                .iter()
+                .position(|&id| id == before)
+                .ok_or_else(|| format!("Task with ID {} not found in plan", before))?,
+            None => order.len(),
+        };
+        // Dependencies decide what runs first, so the order must agree with them
+        if let Some(dep) = moved
+            .dependencies
+            .iter()
+            .find(|dep| order[position..].contains(dep))
+        {
+            return Err(format!(
+                "Task {} depends on task {} and cannot come before it",
+                task_id, dep
+            ));
+        }
+        if let Some(dependent) = tasks.iter().find(|task| {
+            task.dependencies.contains(&task_id) && order[..position].contains(&task.id)
+        }) {
+            return Err(format!(
+                "Task {} depends on task {} and cannot come before it",
+                dependent.id, task_id
+            ));
+        }
+
+        let mut task = None;
+        for phase in &mut self.phases {
+            if let Some(index) = phase.tasks.iter().position(|t| t.id == task_id) {
+                task = Some(phase.tasks.remove(index));
+            }
+        }
+        let task = task.ok_or_else(|| format!("Task with ID {} not found in plan", task_id))?;
+        let phase = match before {
+            Some(before) => self
+                .phases
+                .iter_mut()
+                .find(|phase| phase.tasks.iter().any(|t| t.id == before)),
+            None => self.phases.last_mut(),
+        };
+        match phase {
+            Some(phase) => {
+                let index = before
+                    .and_then(|before| phase.tasks.iter().position(|t| t.id == before))
+                    .unwrap_or(phase.tasks.len());
+                phase.tasks.insert(index, task);
+            }
+            None => return Err("No phases in plan to move the task to".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Build task `id` from an edited definition, checking its tool and dependencies
+    fn task_from_draft(&self, id: usize, draft: &RevisedTask) -> Result<Task, String> {
+        if draft.title.trim().is_empty() {
+            return Err(format!("Task {} needs a title", id));
+        }
+        if !PlanValidator::new().is_known_tool(&draft.tool) {
+            return Err(format!("Task {} uses unknown tool '{}'", id, draft.tool));
+        }
+        let mut dependencies = draft.dependencies.clone();
+        dependencies.extend(template::task_references(&draft.target));
+        dependencies.extend(template::task_references(&draft.content));
+        dependencies.sort();
+        dependencies.dedup();
+        let tasks = self.get_all_tasks();
+        for &dep in &dependencies {
+            if dep == id {
+                return Err(format!("Task {} cannot depend on itself", id));
+            }
+            if !tasks.iter().any(|task| task.id == dep) {
+                return Err(format!("Task {} depends on unknown task {}", id, dep));
+            }
+        }
+
+        Ok(Task::new_planned_task(
+            id,
+            draft.title.clone(),
+            draft.tool.clone(),
+            draft.target.clone(),
+            draft.operation.clone(),
+            draft.content.clone(),
+        )
+        .with_dependencies(dependencies)
+        .with_policy(draft.policy.clone())
+        .with_success_criteria(draft.success_criteria.clone()))
+    }
+
+    /// Whether any of `dependencies` is `task_id` or (transitively) waits on it
+    fn depends_on(&self, dependencies: &[usize], task_id: usize) -> bool {
+        let tasks = self.get_all_tasks();
+        let mut seen = HashSet::new();
+        let mut pending = dependencies.to_vec();
+        while let Some(id) = pending.pop() {
+            if id == task_id {
+                return true;
+            }
+            if seen.insert(id) {
+                if let Some(task) = tasks.iter().find(|task| task.id == id) {
+                    pending.extend(&task.dependencies);
+                }
+            }
+        }
+        false
+    }
+
+    /// Make tasks that were running or had failed when the plan stopped
+    /// runnable again; completed work is kept. Returns how many were reset.
+    pub fn reset_unfinished_tasks(&mut self) -> usize {
+        let mut reset = 0;
+        for task in self.get_all_tasks_mut() {
+            if matches!(task.status, TaskStatus::InProgress | TaskStatus::Failed) {
+                task.set_status(TaskStatus::Pending);
+                reset += 1;
+            }
+        }
+        reset
+    }
+
     pub fn get_progress(&self) -> (usize, usize) {
         let completed = self
             .get_all_tasks()
@@ -459,3 +1045,89 @@ fn create_progress_bar(completed: usize, total: usize, width: usize) -> String {
         completed_bar, remaining_bar, completed, total
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::task::TaskPolicy;
+
+    fn tool_task(id: usize, tool: &str) -> Task {
+        Task::new_tool_task(
+            id,
+            format!("Task {}", id),
+            tool.to_string(),
+            "target".to_string(),
+            String::new(),
+            String::new(),
+        )
+    }
+
+    #[test]
+    fn test_decomposed_tasks_keep_policies() {
+        let mut plan = Plan::new("Build".to_string(), String::new());
+        let mut phase = Phase::new("Work".to_string(), "🔧".to_string());
+        let retried = TaskPolicy {
+            max_retries: 2,
+            timeout_secs: Some(30),
+            ..Default::default()
+        };
+        phase.add_task(tool_task(plan.generate_task_id(), "bash").with_policy(retried.clone()));
+        plan.add_phase(phase);
+
+        let optional = TaskPolicy {
+            optional: true,
+            ..Default::default()
+        };
+        let sub_tasks = vec![
+            tool_task(0, "bash"),
+            tool_task(0, "write_file")
+                .with_policy(optional.clone())
+                .with_success_criteria(Some("File exists".to_string())),
+            tool_task(0, "sub_plan"),
+        ];
+        let ids = plan.replace_task_with_subtasks(1, sub_tasks).unwrap();
+        assert_eq!(ids, vec![2, 3, 4]);
+
+        let tasks = plan.get_all_tasks();
+        assert_eq!(tasks[0].status, TaskStatus::Decomposed);
+        // Sub-tasks without a policy of their own inherit the decomposed task's
+        assert_eq!(tasks[1].policy, retried);
+        assert_eq!(tasks[2].policy, optional);
+        assert_eq!(tasks[2].success_criteria.as_deref(), Some("File exists"));
+        assert!(matches!(tasks[3].execution, TaskExecution::SubPlan(_)));
+        assert_eq!(tasks[3].dependencies, vec![3]);
+    }
+
+    #[test]
+    fn test_number_as_sub_plan_of() {
+        let mut plan = Plan::new("Sub".to_string(), String::new());
+        let mut phase = Phase::new("Work".to_string(), "🔧".to_string());
+        phase.add_task(tool_task(plan.generate_task_id(), "bash"));
+        phase.add_task(
+            Task::new_tool_task(
+                plan.generate_task_id(),
+                "Copy".to_string(),
+                "write_file".to_string(),
+                "{{task.1.stdout}}".to_string(),
+                String::new(),
+                "{{task.1.stdout}} {{task.7.stdout}}".to_string(),
+            )
+            .with_dependencies(vec![1]),
+        );
+        plan.add_phase(phase);
+        plan.add_task_result(TaskResult::failed(1, "boom"));
+
+        plan.number_as_sub_plan_of(3);
+        plan.number_as_sub_plan_of(3);
+        let copy = plan.find_task_by_id(3002).unwrap().clone();
+        assert_eq!(copy.dependencies, vec![3001]);
+        let TaskExecution::ToolCall(tool_call) = &copy.execution else {
+            panic!("task 3002 should be a tool call");
+        };
+        assert_eq!(tool_call.target, "{{task.3001.stdout}}");
+        // Task 7 isn't part of the sub-plan, so the reference is left for the parent
+        assert_eq!(tool_call.content, "{{task.3001.stdout}} {{task.7.stdout}}");
+        assert_eq!(plan.get_task_result(3001).unwrap().task_id, 3001);
+        assert_eq!(plan.generate_task_id(), 3003);
+    }
+}
@@ -0,0 +1,136 @@
+//! Plan review
+//!
+//! Before a plan runs, the user can drop, move, edit or add tasks. Each change
+//! is a `PlanEdit` that `Plan::apply_edit` checks against the rest of the
+//! plan, so a reviewed plan stays runnable. Edited tasks use the same
+//! definition as plan revisions (`RevisedTask`).
+
+use crate::planer::revision::RevisedTask;
+
+/// A change to a plan that has not started yet
+#[derive(Debug, Clone)]
+pub enum PlanEdit {
+    /// Leave a pending task out; it and the tasks depending on it are marked skipped
+    Drop(usize),
+    /// Move a task before another one, or to the end of the plan with `None`
+    Move {
+        task_id: usize,
+        before: Option<usize>,
+    },
+    /// Replace a pending task's definition, keeping its ID
+    Replace { task_id: usize, task: RevisedTask },
+    /// Add a task to the named phase, or to the last phase with `None`
+    Add {
+        task: RevisedTask,
+        phase: Option<String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::plan::{Phase, Plan};
+    use crate::planer::task::{Task, TaskExecution, TaskStatus};
+
+    fn plan() -> Plan {
+        // 1 <- 2, and 3 is independent
+        let mut plan = Plan::new("Build".to_string(), String::new());
+        let mut phase = Phase::new("Work".to_string(), "🔧".to_string());
+        for deps in [vec![], vec![1], vec![]] {
+            let id = plan.generate_task_id();
+            phase.add_task(
+                Task::new_tool_task(
+                    id,
+                    format!("Task {}", id),
+                    "bash".to_string(),
+                    "true".to_string(),
+                    String::new(),
+                    String::new(),
+                )
+                .with_dependencies(deps),
+            );
+        }
+        plan.add_phase(phase);
+        plan
+    }
+
+    fn order(plan: &Plan) -> Vec<usize> {
+        plan.get_all_tasks().iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let mut plan = plan();
+
+        assert!(plan
+            .apply_edit(PlanEdit::Move {
+                task_id: 2,
+                before: Some(1)
+            })
+            .is_err());
+        plan.apply_edit(PlanEdit::Move {
+            task_id: 3,
+            before: Some(1),
+        })
+        .unwrap();
+        assert_eq!(order(&plan), vec![3, 1, 2]);
+
+        let mut draft = RevisedTask::from_task(plan.get_all_tasks()[1]);
+        draft.target = "cat {{task.3.stdout}}".to_string();
+        plan.apply_edit(PlanEdit::Replace {
+            task_id: 1,
+            task: draft.clone(),
+        })
+        .unwrap();
+        let edited = plan.find_task_by_id(1).unwrap().clone();
+        assert_eq!(edited.dependencies, vec![3]);
+        let TaskExecution::ToolCall(tool_call) = &edited.execution else {
+            panic!("task 1 should be a tool call");
+        };
+        assert_eq!(tool_call.target, "cat {{task.3.stdout}}");
+
+        // Task 2 waits on 1, so 1 may not wait on 2
+        draft.dependencies = vec![2];
+        assert!(plan
+            .apply_edit(PlanEdit::Replace {
+                task_id: 1,
+                task: draft.clone()
+            })
+            .is_err());
+        draft.dependencies = vec![];
+        draft.tool = "teleport".to_string();
+        assert!(plan
+            .apply_edit(PlanEdit::Add {
+                task: draft.clone(),
+                phase: None
+            })
+            .is_err());
+
+        draft.tool = "sub_plan".to_string();
+        draft.target = "Write the docs".to_string();
+        draft.dependencies = vec![2];
+        assert_eq!(
+            plan.apply_edit(PlanEdit::Add {
+                task: draft,
+                phase: None
+            }),
+            Ok(4)
+        );
+        assert!(matches!(
+            plan.find_task_by_id(4).unwrap().execution,
+            TaskExecution::SubPlan(_)
+        ));
+        assert_eq!(plan.next_task_id, 5);
+
+        // Task 4 waits on 2, so dropping 2 drops 4 as well
+        assert_eq!(plan.dependents_of(3), vec![1, 2, 4]);
+        assert_eq!(plan.dependents_of(2), vec![4]);
+        plan.apply_edit(PlanEdit::Drop(2)).unwrap();
+        let status = |plan: &mut Plan, id| plan.find_task_by_id(id).unwrap().status.clone();
+        assert_eq!(status(&mut plan, 2), TaskStatus::Skipped);
+        assert_eq!(status(&mut plan, 4), TaskStatus::Skipped);
+        assert_eq!(status(&mut plan, 1), TaskStatus::Pending);
+        assert_eq!(plan.dependents_of(3), vec![1]);
+        assert!(plan.apply_edit(PlanEdit::Drop(2)).is_err());
+    }
+}
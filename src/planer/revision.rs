@@ -0,0 +1,213 @@
+//! Plan revisions
+//!
+//! When a task fails, the planner LLM is asked how to continue: with a fix,
+//! an alternative approach, or by skipping the task. Its answer is a
+//! `PlanRevision` that `Plan::apply_revision` splices into the plan.
+
+use crate::planer::task::{Task, TaskExecution, TaskPolicy, SUB_PLAN_TOOL};
+use crate::planer::validator::PlanValidator;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How a revision deals with the failed task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionAction {
+    /// Repair what went wrong, then carry on as planned
+    Fix,
+    /// Reach the failed task's goal another way
+    Alternative,
+    /// Continue without the failed task
+    Skip,
+}
+
+impl fmt::Display for RevisionAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RevisionAction::Fix => "fix",
+            RevisionAction::Alternative => "alternative",
+            RevisionAction::Skip => "skip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A task proposed by a revision, or edited while reviewing a plan.
+///
+/// `id` is only meaningful within the revision; dependencies may name other
+/// revision tasks or existing plan tasks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisedTask {
+    pub id: usize,
+    pub title: String,
+    pub tool: String,
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub operation: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub dependencies: Vec<usize>,
+    #[serde(default)]
+    pub success_criteria: Option<String>,
+    #[serde(flatten)]
+    pub policy: TaskPolicy,
+}
+
+impl RevisedTask {
+    /// The editable definition of `task`; a sub-plan becomes a `sub_plan`
+    /// task with its title as the goal, to be planned again when it runs
+    pub fn from_task(task: &Task) -> Self {
+        let (tool, target, operation, content) = match &task.execution {
+            TaskExecution::ToolCall(tool_call) => (
+                tool_call.tool.clone(),
+                tool_call.target.clone(),
+                tool_call.operation.clone(),
+                tool_call.content.clone(),
+            ),
+            TaskExecution::SubPlan(plan) => (
+                SUB_PLAN_TOOL.to_string(),
+                plan.title.clone(),
+                plan.overview.clone(),
+                String::new(),
+            ),
+        };
+        Self {
+            id: task.id,
+            title: task.title.clone(),
+            tool,
+            target,
+            operation,
+            content,
+            dependencies: task.dependencies.clone(),
+            success_criteria: task.success_criteria.clone(),
+            policy: task.policy.clone(),
+        }
+    }
+}
+
+/// The planner's answer to a failed task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanRevision {
+    pub action: RevisionAction,
+    #[serde(default)]
+    pub reason: String,
+    /// Tasks that take the failed task's place
+    #[serde(default)]
+    pub tasks: Vec<RevisedTask>,
+    /// Pending tasks that are no longer needed
+    #[serde(default)]
+    pub drop: Vec<usize>,
+}
+
+impl PlanRevision {
+    /// Reject revisions that could not be executed
+    pub fn check(&self, validator: &PlanValidator) -> Result<(), String> {
+        match self.action {
+            RevisionAction::Skip if !self.tasks.is_empty() => {
+                return Err("A 'skip' revision must not add tasks".to_string())
+            }
+            RevisionAction::Fix | RevisionAction::Alternative if self.tasks.is_empty() => {
+                return Err(format!(
+                    "A '{}' revision must add at least one task",
+                    self.action
+                ))
+            }
+            _ => {}
+        }
+        for task in &self.tasks {
+            if !validator.is_known_tool(&task.tool) {
+                return Err(format!(
+                    "Revised task {} uses unknown tool '{}'",
+                    task.id, task.tool
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::plan::{Phase, Plan};
+    use crate::planer::task::{Task, TaskStatus};
+
+    #[test]
+    fn test_parse_and_check() {
+        let validator = PlanValidator::new();
+        let revision: PlanRevision = serde_json::from_str(
+            r#"{"action": "fix", "reason": "missing dir",
+                "tasks": [{"id": 7, "title": "Create dir", "tool": "create_path", "target": "out/"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(revision.action, RevisionAction::Fix);
+        assert!(revision.drop.is_empty());
+        assert!(revision.check(&validator).is_ok());
+
+        let skip: PlanRevision =
+            serde_json::from_str(r#"{"action": "skip", "drop": [4]}"#).unwrap();
+        assert!(skip.check(&validator).is_ok());
+
+        let empty_fix: PlanRevision = serde_json::from_str(r#"{"action": "fix"}"#).unwrap();
+        assert!(empty_fix.check(&validator).is_err());
+
+        let mut unknown = revision.clone();
+        unknown.tasks[0].tool = "teleport".to_string();
+        assert!(unknown.check(&validator).is_err());
+    }
+
+    #[test]
+    fn test_apply_revision_rewires_dependencies() {
+        // 1 <- 2 (failed) <- 3, and 4 is an unrelated pending task
+        let mut plan = Plan::new("Build".to_string(), String::new());
+        let mut phase = Phase::new("Work".to_string(), "🔧".to_string());
+        for (deps, status) in [
+            (vec![], TaskStatus::Completed),
+            (vec![1], TaskStatus::Failed),
+            (vec![2], TaskStatus::Pending),
+            (vec![], TaskStatus::Pending),
+        ] {
+            let id = plan.generate_task_id();
+            let mut task = Task::new_tool_task(
+                id,
+                format!("Task {}", id),
+                "bash".to_string(),
+                "true".to_string(),
+                String::new(),
+                String::new(),
+            )
+            .with_dependencies(deps);
+            task.set_status(status);
+            phase.add_task(task);
+        }
+        plan.add_phase(phase);
+
+        let revision: PlanRevision = serde_json::from_str(
+            r#"{"action": "fix", "drop": [4], "tasks": [
+                {"id": 5, "title": "Install", "tool": "bash", "target": "make deps", "dependencies": [1]},
+                {"id": 6, "title": "Retry", "tool": "bash", "target": "make", "dependencies": [5, 2]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(plan.apply_revision(2, &revision).unwrap(), vec![5, 6]);
+
+        let task = |plan: &mut Plan, id| plan.find_task_by_id(id).unwrap().clone();
+        assert_eq!(task(&mut plan, 2).status, TaskStatus::Skipped);
+        assert_eq!(task(&mut plan, 4).status, TaskStatus::Skipped);
+        assert_eq!(task(&mut plan, 5).dependencies, vec![1]);
+        assert_eq!(task(&mut plan, 6).dependencies, vec![5]);
+        assert_eq!(task(&mut plan, 3).dependencies, vec![5, 6]);
+        assert_eq!(plan.next_task_id, 7);
+        assert_eq!(plan.get_next_ready_tasks()[0].id, 5);
+
+        // A revision task may not wait on a dependent of the failed task
+        let mut cyclic = revision.clone();
+        cyclic.drop.clear();
+        cyclic.tasks[1].dependencies = vec![3];
+        plan.find_task_by_id(6)
+            .unwrap()
+            .set_status(TaskStatus::Failed);
+        assert!(plan.apply_revision(6, &cyclic).is_err());
+    }
+}
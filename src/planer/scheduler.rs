@@ -0,0 +1,684 @@
+//! Concurrent plan scheduler
+//!
+//! Runs a plan's tasks as their dependencies complete, up to a configurable
+//! number at a time. Before a task starts it claims the resources it touches
+//! (paths, or the whole working directory for shell commands); a task that
+//! would write where another running task reads or writes waits until that
+//! task finishes. Results are recorded in `PlanContext` keyed by task ID and
+//! returned in task order, so the outcome does not depend on which task
+//! happened to finish first. With a `PlanJournal` the plan is saved after
+//! every task state change.
+
+use crate::planer::journal::PlanJournal;
+use crate::planer::plan::{Plan, TaskResult};
+use crate::planer::revision::RevisionAction;
+use crate::planer::task::{Task, TaskExecution, TaskStatus};
+use crate::planer::PlanContext;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Tasks run at the same time unless configured otherwise
+pub const DEFAULT_MAX_WORKERS: usize = 4;
+
+/// What running a task produced
+#[derive(Debug)]
+pub enum TaskOutcome {
+    Finished(TaskResult),
+    /// The task was split up; the sub-tasks replace it in the plan
+    Decomposed(Vec<Task>),
+    /// A sub-plan task ran its plan; the plan's new state is kept in the task
+    SubPlan {
+        plan: Box<Plan>,
+        result: TaskResult,
+    },
+}
+
+/// Progress notifications emitted while a plan runs
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    Started {
+        task_id: usize,
+        title: String,
+        running: usize,
+    },
+    Finished {
+        task_id: usize,
+        title: String,
+        success: bool,
+        elapsed: Duration,
+        running: usize,
+    },
+    Decomposed {
+        task_id: usize,
+        title: String,
+        sub_tasks: usize,
+    },
+    /// A failed attempt is run again after `delay`
+    Retrying {
+        task_id: usize,
+        title: String,
+        attempt: u32,
+        max_retries: u32,
+        delay: Duration,
+    },
+    /// A failed task was replaced by a plan revision
+    Replanned {
+        task_id: usize,
+        title: String,
+        action: RevisionAction,
+        reason: String,
+        new_tasks: Vec<usize>,
+        dropped: Vec<usize>,
+    },
+    /// No usable revision could be made for a failed task
+    ReplanFailed {
+        task_id: usize,
+        title: String,
+        error: String,
+    },
+    /// The tasks of a sub-plan task were planned
+    SubPlanExpanded {
+        task_id: usize,
+        title: String,
+        tasks: usize,
+    },
+    /// An event from the sub-plan run by task `task_id`
+    SubPlan {
+        task_id: usize,
+        event: Box<ProgressEvent>,
+    },
+}
+
+/// Receives `ProgressEvent`s; called from the scheduling loop, so keep it quick
+pub type ProgressCallback = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+/// A resource a task uses while it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceClaim {
+    pub path: PathBuf,
+    /// Writers need the path (and everything below it) to themselves
+    pub exclusive: bool,
+}
+
+impl ResourceClaim {
+    pub fn read<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            exclusive: false,
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            exclusive: true,
+        }
+    }
+
+    /// Two claims conflict when one path contains the other and either writes
+    pub fn conflicts_with(&self, other: &ResourceClaim) -> bool {
+        (self.exclusive || other.exclusive)
+            && (self.path.starts_with(&other.path) || other.path.starts_with(&self.path))
+    }
+}
+
+/// Resources held by running tasks
+#[derive(Debug, Default)]
+pub struct ResourceLocks {
+    held: BTreeMap<usize, Vec<ResourceClaim>>,
+}
+
+impl ResourceLocks {
+    /// Take all of `claims` for `task_id`, or none of them if any is in use
+    pub fn try_acquire(&mut self, task_id: usize, claims: &[ResourceClaim]) -> bool {
+        let free = self
+            .held
+            .values()
+            .flatten()
+            .all(|held| claims.iter().all(|claim| !claim.conflicts_with(held)));
+        if free {
+            self.held.insert(task_id, claims.to_vec());
+        }
+        free
+    }
+
+    pub fn release(&mut self, task_id: usize) {
+        self.held.remove(&task_id);
+    }
+}
+
+/// Runs ready plan tasks concurrently (see module docs)
+#[derive(Clone)]
+pub struct PlanScheduler {
+    pub max_workers: usize,
+    progress: Option<ProgressCallback>,
+    /// Where the plan is saved after every task state change
+    journal: Option<PlanJournal>,
+}
+
+impl std::fmt::Debug for PlanScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PlanScheduler")
+            .field("max_workers", &self.max_workers)
+            .field("progress", &self.progress.is_some())
+            .field("journal", &self.journal)
+            .finish()
+    }
+}
+
+impl Default for PlanScheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_WORKERS)
+    }
+}
+
+impl PlanScheduler {
+    pub fn new(max_workers: usize) -> Self {
+        Self {
+            max_workers: max_workers.max(1),
+            progress: None,
+            journal: None,
+        }
+    }
+
+    pub fn with_progress(mut self, progress: Option<ProgressCallback>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_journal(mut self, journal: Option<PlanJournal>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Scheduler for the sub-plan of task `task_id`: its events are wrapped in
+    /// `ProgressEvent::SubPlan`, and it is journaled as part of the parent plan
+    pub fn nested(&self, task_id: usize) -> Self {
+        let progress = self.progress.clone().map(|progress| -> ProgressCallback {
+            Arc::new(move |event: &ProgressEvent| {
+                progress(&ProgressEvent::SubPlan {
+                    task_id,
+                    event: Box::new(event.clone()),
+                })
+            })
+        });
+        Self {
+            max_workers: self.max_workers,
+            progress,
+            journal: None,
+        }
+    }
+
+    /// Journal the plan (best effort: a failed write must not stop the plan)
+    fn save(&self, plan: &Plan) {
+        if let Some(journal) = self.journal.as_ref().filter(|_| !plan.id.is_empty()) {
+            if let Err(e) = journal.save(plan) {
+                eprintln!("WARNING: Failed to journal plan '{}': {}", plan.id, e);
+            }
+        }
+    }
+
+    /// Pass an event to the progress callback, if any
+    pub fn emit(&self, event: ProgressEvent) {
+        if let Some(progress) = &self.progress {
+            progress(&event);
+        }
+    }
+
+    /// Run `plan` until no task is ready or a task fails.
+    ///
+    /// `claims` names the resources a task needs; `run_task` is spawned on the
+    /// runtime with the task and a snapshot of the plan context. Each task's
+    /// `TaskPolicy` is honored: attempts are bounded by its timeout, failures
+    /// are retried with backoff, an optional task's failure marks it skipped,
+    /// and `continue_on_failure` keeps independent tasks starting. Tasks
+    /// already running when one fails are allowed to finish. Returns the
+    /// results of this run ordered by task ID.
+    pub async fn run<C, F, Fut>(&self, plan: &mut Plan, claims: C, run_task: F) -> Vec<TaskResult>
+    where
+        C: Fn(&Task) -> Vec<ResourceClaim>,
+        F: Fn(Task, PlanContext) -> Fut,
+        Fut: Future<Output = TaskOutcome> + Send + 'static,
+    {
+        let mut locks = ResourceLocks::default();
+        let mut running = FuturesUnordered::new();
+        let mut results = Vec::new();
+        let mut failed = false;
+
+        let launch = |task: Task, plan_context: PlanContext, attempt: u32| {
+            let (task_id, policy) = (task.id, task.policy.clone());
+            let delay = if attempt > 0 {
+                policy.backoff(attempt)
+            } else {
+                Duration::ZERO
+            };
+            let work = run_task(task, plan_context);
+            let handle = tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                match policy.timeout() {
+                    Some(limit) => tokio::time::timeout(limit, work).await.unwrap_or_else(|_| {
+                        TaskOutcome::Finished(TaskResult::failed(
+                            task_id,
+                            &format!("Timed out after {}s", limit.as_secs()),
+                        ))
+                    }),
+                    None => work.await,
+                }
+            });
+            let started = Instant::now();
+            async move {
+                let outcome = handle.await.unwrap_or_else(|e| {
+                    TaskOutcome::Finished(TaskResult::failed(task_id, &e.to_string()))
+                });
+                (task_id, attempt, outcome, started.elapsed().saturating_sub(delay))
+            }
+        };
+
+        self.save(plan);
+        loop {
+            if !failed {
+                let ready: Vec<Task> = plan.get_next_ready_tasks().into_iter().cloned().collect();
+                let mut launched = false;
+                for task in ready {
+                    if running.len() >= self.max_workers {
+                        break;
+                    }
+                    if !locks.try_acquire(task.id, &claims(&task)) {
+                        continue;
+                    }
+                    if let Some(t) = plan.find_task_by_id(task.id) {
+                        t.set_status(TaskStatus::InProgress);
+                    }
+                    launched = true;
+
+                    let (task_id, title) = (task.id, task.title.clone());
+                    running.push(launch(task, plan.plan_context.clone(), 0));
+                    self.emit(ProgressEvent::Started {
+                        task_id,
+                        title,
+                        running: running.len(),
+                    });
+                }
+                if launched {
+                    self.save(plan);
+                }
+            }
+
+            let Some((task_id, attempt, outcome, elapsed)) = running.next().await else {
+                break;
+            };
+            // A sub-plan's progress is kept even if the task is retried
+            let outcome = match outcome {
+                TaskOutcome::SubPlan {
+                    plan: sub_plan,
+                    result,
+                } => {
+                    if let Some(t) = plan.find_task_by_id(task_id) {
+                        t.execution = TaskExecution::SubPlan(*sub_plan);
+                    }
+                    TaskOutcome::Finished(result)
+                }
+                outcome => outcome,
+            };
+            let Some(task) = plan.find_task_by_id(task_id).cloned() else {
+                locks.release(task_id);
+                continue;
+            };
+            let title = task.title.clone();
+
+            match outcome {
+                TaskOutcome::Finished(result)
+                    if !result.success && attempt < task.policy.max_retries =>
+                {
+                    // Keep the task's locks: nothing else may touch its paths in between
+                    let retry = attempt + 1;
+                    self.emit(ProgressEvent::Retrying {
+                        task_id,
+                        title,
+                        attempt: retry,
+                        max_retries: task.policy.max_retries,
+                        delay: task.policy.backoff(retry),
+                    });
+                    running.push(launch(task, plan.plan_context.clone(), retry));
+                    continue;
+                }
+                TaskOutcome::Finished(result) => {
+                    locks.release(task_id);
+                    let status = if result.success {
+                        TaskStatus::Completed
+                    } else if task.policy.optional {
+                        TaskStatus::Skipped
+                    } else {
+                        failed |= !task.policy.continue_on_failure;
+                        TaskStatus::Failed
+                    };
+                    if let Some(t) = plan.find_task_by_id(task_id) {
+                        t.set_status(status);
+                    }
+                    self.emit(ProgressEvent::Finished {
+                        task_id,
+                        title,
+                        success: result.success,
+                        elapsed,
+                        running: running.len(),
+                    });
+                    // Extracted variables become `{{vars.*}}` for later tasks
+                    if result.success {
+                        for (key, value) in &result.extracted_variables {
+                            plan.set_plan_variable(key.clone(), value.clone());
+                        }
+                    }
+                    plan.add_task_result(result.clone());
+                    results.push(result);
+                }
+                TaskOutcome::SubPlan { .. } => unreachable!("converted to Finished above"),
+                TaskOutcome::Decomposed(sub_tasks) => {
+                    locks.release(task_id);
+                    let count = sub_tasks.len();
+                    if let Err(e) = plan.replace_task_with_subtasks(task_id, sub_tasks) {
+                        if let Some(t) = plan.find_task_by_id(task_id) {
+                            t.set_status(TaskStatus::Failed);
+                        }
+                        failed = true;
+                        let result = TaskResult::failed(task_id, &e);
+                        plan.add_task_result(result.clone());
+                        results.push(result);
+                    } else {
+                        self.emit(ProgressEvent::Decomposed {
+                            task_id,
+                            title,
+                            sub_tasks: count,
+                        });
+                    }
+                }
+            }
+            self.save(plan);
+        }
+
+        results.sort_by_key(|result| result.task_id);
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::plan::Phase;
+    use crate::planer::task::TaskPolicy;
+    use std::sync::Mutex;
+
+    fn plan_with(tasks: Vec<Task>) -> Plan {
+        let mut plan = Plan::new("Test".to_string(), String::new());
+        let mut phase = Phase::new("Phase".to_string(), "🔧".to_string());
+        for task in tasks {
+            plan.generate_task_id();
+            phase.add_task(task);
+        }
+        plan.add_phase(phase);
+        plan
+    }
+
+    fn task(id: usize, target: &str, dependencies: Vec<usize>) -> Task {
+        Task::new_tool_task(
+            id,
+            format!("Task {}", id),
+            "write_file".to_string(),
+            target.to_string(),
+            String::new(),
+            String::new(),
+        )
+        .with_dependencies(dependencies)
+    }
+
+    fn result(task_id: usize, success: bool) -> TaskResult {
+        TaskResult {
+            task_id,
+            tool_result: String::new(),
+            llm_processed_result: String::new(),
+            extracted_variables: std::collections::HashMap::new(),
+            success,
+            executed_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_resource_locks() {
+        let mut locks = ResourceLocks::default();
+        assert!(locks.try_acquire(1, &[ResourceClaim::read("/w/src")]));
+        assert!(locks.try_acquire(2, &[ResourceClaim::read("/w/src/lib.rs")]));
+        assert!(!locks.try_acquire(3, &[ResourceClaim::write("/w/src/main.rs")]));
+        assert!(locks.try_acquire(4, &[ResourceClaim::write("/w/README.md")]));
+        locks.release(1);
+        assert!(locks.try_acquire(3, &[ResourceClaim::write("/w/src/main.rs")]));
+        assert!(!locks.try_acquire(5, &[ResourceClaim::write("/w")]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_runs_independent_tasks_concurrently() {
+        // 1 and 2 are independent, 3 writes the same file as 1, 4 needs 2 and 3
+        let mut plan = plan_with(vec![
+            task(1, "a.txt", vec![]),
+            task(2, "b.txt", vec![]),
+            task(3, "a.txt", vec![]),
+            task(4, "c.txt", vec![2, 3]),
+        ]);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        let scheduler = PlanScheduler::new(4).with_progress(Some(Arc::new(move |event| {
+            log.lock().unwrap().push(event.clone());
+        })));
+
+        let results = scheduler
+            .run(
+                &mut plan,
+                |task| match &task.execution {
+                    crate::planer::task::TaskExecution::ToolCall(tc) => {
+                        vec![ResourceClaim::write(&tc.target)]
+                    }
+                    _ => Vec::new(),
+                },
+                |task, context| async move {
+                    // Task 4 sees the results of its dependencies
+                    if task.id == 4 {
+                        assert!(context.get_task_result(2).is_some());
+                        assert!(context.get_task_result(3).is_some());
+                    }
+                    let delay = if task.id == 1 { 100 } else { 10 };
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    TaskOutcome::Finished(result(task.id, true))
+                },
+            )
+            .await;
+
+        assert_eq!(
+            results.iter().map(|r| r.task_id).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert!(plan.is_complete());
+
+        let events = events.lock().unwrap();
+        let started: Vec<usize> = events
+            .iter()
+            .filter_map(|e| match e {
+                ProgressEvent::Started { task_id, .. } => Some(*task_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(started, vec![1, 2, 3, 4]);
+        // Task 2 finishes while task 1 is still running; task 3 waits for task 1's lock
+        let max_running = events
+            .iter()
+            .filter_map(|e| match e {
+                ProgressEvent::Started { running, .. } => Some(*running),
+                _ => None,
+            })
+            .max();
+        assert_eq!(max_running, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_stops_scheduling_after_failure() {
+        let mut plan = plan_with(vec![task(1, "a.txt", vec![]), task(2, "b.txt", vec![1])]);
+        let results = PlanScheduler::new(2)
+            .run(
+                &mut plan,
+                |_| Vec::new(),
+                |task, _| async move { TaskOutcome::Finished(result(task.id, false)) },
+            )
+            .await;
+
+        assert_eq!(results.len(), 1);
+        let statuses: Vec<TaskStatus> = plan
+            .get_all_tasks()
+            .iter()
+            .map(|t| t.status.clone())
+            .collect();
+        assert_eq!(statuses, vec![TaskStatus::Failed, TaskStatus::Pending]);
+    }
+
+    #[tokio::test]
+    async fn test_honors_task_policies() {
+        let policy = |f: fn(&mut TaskPolicy)| {
+            let mut policy = TaskPolicy::default();
+            f(&mut policy);
+            policy
+        };
+        // 1 succeeds on its third attempt, 2 is optional, 3 needs 2,
+        // 4 may fail without stopping 5, 6 times out
+        let mut plan = plan_with(vec![
+            task(1, "a", vec![]).with_policy(policy(|p| {
+                p.max_retries = 2;
+                p.retry_backoff_ms = 5;
+            })),
+            task(2, "b", vec![]).with_policy(policy(|p| p.optional = true)),
+            task(3, "c", vec![2]),
+            task(4, "d", vec![]).with_policy(policy(|p| p.continue_on_failure = true)),
+            task(5, "e", vec![]),
+            task(6, "f", vec![]).with_policy(policy(|p| p.timeout_secs = Some(1))),
+        ]);
+        let attempts = Arc::new(Mutex::new(std::collections::HashMap::new()));
+        let counter = attempts.clone();
+
+        let results = PlanScheduler::new(1)
+            .run(
+                &mut plan,
+                |_| Vec::new(),
+                move |task, _| {
+                    let attempt = {
+                        let mut counts = counter.lock().unwrap();
+                        let count = counts.entry(task.id).or_insert(0);
+                        *count += 1;
+                        *count
+                    };
+                    async move {
+                        match task.id {
+                            1 => TaskOutcome::Finished(result(1, attempt == 3)),
+                            2 | 4 => TaskOutcome::Finished(result(task.id, false)),
+                            6 => {
+                                tokio::time::sleep(Duration::from_secs(10)).await;
+                                TaskOutcome::Finished(result(6, true))
+                            }
+                            id => TaskOutcome::Finished(result(id, true)),
+                        }
+                    }
+                },
+            )
+            .await;
+
+        assert_eq!(attempts.lock().unwrap()[&1], 3);
+        assert_eq!(results.len(), 6);
+        assert!(results[5].tool_result.contains("Timed out"));
+        let statuses: Vec<TaskStatus> = plan
+            .get_all_tasks()
+            .iter()
+            .map(|t| t.status.clone())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TaskStatus::Completed,
+                TaskStatus::Skipped,
+                TaskStatus::Completed,
+                TaskStatus::Failed,
+                TaskStatus::Completed,
+                TaskStatus::Failed,
+            ]
+        );
+    }
+
+    /// Run one `tool` task in `dir` with a 1s timeout through the executor
+    async fn run_with_timeout(
+        dir: &std::path::Path,
+        tool: &str,
+        target: &str,
+    ) -> (Duration, TaskResult, TaskStatus) {
+        let executor = crate::planer::task_executor::TaskExecutor::new().with_workdir(dir);
+        let mut task = Task::new_tool_task(
+            1,
+            "Slow".to_string(),
+            tool.to_string(),
+            target.to_string(),
+            String::new(),
+            String::new(),
+        );
+        task.policy.timeout_secs = Some(1);
+        let mut plan = plan_with(vec![task]);
+
+        let started = Instant::now();
+        let results = PlanScheduler::new(1)
+            .run(
+                &mut plan,
+                |_| Vec::new(),
+                move |task, _| {
+                    let executor = executor.clone();
+                    async move {
+                        let crate::planer::task::TaskExecution::ToolCall(tool_call) =
+                            &task.execution
+                        else {
+                            unreachable!()
+                        };
+                        let output = executor.dispatch_tool(tool_call).await;
+                        TaskOutcome::Finished(result(task.id, !output.is_empty()))
+                    }
+                },
+            )
+            .await;
+        let status = plan.get_all_tasks()[0].status.clone();
+        (started.elapsed(), results[0].clone(), status)
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_shell_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let (elapsed, result, status) = run_with_timeout(dir.path(), "bash", "sleep 10").await;
+
+        assert!(elapsed < Duration::from_secs(3), "{:?}", elapsed);
+        assert!(result.tool_result.contains("Timed out after 1s"));
+        assert_eq!(status, TaskStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_test_run() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"slow\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "#[test]\nfn slow() {\n    std::thread::sleep(std::time::Duration::from_secs(30));\n}\n",
+        )
+        .unwrap();
+        let (elapsed, result, status) = run_with_timeout(dir.path(), "run_tests", "").await;
+
+        assert!(elapsed < Duration::from_secs(3), "{:?}", elapsed);
+        assert!(result.tool_result.contains("Timed out after 1s"));
+        assert_eq!(status, TaskStatus::Failed);
+    }
+}
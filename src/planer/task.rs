@@ -1,6 +1,11 @@
 use crate::planer::plan::Plan;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::time::Duration;
+
+/// Planner tool name for a high-level task that is planned as a sub-plan
+/// when it runs: `target` is the goal, `operation` and `content` add detail
+pub const SUB_PLAN_TOOL: &str = "sub_plan";
 
 /// Represents the status of a task
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -10,6 +15,7 @@ pub enum TaskStatus {
     Completed,
     Failed,
     Decomposed,
+    Skipped,
 }
 
 /// Represents a tool call with its arguments
@@ -28,6 +34,42 @@ pub enum TaskExecution {
     SubPlan(Plan),
 }
 
+/// How a task is retried, bounded and treated when it fails
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskPolicy {
+    /// Extra attempts after a failure or timeout
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further retry
+    #[serde(default)]
+    pub retry_backoff_ms: u64,
+    /// Fail an attempt that runs longer than this
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Keep starting independent tasks after this one fails
+    #[serde(default)]
+    pub continue_on_failure: bool,
+    /// A failure is tolerated: the task is skipped and its dependents still run
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl TaskPolicy {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs.map(Duration::from_secs)
+    }
+
+    /// Delay before retry number `retry` (starting at 1)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u64.saturating_pow(retry.saturating_sub(1));
+        Duration::from_millis(self.retry_backoff_ms.saturating_mul(factor))
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == TaskPolicy::default()
+    }
+}
+
 /// Core task structure with essential attributes only
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -36,6 +78,11 @@ pub struct Task {
     pub execution: TaskExecution,
     pub dependencies: Vec<usize>,
     pub status: TaskStatus,
+    #[serde(default, skip_serializing_if = "TaskPolicy::is_default")]
+    pub policy: TaskPolicy,
+    /// What the output must show for the task to count as done, checked by the LLM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_criteria: Option<String>,
 }
 
 impl Task {
@@ -58,7 +105,30 @@ impl Task {
             }),
             dependencies: Vec::new(),
             status: TaskStatus::Pending,
+            policy: TaskPolicy::default(),
+            success_criteria: None,
+        }
+    }
+
+    /// A task as the planner describes it; `SUB_PLAN_TOOL` tasks become
+    /// sub-plans whose tasks are planned when they run
+    pub fn new_planned_task(
+        id: usize,
+        title: String,
+        tool: String,
+        target: String,
+        operation: String,
+        content: String,
+    ) -> Self {
+        if tool != SUB_PLAN_TOOL {
+            return Self::new_tool_task(id, title, tool, target, operation, content);
         }
+        let overview = [operation, content]
+            .into_iter()
+            .filter(|text| !text.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Self::new_sub_plan_task(id, title, Plan::new(target, overview))
     }
 
     pub fn new_sub_plan_task(id: usize, title: String, plan: Plan) -> Self {
@@ -68,6 +138,8 @@ impl Task {
             execution: TaskExecution::SubPlan(plan),
             dependencies: Vec::new(),
             status: TaskStatus::Pending,
+            policy: TaskPolicy::default(),
+            success_criteria: None,
         }
     }
 
@@ -76,6 +148,16 @@ impl Task {
         self
     }
 
+    pub fn with_policy(mut self, policy: TaskPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_success_criteria(mut self, criteria: Option<String>) -> Self {
+        self.success_criteria = criteria.filter(|c| !c.trim().is_empty());
+        self
+    }
+
     pub fn set_status(&mut self, status: TaskStatus) {
         self.status = status;
     }
@@ -97,12 +179,22 @@ impl fmt::Display for TaskStatus {
             TaskStatus::Completed => ("✅", "\x1b[32m"),  // Green
             TaskStatus::Failed => ("❌", "\x1b[31m"),     // Red
             TaskStatus::Decomposed => ("🧬", "\x1b[35m"), // Magenta
+            TaskStatus::Skipped => ("⏭️", "\x1b[90m"),    // Gray
         };
         write!(f, "{}{}{}\x1b[0m", color, icon, self.as_str())
     }
 }
 
 impl TaskStatus {
+    /// Nothing left to run: completed, replaced by sub-tasks, or skipped
+    /// (by a replan or because an optional task failed)
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Completed | TaskStatus::Decomposed | TaskStatus::Skipped
+        )
+    }
+
     fn as_str(&self) -> &str {
         match self {
             TaskStatus::Pending => " Pending",
@@ -110,10 +202,33 @@ impl TaskStatus {
             TaskStatus::Completed => " Completed",
             TaskStatus::Failed => " Failed",
             TaskStatus::Decomposed => " Decomposed",
+            TaskStatus::Skipped => " Skipped",
         }
     }
 }
 
+impl fmt::Display for TaskPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.max_retries > 0 {
+            parts.push(format!(
+                "{} retries ({}ms backoff)",
+                self.max_retries, self.retry_backoff_ms
+            ));
+        }
+        if let Some(secs) = self.timeout_secs {
+            parts.push(format!("{}s timeout", secs));
+        }
+        if self.continue_on_failure {
+            parts.push("continue on failure".to_string());
+        }
+        if self.optional {
+            parts.push("optional".to_string());
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
 impl fmt::Display for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(
@@ -140,11 +255,18 @@ impl fmt::Display for Task {
             TaskExecution::SubPlan(plan) => {
                 writeln!(f, "     \x1b[36m📋 Sub-Plan:\x1b[0m {}", plan.title)?;
                 let (completed, total) = plan.get_progress();
-                writeln!(
-                    f,
-                    "        Progress: {}/{}\x1b[0m tasks completed",
-                    completed, total
-                )?;
+                if total == 0 {
+                    writeln!(f, "        Planned when the task runs")?;
+                } else {
+                    writeln!(
+                        f,
+                        "        Progress: {}/{}\x1b[0m tasks completed",
+                        completed, total
+                    )?;
+                }
+                for task in plan.get_all_tasks() {
+                    writeln!(f, "        {} {}: {}", task.status, task.id, task.title)?;
+                }
             }
         }
 
@@ -158,6 +280,18 @@ impl fmt::Display for Task {
             writeln!(f, "     \x1b[31m🔗 Dependencies:\x1b[0m Tasks [{}]", deps)?;
         }
 
+        if let Some(criteria) = &self.success_criteria {
+            writeln!(
+                f,
+                "     \x1b[32m🎯 Success Criteria:\x1b[0m {}",
+                wrap_text(criteria, 60, "        ")
+            )?;
+        }
+
+        if !self.policy.is_default() {
+            writeln!(f, "     \x1b[34m🛡️  Policy:\x1b[0m {}", self.policy)?;
+        }
+
         Ok(())
     }
 }
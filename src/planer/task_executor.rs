@@ -1,12 +1,31 @@
 use crate::cli::config::OpenRouterConfig;
 use crate::context::context::Context;
 use crate::llm::openrouter::OpenRouterClient;
+use crate::llm::Message;
+use crate::mcp::{self, McpManager};
+use crate::planer::approval::{canonical_tool_name, ApprovalGate, READ_ONLY_TOOLS};
+use crate::planer::checkpoint::{CheckpointStore, FileChange};
+use crate::planer::journal::PlanJournal;
 use crate::planer::plan::{Plan, PlanContext, TaskResult};
+use crate::planer::scheduler::{PlanScheduler, ProgressCallback, ResourceClaim, TaskOutcome};
 use crate::planer::task::{Task, TaskExecution, TaskStatus, ToolCall};
-use crate::tools::{exec, file_system};
+use crate::planer::template;
+use crate::prompts::PromptManager;
+use crate::tools::process::ProcessManager;
+use crate::tools::{diagnostics, diff, exec, file_system, git, http, symbols, reduce, test_runner};
 use chrono::Utc;
 use std::collections::HashMap;
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// The part of a path before its first glob component (`src/**/*.rs` → `src`)
+fn glob_base(path: &Path) -> PathBuf {
+    path.components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[', '{']))
+        .collect()
+}
 
 /// Task executor that handles tool calls, sub-plans, and LLM processing of results.
 #[derive(Debug, Clone)]
@@ -15,6 +34,28 @@ pub struct TaskExecutor {
     pub workdir: PathBuf,
     pub openrouter_client: Option<OpenRouterClient>,
     pub midrange_model: String,
+    /// Snapshot files before mutating tools touch them (see `CheckpointStore`)
+    pub checkpoints_enabled: bool,
+    /// Branch prefix for committing a successful plan's changes (`None` disables auto-commit)
+    pub auto_commit_branch_prefix: Option<String>,
+    /// Approval policy consulted before every tool call (`None` runs everything)
+    pub approval: Option<Arc<ApprovalGate>>,
+    /// Background processes started by plans (shared between clones)
+    pub processes: ProcessManager,
+    /// Let `http_request` reach hosts other than localhost
+    pub allow_remote_http: bool,
+    /// Connected MCP servers that `mcp__*` tool calls are routed to
+    pub mcp: McpManager,
+    /// Refuse file tool paths that resolve outside the working directory
+    pub confine_to_workdir: bool,
+    /// Report what mutating tools would do instead of running them
+    pub dry_run: bool,
+    /// Bytes of tool output handed to the LLM before it is reduced (see `tools::reduce`)
+    pub output_budget: usize,
+    /// Worker limit and progress reporting for `execute_plan`
+    pub scheduler: PlanScheduler,
+    /// Save plan state to `.context/plans` as tasks run (see `PlanJournal`)
+    pub journal_enabled: bool,
 }
 
 impl Default for TaskExecutor {
@@ -33,6 +74,17 @@ impl TaskExecutor {
             workdir,
             openrouter_client: None,
             midrange_model: OpenRouterConfig::default().midrange_model,
+            checkpoints_enabled: true,
+            auto_commit_branch_prefix: None,
+            approval: None,
+            processes: ProcessManager::new(),
+            allow_remote_http: false,
+            mcp: McpManager::default(),
+            confine_to_workdir: false,
+            dry_run: false,
+            output_budget: reduce::DEFAULT_OUTPUT_BUDGET,
+            scheduler: PlanScheduler::default(),
+            journal_enabled: true,
         }
     }
 
@@ -51,6 +103,85 @@ impl TaskExecutor {
         self
     }
 
+    pub fn with_checkpoints(mut self, enabled: bool) -> Self {
+        self.checkpoints_enabled = enabled;
+        self
+    }
+
+    /// Commit the changes of every successful plan on a dedicated `<prefix><plan_id>` branch
+    pub fn with_auto_commit(mut self, branch_prefix: Option<String>) -> Self {
+        self.auto_commit_branch_prefix = branch_prefix;
+        self
+    }
+
+    /// Gate tool calls behind an approval policy
+    pub fn with_approval(mut self, gate: Arc<ApprovalGate>) -> Self {
+        self.approval = Some(gate);
+        self
+    }
+
+    pub fn with_remote_http(mut self, allowed: bool) -> Self {
+        self.allow_remote_http = allowed;
+        self
+    }
+
+    /// Route `mcp__<server>__<tool>` and MCP resource tools to these servers
+    pub fn with_mcp(mut self, mcp: McpManager) -> Self {
+        self.mcp = mcp;
+        self
+    }
+
+    /// Reject file tool calls whose paths escape the working directory
+    pub fn with_workdir_confinement(mut self, confined: bool) -> Self {
+        self.confine_to_workdir = confined;
+        self
+    }
+
+    /// Simulate mutating tools (writes, deletes, shell commands, ...) while
+    /// read-only tools still run
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_output_budget(mut self, bytes: usize) -> Self {
+        self.output_budget = bytes;
+        self
+    }
+
+    /// Run up to `max_workers` independent plan tasks at the same time
+    pub fn with_max_workers(mut self, max_workers: usize) -> Self {
+        self.scheduler.max_workers = max_workers.max(1);
+        self
+    }
+
+    /// Report when plan tasks start and finish
+    pub fn with_progress(mut self, progress: ProgressCallback) -> Self {
+        self.scheduler = self.scheduler.with_progress(Some(progress));
+        self
+    }
+
+    pub fn with_journal(mut self, enabled: bool) -> Self {
+        self.journal_enabled = enabled;
+        self
+    }
+
+    /// Plan journal for the working directory, unless disabled or in dry-run mode
+    pub fn plan_journal(&self) -> Option<PlanJournal> {
+        (self.journal_enabled && !self.dry_run).then(|| PlanJournal::for_workdir(&self.workdir))
+    }
+
+    /// Scheduler for running plans, journaling into the working directory
+    pub fn plan_scheduler(&self) -> PlanScheduler {
+        self.scheduler.clone().with_journal(self.plan_journal())
+    }
+
+    /// Checkpoint store for the current working directory, if checkpoints are enabled
+    pub fn checkpoint_store(&self) -> Option<CheckpointStore> {
+        self.checkpoints_enabled
+            .then(|| CheckpointStore::for_workdir(&self.workdir))
+    }
+
     /// Resolve a path relative to the working directory
     fn resolve_path(&self, path: &str) -> PathBuf {
         let path_buf = PathBuf::from(path);
@@ -66,35 +197,148 @@ impl TaskExecutor {
         path.to_string_lossy().to_string()
     }
 
-    /// Main entry point to execute a plan
-    pub async fn execute_plan(&self, plan: &mut Plan, global_context: &Context) {
-        let mut completed_ids = Vec::new();
+    /// Main entry point to execute a plan.
+    ///
+    /// Independent tasks run concurrently (see `PlanScheduler`); execution
+    /// stops scheduling new tasks once one fails.
+    pub fn execute_plan<'a>(
+        &'a self,
+        plan: &'a mut Plan,
+        global_context: &'a Context,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        // Boxed so sub-plans can run plans recursively on spawned tasks
+        Box::pin(async move {
+            let global_context = Arc::new(global_context.clone());
+            self.plan_scheduler()
+                .run(
+                    plan,
+                    |task| self.resource_claims(task),
+                    |task, plan_context| self.run_task(task, global_context.clone(), plan_context),
+                )
+                .await;
 
-        while !plan.is_complete() {
-            let ready_tasks: Vec<usize> = plan
-                .get_all_tasks()
-                .iter()
-                .filter(|t| t.is_ready_to_execute(&completed_ids))
-                .map(|t| t.id)
-                .collect();
+            if !plan.is_complete() && self.verbose {
+                println!("No more ready tasks, but plan is not complete. Exiting.");
+            }
 
-            if ready_tasks.is_empty() {
+            self.processes.stop_plan(&plan.id).await;
+            if let Some(result) = self.commit_plan_changes(plan) {
                 if self.verbose {
-                    println!("No more ready tasks, but plan is not complete. Exiting.");
+                    println!(
+                        "Auto-commit: {}",
+                        serde_json::to_string(&result).unwrap_or_default()
+                    );
                 }
-                break;
             }
+        })
+    }
 
-            for task_id in ready_tasks {
-                let task = plan.find_task_by_id(task_id).unwrap().clone();
-                let result = self
-                    .execute_task(&task, global_context, &plan.plan_context)
-                    .await;
-                plan.add_task_result(result);
-                completed_ids.push(task.id);
-                plan.find_task_by_id(task_id).unwrap().status = TaskStatus::Completed;
+    /// Run one plan task on the scheduler; sub-plan tasks report their
+    /// plan's new state along with the result
+    fn run_task(
+        &self,
+        task: Task,
+        global_context: Arc<Context>,
+        plan_context: PlanContext,
+    ) -> Pin<Box<dyn Future<Output = TaskOutcome> + Send + 'static>> {
+        let executor = self.clone();
+        Box::pin(async move {
+            match &task.execution {
+                TaskExecution::SubPlan(sub_plan) if !sub_plan.get_all_tasks().is_empty() => {
+                    let sub_plan = sub_plan.clone();
+                    executor
+                        .execute_sub_plan(&task, sub_plan, &plan_context, |task, context| {
+                            executor.run_task(task, global_context.clone(), context)
+                        })
+                        .await
+                }
+                _ => TaskOutcome::Finished(
+                    executor
+                        .execute_task(&task, &global_context, &plan_context)
+                        .await,
+                ),
             }
+        })
+    }
+
+    /// Commit a completed, fully successful plan's changes on its own branch.
+    ///
+    /// Returns `None` when auto-commit is disabled, the plan is not complete or
+    /// had failures, or the working directory is not a git repository. The
+    /// checkout stays on the plan branch so its diff can be reviewed.
+    pub fn commit_plan_changes(&self, plan: &Plan) -> Option<file_system::ToolResult> {
+        let prefix = self
+            .auto_commit_branch_prefix
+            .as_ref()
+            .filter(|_| !self.dry_run)?;
+        // Failures that a plan revision skipped or replaced don't count
+        let skipped: Vec<usize> = plan
+            .get_all_tasks()
+            .iter()
+            .filter(|task| task.status == TaskStatus::Skipped)
+            .map(|task| task.id)
+            .collect();
+        let all_succeeded = plan
+            .plan_context
+            .task_results
+            .values()
+            .all(|result| result.success || skipped.contains(&result.task_id));
+        if !plan.is_complete() || !all_succeeded || !git::is_git_repository(&self.workdir) {
+            return None;
         }
+
+        let branch = format!("{}{}", prefix, plan.id);
+        if git::current_branch(&self.workdir).as_deref() != Some(branch.as_str()) {
+            let switched = git::git_switch(&self.workdir, &branch);
+            if !switched.success {
+                let created = git::git_branch_create(&self.workdir, &branch, None, true);
+                if !created.success {
+                    return Some(created);
+                }
+            }
+        }
+
+        // Stage only the files the plan changed, as recorded by its checkpoints,
+        // so unrelated edits in the working tree stay out of the commit
+        let paths: Vec<String> = CheckpointStore::for_workdir(&self.workdir)
+            .changed_files(&plan.id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|file| file.change != FileChange::Unchanged)
+            .filter_map(|file| {
+                let relative = file.path.strip_prefix(&self.workdir).ok()?;
+                Some(relative.to_string_lossy().to_string())
+            })
+            .collect();
+        if paths.is_empty() {
+            return Some(file_system::ToolResult {
+                success: true,
+                data: Some(serde_json::json!({ "paths": [], "staged_files": [] })),
+                error: None,
+            });
+        }
+        let added = git::git_add(&self.workdir, &paths);
+        // Commit only the plan's paths that differ from HEAD; anything the user
+        // had staged elsewhere stays staged and out of the commit
+        let staged: Vec<String> = added
+            .data
+            .as_ref()
+            .and_then(|d| d["staged_files"].as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|file| file.as_str())
+            .filter(|file| paths.iter().any(|path| path == file))
+            .map(|file| file.to_string())
+            .collect();
+        if !added.success || staged.is_empty() {
+            return Some(added);
+        }
+
+        let message = format!(
+            "{}\n\n{}\n\nKAI plan: {}",
+            plan.title, plan.overview, plan.id
+        );
+        Some(git::git_commit(&self.workdir, &message, false, &staged))
     }
 
     /// Execute a single task and return the result
@@ -113,14 +357,26 @@ impl TaskExecutor {
                 self.execute_tool_call(task, tool_call, global_context, plan_context)
                     .await
             }
-            TaskExecution::SubPlan(sub_plan) => {
-                Box::pin(self.execute_sub_plan(
-                    task,
-                    sub_plan.clone(),
-                    global_context,
-                    plan_context,
-                ))
-                .await
+            TaskExecution::SubPlan(sub_plan) if sub_plan.get_all_tasks().is_empty() => {
+                TaskResult::failed(
+                    task.id,
+                    &format!(
+                        "Sub-plan '{}' has no tasks yet; the planner plans them when it runs the task",
+                        sub_plan.title
+                    ),
+                )
+            }
+            TaskExecution::SubPlan(_) => {
+                let global_context = Arc::new(global_context.clone());
+                match self
+                    .run_task(task.clone(), global_context, plan_context.clone())
+                    .await
+                {
+                    TaskOutcome::Finished(result) | TaskOutcome::SubPlan { result, .. } => result,
+                    TaskOutcome::Decomposed(_) => {
+                        TaskResult::failed(task.id, "Sub-plan task was decomposed")
+                    }
+                }
             }
         }
     }
@@ -133,9 +389,15 @@ impl TaskExecutor {
         global_context: &Context,
         plan_context: &PlanContext,
     ) -> TaskResult {
+        // References to earlier results are resolved before anything else sees the call
+        let tool_call = match template::interpolate_tool_call(tool_call, plan_context) {
+            Ok(tool_call) => tool_call,
+            Err(e) => return TaskResult::failed(task.id, &e),
+        };
+
         // Prepare the tool call with LLM if necessary
         let prepared_tool_call = self
-            .prepare_tool_call_with_llm(task, tool_call, plan_context)
+            .prepare_tool_call_with_llm(task, &tool_call, plan_context)
             .await
             .unwrap_or_else(|e| {
                 if self.verbose {
@@ -144,23 +406,274 @@ impl TaskExecutor {
                 tool_call.clone() // Fallback to original tool_call
             });
 
-        let tool_result = self.dispatch_tool(&prepared_tool_call).await;
+        let tool_result = self
+            .dispatch_tool_for_task(
+                &plan_context.execution_metadata.plan_id,
+                task.id,
+                &prepared_tool_call,
+            )
+            .await;
 
-        let llm_processed_result = self
-            .process_result_with_llm(&tool_result, global_context, plan_context)
+        let condensed = self
+            .condense_output(
+                &plan_context.execution_metadata.plan_id,
+                task.id,
+                &prepared_tool_call.tool,
+                &tool_result,
+                self.openrouter_client
+                    .as_ref()
+                    .map(|client| (client, self.midrange_model.as_str())),
+            )
+            .await;
+        let mut llm_processed_result = self
+            .process_result_with_llm(&condensed, global_context, plan_context)
             .await
             .unwrap_or_else(|e| format!("LLM processing failed: {}", e));
 
+        let mut success = TaskResult::tool_output_succeeded(&tool_result);
+        if success {
+            let llm = self
+                .openrouter_client
+                .as_ref()
+                .map(|client| (client, self.midrange_model.as_str()));
+            if let Err(reason) = self.check_success_criteria(task, &condensed, llm).await {
+                success = false;
+                llm_processed_result.push_str(&format!("\n\n{}", reason));
+            }
+        }
+
+        // Failed tasks don't set variables, so there is nothing to extract from them
+        let mut extracted_variables = HashMap::new();
+        if success && !self.dry_run {
+            if let Some(client) = &self.openrouter_client {
+                let llm = (client, self.midrange_model.as_str());
+                match self
+                    .extract_variables(&condensed, &llm_processed_result, &[], llm)
+                    .await
+                {
+                    Ok(variables) => extracted_variables = variables,
+                    Err(e) if self.verbose => println!("Variable extraction failed: {}", e),
+                    Err(_) => {}
+                }
+            }
+        }
+
         TaskResult {
             task_id: task.id,
             tool_result,
             llm_processed_result,
-            extracted_variables: HashMap::new(), // Placeholder
-            success: true,                       // Placeholder
+            extracted_variables,
+            success,
             executed_at: Utc::now(),
         }
     }
 
+    /// Check a task's `success_criteria` against its (condensed) output.
+    ///
+    /// Errs with the LLM's reason when the criteria are not met. Tasks without
+    /// criteria, dry runs and runs without an LLM pass; so does a check the LLM
+    /// fails to answer, since the tool itself reported success.
+    pub async fn check_success_criteria(
+        &self,
+        task: &Task,
+        output: &str,
+        llm: Option<(&OpenRouterClient, &str)>,
+    ) -> Result<(), String> {
+        let (Some(criteria), Some((client, model))) = (&task.success_criteria, llm) else {
+            return Ok(());
+        };
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let prompt = PromptManager::create_success_criteria_prompt(&task.title, criteria, output);
+        let verdict = match client.send_prompt(model, &prompt, Some(300), Some(0.0)).await {
+            Ok(response) => response
+                .choices
+                .first()
+                .map(|choice| PromptManager::extract_json_from_markdown(&choice.message.content))
+                .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok()),
+            Err(e) => {
+                if self.verbose {
+                    println!("Checking success criteria failed: {}", e);
+                }
+                None
+            }
+        };
+        match verdict {
+            Some(verdict) if verdict["met"].as_bool() == Some(false) => Err(format!(
+                "Success criteria not met: {}",
+                verdict["reason"].as_str().unwrap_or(criteria)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Extract plan variables from a task's output with the LLM.
+    ///
+    /// Only `names` are kept; with no names the LLM picks the values later
+    /// tasks are likely to need.
+    pub async fn extract_variables(
+        &self,
+        output: &str,
+        processed: &str,
+        names: &[String],
+        (client, model): (&OpenRouterClient, &str),
+    ) -> Result<HashMap<String, String>, String> {
+        let wanted = if names.is_empty() {
+            "Any values later tasks are likely to need (paths, names, ids, counts), under short snake_case keys".to_string()
+        } else {
+            names.join(", ")
+        };
+        let processed: String = processed.chars().take(300).collect();
+        let prompt = format!(
+            r#"Extract specific variables from this task execution result.
+
+## Tool Result
+```
+{}
+```
+
+## Processed Analysis
+{}
+
+## Variables to Extract
+{}
+
+Please extract the requested variables and return them in JSON format:
+
+```json
+{{
+  "variable1": "extracted_value1",
+  "variable2": "extracted_value2"
+}}
+```
+
+If a variable cannot be found or extracted, omit it from the response."#,
+            output, processed, wanted
+        );
+
+        let messages = vec![
+            Message {
+                role: "system".to_string(),
+                content: "You are an expert at extracting structured data from text. Extract only the requested variables in valid JSON format.".to_string(),
+            },
+            Message {
+                role: "user".to_string(),
+                content: prompt,
+            },
+        ];
+
+        let response = client
+            .send_conversation(model, messages, Some(400), Some(0.2))
+            .await
+            .map_err(|e| format!("LLM variable extraction failed: {}", e))?;
+
+        let content = response
+            .choices
+            .first()
+            .ok_or("No response from LLM")?
+            .message
+            .content
+            .clone();
+        let json_content = PromptManager::extract_json_from_markdown(&content);
+
+        let parsed_json: serde_json::Value = serde_json::from_str(&json_content).map_err(|e| {
+            format!(
+                "Failed to parse extracted variables: {}. Content: {}",
+                e, content
+            )
+        })?;
+
+        let mut extracted_variables = HashMap::new();
+        if let serde_json::Value::Object(map) = parsed_json {
+            for (key, value) in map {
+                if names.is_empty() || names.contains(&key) {
+                    let value = match value {
+                        serde_json::Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    extracted_variables.insert(key, value);
+                }
+            }
+        }
+
+        Ok(extracted_variables)
+    }
+
+    /// Fit a tool's output into the prompt budget.
+    ///
+    /// Output over budget goes through the tool's reducer; if that is still too
+    /// long and an LLM is available it is summarized, otherwise cut to its head
+    /// and tail. The full output is spilled to `.context/spill/` and the
+    /// returned text says where, so later tasks can page through it.
+    pub async fn condense_output(
+        &self,
+        plan_id: &str,
+        task_id: usize,
+        tool: &str,
+        output: &str,
+        llm: Option<(&OpenRouterClient, &str)>,
+    ) -> String {
+        if output.len() <= self.output_budget {
+            return output.to_string();
+        }
+
+        let mut text = reduce::reduce(tool, output, self.output_budget);
+        if text.len() > self.output_budget {
+            let summary = match llm {
+                Some((client, model)) => self.summarize_output(client, model, tool, &text).await,
+                None => Err("no LLM client".to_string()),
+            };
+            text = summary.unwrap_or_else(|_| reduce::head_tail(&text, self.output_budget));
+        }
+
+        let name = if plan_id.is_empty() {
+            format!("{}_{}", Utc::now().format("%Y%m%d_%H%M%S_%3f"), tool)
+        } else {
+            format!("{}_task{}_{}", plan_id, task_id, tool)
+        };
+        match reduce::spill(&self.workdir, &name, output) {
+            Ok(path) => text.push_str(&format!(
+                "\n\n[Output reduced from {} bytes. Full output: {} (page through it with read_file offset/limit)]",
+                output.len(),
+                path.strip_prefix(&self.workdir).unwrap_or(&path).display()
+            )),
+            Err(e) => {
+                if self.verbose {
+                    println!("Spilling tool output failed: {}", e);
+                }
+            }
+        }
+        text
+    }
+
+    /// Ask the LLM for a summary of an oversized (already reduced) tool output
+    async fn summarize_output(
+        &self,
+        client: &OpenRouterClient,
+        model: &str,
+        tool: &str,
+        text: &str,
+    ) -> Result<String, String> {
+        let prompt = format!(
+            "Summarize the output of the `{}` tool below in at most {} characters. Keep error messages, file paths, line numbers, counts and anything a follow-up step would need. Reply with the summary only.\n\n```\n{}\n```",
+            tool,
+            self.output_budget,
+            reduce::head_tail(text, self.output_budget * 8)
+        );
+        let response = client
+            .send_prompt(model, &prompt, Some((self.output_budget / 3) as u32), Some(0.1))
+            .await
+            .map_err(|e| e.to_string())?;
+        response
+            .choices
+            .first()
+            .map(|c| c.message.content.trim().to_string())
+            .filter(|summary| !summary.is_empty())
+            .ok_or_else(|| "Empty summary".to_string())
+    }
+
     /// Pre-process a tool call with an LLM to generate dynamic content.
     async fn prepare_tool_call_with_llm(
         &self,
@@ -262,40 +775,398 @@ impl TaskExecutor {
         }
     }
 
-    /// Execute a sub-plan recursively
-    async fn execute_sub_plan(
+    /// Run a sub-plan task's plan inside the plan whose context is
+    /// `parent_context`, with `run_task` running each of its tasks.
+    ///
+    /// The sub-plan's tasks are numbered after `task` (see
+    /// `Plan::number_as_sub_plan_of`) and see the parent's results and
+    /// variables; its result (`TaskResult::from_sub_plan`) lists its tasks'
+    /// output and passes its variables up. Tasks that were running or had
+    /// failed in an earlier run are run again.
+    pub async fn execute_sub_plan<F, Fut>(
         &self,
         task: &Task,
         mut sub_plan: Plan,
-        global_context: &Context,
-        _parent_plan_context: &PlanContext,
-    ) -> TaskResult {
+        parent_context: &PlanContext,
+        run_task: F,
+    ) -> TaskOutcome
+    where
+        F: Fn(Task, PlanContext) -> Fut,
+        Fut: Future<Output = TaskOutcome> + Send + 'static,
+    {
         if self.verbose {
             println!("Executing sub-plan: {}", sub_plan.title);
         }
 
-        // The sub-plan's context already inherits from the parent context
-        self.execute_plan(&mut sub_plan, global_context).await;
+        sub_plan.attach_to_parent(parent_context);
+        sub_plan.number_as_sub_plan_of(task.id);
+        sub_plan.reset_unfinished_tasks();
+        self.plan_scheduler()
+            .nested(task.id)
+            .run(&mut sub_plan, |task| self.resource_claims(task), run_task)
+            .await;
+        sub_plan.detach_from_parent();
 
-        let final_results = sub_plan.plan_context.get_all_available_results();
-        let summary = format!(
-            "Sub-plan '{}' completed. {} tasks executed.",
-            sub_plan.title,
-            final_results.len()
-        );
+        let result = TaskResult::from_sub_plan(task.id, &sub_plan);
+        TaskOutcome::SubPlan {
+            plan: Box::new(sub_plan),
+            result,
+        }
+    }
 
-        TaskResult {
-            task_id: task.id,
-            tool_result: summary.clone(),
-            llm_processed_result: summary,
-            extracted_variables: HashMap::new(),
-            success: sub_plan.is_complete(),
-            executed_at: Utc::now(),
+    /// Paths a mutating tool call would modify, resolved against the working directory
+    pub fn affected_paths(&self, tool_call: &ToolCall) -> Vec<PathBuf> {
+        match tool_call.tool.as_str() {
+            "write" | "write_file" | "create_path" => vec![self.resolve_path(&tool_call.target)],
+            "delete_path" => {
+                let pattern = self.path_to_string(&self.resolve_path(&tool_call.target));
+                glob::glob(&pattern)
+                    .map(|paths| paths.filter_map(|p| p.ok()).collect())
+                    .unwrap_or_default()
+            }
+            // Only the files the replacement would actually change
+            "search_replace" => self
+                .search_replace(tool_call, true)
+                .data
+                .and_then(|data| data["modified_files"].as_array().cloned())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|file| file["file"].as_str().map(|f| self.resolve_path(f)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Run `search_replace` (or preview it) confined to the working directory
+    fn search_replace(&self, tool_call: &ToolCall, preview: bool) -> file_system::ToolResult {
+        let args = Self::tool_args(tool_call);
+        let (Some(search_pattern), Some(replace_text)) =
+            (args["search_pattern"].as_str(), args["replace_text"].as_str())
+        else {
+            return file_system::ToolResult {
+                success: false,
+                data: None,
+                error: Some(
+                    "search_replace requires JSON content with 'search_pattern' and 'replace_text'"
+                        .to_string(),
+                ),
+            };
+        };
+        let mut options: file_system::SearchReplaceOptions =
+            serde_json::from_value(args.clone()).unwrap_or_default();
+        options.preview |= preview;
+        options.root = Some(self.workdir.clone());
+        file_system::FileSystemOperations::search_replace(
+            search_pattern,
+            replace_text,
+            &self.path_to_string(&self.resolve_path(&tool_call.target)),
+            &options,
+        )
+    }
+
+    /// Resources a task holds while it runs (see `scheduler::ResourceLocks`).
+    ///
+    /// File tools lock the paths they name (up to the first glob component),
+    /// read-only git tools share the working directory, and shell commands,
+    /// processes, builds and other tools with unknown effects lock all of it.
+    pub fn resource_claims(&self, task: &Task) -> Vec<ResourceClaim> {
+        let TaskExecution::ToolCall(tool_call) = &task.execution else {
+            return vec![ResourceClaim::write(&self.workdir)];
+        };
+        let paths = || -> Vec<PathBuf> {
+            let paths: Vec<PathBuf> = Self::path_arguments(tool_call)
+                .iter()
+                .map(|path| glob_base(&self.resolve_path(path)))
+                .collect();
+            if paths.is_empty() {
+                vec![self.workdir.clone()]
+            } else {
+                paths
+            }
+        };
+        match tool_call.tool.as_str() {
+            "write" | "write_file" | "create_path" | "delete_path" | "search_replace" => {
+                paths().into_iter().map(ResourceClaim::write).collect()
+            }
+            "read" | "read_file" | "ls" | "list_directory" | "grep_files" | "find_files"
+            | "outline" | "find_symbol" => paths().into_iter().map(ResourceClaim::read).collect(),
+            "git_status" | "git_diff" | "git_log" | "git_show" => {
+                vec![ResourceClaim::read(&self.workdir)]
+            }
+            "http_request" | "list_processes" | "read_process_output" | "wait_for_output"
+            | "stop_process" => Vec::new(),
+            _ => vec![ResourceClaim::write(&self.workdir)],
+        }
+    }
+
+    /// Path arguments of a file tool call, as given (relative to the working directory)
+    fn path_arguments(tool_call: &ToolCall) -> Vec<String> {
+        let args = Self::tool_args(tool_call);
+        let arg = |key: &str| args[key].as_str().map(|s| s.to_string());
+        match tool_call.tool.as_str() {
+            "read" | "read_file" | "write" | "write_file" | "ls" | "list_directory"
+            | "create_path" | "delete_path" | "search_replace" | "outline" => {
+                vec![tool_call.target.clone()]
+            }
+            "grep_files" => arg("file_pattern").into_iter().collect(),
+            "find_files" => arg("base_path")
+                .into_iter()
+                .chain(std::iter::once(tool_call.target.clone()))
+                .collect(),
+            "find_symbol" => arg("path").into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The first path argument that resolves outside the working directory
+    pub fn path_outside_workdir(&self, tool_call: &ToolCall) -> Option<String> {
+        Self::path_arguments(tool_call).into_iter().find(|path| {
+            let mut normalized = PathBuf::new();
+            for component in self.resolve_path(path).components() {
+                match component {
+                    std::path::Component::ParentDir => {
+                        normalized.pop();
+                    }
+                    std::path::Component::CurDir => {}
+                    other => normalized.push(other),
+                }
+            }
+            !normalized.starts_with(&self.workdir)
+        })
+    }
+
+    /// Whether a tool call only inspects the workspace (GET/HEAD probes included)
+    pub fn is_read_only(tool_call: &ToolCall) -> bool {
+        let tool = canonical_tool_name(&tool_call.tool);
+        if tool == "http_request" {
+            let method = Self::tool_args(tool_call)["method"]
+                .as_str()
+                .unwrap_or("GET")
+                .to_uppercase();
+            return method == "GET" || method == "HEAD";
+        }
+        READ_ONLY_TOOLS.contains(&tool)
+    }
+
+    /// What a mutating tool call would do, without doing it
+    pub fn dry_run_result(&self, tool_call: &ToolCall) -> file_system::ToolResult {
+        let relative = |path: &Path| {
+            path.strip_prefix(&self.workdir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        let args = Self::tool_args(tool_call);
+        let details = match canonical_tool_name(&tool_call.tool) {
+            "write_file" => {
+                let path = self.resolve_path(&tool_call.target);
+                let old = std::fs::read_to_string(&path).unwrap_or_default();
+                serde_json::json!({
+                    "action": if path.exists() { "overwrite" } else { "create" },
+                    "path": relative(&path),
+                    "bytes": tool_call.content.len(),
+                    "diff": diff::unified_diff(&old, &tool_call.content, &tool_call.target),
+                })
+            }
+            "delete_path" => {
+                let paths = self.affected_paths(tool_call);
+                serde_json::json!({
+                    "action": "delete",
+                    "pattern": tool_call.target,
+                    "recursive": args["recursive"].as_bool().unwrap_or(false),
+                    "paths": paths.iter().map(|p| relative(p)).collect::<Vec<_>>(),
+                })
+            }
+            "create_path" => {
+                let path = self.resolve_path(&tool_call.target);
+                serde_json::json!({
+                    "action": if args["is_directory"].as_bool().unwrap_or(false) {
+                        "create_directory"
+                    } else {
+                        "create_file"
+                    },
+                    "path": relative(&path),
+                    "exists": path.exists(),
+                })
+            }
+            "search_replace" => {
+                let preview = self.search_replace(tool_call, true);
+                serde_json::json!({
+                    "action": "replace",
+                    "pattern": tool_call.target,
+                    "files": preview.data.as_ref().map(|d| d["modified_files"].clone()),
+                    "error": preview.error,
+                })
+            }
+            "bash" => serde_json::json!({
+                "action": "run_command",
+                "command": tool_call.target,
+                "cwd": self.workdir.display().to_string(),
+            }),
+            tool => serde_json::json!({
+                "action": "call_tool",
+                "tool": tool,
+                "target": tool_call.target,
+                "arguments": if args.is_null() {
+                    serde_json::Value::String(tool_call.content.clone())
+                } else {
+                    args
+                },
+            }),
+        };
+
+        let mut data = serde_json::json!({ "dry_run": true, "tool": tool_call.tool });
+        if let (Some(data), Some(details)) = (data.as_object_mut(), details.as_object()) {
+            data.extend(details.clone());
+        }
+        file_system::ToolResult {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    /// Human-readable preview of what a tool call will do: the command for shell
+    /// calls, a unified diff for file writes, the affected paths otherwise
+    pub fn approval_preview(&self, tool_call: &ToolCall) -> String {
+        match tool_call.tool.as_str() {
+            "bash" | "run_shell" => format!("$ {}", tool_call.target),
+            "write" | "write_file" => {
+                let old = std::fs::read_to_string(self.resolve_path(&tool_call.target))
+                    .unwrap_or_default();
+                let patch = diff::unified_diff(&old, &tool_call.content, &tool_call.target);
+                if patch.is_empty() {
+                    format!("{} (no changes)", tool_call.target)
+                } else {
+                    patch
+                }
+            }
+            "search_replace" => {
+                let preview = self.search_replace(tool_call, true);
+                let diffs: Vec<String> = preview
+                    .data
+                    .as_ref()
+                    .and_then(|d| d["modified_files"].as_array())
+                    .map(|files| {
+                        files
+                            .iter()
+                            .filter_map(|f| f["diff"].as_str().map(|d| d.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                match preview.error {
+                    Some(error) => format!("search_replace {}: {}", tool_call.target, error),
+                    None if diffs.is_empty() => format!("{} (no changes)", tool_call.target),
+                    None => diffs.join("\n"),
+                }
+            }
+            _ => {
+                let paths = self.affected_paths(tool_call);
+                let mut lines = vec![format!("{} {}", tool_call.tool, tool_call.target)];
+                lines.extend(
+                    paths
+                        .iter()
+                        .map(|p| format!("  {}", p.strip_prefix(&self.workdir).unwrap_or(p).display())),
+                );
+                if !tool_call.content.is_empty() {
+                    lines.push(tool_call.content.clone());
+                }
+                lines.join("\n")
+            }
+        }
+    }
+
+    /// Dispatch a tool call on behalf of a plan task, checkpointing every file it
+    /// is about to modify so the change can be undone later
+    pub async fn dispatch_tool_for_task(
+        &self,
+        plan_id: &str,
+        task_id: usize,
+        tool_call: &ToolCall,
+    ) -> String {
+        if let Some(path) = self
+            .path_outside_workdir(tool_call)
+            .filter(|_| self.confine_to_workdir)
+        {
+            let refused = file_system::ToolResult {
+                success: false,
+                data: None,
+                error: Some(format!(
+                    "Path '{}' is outside the working directory {}",
+                    path,
+                    self.workdir.display()
+                )),
+            };
+            return serde_json::to_string_pretty(&refused)
+                .unwrap_or_else(|e| format!("Failed to serialize result: {}", e));
+        }
+
+        // Nothing runs in a dry run, so there is nothing to approve or checkpoint
+        if self.dry_run && !Self::is_read_only(tool_call) {
+            return self.dispatch_tool_in_plan(plan_id, tool_call).await;
+        }
+
+        if let Some(gate) = &self.approval {
+            if let Err(reason) = gate
+                .check(tool_call, || self.approval_preview(tool_call))
+                .await
+            {
+                let denied = file_system::ToolResult {
+                    success: false,
+                    data: None,
+                    error: Some(reason),
+                };
+                return serde_json::to_string_pretty(&denied)
+                    .unwrap_or_else(|e| format!("Failed to serialize result: {}", e));
+            }
+        }
+
+        // Without its checkpoint a change couldn't be undone, so it doesn't run
+        if let Some(store) = self.checkpoint_store().filter(|_| !plan_id.is_empty()) {
+            for path in self.affected_paths(tool_call) {
+                if let Err(e) = store.snapshot(plan_id, task_id, &path) {
+                    let failed = file_system::ToolResult {
+                        success: false,
+                        data: None,
+                        error: Some(format!(
+                            "Checkpoint of '{}' failed, so it was left unchanged: {}",
+                            path.display(),
+                            e
+                        )),
+                    };
+                    return serde_json::to_string_pretty(&failed)
+                        .unwrap_or_else(|e| format!("Failed to serialize result: {}", e));
+                }
+            }
         }
+        self.dispatch_tool_in_plan(plan_id, tool_call).await
+    }
+
+    /// Optional structured arguments passed as a JSON object in `ToolCall.content`
+    fn tool_args(tool_call: &ToolCall) -> serde_json::Value {
+        serde_json::from_str::<serde_json::Value>(&tool_call.content)
+            .ok()
+            .filter(|v| v.is_object())
+            .unwrap_or(serde_json::Value::Null)
+    }
+
+    /// The call target, treating empty and "." targets as "not specified"
+    fn optional_target(tool_call: &ToolCall) -> Option<&str> {
+        let target = tool_call.target.trim();
+        (!target.is_empty() && target != "." && target != "./").then_some(target)
     }
 
     /// Dispatch a tool call to the appropriate handler
     pub async fn dispatch_tool(&self, tool_call: &ToolCall) -> String {
+        self.dispatch_tool_in_plan("", tool_call).await
+    }
+
+    /// Dispatch a tool call; background processes it starts are owned by `plan_id`
+    async fn dispatch_tool_in_plan(&self, plan_id: &str, tool_call: &ToolCall) -> String {
+        if self.dry_run && !Self::is_read_only(tool_call) {
+            return serde_json::to_string_pretty(&self.dry_run_result(tool_call))
+                .unwrap_or_else(|e| format!("Failed to serialize result: {}", e));
+        }
         println!(
             "[LLM_DEBUG_INPUT] Prompt for dispatch_tool:\n{}",
             tool_call.tool
@@ -303,7 +1174,12 @@ impl TaskExecutor {
         let result = match tool_call.tool.as_str() {
             "read" | "read_file" => {
                 let resolved_path = self.resolve_path(&tool_call.target);
-                file_system::FileSystemOperations::read_file(&self.path_to_string(&resolved_path))
+                let options: file_system::ReadOptions =
+                    serde_json::from_value(Self::tool_args(tool_call)).unwrap_or_default();
+                file_system::FileSystemOperations::read_file_with_options(
+                    &self.path_to_string(&resolved_path),
+                    &options,
+                )
             }
             "write" | "write_file" => {
                 let resolved_path = self.resolve_path(&tool_call.target);
@@ -316,7 +1192,7 @@ impl TaskExecutor {
             "bash" | "run_shell" => {
                 let command_with_cd =
                     format!("cd {} && {}", self.workdir.display(), tool_call.target);
-                exec::run_shell_command_tool(&command_with_cd)
+                exec::run_shell_command_tool(&command_with_cd).await
             }
             "ls" | "list_directory" => {
                 let resolved_path = if tool_call.target.is_empty()
@@ -327,10 +1203,225 @@ impl TaskExecutor {
                 } else {
                     self.resolve_path(&tool_call.target)
                 };
+                let args = Self::tool_args(tool_call);
                 file_system::FileSystemOperations::list_directory(
                     &self.path_to_string(&resolved_path),
-                    None,
-                    None,
+                    args["pattern"].as_str(),
+                    args["recursive"].as_bool(),
+                    args["no_ignore"].as_bool(),
+                )
+            }
+            "grep_files" => {
+                let args = Self::tool_args(tool_call);
+                let file_pattern = self.resolve_path(args["file_pattern"].as_str().unwrap_or("**/*"));
+                file_system::FileSystemOperations::grep_files(
+                    &tool_call.target,
+                    &self.path_to_string(&file_pattern),
+                    args["case_sensitive"].as_bool(),
+                    args["line_numbers"].as_bool(),
+                    args["context_lines"].as_u64().map(|n| n as u32),
+                    args["no_ignore"].as_bool(),
+                )
+            }
+            "find_files" => {
+                let args = Self::tool_args(tool_call);
+                let base_path = match args["base_path"].as_str() {
+                    Some(path) if !matches!(path, "" | "." | "./") => self.resolve_path(path),
+                    _ => self.workdir.clone(),
+                };
+                file_system::FileSystemOperations::find_files(
+                    &tool_call.target,
+                    Some(&self.path_to_string(&base_path)),
+                    args["file_type"].as_str(),
+                    args["no_ignore"].as_bool(),
+                )
+            }
+            "create_path" => {
+                let resolved_path = self.resolve_path(&tool_call.target);
+                let args = Self::tool_args(tool_call);
+                file_system::FileSystemOperations::create_path(
+                    &self.path_to_string(&resolved_path),
+                    args["is_directory"].as_bool(),
+                )
+            }
+            "delete_path" => {
+                let resolved_path = self.resolve_path(&tool_call.target);
+                let args = Self::tool_args(tool_call);
+                file_system::FileSystemOperations::delete_path(
+                    &self.path_to_string(&resolved_path),
+                    args["recursive"].as_bool(),
+                )
+            }
+            "search_replace" => self.search_replace(tool_call, false),
+            "start_process" => self.processes.start(plan_id, &tool_call.target, &self.workdir),
+            "read_process_output" => {
+                let args = Self::tool_args(tool_call);
+                self.processes.read(
+                    tool_call.target.trim(),
+                    args["since"].as_u64().map(|s| s as usize),
+                )
+            }
+            "wait_for_output" => {
+                let args = Self::tool_args(tool_call);
+                let pattern = args["pattern"]
+                    .as_str()
+                    .map(|p| p.to_string())
+                    .unwrap_or_else(|| tool_call.content.clone());
+                let timeout = std::time::Duration::from_secs(
+                    args["timeout_secs"].as_u64().unwrap_or(30),
+                );
+                self.processes
+                    .wait_for(tool_call.target.trim(), &pattern, timeout)
+                    .await
+            }
+            "send_process_input" => {
+                let args = Self::tool_args(tool_call);
+                let input = args["input"]
+                    .as_str()
+                    .map(|i| i.to_string())
+                    .unwrap_or_else(|| tool_call.content.clone());
+                self.processes.send(tool_call.target.trim(), &input)
+            }
+            "stop_process" => self.processes.stop(tool_call.target.trim()).await,
+            "list_processes" => self.processes.list(Some(plan_id).filter(|id| !id.is_empty())),
+            "http_request" => {
+                let args = Self::tool_args(tool_call);
+                let mut options = http::HttpRequestOptions::new(
+                    args["method"].as_str().unwrap_or("GET"),
+                    tool_call.target.trim(),
+                );
+                if let Some(headers) = args["headers"].as_object() {
+                    options.headers = headers
+                        .iter()
+                        .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                        .collect();
+                }
+                options.body = args["body"].as_str().map(|b| b.to_string());
+                if let Some(secs) = args["timeout_secs"].as_u64() {
+                    options.timeout = std::time::Duration::from_secs(secs);
+                }
+                options.allow_remote = self.allow_remote_http;
+                http::http_request(&options).await
+            }
+            "build_diagnostics" => {
+                let args = Self::tool_args(tool_call);
+                let project = match Self::optional_target(tool_call) {
+                    Some(path) => self.resolve_path(path),
+                    None => self.workdir.clone(),
+                };
+                diagnostics::run_diagnostics(
+                    &project,
+                    args["provider"].as_str(),
+                    args["mode"].as_str().unwrap_or("check"),
+                )
+                .await
+            }
+            "run_tests" => {
+                let args = Self::tool_args(tool_call);
+                let project = match Self::optional_target(tool_call) {
+                    Some(path) => self.resolve_path(path),
+                    None => self.workdir.clone(),
+                };
+                test_runner::run_tests(
+                    &project,
+                    args["framework"].as_str(),
+                    args["filter"].as_str().filter(|f| !f.is_empty()),
+                )
+                .await
+            }
+            "outline" => symbols::outline(&self.resolve_path(&tool_call.target)),
+            "find_symbol" => {
+                let args = Self::tool_args(tool_call);
+                let root = match args["path"].as_str().filter(|p| !p.is_empty()) {
+                    Some(path) => self.resolve_path(path),
+                    None => self.workdir.clone(),
+                };
+                symbols::find_symbol(
+                    &root,
+                    &tool_call.target,
+                    args["kind"].as_str().filter(|k| !k.is_empty()),
+                    args["include_body"].as_bool().unwrap_or(true),
+                    !args["no_ignore"].as_bool().unwrap_or(false),
+                )
+            }
+            mcp::client::LIST_RESOURCES_TOOL => {
+                let args = Self::tool_args(tool_call);
+                let server = args["server"]
+                    .as_str()
+                    .or(Self::optional_target(tool_call))
+                    .filter(|s| !s.is_empty());
+                self.mcp.list_resources(server)
+            }
+            mcp::client::READ_RESOURCE_TOOL => {
+                let args = Self::tool_args(tool_call);
+                let uri = args["uri"].as_str().unwrap_or(tool_call.target.trim());
+                self.mcp.read_resource(uri).await
+            }
+            name if name.starts_with(mcp::client::TOOL_PREFIX) => {
+                self.mcp.call_tool(name, Self::tool_args(tool_call)).await
+            }
+            "git_status" => git::git_status(&self.workdir, Self::optional_target(tool_call)),
+            "git_diff" => {
+                let args = Self::tool_args(tool_call);
+                git::git_diff(
+                    &self.workdir,
+                    args["staged"].as_bool().unwrap_or(false),
+                    Self::optional_target(tool_call),
+                )
+            }
+            "git_log" => {
+                let args = Self::tool_args(tool_call);
+                git::git_log(
+                    &self.workdir,
+                    args["limit"].as_u64().unwrap_or(10) as usize,
+                    args["rev"].as_str(),
+                    Self::optional_target(tool_call),
+                )
+            }
+            "git_show" => git::git_show(
+                &self.workdir,
+                Self::optional_target(tool_call).unwrap_or("HEAD"),
+            ),
+            "git_branch" => {
+                let args = Self::tool_args(tool_call);
+                git::git_branch_create(
+                    &self.workdir,
+                    &tool_call.target,
+                    args["start_point"].as_str(),
+                    args["switch"].as_bool().unwrap_or(false),
+                )
+            }
+            "git_switch" => git::git_switch(&self.workdir, &tool_call.target),
+            "git_add" => {
+                let args = Self::tool_args(tool_call);
+                let paths: Vec<String> = match args["paths"].as_array() {
+                    Some(paths) => paths
+                        .iter()
+                        .filter_map(|p| p.as_str().map(|s| s.to_string()))
+                        .collect(),
+                    None => Self::optional_target(tool_call)
+                        .map(|t| t.split_whitespace().map(|s| s.to_string()).collect())
+                        .unwrap_or_default(),
+                };
+                git::git_add(&self.workdir, &paths)
+            }
+            "git_commit" => {
+                let args = Self::tool_args(tool_call);
+                let message = args["message"]
+                    .as_str()
+                    .map(|m| m.to_string())
+                    .unwrap_or_else(|| tool_call.content.clone());
+                let paths: Vec<String> = args["paths"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|p| p.as_str().map(|s| s.to_string()))
+                    .collect();
+                git::git_commit(
+                    &self.workdir,
+                    &message,
+                    args["all"].as_bool().unwrap_or(false),
+                    &paths,
                 )
             }
             _ => {
@@ -389,7 +1480,7 @@ impl TaskExecutor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::planer::task::Task;
+    use crate::planer::task::{Task, SUB_PLAN_TOOL};
     use tempfile::tempdir;
 
     #[tokio::test]
@@ -428,4 +1519,457 @@ mod tests {
         assert!(read_response.success);
         assert!(read_response.tool_result.contains("Hello, world!"));
     }
+
+    #[tokio::test]
+    async fn test_failures_come_from_tool_results() {
+        let dir = tempdir().unwrap();
+        let executor = TaskExecutor::new().with_workdir(dir.path());
+        let global_context = Context::new();
+        let plan_context = PlanContext::new(None);
+        let run = |id: usize, tool: &str, target: &str| {
+            Task::new_tool_task(
+                id,
+                format!("Task {}", id),
+                tool.to_string(),
+                target.to_string(),
+                String::new(),
+                String::new(),
+            )
+        };
+
+        let failing_shell = executor
+            .execute_task(&run(1, "bash", "exit 3"), &global_context, &plan_context)
+            .await;
+        assert!(!failing_shell.success);
+        let missing_file = executor
+            .execute_task(
+                &run(2, "read_file", "missing.txt"),
+                &global_context,
+                &plan_context,
+            )
+            .await;
+        assert!(!missing_file.success);
+        // Without an LLM the criteria can't be checked, so the tool result decides
+        let with_criteria =
+            run(3, "bash", "echo ok").with_success_criteria(Some("Prints ok".to_string()));
+        let passing_shell = executor
+            .execute_task(&with_criteria, &global_context, &plan_context)
+            .await;
+        assert!(passing_shell.success);
+        assert!(!TaskResult::tool_output_succeeded("not json"));
+    }
+
+    #[tokio::test]
+    async fn test_sub_plan_results_reach_parent() {
+        use crate::planer::plan::Phase;
+
+        let dir = tempdir().unwrap();
+        let executor = TaskExecutor::new().with_workdir(dir.path());
+        let tool_task = |id, tool: &str, target: &str, content: &str| {
+            Task::new_planned_task(
+                id,
+                format!("Task {}", id),
+                tool.to_string(),
+                target.to_string(),
+                String::new(),
+                content.to_string(),
+            )
+        };
+
+        // Sub-plan: write a.txt, then read it back
+        let mut sub_plan = Plan::new("Make a.txt".to_string(), String::new());
+        let mut sub_phase = Phase::new("Sub".to_string(), "🔧".to_string());
+        sub_phase.add_task(tool_task(
+            sub_plan.generate_task_id(),
+            "write",
+            "a.txt",
+            "from sub-plan",
+        ));
+        sub_phase.add_task(
+            tool_task(sub_plan.generate_task_id(), "read", "a.txt", "").with_dependencies(vec![1]),
+        );
+        sub_plan.add_phase(sub_phase);
+
+        // Parent: task 1 runs the sub-plan, task 2 copies what it read
+        let mut plan = Plan::new("Parent".to_string(), String::new());
+        let mut phase = Phase::new("Main".to_string(), "🔧".to_string());
+        phase.add_task(Task::new_sub_plan_task(
+            plan.generate_task_id(),
+            "Prepare".to_string(),
+            sub_plan,
+        ));
+        phase.add_task(
+            tool_task(
+                plan.generate_task_id(),
+                "write",
+                "b.txt",
+                "{{task.1.tasks[1].output.content}}",
+            )
+            .with_dependencies(vec![1]),
+        );
+        plan.add_phase(phase);
+
+        executor.execute_plan(&mut plan, &Context::new()).await;
+        assert!(plan.is_complete());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+            "from sub-plan"
+        );
+        let TaskExecution::SubPlan(sub_plan) = &plan.find_task_by_id(1).unwrap().execution else {
+            panic!("task 1 should still be a sub-plan");
+        };
+        assert_eq!(sub_plan.get_progress(), (2, 2));
+        assert!(sub_plan.plan_context.parent_context.is_none());
+
+        // A sub-plan nobody has planned yet can't run without the planner
+        let unplanned = tool_task(3, SUB_PLAN_TOOL, "Add docs", "");
+        let result = executor
+            .execute_task(&unplanned, &Context::new(), &PlanContext::new(None))
+            .await;
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn test_sub_plan_task_ids_stay_apart_from_parent() {
+        use crate::planer::plan::Phase;
+
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "old a").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "old b").unwrap();
+        let executor = TaskExecutor::new().with_workdir(dir.path());
+        let write = |id, target: &str| {
+            Task::new_tool_task(
+                id,
+                format!("Write {}", target),
+                "write".to_string(),
+                target.to_string(),
+                String::new(),
+                "new".to_string(),
+            )
+        };
+
+        // Parent task 1 and the sub-plan's first task both start out as task 1
+        let mut sub_plan = Plan::new("Sub".to_string(), String::new());
+        let mut sub_phase = Phase::new("Sub".to_string(), "🔧".to_string());
+        sub_phase.add_task(write(sub_plan.generate_task_id(), "b.txt"));
+        sub_phase.add_task(write(sub_plan.generate_task_id(), "c.txt").with_dependencies(vec![1]));
+        sub_plan.add_phase(sub_phase);
+        let mut plan = Plan::new("Parent".to_string(), String::new());
+        plan.set_id("plan_sub".to_string());
+        let mut phase = Phase::new("Main".to_string(), "🔧".to_string());
+        phase.add_task(write(plan.generate_task_id(), "a.txt"));
+        phase.add_task(
+            Task::new_sub_plan_task(plan.generate_task_id(), "Sub".to_string(), sub_plan)
+                .with_dependencies(vec![1]),
+        );
+        plan.add_phase(phase);
+
+        executor.execute_plan(&mut plan, &Context::new()).await;
+        assert!(plan.is_complete());
+        let TaskExecution::SubPlan(sub_plan) = &plan.find_task_by_id(2).unwrap().execution else {
+            panic!("task 2 should still be a sub-plan");
+        };
+        let tasks = sub_plan.get_all_tasks();
+        assert_eq!(
+            tasks.iter().map(|t| t.id).collect::<Vec<_>>(),
+            vec![2001, 2002]
+        );
+        assert_eq!(tasks[1].dependencies, vec![2001]);
+
+        // Undoing the sub-plan's last task leaves the parent's task 1 alone
+        let store = CheckpointStore::for_workdir(dir.path());
+        let report = store.undo_last_task(Some("plan_sub")).unwrap();
+        assert_eq!(report.task_ids, vec![2002]);
+        assert!(!dir.path().join("c.txt").exists());
+        let report = store.undo_last_task(Some("plan_sub")).unwrap();
+        assert_eq!(report.task_ids, vec![2001]);
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("b.txt"), "old b");
+        assert_eq!(read("a.txt"), "new");
+    }
+
+    #[test]
+    fn test_resource_claims() {
+        let executor = TaskExecutor::new().with_workdir("/w");
+        let claims = |tool: &str, target: &str, content: &str| {
+            let task = Task::new_tool_task(
+                1,
+                "Task".to_string(),
+                tool.to_string(),
+                target.to_string(),
+                String::new(),
+                content.to_string(),
+            );
+            executor.resource_claims(&task)
+        };
+
+        assert_eq!(
+            claims("write_file", "src/a.rs", "fn a() {}"),
+            vec![ResourceClaim::write("/w/src/a.rs")]
+        );
+        assert_eq!(
+            claims("grep_files", "TODO", r#"{"file_pattern": "src/**/*.rs"}"#),
+            vec![ResourceClaim::read("/w/src")]
+        );
+        assert_eq!(
+            claims("list_directory", "", ""),
+            vec![ResourceClaim::read("/w")]
+        );
+        assert_eq!(
+            claims("bash", "cargo test", ""),
+            vec![ResourceClaim::write("/w")]
+        );
+        assert!(claims("http_request", "http://localhost:8080", "").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_for_task_checkpoints_writes() {
+        let dir = tempdir().unwrap();
+        let executor = TaskExecutor::new().with_workdir(dir.path());
+        std::fs::write(dir.path().join("config.txt"), "before").unwrap();
+
+        let tool_call = ToolCall {
+            tool: "write_file".to_string(),
+            target: "config.txt".to_string(),
+            operation: "Overwrite config".to_string(),
+            content: "after".to_string(),
+        };
+        executor
+            .dispatch_tool_for_task("plan_test", 1, &tool_call)
+            .await;
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.txt")).unwrap(),
+            "after"
+        );
+
+        let store = executor.checkpoint_store().unwrap();
+        store.rollback_plan("plan_test").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.txt")).unwrap(),
+            "before"
+        );
+
+        // A write that can't be checkpointed doesn't happen
+        std::fs::write(dir.path().join(".context/checkpoints/plan_broken"), "").unwrap();
+        let output = executor
+            .dispatch_tool_for_task("plan_broken", 1, &tool_call)
+            .await;
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["success"], false);
+        assert!(output["error"].as_str().unwrap().contains("Checkpoint of"));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.txt")).unwrap(),
+            "before"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approval_policy_blocks_denied_writes() {
+        use crate::planer::approval::{
+            ApprovalHandler, ApprovalPolicy, ApprovalRequest, ApprovalResponse, PolicyDecision,
+            PolicyRule,
+        };
+
+        struct RejectAll;
+        impl ApprovalHandler for RejectAll {
+            fn request_approval(&self, _request: &ApprovalRequest) -> ApprovalResponse {
+                ApprovalResponse::Deny
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let policy = ApprovalPolicy::default().with_rules(vec![
+            PolicyRule::new("write_file", "src/**", PolicyDecision::Allow),
+            PolicyRule::new("write_file", "*", PolicyDecision::Deny),
+        ]);
+        let executor = TaskExecutor::new()
+            .with_workdir(dir.path())
+            .with_approval(Arc::new(ApprovalGate::new(policy, Arc::new(RejectAll))));
+
+        let write = |target: &str| ToolCall {
+            tool: "write_file".to_string(),
+            target: target.to_string(),
+            operation: "Write".to_string(),
+            content: "data".to_string(),
+        };
+        let denied = executor.dispatch_tool_for_task("plan", 1, &write("notes.txt")).await;
+        assert!(denied.contains("Denied by approval policy"));
+        assert!(!dir.path().join("notes.txt").exists());
+
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        executor.dispatch_tool_for_task("plan", 2, &write("src/lib.rs")).await;
+        assert!(dir.path().join("src/lib.rs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_without_touching_disk() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("config.txt"), "port = 80\n").unwrap();
+        std::fs::write(dir.path().join("old.log"), "log").unwrap();
+        let executor = TaskExecutor::new()
+            .with_workdir(dir.path())
+            .with_dry_run(true);
+
+        let call = |tool: &str, target: &str, content: &str| ToolCall {
+            tool: tool.to_string(),
+            target: target.to_string(),
+            operation: String::new(),
+            content: content.to_string(),
+        };
+        let run = |tool_call: ToolCall| {
+            let executor = executor.clone();
+            async move {
+                let output = executor.dispatch_tool_for_task("plan", 1, &tool_call).await;
+                serde_json::from_str::<serde_json::Value>(&output).unwrap()
+            }
+        };
+
+        let write = run(call("write_file", "config.txt", "port = 8080\n")).await;
+        assert_eq!(write["data"]["dry_run"], true);
+        assert_eq!(write["data"]["action"], "overwrite");
+        assert!(write["data"]["diff"].as_str().unwrap().contains("+port = 8080"));
+
+        let delete = run(call("delete_path", "*.log", "")).await;
+        assert_eq!(delete["data"]["paths"][0], "old.log");
+
+        let replace = run(call(
+            "search_replace",
+            "*.txt",
+            r#"{"search_pattern": "port = (\\d+)", "replace_text": "port = 9$1"}"#,
+        ))
+        .await;
+        assert_eq!(replace["data"]["files"][0]["replacements"], 1);
+
+        let shell = run(call("bash", "touch created.txt", "")).await;
+        assert_eq!(shell["data"]["command"], "touch created.txt");
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.txt")).unwrap(),
+            "port = 80\n"
+        );
+        assert!(dir.path().join("old.log").exists());
+        assert!(!dir.path().join("created.txt").exists());
+
+        let read = run(call("read_file", "config.txt", "")).await;
+        assert_eq!(read["success"], true);
+        assert!(read["data"]["dry_run"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_condense_output_reduces_and_spills() {
+        let dir = tempdir().unwrap();
+        let executor = TaskExecutor::new()
+            .with_workdir(dir.path())
+            .with_output_budget(1000);
+
+        let small = r#"{"success": true}"#;
+        assert_eq!(
+            executor.condense_output("plan", 1, "bash", small, None).await,
+            small
+        );
+
+        let log: String = (0..500).map(|i| format!("line {}\n", i)).collect();
+        let raw = serde_json::json!({
+            "success": true,
+            "data": { "stdout": log, "stderr": "", "exit_code": 0 },
+            "error": null
+        })
+        .to_string();
+        let condensed = executor.condense_output("plan", 3, "bash", &raw, None).await;
+        assert!(condensed.len() < 1400, "{}", condensed.len());
+        assert!(condensed.contains("line 0\n") && condensed.contains("line 499"));
+        assert!(condensed.contains(".context/spill/plan_task3_bash.txt"));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(".context/spill/plan_task3_bash.txt")).unwrap(),
+            raw
+        );
+    }
+
+    #[test]
+    fn test_commit_plan_changes_on_plan_branch() {
+        let dir = tempdir().unwrap();
+        for args in [
+            vec!["init", "-q", "-b", "main"],
+            vec!["config", "user.name", "KAI Test"],
+            vec!["config", "user.email", "kai@example.com"],
+        ] {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(args)
+                .output()
+                .unwrap();
+        }
+        let main = dir.path().join("main.rs");
+        CheckpointStore::for_workdir(dir.path())
+            .snapshot("plan_git", 1, &main)
+            .unwrap();
+        std::fs::write(&main, "fn main() {}\n").unwrap();
+        // Edits the plan didn't make stay out of its commit
+        std::fs::write(dir.path().join("notes.txt"), "unrelated").unwrap();
+
+        let mut plan = Plan::new("Add entry point".to_string(), "Create main.rs".to_string());
+        plan.set_id("plan_git".to_string());
+
+        let disabled = TaskExecutor::new().with_workdir(dir.path());
+        assert!(disabled.commit_plan_changes(&plan).is_none());
+
+        let executor = disabled.with_auto_commit(Some("kai/".to_string()));
+        let result = executor.commit_plan_changes(&plan).unwrap();
+        assert!(result.success, "{:?}", result.error);
+        let data = result.data.unwrap();
+        assert_eq!(data["branch"], "kai/plan_git");
+        let files = data["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["path"], "main.rs");
+        let status = git::git_status(dir.path(), Some("notes.txt")).data.unwrap();
+        assert_eq!(status["entries"][0]["untracked"], true);
+    }
+
+    #[test]
+    fn test_commit_plan_changes_from_subdirectory() {
+        let dir = tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(args)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.name", "KAI Test"]);
+        git(&["config", "user.email", "kai@example.com"]);
+        // Something the user staged before the plan ran
+        std::fs::write(dir.path().join("staged.txt"), "mine").unwrap();
+        git(&["add", "staged.txt"]);
+
+        let workdir = dir.path().join("workdir");
+        std::fs::create_dir(&workdir).unwrap();
+        let main = workdir.join("main.rs");
+        CheckpointStore::for_workdir(&workdir)
+            .snapshot("plan_sub", 1, &main)
+            .unwrap();
+        std::fs::write(&main, "fn main() {}\n").unwrap();
+        let mut plan = Plan::new("Add entry point".to_string(), String::new());
+        plan.set_id("plan_sub".to_string());
+
+        let executor = TaskExecutor::new()
+            .with_workdir(&workdir)
+            .with_auto_commit(Some("kai/".to_string()));
+        let result = executor.commit_plan_changes(&plan).unwrap();
+        assert!(result.success, "{:?}", result.error);
+        let data = result.data.unwrap();
+        assert!(data["commit"].is_string(), "{}", data);
+        assert_eq!(
+            git(&["show", "--name-only", "--format=", "HEAD"]).trim(),
+            "workdir/main.rs"
+        );
+        // The user's staged file is still staged, not committed
+        assert_eq!(
+            git(&["diff", "--cached", "--name-only"]).trim(),
+            "staged.txt"
+        );
+    }
 }
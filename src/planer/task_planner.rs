@@ -1,11 +1,25 @@
 use crate::llm::{Message, OpenRouterClient};
-use crate::planer::plan::{Phase, Plan};
+use crate::planer::plan::{Phase, Plan, PlanContext};
 use crate::planer::queue::{ExecutionQueue, QueueRequest, QueueResponse};
-use crate::planer::task::{Task, TaskExecution, TaskStatus, ToolCall};
+use crate::planer::revision::{PlanRevision, RevisedTask};
+use crate::planer::task::{Task, TaskExecution, TaskPolicy, TaskStatus, ToolCall};
+use crate::planer::validator::{PlanValidator, ValidationReport};
 use crate::prompts::PromptManager;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+/// How often the LLM is asked to fix a plan that fails validation
+const MAX_PLAN_CORRECTIONS: usize = 2;
+
+/// How often a plan may be revised after failed tasks, by default
+pub const DEFAULT_MAX_REPLANS: usize = 2;
+
+/// How much of each task result is shown to the LLM when replanning
+const REPLAN_RESULT_CHARS: usize = 600;
+
+/// How many sub-plans may be nested inside each other
+const MAX_SUB_PLAN_DEPTH: usize = 3;
+
 /// A temporary struct that mirrors the flat JSON structure produced by the LLM for a task.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LlmTask {
@@ -17,6 +31,11 @@ pub struct LlmTask {
     pub content: String,
     pub dependencies: Vec<usize>,
     pub status: TaskStatus,
+    #[serde(default)]
+    pub success_criteria: Option<String>,
+    /// Optional retry, timeout and failure handling
+    #[serde(flatten)]
+    pub policy: TaskPolicy,
 }
 
 /// Minimal plan response from LLM
@@ -27,6 +46,45 @@ pub struct PlanResponse {
     pub phases: Vec<PlanPhase>,
 }
 
+impl PlanResponse {
+    /// The LLM's view of a plan that has not run yet; skipped tasks are left out
+    pub fn from_plan(plan: &Plan) -> Self {
+        let phases = plan
+            .phases
+            .iter()
+            .map(|phase| PlanPhase {
+                name: phase.name.clone(),
+                emoji: phase.emoji.clone(),
+                tasks: phase
+                    .tasks
+                    .iter()
+                    .filter(|task| task.status != TaskStatus::Skipped)
+                    .map(|task| {
+                        let draft = RevisedTask::from_task(task);
+                        LlmTask {
+                            id: task.id,
+                            title: draft.title,
+                            tool: draft.tool,
+                            target: draft.target,
+                            operation: draft.operation,
+                            content: draft.content,
+                            dependencies: draft.dependencies,
+                            status: task.status.clone(),
+                            success_criteria: draft.success_criteria,
+                            policy: draft.policy,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            title: plan.title.clone(),
+            overview: plan.overview.clone(),
+            phases,
+        }
+    }
+}
+
 /// Simple phase structure for LLM communication, using the temporary LlmTask.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PlanPhase {
@@ -47,6 +105,7 @@ pub struct TaskPlanner {
     next_plan_id: usize,
     llm_client: Option<Arc<OpenRouterClient>>,
     model: String,
+    max_replans: usize,
 }
 
 impl Default for TaskPlanner {
@@ -63,6 +122,7 @@ impl TaskPlanner {
             next_plan_id: 1,
             llm_client: None,
             model: "openai/gpt-4o-mini".to_string(),
+            max_replans: DEFAULT_MAX_REPLANS,
         }
     }
 
@@ -74,6 +134,7 @@ impl TaskPlanner {
             next_plan_id: 1,
             llm_client: Some(llm_client),
             model: "openai/gpt-4o-mini".to_string(),
+            max_replans: DEFAULT_MAX_REPLANS,
         }
     }
 
@@ -83,6 +144,32 @@ impl TaskPlanner {
         self
     }
 
+    /// Limit how often a plan is revised after failed tasks (0 disables replanning)
+    pub fn with_max_replans(mut self, max_replans: usize) -> Self {
+        self.max_replans = max_replans;
+        self
+    }
+
+    /// How often a plan may be revised after failed tasks
+    pub fn max_replans(&self) -> usize {
+        if self.llm_client.is_some() {
+            self.max_replans
+        } else {
+            0
+        }
+    }
+
+    /// A planner with the same LLM settings but no plans or queue, e.g. for
+    /// decomposing tasks from concurrently running plan tasks
+    pub fn llm_planner(&self) -> TaskPlanner {
+        TaskPlanner {
+            llm_client: self.llm_client.clone(),
+            model: self.model.clone(),
+            max_replans: self.max_replans,
+            ..TaskPlanner::new()
+        }
+    }
+
     /// Get the LLM client if available
     pub fn get_llm_client(&self) -> Option<Arc<OpenRouterClient>> {
         self.llm_client.clone()
@@ -99,7 +186,35 @@ impl TaskPlanner {
         let system_prompt = PromptManager::get_enhanced_system_prompt_with_context(context);
         let user_prompt = PromptManager::create_plan_user_message_with_context(user_input, context);
 
-        let messages = vec![
+        let (plan_response, report) = self.request_valid_plan(system_prompt, user_prompt).await?;
+
+        let mut plan = Self::convert_plan_response_to_plan(plan_response)?;
+        plan.set_id(self.generate_plan_id());
+        let request_ids = self.execution_queue.push_plan_tasks(&plan);
+        let plan_display = format!("{}", plan);
+        self.active_plans.push(plan);
+
+        let repairs: String = report
+            .repairs
+            .iter()
+            .map(|repair| format!("\n🔧 {}", repair))
+            .collect();
+        Ok(format!(
+            "{}{}\n\n✅ Plan created successfully with {} tasks queued for execution.",
+            plan_display,
+            repairs,
+            request_ids.len()
+        ))
+    }
+
+    /// Ask the LLM for a plan, sending validation problems back for a
+    /// corrected plan up to `MAX_PLAN_CORRECTIONS` times
+    async fn request_valid_plan(
+        &self,
+        system_prompt: String,
+        user_prompt: String,
+    ) -> Result<(PlanResponse, ValidationReport), String> {
+        let mut messages = vec![
             Message {
                 role: "system".to_string(),
                 content: system_prompt,
@@ -110,25 +225,99 @@ impl TaskPlanner {
             },
         ];
 
-        let content = self.send_llm_request(messages).await?;
-        let json_content = PromptManager::extract_json_from_markdown(&content);
-        let plan_response: PlanResponse = serde_json::from_str(&json_content).map_err(|e| {
-            format!(
-                "Failed to parse LLM response as JSON: {}. Response: {}",
-                e, content
+        let validator = PlanValidator::new();
+        let mut corrections = 0;
+        loop {
+            let content = self.send_llm_request(messages.clone()).await?;
+            let json_content = PromptManager::extract_json_from_markdown(&content);
+            let mut plan_response: PlanResponse =
+                serde_json::from_str(&json_content).map_err(|e| {
+                    format!(
+                        "Failed to parse LLM response as JSON: {}. Response: {}",
+                        e, content
+                    )
+                })?;
+
+            let report = validator.validate(&mut plan_response);
+            if report.is_valid() {
+                return Ok((plan_response, report));
+            }
+            if corrections >= MAX_PLAN_CORRECTIONS {
+                return Err(format!(
+                    "The generated plan is invalid after {} correction attempts: {}",
+                    corrections,
+                    report.summary()
+                ));
+            }
+            corrections += 1;
+            messages.push(Message {
+                role: "assistant".to_string(),
+                content,
+            });
+            messages.push(Message {
+                role: "user".to_string(),
+                content: report.correction_request(),
+            });
+        }
+    }
+
+    /// Ask the LLM to rework a plan that has not run yet according to the
+    /// user's `feedback`. The revised plan keeps the original plan's ID.
+    pub async fn revise_plan_with_feedback(
+        &self,
+        plan: &Plan,
+        feedback: &str,
+        context: &crate::context::Context,
+    ) -> Result<Plan, String> {
+        let current = serde_json::to_string_pretty(&PlanResponse::from_plan(plan))
+            .map_err(|e| format!("Failed to serialize plan: {}", e))?;
+        let request = PromptManager::create_plan_feedback_request(&current, feedback);
+        let (plan_response, _) = self
+            .request_valid_plan(
+                PromptManager::get_enhanced_system_prompt_with_context(context),
+                PromptManager::create_plan_user_message_with_context(&request, context),
             )
-        })?;
+            .await?;
 
-        let plan = self.convert_plan_response_to_plan(plan_response)?;
-        let request_ids = self.execution_queue.push_plan_tasks(&plan);
-        let plan_display = format!("{}", plan);
-        self.active_plans.push(plan);
+        let mut revised = Self::convert_plan_response_to_plan(plan_response)?;
+        revised.set_id(plan.id.clone());
+        Ok(revised)
+    }
 
-        Ok(format!(
-            "{}\n\n✅ Plan created successfully with {} tasks queued for execution.",
-            plan_display,
-            request_ids.len()
-        ))
+    /// Plan the tasks of a `sub_plan` task that is about to run.
+    ///
+    /// The LLM sees the sub-plan's goal and the results of the task's
+    /// dependencies in `parent_context`. The returned plan is not yet attached
+    /// to the parent; see `Plan::attach_to_parent`.
+    pub async fn expand_sub_plan(
+        &self,
+        task: &Task,
+        main_context: &crate::context::Context,
+        parent_context: &PlanContext,
+    ) -> Result<Plan, String> {
+        let TaskExecution::SubPlan(outline) = &task.execution else {
+            return Err(format!("Task {} is not a sub-plan", task.id));
+        };
+        if parent_context.depth() >= MAX_SUB_PLAN_DEPTH {
+            return Err(format!(
+                "Sub-plans can only be nested {} levels deep",
+                MAX_SUB_PLAN_DEPTH
+            ));
+        }
+
+        let request = PromptManager::create_sub_plan_request(
+            &task.title,
+            &outline.title,
+            &outline.overview,
+            &parent_context.format_for_llm(&task.dependencies),
+        );
+        let (plan_response, _) = self
+            .request_valid_plan(
+                PromptManager::get_enhanced_system_prompt_with_context(main_context),
+                PromptManager::create_plan_user_message_with_context(&request, main_context),
+            )
+            .await?;
+        Self::convert_plan_response_to_plan(plan_response)
     }
 
     /// Decompose a complex task into smaller, executable sub-tasks
@@ -176,11 +365,79 @@ impl TaskPlanner {
         Ok(sub_tasks)
     }
 
-    /// Convert LLM PlanResponse to internal Plan structure
-    fn convert_plan_response_to_plan(
-        &mut self,
-        plan_response: PlanResponse,
-    ) -> Result<Plan, String> {
+    /// Ask the LLM how to continue a plan after `failed_task_id` failed.
+    ///
+    /// The revision is checked but not applied; see `Plan::apply_revision`.
+    pub async fn revise_plan(
+        &self,
+        plan: &Plan,
+        failed_task_id: usize,
+    ) -> Result<PlanRevision, String> {
+        let tasks = plan.get_all_tasks();
+        let failed = tasks
+            .iter()
+            .find(|task| task.id == failed_task_id)
+            .ok_or_else(|| format!("Task with ID {} not found in plan", failed_task_id))?;
+
+        let outline = format!(
+            "{}\n{}\n\n{}",
+            plan.title,
+            plan.overview,
+            tasks
+                .iter()
+                .map(|task| describe_task(task))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        let error = plan
+            .get_task_result(failed_task_id)
+            .map(|result| truncate(&result.tool_result, REPLAN_RESULT_CHARS))
+            .unwrap_or_else(|| "No result was recorded".to_string());
+        let completed: Vec<String> = tasks
+            .iter()
+            .filter(|task| task.status == TaskStatus::Completed)
+            .filter_map(|task| {
+                let result = plan.get_task_result(task.id)?;
+                Some(format!(
+                    "### Task {}\n{}",
+                    task.id,
+                    truncate(&result.tool_result, REPLAN_RESULT_CHARS)
+                ))
+            })
+            .collect();
+        let completed = if completed.is_empty() {
+            "None".to_string()
+        } else {
+            completed.join("\n\n")
+        };
+
+        let prompt = PromptManager::create_replan_prompt(
+            &outline,
+            &describe_task(failed),
+            &error,
+            &completed,
+            plan.next_task_id,
+        );
+        let content = self
+            .send_llm_request(vec![Message {
+                role: "user".to_string(),
+                content: prompt,
+            }])
+            .await?;
+        let json_content = PromptManager::extract_json_from_markdown(&content);
+        let revision: PlanRevision = serde_json::from_str(&json_content).map_err(|e| {
+            format!(
+                "Failed to parse LLM revision response as JSON: {}. Response: {}",
+                e, content
+            )
+        })?;
+        revision.check(&PlanValidator::new())?;
+        Ok(revision)
+    }
+
+    /// Convert LLM PlanResponse to internal Plan structure.
+    /// Expects a response checked by `PlanValidator`, whose task IDs match `generate_task_id`.
+    fn convert_plan_response_to_plan(plan_response: PlanResponse) -> Result<Plan, String> {
         let mut plan = Plan::new(plan_response.title, plan_response.overview);
 
         for plan_phase in plan_response.phases {
@@ -188,7 +445,8 @@ impl TaskPlanner {
 
             for llm_task in plan_phase.tasks {
                 let task_id = plan.generate_task_id();
-                let task = Task::new_tool_task(
+                debug_assert_eq!(task_id, llm_task.id);
+                let task = Task::new_planned_task(
                     task_id,
                     llm_task.title,
                     llm_task.tool,
@@ -196,7 +454,9 @@ impl TaskPlanner {
                     llm_task.operation,
                     llm_task.content,
                 )
-                .with_dependencies(llm_task.dependencies);
+                .with_dependencies(llm_task.dependencies)
+                .with_policy(llm_task.policy)
+                .with_success_criteria(llm_task.success_criteria);
                 phase.add_task(task);
             }
             plan.add_phase(phase);
@@ -231,57 +491,8 @@ impl TaskPlanner {
             .find(|p| p.id == plan_id)
             .ok_or_else(|| format!("Plan with ID '{}' not found", plan_id))?;
 
-        let original_task = plan
-            .find_task_by_id(original_task_id)
-            .ok_or_else(|| format!("Task with ID {} not found in plan", original_task_id))?
-            .clone();
-
-        let original_dependencies = original_task.dependencies.clone();
-
-        let task_to_decompose = plan.find_task_by_id(original_task_id).unwrap();
-        task_to_decompose.set_status(TaskStatus::Decomposed);
-
-        let mut new_task_ids = Vec::new();
-        let mut last_task_id = original_task_id;
-
-        for (i, sub_task_template) in sub_tasks.into_iter().enumerate() {
-            if let TaskExecution::ToolCall(tool_call) = sub_task_template.execution {
-                let new_task_id = plan.generate_task_id();
-                let mut new_dependencies = sub_task_template.dependencies;
-
-                if i == 0 {
-                    new_dependencies.extend(original_dependencies.clone());
-                } else {
-                    new_dependencies.push(last_task_id);
-                }
-                new_dependencies.sort();
-                new_dependencies.dedup();
-
-                let new_task = Task::new_tool_task(
-                    new_task_id,
-                    sub_task_template.title,
-                    tool_call.tool,
-                    tool_call.target,
-                    tool_call.operation,
-                    tool_call.content,
-                )
-                .with_dependencies(new_dependencies);
-
-                plan.add_task_to_phase(&new_task, None)?;
-                new_task_ids.push(new_task_id);
-                last_task_id = new_task_id;
-            }
-        }
-
-        for task in plan.get_all_tasks_mut() {
-            if task.dependencies.contains(&original_task_id) {
-                task.dependencies.retain(|&dep| dep != original_task_id);
-                task.dependencies.extend(new_task_ids.clone());
-                task.dependencies.sort();
-                task.dependencies.dedup();
-            }
-        }
-        Ok(())
+        plan.replace_task_with_subtasks(original_task_id, sub_tasks)
+            .map(|_| ())
     }
 
     /// Add a high-priority user prompt
@@ -322,9 +533,13 @@ impl TaskPlanner {
         false
     }
 
-    /// Generate unique plan ID
+    /// Generate unique plan ID (timestamped so IDs stay unique across restarts)
     fn generate_plan_id(&mut self) -> String {
-        let id = format!("plan_{}", self.next_plan_id);
+        let id = format!(
+            "plan_{}_{}",
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            self.next_plan_id
+        );
         self.next_plan_id += 1;
         id
     }
@@ -388,3 +603,30 @@ impl TaskPlanner {
         }
     }
 }
+
+/// One-line description of a task for replanning prompts
+fn describe_task(task: &Task) -> String {
+    match &task.execution {
+        TaskExecution::ToolCall(tool_call) => format!(
+            "- Task {} [{:?}] {}: {}({}) {}; depends on {:?}",
+            task.id,
+            task.status,
+            task.title,
+            tool_call.tool,
+            tool_call.target,
+            tool_call.operation,
+            task.dependencies
+        ),
+        TaskExecution::SubPlan(plan) => format!(
+            "- Task {} [{:?}] {}: sub-plan '{}'; depends on {:?}",
+            task.id, task.status, task.title, plan.title, task.dependencies
+        ),
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
@@ -3,6 +3,7 @@ use crate::llm::{Message, OpenRouterClient};
 use crate::planer::plan::{PlanContext, TaskResult};
 use crate::planer::task::{Task, TaskExecution, ToolCall};
 use crate::planer::task_executor::TaskExecutor;
+use crate::planer::template;
 use crate::prompts::PromptManager;
 use crate::tools::{exec, file_system};
 use chrono::Utc;
@@ -11,6 +12,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 /// LLM-powered task processor that executes tasks with context awareness
+#[derive(Clone)]
 pub struct TaskProcessor {
     llm_client: Arc<OpenRouterClient>,
     model: String,
@@ -87,19 +89,27 @@ impl TaskProcessor {
 
         let tool_call = match &task.execution {
             TaskExecution::ToolCall(tc) => tc,
+            // Planned sub-plans run through the executor; `Planner` plans them first
             TaskExecution::SubPlan(_) => {
-                return Ok(TaskResult {
-                    task_id: task.id,
-                    tool_result: "Sub-plan execution not handled by this processor".to_string(),
-                    llm_processed_result: "Sub-plan execution should be handled by TaskExecutor"
-                        .to_string(),
-                    extracted_variables: HashMap::new(),
-                    success: false,
-                    executed_at: Utc::now(),
-                });
+                return Ok(self
+                    .task_executor
+                    .execute_task(
+                        task,
+                        &execution_context.main_context,
+                        &execution_context.plan_context,
+                    )
+                    .await);
             }
         };
 
+        // References to earlier results are resolved before the LLM or the tool sees them
+        let tool_call =
+            match template::interpolate_tool_call(tool_call, &execution_context.plan_context) {
+                Ok(tool_call) => tool_call,
+                Err(e) => return Ok(TaskResult::failed(task.id, &e)),
+            };
+        let tool_call = &tool_call;
+
         // Step 1: LLM analyzes the task with full context
         let analysis = self
             .analyze_task_with_context(task, tool_call, &execution_context)
@@ -117,14 +127,28 @@ impl TaskProcessor {
         }
 
         // Step 2: Execute the actual tool operation
-        let tool_result = self.execute_tool_operation(tool_call).await?;
+        let tool_result = self
+            .execute_tool_operation(task, tool_call, &execution_context)
+            .await?;
+
+        // Oversized output is reduced (and spilled) before it reaches a prompt
+        let condensed_result = self
+            .task_executor
+            .condense_output(
+                &execution_context.plan_context.execution_metadata.plan_id,
+                task.id,
+                &tool_call.tool,
+                &tool_result,
+                Some((&self.llm_client, self.model.as_str())),
+            )
+            .await;
 
         // Step 3: LLM processes the result with context awareness
-        let processed_result = self
+        let mut processed_result = self
             .process_result_with_context(
                 task,
                 tool_call,
-                &tool_result,
+                &condensed_result,
                 &execution_context,
                 &analysis,
             )
@@ -133,18 +157,35 @@ impl TaskProcessor {
         // Step 4: Extract variables as suggested by LLM analysis
         let extracted_variables = self
             .extract_variables_from_result(
-                &tool_result,
+                &condensed_result,
                 &processed_result,
                 &analysis.variables_to_extract,
             )
             .await?;
 
+        // Step 5: Success comes from the tool's own result, then the success criteria
+        let mut success = TaskResult::tool_output_succeeded(&tool_result);
+        if success {
+            if let Err(reason) = self
+                .task_executor
+                .check_success_criteria(
+                    task,
+                    &condensed_result,
+                    Some((&self.llm_client, self.model.as_str())),
+                )
+                .await
+            {
+                success = false;
+                processed_result.push_str(&format!("\n\n{}", reason));
+            }
+        }
+
         Ok(TaskResult {
             task_id: task.id,
             tool_result: tool_result.clone(),
             llm_processed_result: processed_result,
             extracted_variables,
-            success: true,
+            success,
             executed_at: Utc::now(),
         })
     }
@@ -274,8 +315,20 @@ Consider:
     }
 
     /// Execute the actual tool operation
-    async fn execute_tool_operation(&self, tool_call: &ToolCall) -> Result<String, String> {
-        Ok(self.task_executor.dispatch_tool(tool_call).await)
+    async fn execute_tool_operation(
+        &self,
+        task: &Task,
+        tool_call: &ToolCall,
+        context: &TaskExecutionContext,
+    ) -> Result<String, String> {
+        Ok(self
+            .task_executor
+            .dispatch_tool_for_task(
+                &context.plan_context.execution_metadata.plan_id,
+                task.id,
+                tool_call,
+            )
+            .await)
     }
 
     /// LLM processes the tool result with context awareness
@@ -318,7 +371,7 @@ Respond with a clear, structured analysis that will be useful for subsequent tas
             tool_call.tool,
             analysis.analysis,
             analysis.expected_outcome,
-            Self::truncate_text(tool_result, 1000),
+            tool_result,
             context.plan_context.format_for_llm(&task.dependencies)
         );
 
@@ -359,78 +412,14 @@ Respond with a clear, structured analysis that will be useful for subsequent tas
             return Ok(HashMap::new());
         }
 
-        let prompt = format!(
-            r#"Extract specific variables from this task execution result.
-
-## Tool Result
-```
-{}
-```
-
-## Processed Analysis
-{}
-
-## Variables to Extract
-{}
-
-Please extract the requested variables and return them in JSON format:
-
-```json
-{{
-  "variable1": "extracted_value1",
-  "variable2": "extracted_value2"
-}}
-```
-
-If a variable cannot be found or extracted, omit it from the response."#,
-            Self::truncate_text(tool_result, 800),
-            Self::truncate_text(processed_result, 300),
-            variables_to_extract.join(", ")
-        );
-
-        let messages = vec![
-            Message {
-                role: "system".to_string(),
-                content: "You are an expert at extracting structured data from text. Extract only the requested variables in valid JSON format.".to_string(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: prompt,
-            },
-        ];
-
-        let response = self
-            .llm_client
-            .send_conversation(&self.model, messages, Some(400), Some(0.2))
-            .await
-            .map_err(|e| format!("LLM variable extraction failed: {}", e))?;
-
-        let content = response
-            .choices
-            .first()
-            .ok_or("No response from LLM")?
-            .message
-            .content
-            .clone();
-        let json_content = PromptManager::extract_json_from_markdown(&content);
-
-        let parsed_json: serde_json::Value = serde_json::from_str(&json_content).map_err(|e| {
-            format!(
-                "Failed to parse extracted variables: {}. Content: {}",
-                e, content
+        self.task_executor
+            .extract_variables(
+                tool_result,
+                processed_result,
+                variables_to_extract,
+                (&self.llm_client, self.model.as_str()),
             )
-        })?;
-
-        let mut extracted_variables = HashMap::new();
-        if let serde_json::Value::Object(map) = parsed_json {
-            for (key, value) in map {
-                if variables_to_extract.contains(&key) {
-                    extracted_variables.insert(key, value.to_string());
-                }
-            }
-        }
-
-        Ok(extracted_variables)
+            .await
     }
 
     /// Truncate text for prompt inclusion
@@ -0,0 +1,470 @@
+//! Templates in tool call arguments
+//!
+//! A tool call's `target` and `content` may reference earlier results:
+//!
+//! - `{{task.3.stdout}}`: a field of task 3's `ToolResult` data (`error` and
+//!   other top-level fields work too), with `.field` and `[index]` steps such
+//!   as `{{task.2.files[0].name}}`
+//! - `{{task.3.success}}`, `{{task.3.summary}}` and `{{task.3.vars.name}}`:
+//!   the success flag, LLM summary and extracted variables of task 3
+//! - `{{vars.main_file}}`: a plan variable, looked up through parent plans
+//!
+//! Strings are inserted as-is, file entries (objects with a `path`) as their
+//! path and anything else as JSON. In `bash` commands each value is
+//! shell-quoted, so it stays one word whatever it contains; inside quotes the
+//! command already has, it is escaped for those quotes instead. Other
+//! `{{...}}` text is left alone, so file content with its own templates
+//! survives.
+
+use crate::planer::approval::canonical_tool_name;
+use crate::planer::plan::{PlanContext, TaskResult};
+use crate::planer::task::ToolCall;
+use serde_json::Value;
+
+/// Resolve the templates in a tool call's target and content; values in a
+/// shell command are quoted
+pub fn interpolate_tool_call(
+    tool_call: &ToolCall,
+    context: &PlanContext,
+) -> Result<ToolCall, String> {
+    let quote = canonical_tool_name(&tool_call.tool) == "bash";
+    Ok(ToolCall {
+        target: interpolate_with(&tool_call.target, context, quote)?,
+        content: interpolate(&tool_call.content, context)?,
+        ..tool_call.clone()
+    })
+}
+
+/// Replace every `{{task...}}` and `{{vars...}}` reference in `text`
+pub fn interpolate(text: &str, context: &PlanContext) -> Result<String, String> {
+    interpolate_with(text, context, false)
+}
+
+fn interpolate_with(text: &str, context: &PlanContext, quote: bool) -> Result<String, String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    // Quotes opened by the command text so far (values never change it)
+    let mut quoting = Quoting::None;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let inner = &rest[start + 2..start + 2 + len];
+        output.push_str(&rest[..start]);
+        quoting = quoting.after(&rest[..start]);
+        let expr = inner.trim();
+        if expr.starts_with("task.") || expr.starts_with("vars.") {
+            let value = resolve(expr, context)
+                .map_err(|e| format!("Template '{{{{{}}}}}': {}", expr, e))?;
+            if quote {
+                output.push_str(&quoting.quote(&value));
+            } else {
+                output.push_str(&value);
+            }
+        } else {
+            output.push_str(&rest[start..start + 4 + len]);
+            quoting = quoting.after(&rest[start..start + 4 + len]);
+        }
+        rest = &rest[start + 4 + len..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// The shell quotes a template reference sits in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quoting {
+    None,
+    Single,
+    Double,
+}
+
+impl Quoting {
+    /// The quoting in effect after the shell reads `text`
+    fn after(self, text: &str) -> Self {
+        let mut state = self;
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            state = match (state, c) {
+                (Quoting::None | Quoting::Double, '\\') => {
+                    chars.next();
+                    state
+                }
+                (Quoting::None, '\'') => Quoting::Single,
+                (Quoting::None, '"') => Quoting::Double,
+                (Quoting::Single, '\'') | (Quoting::Double, '"') => Quoting::None,
+                _ => state,
+            };
+        }
+        state
+    }
+
+    /// `value` as literal text at this point of a shell command
+    fn quote(self, value: &str) -> String {
+        match self {
+            Quoting::None => shell_quote(value),
+            Quoting::Single => value.replace('\'', "'\\''"),
+            Quoting::Double => value
+                .chars()
+                .flat_map(|c| {
+                    let escape = matches!(c, '\\' | '"' | '$' | '`').then_some('\\');
+                    escape.into_iter().chain(std::iter::once(c))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Quote `value` as a single shell word; plain words are left bare
+fn shell_quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// IDs of the tasks whose results `text` references
+pub fn task_references(text: &str) -> Vec<usize> {
+    let mut ids = Vec::new();
+    renumber_task_references(text, |id| {
+        ids.push(id);
+        None
+    });
+    ids
+}
+
+/// Rewrite the task IDs in `{{task.N...}}` references; IDs mapped to `None` stay
+pub fn renumber_task_references(
+    text: &str,
+    mut renumber: impl FnMut(usize) -> Option<usize>,
+) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let inner = &rest[start + 2..];
+        let expr = inner.trim_start();
+        let Some(after) = expr.strip_prefix("task.") else {
+            output.push_str(&rest[..start + 2]);
+            rest = inner;
+            continue;
+        };
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let id_start = start + 2 + (inner.len() - after.len());
+        output.push_str(&rest[..id_start]);
+        match after[..digits].parse().ok().and_then(&mut renumber) {
+            Some(id) => output.push_str(&id.to_string()),
+            None => output.push_str(&after[..digits]),
+        }
+        rest = &after[digits..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn resolve(expr: &str, context: &PlanContext) -> Result<String, String> {
+    let mut steps = parse_path(expr)?.into_iter();
+    match steps.next() {
+        Some(Step::Key(root)) if root == "vars" => {
+            let Some(Step::Key(name)) = steps.next() else {
+                return Err("expected a variable name after 'vars.'".to_string());
+            };
+            let value = context
+                .get_variable(&name)
+                .ok_or_else(|| format!("plan variable '{}' is not set", name))?;
+            if steps.len() > 0 {
+                return Err(format!("plan variable '{}' has no fields", name));
+            }
+            Ok(value.clone())
+        }
+        Some(Step::Key(_)) => {
+            let Some(Step::Key(id)) = steps.next() else {
+                return Err("expected a task ID after 'task.'".to_string());
+            };
+            let id: usize = id
+                .parse()
+                .map_err(|_| format!("'{}' is not a task ID", id))?;
+            let result = context.get_task_result(id).ok_or_else(|| {
+                format!(
+                    "task {} has no result yet (is it listed in the dependencies?)",
+                    id
+                )
+            })?;
+            let steps: Vec<Step> = steps.collect();
+            task_value(result, &steps).map(render)
+        }
+        _ => Err("empty reference".to_string()),
+    }
+}
+
+fn task_value(result: &TaskResult, steps: &[Step]) -> Result<Value, String> {
+    let id = result.task_id;
+    match steps {
+        [] => Err(format!("expected a field of task {}", id)),
+        [Step::Key(key)] if key == "success" => Ok(Value::Bool(result.success)),
+        [Step::Key(key)] if key == "summary" => {
+            Ok(Value::String(result.llm_processed_result.clone()))
+        }
+        [Step::Key(key), Step::Key(name)] if key == "vars" => result
+            .extracted_variables
+            .get(name)
+            .map(|value| Value::String(value.clone()))
+            .ok_or_else(|| format!("task {} extracted no variable '{}'", id, name)),
+        _ => {
+            let output: Value = serde_json::from_str(&result.tool_result)
+                .map_err(|_| format!("task {} output is not structured", id))?;
+            // Fields are looked up in `data` first, so `task.3.stdout` works
+            let root = match &steps[0] {
+                Step::Key(key) if output["data"].get(key).is_some() => &output["data"],
+                _ => &output,
+            };
+            let mut value = root;
+            let mut path = String::new();
+            for step in steps {
+                value = match step {
+                    Step::Key(key) => {
+                        path.push_str(&format!(".{}", key));
+                        value.get(key)
+                    }
+                    Step::Index(index) => {
+                        path.push_str(&format!("[{}]", index));
+                        value.get(index)
+                    }
+                }
+                .filter(|v| !v.is_null())
+                .ok_or_else(|| format!("task {} output has no '{}'", id, &path[1..]))?;
+            }
+            Ok(value.clone())
+        }
+    }
+}
+
+fn render(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        Value::Object(map) if map.get("path").is_some_and(Value::is_string) => {
+            map["path"].as_str().unwrap_or_default().to_string()
+        }
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Split `task.2.files[0].name` into keys and indexes
+fn parse_path(expr: &str) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    for part in expr.split('.') {
+        let (key, mut indexes) = match part.find('[') {
+            Some(pos) => (&part[..pos], &part[pos..]),
+            None => (part, ""),
+        };
+        if key.is_empty() {
+            return Err(format!("empty field name in '{}'", expr));
+        }
+        steps.push(Step::Key(key.to_string()));
+        while !indexes.is_empty() {
+            let end = indexes
+                .find(']')
+                .filter(|_| indexes.starts_with('['))
+                .ok_or_else(|| format!("malformed index in '{}'", part))?;
+            let index = indexes[1..end]
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' is not an index", &indexes[1..end]))?;
+            steps.push(Step::Index(index));
+            indexes = &indexes[end + 1..];
+        }
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn result(task_id: usize, tool_result: serde_json::Value) -> TaskResult {
+        TaskResult {
+            task_id,
+            tool_result: tool_result.to_string(),
+            llm_processed_result: "Listed two files".to_string(),
+            extracted_variables: HashMap::from([("lang".to_string(), "rust".to_string())]),
+            success: true,
+            executed_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let mut parent = PlanContext::new(None);
+        parent.set_variable("main_file".to_string(), "src/main.rs".to_string());
+        let mut context = PlanContext::new(Some(parent));
+        context.add_task_result(result(
+            2,
+            serde_json::json!({"success": true, "data": {"files": [
+                {"path": "/w/src/lib.rs", "name": "lib.rs"},
+                {"path": "/w/src/main.rs", "name": "main.rs"}
+            ]}}),
+        ));
+        context.add_task_result(result(
+            3,
+            serde_json::json!({"success": true, "data": {"stdout": "1.2.3\n", "exit_code": 0}}),
+        ));
+
+        let resolved = interpolate(
+            "{{task.2.files[0]}} {{ task.2.files[1].name }} {{vars.main_file}} v{{task.3.stdout}}",
+            &context,
+        )
+        .unwrap();
+        assert_eq!(resolved, "/w/src/lib.rs main.rs src/main.rs v1.2.3\n");
+        assert_eq!(
+            interpolate(
+                "{{task.3.exit_code}} {{task.3.success}} {{task.2.vars.lang}}",
+                &context
+            )
+            .unwrap(),
+            "0 true rust"
+        );
+        // Templates that aren't ours pass through untouched
+        assert_eq!(
+            interpolate("Hello {{ name }}", &context).unwrap(),
+            "Hello {{ name }}"
+        );
+
+        let missing = interpolate("{{task.4.stdout}}", &context).unwrap_err();
+        assert!(missing.contains("task 4 has no result"), "{}", missing);
+        let no_field = interpolate("{{task.2.files[5]}}", &context).unwrap_err();
+        assert!(no_field.contains("has no 'files[5]'"), "{}", no_field);
+        assert!(interpolate("{{vars.unknown}}", &context)
+            .unwrap_err()
+            .contains("'unknown' is not set"));
+    }
+
+    #[test]
+    fn test_parent_context_lookup() {
+        let mut parent = PlanContext::new(None);
+        parent.add_task_result(result(1, serde_json::json!({"data": {"stdout": "parent"}})));
+        let mut context = PlanContext::new(Some(parent));
+        context.add_task_result(result(2, serde_json::json!({"data": {"stdout": "child"}})));
+
+        assert_eq!(
+            interpolate("{{task.1.stdout}} {{task.2.stdout}}", &context).unwrap(),
+            "parent child"
+        );
+        assert_eq!(
+            interpolate("{{task.1.vars.lang}}", &context).unwrap(),
+            "rust"
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_index_and_missing_field() {
+        let mut context = PlanContext::new(None);
+        context.add_task_result(result(
+            2,
+            serde_json::json!({"data": {"files": [{"path": "a.rs"}], "count": 1}}),
+        ));
+
+        let index = interpolate("{{task.2.files[1]}}", &context).unwrap_err();
+        assert!(
+            index.contains("task 2 output has no 'files[1]'"),
+            "{}",
+            index
+        );
+        let field = interpolate("{{task.2.files[0].size}}", &context).unwrap_err();
+        assert!(field.contains("has no 'files[0].size'"), "{}", field);
+        let top = interpolate("{{task.2.stdout}}", &context).unwrap_err();
+        assert!(top.contains("has no 'stdout'"), "{}", top);
+        let var = interpolate("{{task.2.vars.version}}", &context).unwrap_err();
+        assert!(var.contains("extracted no variable 'version'"), "{}", var);
+        // Field errors name the whole reference
+        assert!(top.starts_with("Template '{{task.2.stdout}}'"), "{}", top);
+    }
+
+    #[test]
+    fn test_references_after_renumbering() {
+        let text = "cat {{task.2.files[0]}} {{ task.3.stdout }} {{task.9.stdout}} {{vars.x}}";
+        let ids = HashMap::from([(2, 7), (3, 8)]);
+        let renumbered = renumber_task_references(text, |id| ids.get(&id).copied());
+        assert_eq!(
+            renumbered,
+            "cat {{task.7.files[0]}} {{ task.8.stdout }} {{task.9.stdout}} {{vars.x}}"
+        );
+        assert_eq!(task_references(&renumbered), vec![7, 8, 9]);
+
+        let mut context = PlanContext::new(None);
+        context.set_variable("x".to_string(), "y".to_string());
+        context.add_task_result(result(7, serde_json::json!({"data": {"files": ["a.rs"]}})));
+        context.add_task_result(result(8, serde_json::json!({"data": {"stdout": "ok"}})));
+        context.add_task_result(result(9, serde_json::json!({"data": {"stdout": "kept"}})));
+        assert_eq!(
+            interpolate(&renumbered, &context).unwrap(),
+            "cat a.rs ok kept y"
+        );
+        // The old IDs are gone
+        assert!(interpolate(text, &context).is_err());
+    }
+
+    #[test]
+    fn test_bash_values_are_quoted() {
+        let mut context = PlanContext::new(None);
+        context.add_task_result(result(
+            1,
+            serde_json::json!({"data": {"stdout": "it's; rm -rf /", "path": "src/main.rs"}}),
+        ));
+        let call = |tool: &str| ToolCall {
+            tool: tool.to_string(),
+            target: "echo {{task.1.stdout}} {{task.1.path}}".to_string(),
+            operation: String::new(),
+            content: "{{task.1.stdout}}".to_string(),
+        };
+
+        let bash = interpolate_tool_call(&call("run_shell"), &context).unwrap();
+        assert_eq!(bash.target, r"echo 'it'\''s; rm -rf /' src/main.rs");
+        assert_eq!(bash.content, "it's; rm -rf /");
+        let write = interpolate_tool_call(&call("write_file"), &context).unwrap();
+        assert_eq!(write.target, "echo it's; rm -rf / src/main.rs");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_bash_values_inside_quotes() {
+        let value = r#"a b "c" $HOME `id` 'd' \e"#;
+        let mut context = PlanContext::new(None);
+        context.set_variable("v".to_string(), value.to_string());
+        let run = |command: &str| {
+            let call = ToolCall {
+                tool: "bash".to_string(),
+                target: command.to_string(),
+                operation: String::new(),
+                content: String::new(),
+            };
+            let target = interpolate_tool_call(&call, &context).unwrap().target;
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(&target)
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        assert_eq!(run("printf %s {{vars.v}}"), value);
+        assert_eq!(run(r#"printf %s "{{vars.v}}""#), value);
+        assert_eq!(run("printf %s '{{vars.v}}'"), value);
+        assert_eq!(
+            run(r#"printf %s "[{{vars.v}}]" '[{{vars.v}}]'"#),
+            format!("[{0}][{0}]", value)
+        );
+        // Quote characters that are escaped or inside the other quotes don't count
+        assert_eq!(
+            run(r#"printf %s \' "'" {{vars.v}}"#),
+            format!("''{}", value)
+        );
+    }
+}
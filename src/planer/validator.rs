@@ -0,0 +1,443 @@
+//! Plan validation
+//!
+//! The LLM numbers its tasks itself, and those numbers are not the IDs the
+//! plan assigns (`Plan::generate_task_id`). `PlanValidator` checks a
+//! `PlanResponse` before it is converted: task IDs are remapped to the
+//! internal sequence, self-dependencies and references to unknown tasks are
+//! dropped, tasks using another task's result (`{{task.N...}}`) are made to
+//! depend on it, and cycles, unknown tools, missing targets and references to
+//! unknown tasks are reported as structured issues that can be sent back to the LLM for a corrected plan.
+
+use crate::mcp;
+use crate::planer::task::SUB_PLAN_TOOL;
+use crate::planer::task_planner::PlanResponse;
+use crate::planer::template;
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Short tool names the executor accepts in addition to the advertised ones
+const TOOL_ALIASES: &[&str] = &["read", "write", "ls", "bash"];
+
+/// Tools that run fine with an empty target (it defaults to the workdir, HEAD, etc.)
+const TARGET_OPTIONAL_TOOLS: &[&str] = &[
+    "ls",
+    "list_directory",
+    "list_processes",
+    "build_diagnostics",
+    "run_tests",
+    "git_status",
+    "git_diff",
+    "git_log",
+    "git_show",
+    "git_add",
+    "git_commit",
+    mcp::client::LIST_RESOURCES_TOOL,
+    mcp::client::READ_RESOURCE_TOOL,
+];
+
+/// A problem in a plan that cannot be repaired automatically.
+/// Task numbers are the ones the LLM used, so they can be sent back verbatim.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlanIssue {
+    /// The plan contains no tasks at all
+    EmptyPlan,
+    /// Tasks that (transitively) depend on each other
+    Cycle { tasks: Vec<usize> },
+    /// A task names a tool the executor does not know
+    UnknownTool { task: usize, tool: String },
+    /// A task's tool needs a target (path, command, URL, ...) but none was given
+    EmptyTarget { task: usize, tool: String },
+    /// A `{{task.N...}}` template names a missing task or the task itself
+    InvalidReference { task: usize, referenced: usize },
+}
+
+impl fmt::Display for PlanIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanIssue::EmptyPlan => write!(f, "the plan has no tasks"),
+            PlanIssue::Cycle { tasks } => {
+                let path: Vec<String> = tasks.iter().map(|id| id.to_string()).collect();
+                write!(f, "dependency cycle between tasks {}", path.join(" -> "))
+            }
+            PlanIssue::UnknownTool { task, tool } => {
+                write!(f, "task {} uses unknown tool '{}'", task, tool)
+            }
+            PlanIssue::EmptyTarget { task, tool } => {
+                write!(f, "task {} ({}) has an empty target", task, tool)
+            }
+            PlanIssue::InvalidReference { task, referenced } => write!(
+                f,
+                "task {} uses the result of task {}, which is not another task in the plan",
+                task, referenced
+            ),
+        }
+    }
+}
+
+/// Outcome of validating a plan: repairs applied in place plus remaining issues
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub repairs: Vec<String>,
+    pub issues: Vec<PlanIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Message asking the LLM to fix the remaining issues, with the issues as JSON
+    pub fn correction_request(&self) -> String {
+        let issues = serde_json::to_string_pretty(&serde_json::json!({
+            "error": "invalid_plan",
+            "issues": self.issues,
+        }))
+        .unwrap_or_default();
+        format!(
+            "The plan you returned cannot be executed:\n\n```json\n{}\n```\n\n\
+             Return the complete corrected plan as JSON in the same format. \
+             Every task needs a unique id, dependencies may only name ids of other tasks \
+             in the plan, the dependency graph must not contain cycles, tools must be \
+             from the available tool list, and targets must not be empty.",
+            issues
+        )
+    }
+
+    /// One-line summary of the unrepaired issues
+    pub fn summary(&self) -> String {
+        self.issues
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Checks and repairs LLM-generated plans before they are executed
+#[derive(Debug, Clone)]
+pub struct PlanValidator {
+    known_tools: HashSet<String>,
+}
+
+impl Default for PlanValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlanValidator {
+    /// Validator that knows every built-in tool, its aliases, MCP tools and `sub_plan`
+    pub fn new() -> Self {
+        let known_tools = crate::tools::get_all_tools()
+            .into_iter()
+            .map(|tool| tool.function.name)
+            .chain(TOOL_ALIASES.iter().map(|alias| alias.to_string()))
+            .chain([
+                mcp::client::LIST_RESOURCES_TOOL.to_string(),
+                mcp::client::READ_RESOURCE_TOOL.to_string(),
+                SUB_PLAN_TOOL.to_string(),
+            ])
+            .collect();
+        Self { known_tools }
+    }
+
+    /// Accept additional tool names
+    pub fn with_tools<I: IntoIterator<Item = String>>(mut self, tools: I) -> Self {
+        self.known_tools.extend(tools);
+        self
+    }
+
+    pub fn is_known_tool(&self, tool: &str) -> bool {
+        self.known_tools.contains(tool) || tool.starts_with(mcp::client::TOOL_PREFIX)
+    }
+
+    /// Validate `plan`, rewriting task IDs to the internal sequence (1, 2, ... in
+    /// phase order, matching `Plan::generate_task_id`) and applying safe repairs.
+    pub fn validate(&self, plan: &mut PlanResponse) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        // LLM id -> internal id; the first task with a given id owns it
+        let mut id_map: HashMap<usize, usize> = HashMap::new();
+        let mut llm_ids = Vec::new();
+        for (index, task) in plan.phases.iter().flat_map(|p| &p.tasks).enumerate() {
+            let internal_id = index + 1;
+            if let Entry::Vacant(entry) = id_map.entry(task.id) {
+                entry.insert(internal_id);
+            } else {
+                report.repairs.push(format!(
+                    "Task '{}' reuses id {}; references to {} point to the first task with that id",
+                    task.title, task.id, task.id
+                ));
+            }
+            llm_ids.push(task.id);
+        }
+
+        if llm_ids.is_empty() {
+            report.issues.push(PlanIssue::EmptyPlan);
+            return report;
+        }
+
+        let mut dependencies: Vec<Vec<usize>> = Vec::with_capacity(llm_ids.len());
+        for (index, task) in plan
+            .phases
+            .iter_mut()
+            .flat_map(|p| p.tasks.iter_mut())
+            .enumerate()
+        {
+            let internal_id = index + 1;
+            let llm_id = task.id;
+
+            if !self.is_known_tool(&task.tool) {
+                report.issues.push(PlanIssue::UnknownTool {
+                    task: llm_id,
+                    tool: task.tool.clone(),
+                });
+            } else if task.target.trim().is_empty()
+                && !TARGET_OPTIONAL_TOOLS.contains(&task.tool.as_str())
+                && !task.tool.starts_with(mcp::client::TOOL_PREFIX)
+            {
+                report.issues.push(PlanIssue::EmptyTarget {
+                    task: llm_id,
+                    tool: task.tool.clone(),
+                });
+            }
+
+            let mut deps = Vec::new();
+            for &dep in &task.dependencies {
+                match id_map.get(&dep) {
+                    Some(&target) if target == internal_id => report
+                        .repairs
+                        .push(format!("Removed self-dependency of task {}", llm_id)),
+                    Some(&target) => {
+                        if !deps.contains(&target) {
+                            deps.push(target);
+                        }
+                    }
+                    None => report.repairs.push(format!(
+                        "Removed dependency of task {} on nonexistent task {}",
+                        llm_id, dep
+                    )),
+                }
+            }
+
+            // A task using another task's result must wait for it
+            for field in [&mut task.target, &mut task.content] {
+                for referenced in template::task_references(field) {
+                    match id_map.get(&referenced) {
+                        Some(&target) if target != internal_id => {
+                            if !deps.contains(&target) {
+                                deps.push(target);
+                                report.repairs.push(format!(
+                                    "Task {} uses the result of task {}; added it as a dependency",
+                                    llm_id, referenced
+                                ));
+                            }
+                        }
+                        _ => report.issues.push(PlanIssue::InvalidReference {
+                            task: llm_id,
+                            referenced,
+                        }),
+                    }
+                }
+                *field = template::renumber_task_references(field, |id| id_map.get(&id).copied());
+            }
+
+            task.id = internal_id;
+            task.dependencies = deps.clone();
+            dependencies.push(deps);
+        }
+
+        for cycle in find_cycles(&dependencies) {
+            report.issues.push(PlanIssue::Cycle {
+                tasks: cycle.iter().map(|&id| llm_ids[id - 1]).collect(),
+            });
+        }
+
+        report
+    }
+}
+
+/// Find dependency cycles; `dependencies[i]` lists the (1-based) dependencies of task `i + 1`.
+/// Each returned cycle starts and ends with the same task.
+fn find_cycles(dependencies: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    // Kahn's algorithm: whatever cannot be scheduled is on or behind a cycle
+    let count = dependencies.len();
+    let mut remaining: Vec<usize> = dependencies.iter().map(|deps| deps.len()).collect();
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); count + 1];
+    for (index, deps) in dependencies.iter().enumerate() {
+        for &dep in deps {
+            dependents[dep].push(index + 1);
+        }
+    }
+    let mut ready: Vec<usize> = (1..=count).filter(|&id| remaining[id - 1] == 0).collect();
+    let mut scheduled = vec![false; count + 1];
+    while let Some(id) = ready.pop() {
+        scheduled[id] = true;
+        for &dependent in &dependents[id] {
+            remaining[dependent - 1] -= 1;
+            if remaining[dependent - 1] == 0 {
+                ready.push(dependent);
+            }
+        }
+    }
+
+    // Walk unscheduled dependencies until a task repeats to extract each cycle
+    let mut cycles = Vec::new();
+    let mut reported = vec![false; count + 1];
+    for start in 1..=count {
+        if scheduled[start] || reported[start] {
+            continue;
+        }
+        let mut path = vec![start];
+        let mut current = start;
+        while let Some(&next) = dependencies[current - 1].iter().find(|&&d| !scheduled[d]) {
+            if let Some(pos) = path.iter().position(|&id| id == next) {
+                let mut cycle = path[pos..].to_vec();
+                if cycle.iter().all(|&id| !reported[id]) {
+                    for &id in &cycle {
+                        reported[id] = true;
+                    }
+                    cycle.push(next);
+                    cycles.push(cycle);
+                }
+                break;
+            }
+            path.push(next);
+            current = next;
+        }
+        // Tasks merely blocked by a cycle are not reported on their own
+        for id in path {
+            reported[id] = true;
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planer::task::TaskStatus;
+    use crate::planer::task_planner::{LlmTask, PlanPhase};
+
+    fn task(id: usize, tool: &str, target: &str, dependencies: Vec<usize>) -> LlmTask {
+        LlmTask {
+            id,
+            title: format!("Task {}", id),
+            tool: tool.to_string(),
+            target: target.to_string(),
+            operation: String::new(),
+            content: String::new(),
+            dependencies,
+            status: TaskStatus::Pending,
+            success_criteria: None,
+            policy: Default::default(),
+        }
+    }
+
+    fn plan(tasks: Vec<LlmTask>) -> PlanResponse {
+        PlanResponse {
+            title: "Test".to_string(),
+            overview: String::new(),
+            phases: vec![PlanPhase {
+                name: "Phase".to_string(),
+                emoji: "🔧".to_string(),
+                tasks,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_validate_remaps_and_repairs_dependencies() {
+        let mut response = plan(vec![
+            task(10, "read_file", "src/main.rs", vec![10]),
+            task(20, "write_file", "src/lib.rs", vec![10, 99]),
+            task(30, "git_status", "", vec![20, 10, 20]),
+        ]);
+        let report = PlanValidator::new().validate(&mut response);
+
+        assert!(report.is_valid(), "{}", report.summary());
+        assert_eq!(report.repairs.len(), 2);
+        let tasks = &response.phases[0].tasks;
+        assert_eq!(
+            tasks.iter().map(|t| t.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(tasks[0].dependencies.is_empty());
+        assert_eq!(tasks[1].dependencies, vec![1]);
+        assert_eq!(tasks[2].dependencies, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_validate_reports_cycles_tools_and_targets() {
+        let mut response = plan(vec![
+            task(1, "read_file", "a.txt", vec![3]),
+            task(2, "bash", "cargo build", vec![1]),
+            task(3, "bash", "cargo test", vec![2]),
+            task(4, "bash", "echo blocked", vec![3]),
+            task(5, "teleport", "x", vec![]),
+            task(6, "write_file", "  ", vec![]),
+            task(7, "mcp__docs__search", "", vec![]),
+        ]);
+        let report = PlanValidator::new().validate(&mut response);
+
+        assert_eq!(
+            report.issues,
+            vec![
+                PlanIssue::UnknownTool {
+                    task: 5,
+                    tool: "teleport".to_string()
+                },
+                PlanIssue::EmptyTarget {
+                    task: 6,
+                    tool: "write_file".to_string()
+                },
+                PlanIssue::Cycle {
+                    tasks: vec![1, 3, 2, 1]
+                },
+            ]
+        );
+        let request = report.correction_request();
+        assert!(request.contains("\"kind\": \"cycle\""));
+        assert!(request.contains("\"tool\": \"teleport\""));
+
+        let mut empty = plan(Vec::new());
+        assert_eq!(
+            PlanValidator::new().validate(&mut empty).issues,
+            vec![PlanIssue::EmptyPlan]
+        );
+    }
+
+    #[test]
+    fn test_validate_links_result_references() {
+        let mut reading = task(20, "bash", "cat {{task.10.files[0]}}", vec![]);
+        reading.content = "{{ task.10.count }} {{ name }}".to_string();
+        let mut response = plan(vec![
+            task(10, "list_directory", ".", vec![]),
+            reading,
+            task(30, "bash", "echo {{task.30.stdout}} {{task.99.stdout}}", vec![]),
+        ]);
+        let report = PlanValidator::new().validate(&mut response);
+
+        let tasks = &response.phases[0].tasks;
+        assert_eq!(tasks[1].target, "cat {{task.1.files[0]}}");
+        assert_eq!(tasks[1].content, "{{ task.1.count }} {{ name }}");
+        assert_eq!(tasks[1].dependencies, vec![1]);
+        assert_eq!(report.repairs.len(), 1);
+        assert_eq!(
+            report.issues,
+            vec![
+                PlanIssue::InvalidReference {
+                    task: 30,
+                    referenced: 30
+                },
+                PlanIssue::InvalidReference {
+                    task: 30,
+                    referenced: 99
+                },
+            ]
+        );
+    }
+}
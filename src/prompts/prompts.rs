@@ -178,16 +178,42 @@ For each implementation step, specify:
 - **Target**: Use for specific file paths discovered in Analysis phase or Discovery phase. The tool should execute the operation on the target (read  / write / delete Etc).
     Incase of command execution - linux bash command ( could include linux command/s or script/s to run as one line with `&&` operator).
     Refer to the following tool types and relevant value in "target":
-    tool - list_directory: directory path to list
-    tool - read_file: file path to read - "content" will be hold the result of reading the file
-    tool - grep_files: pattern to search in files
-    tool - find_files: pattern to locate files
+    tool - list_directory: directory path to list ("content" may be {{"pattern": "*.rs", "recursive": true}})
+    Searches and listings skip files excluded by .gitignore, .ignore and .kaiignore; add "no_ignore": true to "content" to include them
+    tool - read_file: file path to read - "content" will be hold the result of reading the file; for large files "content" may be {{"offset": 100, "limit": 50}}, {{"head": 20}}, {{"tail": 50}} or {{"byte_offset": 0, "byte_limit": 4096}}
+    tool - grep_files: regex to search for, "content" may be {{"file_pattern": "src/**/*.rs", "context_lines": 2}}
+    tool - find_files: pattern to locate files, "content" may be {{"base_path": "src", "file_type": "file"}}
     tool - write_file: file path to write the "content" into
+    tool - create_path: file or directory path to create ("content" may be {{"is_directory": true}})
+    tool - delete_path: file path or wildcard pattern to delete ("content" may be {{"recursive": true}})
+    tool - search_replace: wildcard pattern of files to modify, "content" must be {{"search_pattern": "...", "replace_text": "..."}} and may add "literal": true, "preview": true or "max_replacements": N; capture groups are $1 / ${{name}}
+    tool - git_status / git_diff / git_log: optional path to inspect (git_diff "content" may be {{"staged": true}}, git_log {{"limit": 10}})
+    tool - git_show: revision to show (default HEAD)
+    tool - git_branch / git_switch: branch name (git_branch "content" may be {{"switch": true}})
+    tool - git_add: paths to stage; git_commit: "content" holds the commit message
+    tool - start_process: long-running shell command to start in the background (servers, watchers); returns a "handle"
+    tool - wait_for_output: process handle, "content" must be {{"pattern": "listening on", "timeout_secs": 30}}
+    tool - read_process_output / stop_process: process handle
+    tool - send_process_input: process handle, "content" holds the text to write to stdin
+    tool - http_request: URL on localhost to call, "content" may be {{"method": "POST", "headers": {{...}}, "body": "...", "timeout_secs": 10}}
+    tool - build_diagnostics: project directory ("." for the working directory), "content" may be {{"provider": "cargo|tsc|pytest", "mode": "check|build|test"}}
+    tool - run_tests: project directory ("." for the working directory), "content" may be {{"framework": "cargo|pytest|jest|go", "filter": "test name"}}
+    tool - outline: source file to list definitions of (functions, structs, classes, impls with line spans)
+    tool - find_symbol: symbol name such as "parse_config" or "TaskExecutor::dispatch_tool", "content" may be {{"path": "src", "kind": "function", "include_body": true}}
+    tool - mcp__<server>__<tool>: tools provided by connected MCP servers, "content" holds the JSON arguments matching the tool's input schema
+    tool - mcp_list_resources / mcp_read_resource: optional server name / resource URI from an MCP server
     tool - bash: shell command to execute
 - **Content**: The exact content to be written/modified incase of write_file or read_file, could be source code or document content.
+- **Result References**: "target" and "content" may use results of earlier tasks instead of guessing them, e.g. {{{{task.3.stdout}}}} (shell output), {{{{task.2.files[0]}}}} (first file found by list_directory), {{{{task.4.results[0]}}}} (find_files), {{{{task.5.content}}}} (read_file), {{{{task.3.success}}}} or {{{{vars.name}}}} (variables extracted from earlier results). They are filled in right before the task runs; the referenced task becomes a dependency.
 - **Files**: ONLY use file paths discovered in Analysis phase
 - **Dependencies**: Must depend on discovery tasks that found the files
-- **Validation**: How to verify the step succeeded
+- **Validation**: How to verify the step succeeded, in "success_criteria" (checked against the tool output once the task has run; a task whose output does not meet it fails)
+- **Sub-Plans**: A large, self-contained step whose details depend on earlier results may use the tool "sub_plan" instead of listing every task now: "target" holds the step's goal and "operation" what it involves. Its tasks are planned when the step runs, using the results of its dependencies. Variables extracted by its tasks become {{{{vars.name}}}} for later tasks, and {{{{task.N.tasks[0].output}}}} holds the output of its first task.
+- **Execution Policy** (optional task fields, omit them when the defaults fit):
+    "max_retries": N and "retry_backoff_ms": first retry delay, doubled for each further retry - for flaky steps such as network installs or downloads
+    "timeout_secs": N - fail an attempt that runs longer (builds or commands that may hang)
+    "optional": true - a failure is tolerated and dependent tasks still run (lint, formatting, nice-to-have checks)
+    "continue_on_failure": true - independent tasks keep starting after this task fails
 
 ### 4. **Verification Phase**
 - Testing procedures using actual discovered files
@@ -270,6 +296,17 @@ The JSON must be able to be parsed programmatically without any cleaning or modi
 - **grep_files**: For searching content across discovered files
 - **find_files**: For locating files by pattern
 - **write_file**: For modifying discovered files
+- **search_replace**: For targeted regex replacements across discovered files
+- **create_path** / **delete_path**: For creating or removing files and directories
+- **git_status** / **git_diff** / **git_log** / **git_show**: For inspecting repository state instead of parsing bash git output
+- **git_branch** / **git_switch** / **git_add** / **git_commit**: For branch and commit operations
+- **start_process** / **wait_for_output** / **read_process_output** / **send_process_input** / **stop_process**: For servers and watchers that must keep running while later tasks probe them (processes are stopped when the plan ends)
+- **http_request**: For checking endpoints of locally running services instead of curl
+- **build_diagnostics**: For verification builds; returns exact file/line/column locations for follow-up fix tasks
+- **run_tests**: For running tests; reports which individual tests passed, failed or were skipped
+- **outline** / **find_symbol**: For locating definitions and reading just one function or type instead of whole files
+- **mcp__<server>__<tool>**: For capabilities offered by connected MCP servers; use mcp_read_resource for documents they expose
+- **sub_plan**: For a large step that can only be broken down once earlier tasks have run
 - **bash**: For shell commands - operation field must contain the actual command (e.g., "cargo build", "npm test", "python script.py")
 
 ## Response Requirements
@@ -356,6 +393,139 @@ Break down the following high-level task into a sequence of atomic tasks that ca
         )
     }
 
+    /// Ask for a plan to be reworked according to the user's feedback
+    pub fn create_plan_feedback_request(plan_json: &str, feedback: &str) -> String {
+        format!(
+            r#"Revise the following plan according to the feedback below. None of its tasks have run yet.
+
+## Current Plan
+```json
+{}
+```
+
+## Feedback
+{}
+
+Return the complete revised plan, not only the changes. Keep the tasks the feedback does not concern as they are."#,
+            plan_json, feedback
+        )
+    }
+
+    /// Describe a `sub_plan` task as the request for a plan of its own
+    pub fn create_sub_plan_request(
+        task_title: &str,
+        goal: &str,
+        details: &str,
+        parent_context: &str,
+    ) -> String {
+        format!(
+            r#"Plan the sub-plan for one step of a larger plan that is already running.
+
+## Step
+- **Title**: {}
+- **Goal**: {}
+- **Details**: {}
+
+{}
+
+Plan only this step. Earlier steps already ran, so reuse their results instead of repeating them. Tasks of this sub-plan reference each other with {{{{task.N...}}}}; results of the larger plan are available as {{{{vars.name}}}} where listed above."#,
+            task_title, goal, details, parent_context
+        )
+    }
+
+    /// Ask for a revised remainder of a plan after one of its tasks failed
+    pub fn create_replan_prompt(
+        plan_outline: &str,
+        failed_task: &str,
+        error: &str,
+        completed_results: &str,
+        next_task_id: usize,
+    ) -> String {
+        format!(
+            r#"You are recovering a partially executed plan after one of its tasks failed.
+Decide how the plan should continue and describe only the changes.
+
+## Plan
+{}
+
+## Failed Task
+{}
+
+## Error
+{}
+
+## Completed Task Results
+{}
+
+## Options
+- `fix`: add tasks that repair the cause of the failure and then redo the failed step.
+- `alternative`: add tasks that reach the failed task's goal a different way.
+- `skip`: add no tasks and continue without the failed task.
+
+## Revision Requirements
+- New tasks take the failed task's place; tasks that depended on it will wait for all new tasks.
+- Number new tasks from {} upwards.
+- `dependencies` may name completed tasks or earlier new tasks, never tasks that wait on the failed task.
+- `drop` lists pending task IDs that are no longer needed. Leave it empty otherwise.
+- Use the same tool names as the plan.
+- "target" and "content" may reference completed results with {{{{task.N.field}}}}, e.g. {{{{task.3.stdout}}}}.
+- New tasks may set "success_criteria", "max_retries", "retry_backoff_ms", "timeout_secs", "optional" and "continue_on_failure" like plan tasks.
+
+## Required Output Format
+**IMPORTANT**: Your response MUST be a single, valid JSON object. Do not include any other text or explanations.
+
+```json
+{{
+  "action": "fix",
+  "reason": "The output directory does not exist",
+  "tasks": [
+    {{
+      "id": {},
+      "title": "Create output directory",
+      "tool": "create_path",
+      "target": "build/",
+      "operation": "Create the missing directory",
+      "content": "",
+      "dependencies": []
+    }}
+  ],
+  "drop": []
+}}
+```
+"#,
+            plan_outline, failed_task, error, completed_results, next_task_id, next_task_id
+        )
+    }
+
+    /// Ask whether a task's output meets its success criteria
+    pub fn create_success_criteria_prompt(task_title: &str, criteria: &str, output: &str) -> String {
+        format!(
+            r#"Check whether a task achieved what it was meant to, judging only by its output.
+
+## Task
+{}
+
+## Success Criteria
+{}
+
+## Output
+```
+{}
+```
+
+Respond with a single JSON object and nothing else:
+
+```json
+{{
+  "met": true,
+  "reason": "One sentence citing the part of the output that decides it"
+}}
+```
+"#,
+            task_title, criteria, output
+        )
+    }
+
     /// Extract JSON content from markdown-wrapped responses
     pub fn extract_json_from_markdown(content: &str) -> String {
         let content = content.trim();
@@ -0,0 +1,444 @@
+//! Structured build diagnostics
+//!
+//! Runs a project's compiler or checker and turns its output into a list of
+//! diagnostics with exact locations, so fix tasks can target them. Providers
+//! are pluggable: cargo (JSON messages), tsc and pytest (JUnit report) are
+//! built in.
+
+use crate::tools::exec;
+use crate::tools::file_system::{FileSystemTool, ToolFunction, ToolParameters, ToolResult};
+use crate::tools::junit::{self, TestOutcome};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// Maximum diagnostics returned by one run
+const MAX_DIAGNOSTICS: usize = 200;
+
+/// A single compiler / checker message
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub end_line: Option<u32>,
+    pub end_column: Option<u32>,
+    /// `error`, `warning`, `note`, ...
+    pub severity: String,
+    pub code: Option<String>,
+    pub message: String,
+    /// Replacement text proposed by the compiler, if any
+    pub suggestion: Option<String>,
+}
+
+/// Captured output of a provider command
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+}
+
+/// A tool that can produce diagnostics for a project
+pub trait DiagnosticsProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Whether the project at `project` looks like it uses this tool
+    fn detect(&self, project: &Path) -> bool;
+    /// Program and arguments for `mode`; `report` is a scratch file path for report output
+    fn command(&self, mode: &str, report: &Path) -> Vec<String>;
+    fn parse(&self, output: &CommandOutput, report: &Path) -> Vec<Diagnostic>;
+}
+
+/// `cargo check/build/test --message-format=json`
+pub struct CargoProvider;
+
+impl CargoProvider {
+    fn parse_message(message: &serde_json::Value) -> Option<Diagnostic> {
+        let text = message["message"].as_str()?.to_string();
+        let spans = message["spans"].as_array().cloned().unwrap_or_default();
+        if spans.is_empty()
+            && (text.starts_with("aborting due to")
+                || text.contains("warning emitted")
+                || text.contains("warnings emitted"))
+        {
+            return None;
+        }
+
+        let primary = spans
+            .iter()
+            .find(|s| s["is_primary"].as_bool().unwrap_or(false))
+            .or_else(|| spans.first());
+        let as_u32 = |v: &serde_json::Value| v.as_u64().map(|n| n as u32);
+
+        // Suggestions live on the spans of `help` children
+        let suggestion = message["children"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|child| child["spans"].as_array().cloned().unwrap_or_default())
+            .chain(spans.iter().cloned())
+            .find_map(|span| {
+                span["suggested_replacement"]
+                    .as_str()
+                    .map(|s| s.to_string())
+            });
+
+        Some(Diagnostic {
+            file: primary.and_then(|s| s["file_name"].as_str().map(|f| f.to_string())),
+            line: primary.and_then(|s| as_u32(&s["line_start"])),
+            column: primary.and_then(|s| as_u32(&s["column_start"])),
+            end_line: primary.and_then(|s| as_u32(&s["line_end"])),
+            end_column: primary.and_then(|s| as_u32(&s["column_end"])),
+            severity: message["level"].as_str().unwrap_or("error").to_string(),
+            code: message["code"]["code"].as_str().map(|c| c.to_string()),
+            message: text,
+            suggestion,
+        })
+    }
+}
+
+impl DiagnosticsProvider for CargoProvider {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn detect(&self, project: &Path) -> bool {
+        project.join("Cargo.toml").exists()
+    }
+
+    fn command(&self, mode: &str, _report: &Path) -> Vec<String> {
+        let mut args = vec!["cargo".to_string()];
+        match mode {
+            "build" => args.push("build".to_string()),
+            "test" => args.extend(["test".to_string(), "--no-run".to_string()]),
+            _ => args.push("check".to_string()),
+        }
+        args.push("--message-format=json".to_string());
+        args
+    }
+
+    fn parse(&self, output: &CommandOutput, _report: &Path) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        for line in output.stdout.lines() {
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            if value["reason"] != "compiler-message" {
+                continue;
+            }
+            if let Some(diagnostic) = Self::parse_message(&value["message"]) {
+                // Tests and the lib often report the same message twice
+                if !diagnostics.contains(&diagnostic) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// `tsc --noEmit --pretty false`
+pub struct TscProvider;
+
+impl TscProvider {
+    fn parse_text(text: &str) -> Vec<Diagnostic> {
+        let line_re =
+            Regex::new(r"^(.+?)\((\d+),(\d+)\): (error|warning|message) (TS\d+): (.*)$").unwrap();
+        text.lines()
+            .filter_map(|line| line_re.captures(line.trim_end()))
+            .map(|c| Diagnostic {
+                file: Some(c[1].to_string()),
+                line: c[2].parse().ok(),
+                column: c[3].parse().ok(),
+                end_line: None,
+                end_column: None,
+                severity: c[4].to_string(),
+                code: Some(c[5].to_string()),
+                message: c[6].to_string(),
+                suggestion: None,
+            })
+            .collect()
+    }
+}
+
+impl DiagnosticsProvider for TscProvider {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn detect(&self, project: &Path) -> bool {
+        project.join("tsconfig.json").exists()
+    }
+
+    fn command(&self, _mode: &str, _report: &Path) -> Vec<String> {
+        [
+            "npx",
+            "--no-install",
+            "tsc",
+            "--noEmit",
+            "--pretty",
+            "false",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    }
+
+    fn parse(&self, output: &CommandOutput, _report: &Path) -> Vec<Diagnostic> {
+        Self::parse_text(&output.stdout)
+    }
+}
+
+/// `pytest` with a JUnit XML report; failing tests become diagnostics
+pub struct PytestProvider;
+
+impl DiagnosticsProvider for PytestProvider {
+    fn name(&self) -> &'static str {
+        "pytest"
+    }
+
+    fn detect(&self, project: &Path) -> bool {
+        [
+            "pytest.ini",
+            "conftest.py",
+            "setup.cfg",
+            "tox.ini",
+            "pyproject.toml",
+        ]
+        .iter()
+        .any(|f| project.join(f).exists())
+    }
+
+    fn command(&self, _mode: &str, report: &Path) -> Vec<String> {
+        vec![
+            "python3".to_string(),
+            "-m".to_string(),
+            "pytest".to_string(),
+            "-q".to_string(),
+            "-o".to_string(),
+            "junit_family=xunit1".to_string(),
+            format!("--junitxml={}", report.display()),
+        ]
+    }
+
+    fn parse(&self, _output: &CommandOutput, report: &Path) -> Vec<Diagnostic> {
+        let xml = std::fs::read_to_string(report).unwrap_or_default();
+        junit::parse_junit(&xml)
+            .into_iter()
+            .filter(|case| case.outcome == TestOutcome::Failed)
+            .map(|case| Diagnostic {
+                file: case.file.clone(),
+                line: case.line,
+                column: None,
+                end_line: None,
+                end_column: None,
+                severity: "error".to_string(),
+                code: Some("test_failed".to_string()),
+                message: format!(
+                    "{}::{} failed: {}",
+                    case.classname,
+                    case.name,
+                    case.message.as_deref().unwrap_or("no message")
+                ),
+                suggestion: None,
+            })
+            .collect()
+    }
+}
+
+/// Built-in providers in detection order
+pub fn providers() -> Vec<Box<dyn DiagnosticsProvider>> {
+    vec![
+        Box::new(CargoProvider),
+        Box::new(TscProvider),
+        Box::new(PytestProvider),
+    ]
+}
+
+fn error_result(message: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        data: None,
+        error: Some(message),
+    }
+}
+
+async fn run_command(args: &[String], project: &Path) -> std::io::Result<CommandOutput> {
+    let output = exec::run_command(args, project).await?;
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        exit_code: output.status.code(),
+    })
+}
+
+fn report_path(provider: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "kai-{}-{}-{}.xml",
+        provider,
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ))
+}
+
+/// Run diagnostics for `project` with the named provider (auto-detected when
+/// `None`). Dropping the future kills the checker.
+pub async fn run_diagnostics(project: &Path, provider: Option<&str>, mode: &str) -> ToolResult {
+    let all = providers();
+    let selected = match provider {
+        Some(name) => all.iter().find(|p| p.name() == name),
+        None => all.iter().find(|p| p.detect(project)),
+    };
+    let Some(provider) = selected else {
+        return error_result(match provider {
+            Some(name) => format!(
+                "Unknown diagnostics provider '{}'. Available: {}",
+                name,
+                all.iter().map(|p| p.name()).collect::<Vec<_>>().join(", ")
+            ),
+            None => format!("No supported build tool detected in {}", project.display()),
+        });
+    };
+
+    let report = report_path(provider.name());
+    let command = provider.command(mode, &report);
+    let output = match run_command(&command, project).await {
+        Ok(output) => output,
+        Err(e) => return error_result(format!("Failed to run '{}': {}", command.join(" "), e)),
+    };
+    let mut diagnostics = provider.parse(&output, &report);
+    let _ = std::fs::remove_file(&report);
+
+    let count = |severity: &str| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+    let errors = count("error");
+    let warnings = count("warning");
+    let total = diagnostics.len();
+    diagnostics.truncate(MAX_DIAGNOSTICS);
+    let succeeded = output.exit_code == Some(0) && errors == 0;
+
+    // Without parsed diagnostics the raw stderr is the only clue to what went wrong
+    let stderr_tail = if !succeeded && total == 0 {
+        let lines: Vec<&str> = output.stderr.lines().collect();
+        Some(lines[lines.len().saturating_sub(30)..].join("\n"))
+    } else {
+        None
+    };
+
+    ToolResult {
+        success: succeeded,
+        data: Some(json!({
+            "provider": provider.name(),
+            "command": command.join(" "),
+            "exit_code": output.exit_code,
+            "errors": errors,
+            "warnings": warnings,
+            "total": total,
+            "diagnostics": diagnostics,
+            "stderr_tail": stderr_tail,
+        })),
+        error: if succeeded {
+            None
+        } else {
+            Some(format!(
+                "{} reported {} error(s) and {} warning(s)",
+                provider.name(),
+                errors,
+                warnings
+            ))
+        },
+    }
+}
+
+/// Generate the OpenRouter-compatible definition for the diagnostics tool
+pub fn get_diagnostics_tool() -> FileSystemTool {
+    FileSystemTool {
+        tool_type: "function".to_string(),
+        function: ToolFunction {
+            name: "build_diagnostics".to_string(),
+            description: "Build or check a project and return structured diagnostics (file, line, column, severity, code, message, suggested replacement). Supports cargo, tsc and pytest.".to_string(),
+            parameters: ToolParameters {
+                param_type: "object".to_string(),
+                properties: json!({
+                    "path": {
+                        "type": "string",
+                        "description": "Project directory (default: working directory).",
+                        "default": "."
+                    },
+                    "provider": {
+                        "type": "string",
+                        "enum": ["cargo", "tsc", "pytest"],
+                        "description": "Build tool to use; detected from project files when omitted."
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["check", "build", "test"],
+                        "description": "For cargo: check, build, or compile tests without running them.",
+                        "default": "check"
+                    }
+                }),
+                required: vec![],
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_cargo_and_tsc_output() {
+        let stdout = r#"{"reason":"compiler-artifact","target":{}}
+{"reason":"compiler-message","message":{"message":"cannot find value `y` in this scope","code":{"code":"E0425","explanation":null},"level":"error","spans":[{"file_name":"src/main.rs","line_start":3,"line_end":3,"column_start":13,"column_end":14,"is_primary":true,"suggested_replacement":null}],"children":[{"message":"a local variable with a similar name exists","level":"help","spans":[{"file_name":"src/main.rs","line_start":3,"line_end":3,"column_start":13,"column_end":14,"is_primary":true,"suggested_replacement":"x"}],"children":[]}]}}
+{"reason":"compiler-message","message":{"message":"aborting due to 1 previous error","code":null,"level":"error","spans":[],"children":[]}}"#;
+        let output = CommandOutput {
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: Some(101),
+        };
+        let diagnostics = CargoProvider.parse(&output, Path::new(""));
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert_eq!(d.file.as_deref(), Some("src/main.rs"));
+        assert_eq!((d.line, d.column), (Some(3), Some(13)));
+        assert_eq!(d.code.as_deref(), Some("E0425"));
+        assert_eq!(d.suggestion.as_deref(), Some("x"));
+
+        let tsc = TscProvider::parse_text(
+            "src/app.ts(4,7): error TS2322: Type 'string' is not assignable to type 'number'.\n",
+        );
+        assert_eq!(tsc.len(), 1);
+        assert_eq!(tsc[0].code.as_deref(), Some("TS2322"));
+        assert_eq!(tsc[0].line, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_run_cargo_diagnostics() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"broken\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "fn main() {\n    let x = 1;\n    println!(\"{}\", y);\n}\n",
+        )
+        .unwrap();
+
+        let result = run_diagnostics(dir.path(), None, "check").await;
+        assert!(!result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["provider"], "cargo");
+        assert_eq!(data["errors"], 1);
+        assert_eq!(data["diagnostics"][0]["line"], 3);
+    }
+}
@@ -0,0 +1,44 @@
+use similar::TextDiff;
+
+/// Render a unified diff between two versions of a file (empty string when identical)
+pub fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+/// Count added and removed lines between two versions of a file
+pub fn line_stats(old: &str, new: &str) -> (usize, usize) {
+    let diff = TextDiff::from_lines(old, new);
+    let mut added = 0;
+    let mut removed = 0;
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            similar::ChangeTag::Insert => added += 1,
+            similar::ChangeTag::Delete => removed += 1,
+            similar::ChangeTag::Equal => {}
+        }
+    }
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified_diff("a\nb\nc\n", "a\nB\nc\n", "file.txt");
+        assert!(diff.starts_with("--- a/file.txt\n+++ b/file.txt\n"));
+        assert!(diff.contains("-b\n"));
+        assert!(diff.contains("+B\n"));
+        assert_eq!(line_stats("a\nb\nc\n", "a\nB\nc\nd\n"), (2, 1));
+        assert!(unified_diff("same", "same", "file.txt").is_empty());
+    }
+}
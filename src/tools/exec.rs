@@ -1,11 +1,31 @@
 use crate::tools::file_system::{FileSystemTool, ToolFunction, ToolParameters, ToolResult};
 use serde_json::json;
-use std::process::Command;
+use std::path::Path;
+use std::process::{Output, Stdio};
+use tokio::process::Command;
 
-pub fn run_shell_command_tool(command: &str) -> ToolResult {
-    let output = Command::new("sh").arg("-c").arg(command).output();
+/// Kills a command's process group if it is dropped before the command
+/// finishes, e.g. when a task timeout cancels the future waiting on it
+struct ProcessGroupGuard(Option<u32>);
 
-    match output {
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            // SAFETY: killpg only sends a signal to the command's own group
+            unsafe {
+                libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Run `command` through `sh -c` without blocking the runtime. Dropping the
+/// returned future kills the command and everything it started.
+pub async fn run_shell_command_tool(command: &str) -> ToolResult {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    match output_of(cmd).await {
         Ok(output) => {
             let stdout = String::from_utf8_lossy(&output.stdout).to_string();
             let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -31,6 +51,32 @@ pub fn run_shell_command_tool(command: &str) -> ToolResult {
     }
 }
 
+/// Run `args[0]` with the rest of `args` in `dir`, the same way
+/// `run_shell_command_tool` runs a shell command
+pub async fn run_command(args: &[String], dir: &Path) -> std::io::Result<Output> {
+    let mut cmd = Command::new(&args[0]);
+    cmd.args(&args[1..]).current_dir(dir);
+    output_of(cmd).await
+}
+
+/// Collect a command's output without blocking the runtime; dropping the
+/// future kills its process group
+async fn output_of(mut cmd: Command) -> std::io::Result<Output> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let child = cmd.spawn()?;
+    let mut guard = ProcessGroupGuard(child.id());
+    let output = child.wait_with_output().await;
+    // Background jobs the command started on purpose keep running
+    guard.0 = None;
+    output
+}
+
 pub fn get_shell_tool() -> FileSystemTool {
     FileSystemTool {
         tool_type: "function".to_string(),
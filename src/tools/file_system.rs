@@ -3,6 +3,7 @@ use std::fs;
 use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
 use glob::glob;
+use crate::tools::walk;
 use regex::Regex;
 
 /// File system tools for OpenRouter LLM integration
@@ -38,27 +39,327 @@ pub struct ToolResult {
     pub error: Option<String>,
 }
 
+/// Text returned by a read without an explicit range
+pub const DEFAULT_MAX_READ_BYTES: usize = 100 * 1024;
+
+/// Bytes shown in the hex preview of binary files
+const BINARY_PREVIEW_BYTES: usize = 256;
+
+/// Which part of a file `read_file_with_options` returns.
+/// Line numbers are 1-based; byte ranges take precedence over line ranges.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// First line to return
+    pub offset: Option<usize>,
+    /// Number of lines to return
+    pub limit: Option<usize>,
+    /// First byte to return
+    pub byte_offset: Option<u64>,
+    /// Number of bytes to return
+    pub byte_limit: Option<u64>,
+    /// Return only the first N lines
+    pub head: Option<usize>,
+    /// Return only the last N lines
+    pub tail: Option<usize>,
+}
+
+/// How `search_replace` matches and applies replacements
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SearchReplaceOptions {
+    /// Match case (default true)
+    pub case_sensitive: Option<bool>,
+    /// Treat the pattern and replacement as plain text instead of regex / `$1` templates
+    #[serde(default)]
+    pub literal: bool,
+    /// Report the diffs without writing
+    #[serde(default)]
+    pub preview: bool,
+    /// Refuse to change anything if there are more matches than this
+    pub max_replacements: Option<usize>,
+    /// Include files excluded by ignore files
+    pub no_ignore: Option<bool>,
+    /// Refuse to modify files outside this directory
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+}
+
+/// Rewrite sed-style `\1` backreferences as `${1}` for the regex crate
+fn expand_backreferences(replacement: &str) -> String {
+    let mut expanded = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(d)) if d.is_ascii_digit() => {
+                let digit = *d;
+                chars.next();
+                expanded.push_str(&format!("${{{}}}", digit));
+            }
+            _ => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+impl ReadOptions {
+    /// Zero-based, end-exclusive line range selected by these options
+    fn line_range(&self, total_lines: usize) -> (usize, usize) {
+        if let Some(tail) = self.tail {
+            return (total_lines.saturating_sub(tail), total_lines);
+        }
+        if let Some(head) = self.head {
+            return (0, head.min(total_lines));
+        }
+        let start = self.offset.unwrap_or(1).saturating_sub(1).min(total_lines);
+        let end = match self.limit {
+            Some(limit) => (start + limit).min(total_lines),
+            None => total_lines,
+        };
+        (start, end)
+    }
+}
+
+/// Encoding detected from a file's bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Binary,
+}
+
+impl TextEncoding {
+    fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Utf8Bom => "utf-8-bom",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Utf16Be => "utf-16be",
+            TextEncoding::Latin1 => "latin-1",
+            TextEncoding::Binary => "binary",
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        let utf16 = |to_u16: fn([u8; 2]) -> u16| {
+            let units: Vec<u16> = bytes[2..].chunks_exact(2).map(|c| to_u16([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        };
+        match self {
+            TextEncoding::Utf8 | TextEncoding::Binary => String::from_utf8_lossy(bytes).to_string(),
+            TextEncoding::Utf8Bom => String::from_utf8_lossy(&bytes[3..]).to_string(),
+            TextEncoding::Utf16Le => utf16(u16::from_le_bytes),
+            TextEncoding::Utf16Be => utf16(u16::from_be_bytes),
+            TextEncoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        }
+    }
+}
+
+/// Guess the encoding of `bytes`, or `Binary` for non-text content
+fn detect_encoding(bytes: &[u8]) -> TextEncoding {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return TextEncoding::Utf8Bom;
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return TextEncoding::Utf16Le;
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return TextEncoding::Utf16Be;
+    }
+
+    let sample = &bytes[..bytes.len().min(8192)];
+    if sample.contains(&0) {
+        return TextEncoding::Binary;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => TextEncoding::Utf8,
+        // A multi-byte character cut off at the end of the sample
+        Err(e) if e.error_len().is_none() => TextEncoding::Utf8,
+        Err(_) => {
+            let control = sample
+                .iter()
+                .filter(|&&b| b < 0x20 && !matches!(b, b'\n' | b'\r' | b'\t' | 0x0C))
+                .count();
+            if control * 10 > sample.len() {
+                TextEncoding::Binary
+            } else {
+                TextEncoding::Latin1
+            }
+        }
+    }
+}
+
+/// Identify common binary formats by their magic bytes
+fn guess_binary_type(bytes: &[u8]) -> &'static str {
+    let signatures: [(&[u8], &str); 9] = [
+        (b"\x89PNG", "png image"),
+        (b"\xFF\xD8\xFF", "jpeg image"),
+        (b"GIF8", "gif image"),
+        (b"%PDF", "pdf document"),
+        (b"PK\x03\x04", "zip archive"),
+        (b"\x1F\x8B", "gzip archive"),
+        (b"\x7FELF", "elf executable"),
+        (b"MZ", "windows executable"),
+        (b"\0asm", "wasm module"),
+    ];
+    signatures
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+        .map(|(_, kind)| *kind)
+        .unwrap_or("unknown binary")
+}
+
+/// `xxd`-style dump: offset, hex bytes and printable ASCII per 16-byte row
+fn hex_dump(bytes: &[u8], base_offset: u64) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            format!("{:08x}: {:<47}  {}", base_offset + (i * 16) as u64, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn read_byte_range(path: &str, offset: u64, limit: u64) -> io::Result<Vec<u8>> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.take(limit).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
 /// File system operations implementation
 pub struct FileSystemOperations;
 
 impl FileSystemOperations {
-    /// Read file contents
+    /// Read file contents (large files are truncated, see `read_file_with_options`)
     pub fn read_file(path: &str) -> ToolResult {
-        match std::fs::read_to_string(path) {
-            Ok(content) => ToolResult {
+        Self::read_file_with_options(path, &ReadOptions::default())
+    }
+
+    /// Read part of a file: a line range, a byte range, or its head/tail.
+    /// Binary files return a hex preview instead of content.
+    pub fn read_file_with_options(path: &str, options: &ReadOptions) -> ToolResult {
+        let error = |e: io::Error| ToolResult {
+            success: false,
+            data: None,
+            error: Some(format!("Failed to read file '{}': {}", path, e)),
+        };
+
+        let total_size = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                return ToolResult {
+                    success: false,
+                    data: None,
+                    error: Some(format!("'{}' is a directory, not a file", path)),
+                }
+            }
+            Ok(metadata) => metadata.len(),
+            Err(e) => return error(e),
+        };
+
+        // Byte ranges are read directly so huge files are never fully loaded
+        let byte_range = options.byte_offset.is_some() || options.byte_limit.is_some();
+        let byte_offset = options.byte_offset.unwrap_or(0).min(total_size);
+        let bytes = if byte_range {
+            let limit = options.byte_limit.unwrap_or(DEFAULT_MAX_READ_BYTES as u64);
+            read_byte_range(path, byte_offset, limit)
+        } else {
+            fs::read(path)
+        };
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => return error(e),
+        };
+
+        let encoding = detect_encoding(&bytes);
+        if encoding == TextEncoding::Binary {
+            let preview_len = bytes.len().min(BINARY_PREVIEW_BYTES);
+            return ToolResult {
                 success: true,
                 data: Some(serde_json::json!({
                     "path": path,
-                    "content": content,
-                    "size": content.len()
+                    "binary": true,
+                    "file_type": guess_binary_type(&bytes),
+                    "total_size": total_size,
+                    "size": total_size,
+                    "byte_offset": byte_offset,
+                    "preview": hex_dump(&bytes[..preview_len], byte_offset),
+                    "preview_bytes": preview_len,
                 })),
                 error: None,
-            },
-            Err(e) => ToolResult {
-                success: false,
-                data: None,
-                error: Some(format!("Failed to read file '{}': {}", path, e)),
-            },
+            };
+        }
+
+        let text = encoding.decode(&bytes);
+        if byte_range {
+            let end = byte_offset + bytes.len() as u64;
+            return ToolResult {
+                success: true,
+                data: Some(serde_json::json!({
+                    "path": path,
+                    "content": text,
+                    "encoding": encoding.name(),
+                    "binary": false,
+                    "total_size": total_size,
+                    "size": total_size,
+                    "byte_offset": byte_offset,
+                    "bytes_read": bytes.len(),
+                    "has_more": end < total_size,
+                    "next_byte_offset": (end < total_size).then_some(end),
+                })),
+                error: None,
+            };
+        }
+
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let total_lines = lines.len();
+        let (start, end) = options.line_range(total_lines);
+        let mut content: String = lines[start..end].concat();
+
+        // Without an explicit range, cap the amount of text returned
+        let mut end = end;
+        let explicit_range = options.offset.is_some()
+            || options.limit.is_some()
+            || options.head.is_some()
+            || options.tail.is_some();
+        if !explicit_range && content.len() > DEFAULT_MAX_READ_BYTES {
+            let mut taken = 0;
+            end = start;
+            for line in &lines[start..] {
+                if taken + line.len() > DEFAULT_MAX_READ_BYTES && end > start {
+                    break;
+                }
+                taken += line.len();
+                end += 1;
+            }
+            content = lines[start..end].concat();
+        }
+
+        ToolResult {
+            success: true,
+            data: Some(serde_json::json!({
+                "path": path,
+                "content": content,
+                "encoding": encoding.name(),
+                "binary": false,
+                "total_size": total_size,
+                "size": total_size,
+                "total_lines": total_lines,
+                "start_line": if end > start { start + 1 } else { 0 },
+                "end_line": end,
+                "has_more": end < total_lines,
+                "next_offset": (end < total_lines).then_some(end + 1),
+            })),
+            error: None,
         }
     }
 
@@ -94,9 +395,13 @@ impl FileSystemOperations {
         }
     }
 
-    /// List directory contents with optional wildcard pattern
-    pub fn list_directory(path: &str, pattern: Option<&str>, recursive: Option<bool>) -> ToolResult {
+    /// List directory contents with optional wildcard pattern. Entries
+    /// excluded by `.gitignore`, `.ignore` or `.kaiignore` are skipped
+    /// unless `no_ignore` is set.
+    pub fn list_directory(path: &str, pattern: Option<&str>, recursive: Option<bool>,
+                          no_ignore: Option<bool>) -> ToolResult {
         let recursive_mode = recursive.unwrap_or(false);
+        let respect_ignore = !no_ignore.unwrap_or(false);
         let search_pattern = if let Some(p) = pattern {
             format!("{}/{}", path, p)
         } else {
@@ -109,42 +414,31 @@ impl FileSystemOperations {
             search_pattern
         };
 
-        match glob(&glob_pattern) {
+        match walk::glob_paths(&glob_pattern, respect_ignore) {
             Ok(entries) => {
                 let mut files = Vec::new();
                 let mut dirs = Vec::new();
                 
-                for entry in entries {
-                    match entry {
-                        Ok(path_buf) => {
-                            let path_str = path_buf.to_string_lossy().to_string();
-                            let metadata = std::fs::metadata(&path_buf);
-                            
-                            if let Ok(meta) = metadata {
-                                let entry_info = serde_json::json!({
-                                    "path": path_str,
-                                    "name": path_buf.file_name().unwrap_or_default().to_string_lossy(),
-                                    "size": meta.len(),
-                                    "is_file": meta.is_file(),
-                                    "is_dir": meta.is_dir(),
-                                    "modified": meta.modified().ok()
-                                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                                        .map(|d| d.as_secs())
-                                });
-                                
-                                if meta.is_file() {
-                                    files.push(entry_info);
-                                } else if meta.is_dir() {
-                                    dirs.push(entry_info);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            return ToolResult {
-                                success: false,
-                                data: None,
-                                error: Some(format!("Error reading directory entry: {}", e)),
-                            };
+                for path_buf in entries {
+                    let path_str = path_buf.to_string_lossy().to_string();
+                    let metadata = std::fs::metadata(&path_buf);
+                    
+                    if let Ok(meta) = metadata {
+                        let entry_info = serde_json::json!({
+                            "path": path_str,
+                            "name": path_buf.file_name().unwrap_or_default().to_string_lossy(),
+                            "size": meta.len(),
+                            "is_file": meta.is_file(),
+                            "is_dir": meta.is_dir(),
+                            "modified": meta.modified().ok()
+                                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                                .map(|d| d.as_secs())
+                        });
+                        
+                        if meta.is_file() {
+                            files.push(entry_info);
+                        } else if meta.is_dir() {
+                            dirs.push(entry_info);
                         }
                     }
                 }
@@ -155,6 +449,7 @@ impl FileSystemOperations {
                         "path": path,
                         "pattern": pattern,
                         "recursive": recursive_mode,
+                        "no_ignore": !respect_ignore,
                         "directories": dirs,
                         "files": files,
                         "total_count": files.len() + dirs.len()
@@ -262,8 +557,10 @@ impl FileSystemOperations {
 
     /// Search for text in files using grep-like functionality
     pub fn grep_files(pattern: &str, file_pattern: &str, case_sensitive: Option<bool>, 
-                      line_numbers: Option<bool>, context_lines: Option<u32>) -> ToolResult {
+                      line_numbers: Option<bool>, context_lines: Option<u32>,
+                      no_ignore: Option<bool>) -> ToolResult {
         let case_sens = case_sensitive.unwrap_or(true);
+        let respect_ignore = !no_ignore.unwrap_or(false);
         let show_line_nums = line_numbers.unwrap_or(true);
         let context = context_lines.unwrap_or(0);
 
@@ -283,57 +580,52 @@ impl FileSystemOperations {
         let mut results = Vec::new();
         let mut total_matches = 0;
 
-        match glob(file_pattern) {
+        match walk::glob_paths(file_pattern, respect_ignore) {
             Ok(entries) => {
-                for entry in entries {
-                    match entry {
-                        Ok(path_buf) => {
-                            if path_buf.is_file() {
-                                match std::fs::read_to_string(&path_buf) {
-                                    Ok(content) => {
-                                        let lines: Vec<&str> = content.lines().collect();
-                                        let mut file_matches = Vec::new();
-
-                                        for (line_num, line) in lines.iter().enumerate() {
-                                            if regex.is_match(line) {
-                                                let mut match_info = serde_json::json!({
-                                                    "line": line.to_string(),
-                                                    "line_number": line_num + 1
-                                                });
-
-                                                if context > 0 {
-                                                    let start = line_num.saturating_sub(context as usize);
-                                                    let end = std::cmp::min(line_num + context as usize + 1, lines.len());
-                                                    let context_lines: Vec<String> = lines[start..end]
-                                                        .iter()
-                                                        .enumerate()
-                                                        .map(|(i, l)| format!("{}: {}", start + i + 1, l))
-                                                        .collect();
-                                                    match_info.as_object_mut().unwrap()
-                                                        .insert("context".to_string(), serde_json::json!(context_lines));
-                                                }
-
-                                                file_matches.push(match_info);
-                                                total_matches += 1;
-                                            }
-                                        }
+                for path_buf in entries {
+                    if path_buf.is_file() {
+                        match std::fs::read_to_string(&path_buf) {
+                            Ok(content) => {
+                                let lines: Vec<&str> = content.lines().collect();
+                                let mut file_matches = Vec::new();
+
+                                for (line_num, line) in lines.iter().enumerate() {
+                                    if regex.is_match(line) {
+                                        let mut match_info = serde_json::json!({
+                                            "line": line.to_string(),
+                                            "line_number": line_num + 1
+                                        });
 
-                                        if !file_matches.is_empty() {
-                                            results.push(serde_json::json!({
-                                                "file": path_buf.to_string_lossy(),
-                                                "matches": file_matches,
-                                                "match_count": file_matches.len()
-                                            }));
+                                        if context > 0 {
+                                            let start = line_num.saturating_sub(context as usize);
+                                            let end = std::cmp::min(line_num + context as usize + 1, lines.len());
+                                            let context_lines: Vec<String> = lines[start..end]
+                                                .iter()
+                                                .enumerate()
+                                                .map(|(i, l)| format!("{}: {}", start + i + 1, l))
+                                                .collect();
+                                            match_info.as_object_mut().unwrap()
+                                                .insert("context".to_string(), serde_json::json!(context_lines));
                                         }
-                                    }
-                                    Err(e) => {
-                                        // Skip files that can't be read (binary files, etc.)
-                                        continue;
+
+                                        file_matches.push(match_info);
+                                        total_matches += 1;
                                     }
                                 }
+
+                                if !file_matches.is_empty() {
+                                    results.push(serde_json::json!({
+                                        "file": path_buf.to_string_lossy(),
+                                        "matches": file_matches,
+                                        "match_count": file_matches.len()
+                                    }));
+                                }
+                            }
+                            Err(e) => {
+                                // Skip files that can't be read (binary files, etc.)
+                                continue;
                             }
                         }
-                        Err(_) => continue,
                     }
                 }
 
@@ -343,6 +635,7 @@ impl FileSystemOperations {
                         "pattern": pattern,
                         "file_pattern": file_pattern,
                         "case_sensitive": case_sens,
+                        "no_ignore": !respect_ignore,
                         "results": results,
                         "total_matches": total_matches,
                         "files_with_matches": results.len()
@@ -353,148 +646,191 @@ impl FileSystemOperations {
             Err(e) => ToolResult {
                 success: false,
                 data: None,
-                error: Some(format!("Invalid file pattern '{}': {}", file_pattern, e)),
+                error: Some(e),
             },
         }
     }
 
-    /// Search and replace text in files
-    pub fn search_replace(search_pattern: &str, replace_text: &str, file_pattern: &str, 
-                         case_sensitive: Option<bool>, backup: Option<bool>) -> ToolResult {
-        let case_sens = case_sensitive.unwrap_or(true);
-        let create_backup = backup.unwrap_or(true);
+    /// Search and replace text in files.
+    ///
+    /// All replacements are computed before anything is written, so a
+    /// `max_replacements` overrun or a file outside `root` leaves every file
+    /// untouched. Each modified file is reported with its match count and a
+    /// unified diff; with `preview` nothing is written at all. No backup files
+    /// are created: callers that need undo snapshot the files first (the task
+    /// executor checkpoints them).
+    pub fn search_replace(search_pattern: &str, replace_text: &str, file_pattern: &str,
+                          options: &SearchReplaceOptions) -> ToolResult {
+        let case_sens = options.case_sensitive.unwrap_or(true);
+        let respect_ignore = !options.no_ignore.unwrap_or(false);
+        let failure = |message: String| ToolResult {
+            success: false,
+            data: None,
+            error: Some(message),
+        };
 
-        let regex = match if case_sens {
-            Regex::new(search_pattern)
+        let pattern = if options.literal {
+            regex::escape(search_pattern)
         } else {
-            Regex::new(&format!("(?i){}", search_pattern))
-        } {
+            search_pattern.to_string()
+        };
+        let regex = match regex::RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sens)
+            .build()
+        {
             Ok(r) => r,
-            Err(e) => return ToolResult {
-                success: false,
-                data: None,
-                error: Some(format!("Invalid regex pattern '{}': {}", search_pattern, e)),
-            },
+            Err(e) => return failure(format!("Invalid regex pattern '{}': {}", search_pattern, e)),
+        };
+        let replacement = if options.literal {
+            replace_text.to_string()
+        } else {
+            expand_backreferences(replace_text)
         };
 
-        let mut modified_files = Vec::new();
+        let entries = match walk::glob_paths(file_pattern, respect_ignore) {
+            Ok(entries) => entries,
+            Err(e) => return failure(e),
+        };
+
+        let root = match options.root.as_ref().map(fs::canonicalize).transpose() {
+            Ok(root) => root,
+            Err(e) => return failure(format!("Invalid root directory: {}", e)),
+        };
+        let display_path = |path: &Path| -> String {
+            match &options.root {
+                Some(root) => path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_string(),
+                None => path.to_string_lossy().to_string(),
+            }
+        };
+
+        let mut changes = Vec::new();
         let mut errors = Vec::new();
         let mut total_replacements = 0;
+        for path_buf in entries.into_iter().filter(|p| p.is_file()) {
+            if let Some(root) = &root {
+                let inside = fs::canonicalize(&path_buf)
+                    .map(|p| p.starts_with(root))
+                    .unwrap_or(false);
+                if !inside {
+                    return failure(format!(
+                        "Refusing to modify '{}': outside {}",
+                        path_buf.display(),
+                        root.display()
+                    ));
+                }
+            }
 
-        match glob(file_pattern) {
-            Ok(entries) => {
-                for entry in entries {
-                    match entry {
-                        Ok(path_buf) => {
-                            if path_buf.is_file() {
-                                match std::fs::read_to_string(&path_buf) {
-                                    Ok(content) => {
-                                        let original_content = content.clone();
-                                        let new_content = regex.replace_all(&content, replace_text);
-                                        
-                                        if new_content != original_content {
-                                            // Create backup if requested
-                                            if create_backup {
-                                                let backup_path = format!("{}.backup", path_buf.to_string_lossy());
-                                                if let Err(e) = std::fs::write(&backup_path, &original_content) {
-                                                    errors.push(format!("Failed to create backup for '{}': {}", 
-                                                        path_buf.to_string_lossy(), e));
-                                                    continue;
-                                                }
-                                            }
-
-                                            // Write modified content
-                                            match std::fs::write(&path_buf, new_content.as_ref()) {
-                                                Ok(_) => {
-                                                    let replacements = regex.find_iter(&original_content).count();
-                                                    total_replacements += replacements;
-                                                    modified_files.push(serde_json::json!({
-                                                        "file": path_buf.to_string_lossy(),
-                                                        "replacements": replacements,
-                                                        "backup_created": create_backup
-                                                    }));
-                                                }
-                                                Err(e) => {
-                                                    errors.push(format!("Failed to write to '{}': {}", 
-                                                        path_buf.to_string_lossy(), e));
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        errors.push(format!("Failed to read '{}': {}", 
-                                            path_buf.to_string_lossy(), e));
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            errors.push(format!("Pattern error: {}", e));
-                        }
-                    }
+            let original_content = match fs::read_to_string(&path_buf) {
+                Ok(content) => content,
+                Err(e) => {
+                    errors.push(format!("Failed to read '{}': {}", path_buf.to_string_lossy(), e));
+                    continue;
                 }
+            };
+            let replacements = regex.find_iter(&original_content).count();
+            if replacements == 0 {
+                continue;
+            }
+            let new_content = if options.literal {
+                regex.replace_all(&original_content, regex::NoExpand(&replacement))
+            } else {
+                regex.replace_all(&original_content, replacement.as_str())
+            };
+            if new_content == original_content {
+                continue;
+            }
+            total_replacements += replacements;
+            let display = display_path(&path_buf);
+            let diff = crate::tools::diff::unified_diff(&original_content, &new_content, &display);
+            changes.push((path_buf, display, new_content.into_owned(), replacements, diff));
+        }
 
-                ToolResult {
-                    success: errors.is_empty(),
+        if let Some(max) = options.max_replacements {
+            if total_replacements > max {
+                return ToolResult {
+                    success: false,
                     data: Some(serde_json::json!({
-                        "search_pattern": search_pattern,
-                        "replace_text": replace_text,
-                        "file_pattern": file_pattern,
-                        "case_sensitive": case_sens,
-                        "backup_created": create_backup,
-                        "modified_files": modified_files,
-                        "files_modified": modified_files.len(),
                         "total_replacements": total_replacements,
-                        "errors": errors
+                        "max_replacements": max,
+                        "files": changes.iter().map(|(_, file, _, count, _)| {
+                            serde_json::json!({ "file": file, "replacements": count })
+                        }).collect::<Vec<_>>(),
                     })),
-                    error: if errors.is_empty() { None } else { Some(errors.join("; ")) },
+                    error: Some(format!(
+                        "Pattern matches {} times, more than max_replacements ({}); no files were changed",
+                        total_replacements, max
+                    )),
+                };
+            }
+        }
+
+        let mut modified_files = Vec::new();
+        for (path_buf, display, new_content, replacements, diff) in changes {
+            if !options.preview {
+                if let Err(e) = fs::write(&path_buf, &new_content) {
+                    errors.push(format!("Failed to write to '{}': {}", display, e));
+                    continue;
                 }
             }
-            Err(e) => ToolResult {
-                success: false,
-                data: None,
-                error: Some(format!("Invalid file pattern '{}': {}", file_pattern, e)),
-            },
+            modified_files.push(serde_json::json!({
+                "file": display,
+                "replacements": replacements,
+                "diff": diff
+            }));
+        }
+
+        ToolResult {
+            success: errors.is_empty(),
+            data: Some(serde_json::json!({
+                "search_pattern": search_pattern,
+                "replace_text": replace_text,
+                "file_pattern": file_pattern,
+                "case_sensitive": case_sens,
+                "literal": options.literal,
+                "preview": options.preview,
+                "modified_files": modified_files,
+                "files_modified": modified_files.len(),
+                "total_replacements": total_replacements,
+                "errors": errors
+            })),
+            error: if errors.is_empty() { None } else { Some(errors.join("; ")) },
         }
     }
 
     /// Find files by name pattern
-    pub fn find_files(name_pattern: &str, base_path: Option<&str>, file_type: Option<&str>) -> ToolResult {
+    pub fn find_files(name_pattern: &str, base_path: Option<&str>, file_type: Option<&str>,
+                      no_ignore: Option<bool>) -> ToolResult {
         let search_path = base_path.unwrap_or(".");
         let search_pattern = format!("{}/{}", search_path, name_pattern);
+        let respect_ignore = !no_ignore.unwrap_or(false);
         
-        match glob(&search_pattern) {
+        match walk::glob_paths(&search_pattern, respect_ignore) {
             Ok(entries) => {
                 let mut results = Vec::new();
                 
-                for entry in entries {
-                    match entry {
-                        Ok(path_buf) => {
-                            let metadata = std::fs::metadata(&path_buf);
-                            
-                            if let Ok(meta) = metadata {
-                                // Filter by file type if specified
-                                let include = match file_type {
-                                    Some("file") => meta.is_file(),
-                                    Some("dir") => meta.is_dir(),
-                                    _ => true,
-                                };
-
-                                if include {
-                                    results.push(serde_json::json!({
-                                        "path": path_buf.to_string_lossy(),
-                                        "name": path_buf.file_name().unwrap_or_default().to_string_lossy(),
-                                        "size": meta.len(),
-                                        "is_file": meta.is_file(),
-                                        "is_dir": meta.is_dir(),
-                                        "modified": meta.modified().ok()
-                                            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                                            .map(|d| d.as_secs())
-                                    }));
-                                }
-                            }
+                for path_buf in entries {
+                    let metadata = std::fs::metadata(&path_buf);
+                    
+                    if let Ok(meta) = metadata {
+                        // Filter by file type if specified
+                        let include = match file_type {
+                            Some("file") => meta.is_file(),
+                            Some("dir") => meta.is_dir(),
+                            _ => true,
+                        };
+
+                        if include {
+                            results.push(serde_json::json!({
+                                "path": path_buf.to_string_lossy(),
+                                "name": path_buf.file_name().unwrap_or_default().to_string_lossy(),
+                                "size": meta.len(),
+                                "is_file": meta.is_file(),
+                                "is_dir": meta.is_dir(),
+                                "modified": meta.modified().ok()
+                                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                                    .map(|d| d.as_secs())
+                            }));
                         }
-                        Err(_) => continue,
                     }
                 }
 
@@ -504,6 +840,7 @@ impl FileSystemOperations {
                         "name_pattern": name_pattern,
                         "base_path": search_path,
                         "file_type": file_type,
+                        "no_ignore": !respect_ignore,
                         "results": results,
                         "count": results.len()
                     })),
@@ -513,7 +850,7 @@ impl FileSystemOperations {
             Err(e) => ToolResult {
                 success: false,
                 data: None,
-                error: Some(format!("Invalid search pattern '{}': {}", search_pattern, e)),
+                error: Some(e),
             },
         }
     }
@@ -527,13 +864,37 @@ pub fn get_file_system_tools() -> Vec<FileSystemTool> {
             tool_type: "function".to_string(),
             function: ToolFunction {
                 name: "read_file".to_string(),
-                description: "Read the complete contents of a file. Returns the file content, size, and path information.".to_string(),
+                description: "Read a file, or part of it, by line range, byte range, head or tail. Returns the content with total size, line counts, detected encoding and whether more remains. Binary files return a hex preview. Files over 100KB are truncated unless a range is given.".to_string(),
                 parameters: ToolParameters {
                     param_type: "object".to_string(),
                     properties: serde_json::json!({
                         "path": {
                             "type": "string",
                             "description": "Path to the file to read. Supports absolute and relative paths."
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "description": "First line to return (1-based)."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of lines to return."
+                        },
+                        "byte_offset": {
+                            "type": "integer",
+                            "description": "First byte to return; use with byte_limit to page through very large files."
+                        },
+                        "byte_limit": {
+                            "type": "integer",
+                            "description": "Maximum number of bytes to return."
+                        },
+                        "head": {
+                            "type": "integer",
+                            "description": "Return only the first N lines."
+                        },
+                        "tail": {
+                            "type": "integer",
+                            "description": "Return only the last N lines (e.g. the end of a log)."
                         }
                     }),
                     required: vec!["path".to_string()],
@@ -590,6 +951,11 @@ pub fn get_file_system_tools() -> Vec<FileSystemTool> {
                             "type": "boolean",
                             "description": "If true, list files recursively in subdirectories.",
                             "default": false
+                        },
+                        "no_ignore": {
+                            "type": "boolean",
+                            "description": "If true, include paths excluded by .gitignore, .ignore and .kaiignore.",
+                            "default": false
                         }
                     }),
                     required: vec!["path".to_string()],
@@ -677,6 +1043,11 @@ pub fn get_file_system_tools() -> Vec<FileSystemTool> {
                             "description": "Number of context lines to show around each match.",
                             "default": 0,
                             "minimum": 0
+                        },
+                        "no_ignore": {
+                            "type": "boolean",
+                            "description": "If true, include paths excluded by .gitignore, .ignore and .kaiignore.",
+                            "default": false
                         }
                     }),
                     required: vec!["pattern".to_string(), "file_pattern".to_string()],
@@ -689,7 +1060,7 @@ pub fn get_file_system_tools() -> Vec<FileSystemTool> {
             tool_type: "function".to_string(),
             function: ToolFunction {
                 name: "search_replace".to_string(),
-                description: "Search and replace text in files using regular expressions. Supports batch operations across multiple files with backup creation.".to_string(),
+                description: "Search and replace text in files using regular expressions or plain text. Returns each modified file with its match count and a unified diff; use preview to see the diffs without writing. Changes can be undone with the plan checkpoints.".to_string(),
                 parameters: ToolParameters {
                     param_type: "object".to_string(),
                     properties: serde_json::json!({
@@ -699,7 +1070,7 @@ pub fn get_file_system_tools() -> Vec<FileSystemTool> {
                         },
                         "replace_text": {
                             "type": "string",
-                            "description": "Replacement text. Can include regex capture group references like $1, $2, etc."
+                            "description": "Replacement text. Can reference capture groups as $1, ${name} or \\1."
                         },
                         "file_pattern": {
                             "type": "string",
@@ -710,10 +1081,25 @@ pub fn get_file_system_tools() -> Vec<FileSystemTool> {
                             "description": "If true, search is case-sensitive. If false, search is case-insensitive.",
                             "default": true
                         },
-                        "backup": {
+                        "literal": {
                             "type": "boolean",
-                            "description": "If true, create .backup files before modification.",
-                            "default": true
+                            "description": "If true, search_pattern and replace_text are plain text, not a regex and template.",
+                            "default": false
+                        },
+                        "preview": {
+                            "type": "boolean",
+                            "description": "If true, return the diffs without modifying any file.",
+                            "default": false
+                        },
+                        "max_replacements": {
+                            "type": "integer",
+                            "description": "Abort without changing anything if the pattern matches more often than this.",
+                            "minimum": 0
+                        },
+                        "no_ignore": {
+                            "type": "boolean",
+                            "description": "If true, include paths excluded by .gitignore, .ignore and .kaiignore.",
+                            "default": false
                         }
                     }),
                     required: vec!["search_pattern".to_string(), "replace_text".to_string(), "file_pattern".to_string()],
@@ -743,6 +1129,11 @@ pub fn get_file_system_tools() -> Vec<FileSystemTool> {
                             "type": "string",
                             "description": "Filter by type: 'file' for files only, 'dir' for directories only, or omit for both.",
                             "enum": ["file", "dir"]
+                        },
+                        "no_ignore": {
+                            "type": "boolean",
+                            "description": "If true, include paths excluded by .gitignore, .ignore and .kaiignore.",
+                            "default": false
                         }
                     }),
                     required: vec!["name_pattern".to_string()],
@@ -778,6 +1169,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_read_file_ranges_and_binary() {
+        let temp_dir = TempDir::new().unwrap();
+        let text_path = temp_dir.path().join("log.txt");
+        let text: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&text_path, &text).unwrap();
+        let text_path = text_path.to_str().unwrap();
+
+        let options = ReadOptions { offset: Some(3), limit: Some(2), ..Default::default() };
+        let data = FileSystemOperations::read_file_with_options(text_path, &options).data.unwrap();
+        assert_eq!(data["content"], "line 3\nline 4\n");
+        assert_eq!(data["total_lines"], 10);
+        assert_eq!(data["total_size"], text.len());
+        assert_eq!(data["next_offset"], 5);
+
+        let options = ReadOptions { tail: Some(1), ..Default::default() };
+        let data = FileSystemOperations::read_file_with_options(text_path, &options).data.unwrap();
+        assert_eq!(data["content"], "line 10\n");
+        assert_eq!(data["has_more"], false);
+
+        let options = ReadOptions { byte_offset: Some(7), byte_limit: Some(6), ..Default::default() };
+        let data = FileSystemOperations::read_file_with_options(text_path, &options).data.unwrap();
+        assert_eq!(data["content"], "line 2");
+        assert_eq!(data["next_byte_offset"], 13);
+
+        let latin1_path = temp_dir.path().join("latin1.txt");
+        fs::write(&latin1_path, b"caf\xe9\n").unwrap();
+        let data = FileSystemOperations::read_file(latin1_path.to_str().unwrap()).data.unwrap();
+        assert_eq!(data["encoding"], "latin-1");
+        assert_eq!(data["content"], "caf\u{e9}\n");
+
+        let binary_path = temp_dir.path().join("image.png");
+        fs::write(&binary_path, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let data = FileSystemOperations::read_file(binary_path.to_str().unwrap()).data.unwrap();
+        assert_eq!(data["binary"], true);
+        assert_eq!(data["file_type"], "png image");
+        assert!(data["preview"].as_str().unwrap().starts_with("00000000: 89 50 4e 47"));
+        assert!(data.get("content").is_none());
+    }
+
     #[test]
     fn test_list_directory() {
         let temp_dir = TempDir::new().unwrap();
@@ -787,7 +1218,7 @@ mod tests {
         fs::write(temp_dir.path().join("file1.txt"), "content1").unwrap();
         fs::write(temp_dir.path().join("file2.rs"), "content2").unwrap();
         
-        let result = FileSystemOperations::list_directory(dir_path, Some("*.txt"), None);
+        let result = FileSystemOperations::list_directory(dir_path, Some("*.txt"), None, None);
         assert!(result.success);
         
         if let Some(data) = result.data {
@@ -804,11 +1235,54 @@ mod tests {
         fs::write(&file_path, "Hello World\nThis is a test\nHello again").unwrap();
         
         let file_pattern = format!("{}/*.txt", temp_dir.path().to_str().unwrap());
-        let result = FileSystemOperations::grep_files("Hello", &file_pattern, None, None, None);
+        let result = FileSystemOperations::grep_files("Hello", &file_pattern, None, None, None, None);
         
         assert!(result.success);
         if let Some(data) = result.data {
             assert_eq!(data["total_matches"], 2);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_search_replace_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(&root).unwrap();
+        let file_path = root.join("config.txt");
+        fs::write(&file_path, "port=80\nhost=a.b\n").unwrap();
+        let pattern = format!("{}/*.txt", root.to_str().unwrap());
+        let options = |preview: bool| SearchReplaceOptions {
+            preview,
+            root: Some(root.clone()),
+            ..Default::default()
+        };
+
+        // Preview reports a diff and leaves the file alone
+        let preview = FileSystemOperations::search_replace(r"port=(\d+)", "port=${1}80", &pattern, &options(true));
+        assert!(preview.success);
+        let data = preview.data.unwrap();
+        assert_eq!(data["total_replacements"], 1);
+        assert!(data["modified_files"][0]["diff"].as_str().unwrap().contains("+port=8080"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "port=80\nhost=a.b\n");
+
+        // Sed-style backreferences and literal mode
+        let applied = FileSystemOperations::search_replace(r"port=(\d+)", r"port=\1\1", &pattern, &options(false));
+        assert!(applied.success);
+        let literal = SearchReplaceOptions { literal: true, ..options(false) };
+        FileSystemOperations::search_replace("a.b", "$1", &pattern, &literal);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "port=8080\nhost=$1\n");
+
+        // The guard aborts before writing anything
+        let limited = SearchReplaceOptions { max_replacements: Some(1), ..options(false) };
+        let aborted = FileSystemOperations::search_replace("[0-9]", "x", &pattern, &limited);
+        assert!(!aborted.success);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "port=8080\nhost=$1\n");
+
+        // Files outside the root are refused
+        fs::write(temp_dir.path().join("outside.txt"), "port=1").unwrap();
+        let escape = format!("{}/../*.txt", root.to_str().unwrap());
+        assert!(!FileSystemOperations::search_replace("port", "p", &escape, &options(false)).success);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("outside.txt")).unwrap(), "port=1");
+        assert!(!root.join("config.txt.backup").exists());
+    }
+}
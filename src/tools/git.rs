@@ -0,0 +1,640 @@
+use crate::tools::file_system::{FileSystemTool, ToolFunction, ToolParameters, ToolResult};
+use serde_json::json;
+use std::path::Path;
+use std::process::Command;
+
+/// Run a git command in `repo` and return its stdout, or stderr on failure
+fn run_git(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute git {}: {}", args.join(" "), e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Refuse revisions and branch names that git would read as options
+/// (`--output=/tmp/x` makes even `git show` write a file)
+fn check_not_option(kind: &str, value: &str) -> Result<(), String> {
+    if value.starts_with('-') {
+        Err(format!(
+            "Invalid {} '{}': must not start with '-'",
+            kind, value
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn error_result(error: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        data: None,
+        error: Some(error),
+    }
+}
+
+/// Check whether a directory is inside a git work tree
+pub fn is_git_repository(repo: &Path) -> bool {
+    run_git(repo, &["rev-parse", "--is-inside-work-tree"])
+        .map(|out| out.trim() == "true")
+        .unwrap_or(false)
+}
+
+/// Name of the currently checked out branch
+pub fn current_branch(repo: &Path) -> Option<String> {
+    run_git(repo, &["rev-parse", "--abbrev-ref", "HEAD"])
+        .ok()
+        .map(|out| out.trim().to_string())
+}
+
+/// Parse `git diff --numstat` output into per-file statistics
+fn parse_numstat(output: &str) -> Vec<serde_json::Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let additions = parts.next()?;
+            let deletions = parts.next()?;
+            let path = parts.next()?;
+            Some(json!({
+                "path": path,
+                "additions": additions.parse::<u64>().ok(),
+                "deletions": deletions.parse::<u64>().ok(),
+                "binary": additions == "-" && deletions == "-",
+            }))
+        })
+        .collect()
+}
+
+/// Working tree status: branch tracking information and changed entries,
+/// limited to `path` when given
+pub fn git_status(repo: &Path, path: Option<&str>) -> ToolResult {
+    let mut args = vec!["status", "--porcelain=v1", "--branch"];
+    if let Some(path) = path {
+        args.extend(["--", path]);
+    }
+    let output = match run_git(repo, &args) {
+        Ok(output) => output,
+        Err(e) => return error_result(e),
+    };
+
+    let mut branch = None;
+    let mut upstream = None;
+    let mut ahead = 0u64;
+    let mut behind = 0u64;
+    let mut entries = Vec::new();
+
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("## ") {
+            let (names, tracking) = match header.find(" [") {
+                Some(idx) => (&header[..idx], Some(&header[idx + 2..header.len() - 1])),
+                None => (header, None),
+            };
+            match names.split_once("...") {
+                Some((local, remote)) => {
+                    branch = Some(local.to_string());
+                    upstream = Some(remote.to_string());
+                }
+                None => branch = Some(names.trim_start_matches("No commits yet on ").to_string()),
+            }
+            for part in tracking.unwrap_or("").split(", ") {
+                if let Some(n) = part.strip_prefix("ahead ") {
+                    ahead = n.parse().unwrap_or(0);
+                } else if let Some(n) = part.strip_prefix("behind ") {
+                    behind = n.parse().unwrap_or(0);
+                }
+            }
+            continue;
+        }
+        if line.len() < 4 {
+            continue;
+        }
+
+        let index_status = &line[0..1];
+        let worktree_status = &line[1..2];
+        let path_part = &line[3..];
+        let (orig_path, path) = match path_part.split_once(" -> ") {
+            Some((from, to)) => (Some(from), to),
+            None => (None, path_part),
+        };
+        let untracked = index_status == "?";
+
+        entries.push(json!({
+            "path": path,
+            "orig_path": orig_path,
+            "index_status": index_status,
+            "worktree_status": worktree_status,
+            "staged": !untracked && index_status != " ",
+            "unstaged": !untracked && worktree_status != " ",
+            "untracked": untracked,
+        }));
+    }
+
+    ToolResult {
+        success: true,
+        data: Some(json!({
+            "branch": branch,
+            "upstream": upstream,
+            "ahead": ahead,
+            "behind": behind,
+            "clean": entries.is_empty(),
+            "entries": entries,
+        })),
+        error: None,
+    }
+}
+
+/// Diff of the working tree (or the index when `staged`), optionally limited to a path
+pub fn git_diff(repo: &Path, staged: bool, path: Option<&str>) -> ToolResult {
+    let mut args = vec!["diff"];
+    if staged {
+        args.push("--cached");
+    }
+
+    let mut numstat_args = args.clone();
+    numstat_args.push("--numstat");
+    if let Some(path) = path {
+        args.extend(["--", path]);
+        numstat_args.extend(["--", path]);
+    }
+
+    let stats = match run_git(repo, &numstat_args) {
+        Ok(output) => parse_numstat(&output),
+        Err(e) => return error_result(e),
+    };
+    let patch = match run_git(repo, &args) {
+        Ok(output) => output,
+        Err(e) => return error_result(e),
+    };
+
+    let total_additions: u64 = stats.iter().filter_map(|f| f["additions"].as_u64()).sum();
+    let total_deletions: u64 = stats.iter().filter_map(|f| f["deletions"].as_u64()).sum();
+
+    ToolResult {
+        success: true,
+        data: Some(json!({
+            "staged": staged,
+            "path": path,
+            "files": stats,
+            "files_changed": stats.len(),
+            "total_additions": total_additions,
+            "total_deletions": total_deletions,
+            "patch": patch,
+        })),
+        error: None,
+    }
+}
+
+/// Field and record separators used to parse `git log` output
+const FIELD_SEP: &str = "\x1f";
+const RECORD_SEP: &str = "\x1e";
+const LOG_FORMAT: &str = "--format=%H%x1f%h%x1f%an%x1f%ae%x1f%aI%x1f%s%x1f%b%x1e";
+
+fn parse_log(output: &str) -> Vec<serde_json::Value> {
+    output
+        .split(RECORD_SEP)
+        .map(|record| record.trim_start_matches('\n'))
+        .filter(|record| !record.trim().is_empty())
+        .filter_map(|record| {
+            let fields: Vec<&str> = record.split(FIELD_SEP).collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            Some(json!({
+                "hash": fields[0],
+                "short_hash": fields[1],
+                "author": fields[2],
+                "email": fields[3],
+                "date": fields[4],
+                "subject": fields[5],
+                "body": fields[6].trim(),
+            }))
+        })
+        .collect()
+}
+
+/// Commit history, newest first, optionally starting from `rev` and limited to a path
+pub fn git_log(repo: &Path, limit: usize, rev: Option<&str>, path: Option<&str>) -> ToolResult {
+    let limit_arg = format!("-n{}", limit.max(1));
+    let mut args = vec!["log", limit_arg.as_str(), LOG_FORMAT];
+    if let Some(rev) = rev {
+        if let Err(e) = check_not_option("revision", rev) {
+            return error_result(e);
+        }
+        args.push(rev);
+    }
+    if let Some(path) = path {
+        args.extend(["--", path]);
+    }
+
+    match run_git(repo, &args) {
+        Ok(output) => {
+            let commits = parse_log(&output);
+            ToolResult {
+                success: true,
+                data: Some(json!({
+                    "limit": limit,
+                    "rev": rev,
+                    "path": path,
+                    "count": commits.len(),
+                    "commits": commits,
+                })),
+                error: None,
+            }
+        }
+        Err(e) => error_result(e),
+    }
+}
+
+/// Metadata, per-file statistics and patch of a single commit
+pub fn git_show(repo: &Path, rev: &str) -> ToolResult {
+    if let Err(e) = check_not_option("revision", rev) {
+        return error_result(e);
+    }
+    let commit = match run_git(repo, &["log", "-n1", LOG_FORMAT, rev]) {
+        Ok(output) => parse_log(&output).into_iter().next(),
+        Err(e) => return error_result(e),
+    };
+    let stats = match run_git(repo, &["show", "--numstat", "--format=", rev]) {
+        Ok(output) => parse_numstat(&output),
+        Err(e) => return error_result(e),
+    };
+    let patch = match run_git(repo, &["show", "--format=", rev]) {
+        Ok(output) => output,
+        Err(e) => return error_result(e),
+    };
+
+    ToolResult {
+        success: true,
+        data: Some(json!({
+            "rev": rev,
+            "commit": commit,
+            "files": stats,
+            "patch": patch,
+        })),
+        error: None,
+    }
+}
+
+/// Create a branch, optionally from `start_point`, and optionally switch to it
+pub fn git_branch_create(
+    repo: &Path,
+    name: &str,
+    start_point: Option<&str>,
+    switch: bool,
+) -> ToolResult {
+    let checked = check_not_option("branch name", name)
+        .and_then(|_| start_point.map_or(Ok(()), |s| check_not_option("start point", s)));
+    if let Err(e) = checked {
+        return error_result(e);
+    }
+    let mut args = if switch {
+        vec!["switch", "-c", name]
+    } else {
+        vec!["branch", name]
+    };
+    if let Some(start) = start_point {
+        args.push(start);
+    }
+
+    match run_git(repo, &args) {
+        Ok(_) => ToolResult {
+            success: true,
+            data: Some(json!({
+                "branch": name,
+                "start_point": start_point,
+                "switched": switch,
+                "current_branch": current_branch(repo),
+            })),
+            error: None,
+        },
+        Err(e) => error_result(e),
+    }
+}
+
+/// Switch to an existing branch
+pub fn git_switch(repo: &Path, name: &str) -> ToolResult {
+    if let Err(e) = check_not_option("branch name", name) {
+        return error_result(e);
+    }
+    let previous = current_branch(repo);
+    match run_git(repo, &["switch", name]) {
+        Ok(_) => ToolResult {
+            success: true,
+            data: Some(json!({
+                "branch": name,
+                "previous_branch": previous,
+            })),
+            error: None,
+        },
+        Err(e) => error_result(e),
+    }
+}
+
+/// Stage paths (`.` when empty); `staged_files` are relative to `repo`, which
+/// may be a subdirectory of the work tree
+pub fn git_add(repo: &Path, paths: &[String]) -> ToolResult {
+    let mut args = vec!["add", "--"];
+    if paths.is_empty() {
+        args.push(".");
+    } else {
+        args.extend(paths.iter().map(|p| p.as_str()));
+    }
+
+    match run_git(repo, &args) {
+        Ok(_) => {
+            let staged = run_git(repo, &["diff", "--cached", "--name-only", "--relative"])
+                .map(|out| out.lines().map(|l| l.to_string()).collect::<Vec<_>>())
+                .unwrap_or_default();
+            ToolResult {
+                success: true,
+                data: Some(json!({
+                    "paths": if paths.is_empty() { vec![".".to_string()] } else { paths.to_vec() },
+                    "staged_files": staged,
+                })),
+                error: None,
+            }
+        }
+        Err(e) => error_result(e),
+    }
+}
+
+/// Commit staged changes (all tracked changes when `all`). With `paths`, only
+/// those paths are committed and anything else staged stays staged.
+pub fn git_commit(repo: &Path, message: &str, all: bool, paths: &[String]) -> ToolResult {
+    if message.trim().is_empty() {
+        return error_result("Commit message cannot be empty".to_string());
+    }
+    if all && !paths.is_empty() {
+        return error_result("Commit either all changes or the given paths, not both".to_string());
+    }
+
+    let mut args = vec!["commit", "-m", message];
+    if all {
+        args.push("-a");
+    }
+    if !paths.is_empty() {
+        args.push("--");
+        args.extend(paths.iter().map(|p| p.as_str()));
+    }
+
+    match run_git(repo, &args) {
+        Ok(_) => {
+            let hash = run_git(repo, &["rev-parse", "HEAD"])
+                .map(|out| out.trim().to_string())
+                .ok();
+            let files = run_git(repo, &["show", "--numstat", "--format=", "HEAD"])
+                .map(|out| parse_numstat(&out))
+                .unwrap_or_default();
+            ToolResult {
+                success: true,
+                data: Some(json!({
+                    "commit": hash,
+                    "branch": current_branch(repo),
+                    "message": message,
+                    "files": files,
+                })),
+                error: None,
+            }
+        }
+        Err(e) => error_result(e),
+    }
+}
+
+/// Generate OpenRouter-compatible definitions for the git tools
+pub fn get_git_tools() -> Vec<FileSystemTool> {
+    let tool = |name: &str, description: &str, properties: serde_json::Value, required: &[&str]| {
+        FileSystemTool {
+            tool_type: "function".to_string(),
+            function: ToolFunction {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: ToolParameters {
+                    param_type: "object".to_string(),
+                    properties,
+                    required: required.iter().map(|r| r.to_string()).collect(),
+                },
+            },
+        }
+    };
+
+    vec![
+        tool(
+            "git_status",
+            "Show the repository status: current branch, upstream tracking and changed, staged and untracked files.",
+            json!({
+                "path": {
+                    "type": "string",
+                    "description": "Optional path to limit the status to."
+                }
+            }),
+            &[],
+        ),
+        tool(
+            "git_diff",
+            "Show changes as per-file statistics plus a unified patch. Unstaged by default.",
+            json!({
+                "path": {
+                    "type": "string",
+                    "description": "Optional path to limit the diff to."
+                },
+                "staged": {
+                    "type": "boolean",
+                    "description": "If true, diff the index against HEAD instead of the working tree.",
+                    "default": false
+                }
+            }),
+            &[],
+        ),
+        tool(
+            "git_log",
+            "List recent commits with hash, author, date and subject.",
+            json!({
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of commits to return.",
+                    "default": 10,
+                    "minimum": 1
+                },
+                "rev": {
+                    "type": "string",
+                    "description": "Optional revision or range to start from (e.g. 'main', 'HEAD~5..HEAD')."
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Optional path to limit history to."
+                }
+            }),
+            &[],
+        ),
+        tool(
+            "git_show",
+            "Show a single commit: metadata, per-file statistics and patch.",
+            json!({
+                "rev": {
+                    "type": "string",
+                    "description": "Commit hash, branch or other revision to show."
+                }
+            }),
+            &["rev"],
+        ),
+        tool(
+            "git_branch",
+            "Create a new branch, optionally switching to it.",
+            json!({
+                "name": {
+                    "type": "string",
+                    "description": "Name of the branch to create."
+                },
+                "start_point": {
+                    "type": "string",
+                    "description": "Optional revision to branch from. Defaults to HEAD."
+                },
+                "switch": {
+                    "type": "boolean",
+                    "description": "If true, switch to the new branch after creating it.",
+                    "default": false
+                }
+            }),
+            &["name"],
+        ),
+        tool(
+            "git_switch",
+            "Switch to an existing branch.",
+            json!({
+                "name": {
+                    "type": "string",
+                    "description": "Name of the branch to switch to."
+                }
+            }),
+            &["name"],
+        ),
+        tool(
+            "git_add",
+            "Stage files for the next commit.",
+            json!({
+                "paths": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Paths to stage. Stages everything under the working directory if omitted."
+                }
+            }),
+            &[],
+        ),
+        tool(
+            "git_commit",
+            "Commit staged changes with a message.",
+            json!({
+                "message": {
+                    "type": "string",
+                    "description": "Commit message."
+                },
+                "all": {
+                    "type": "boolean",
+                    "description": "If true, automatically stage modified and deleted tracked files before committing.",
+                    "default": false
+                },
+                "paths": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Commit only these paths; other staged changes stay staged. Cannot be combined with all."
+                }
+            }),
+            &["message"],
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn init_repo(dir: &Path) {
+        run_git(dir, &["init", "-q", "-b", "main"]).unwrap();
+        run_git(dir, &["config", "user.name", "KAI Test"]).unwrap();
+        run_git(dir, &["config", "user.email", "kai@example.com"]).unwrap();
+    }
+
+    #[test]
+    fn test_status_diff_commit_log() {
+        let dir = tempdir().unwrap();
+        init_repo(dir.path());
+        fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+
+        fs::write(dir.path().join("b.txt"), "two\n").unwrap();
+
+        let status = git_status(dir.path(), None);
+        assert!(status.success);
+        let data = status.data.unwrap();
+        assert_eq!(data["entries"][0]["path"], "a.txt");
+        assert_eq!(data["entries"][0]["untracked"], true);
+        let data = git_status(dir.path(), Some("b.txt")).data.unwrap();
+        assert_eq!(data["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(data["entries"][0]["path"], "b.txt");
+
+        assert!(git_add(dir.path(), &[]).success);
+        let commit = git_commit(dir.path(), "Add a.txt", false, &[]);
+        assert!(commit.success);
+
+        fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        let diff = git_diff(dir.path(), false, Some("a.txt")).data.unwrap();
+        assert_eq!(diff["files"][0]["additions"], 1);
+        assert!(diff["patch"].as_str().unwrap().contains("+two"));
+
+        let log = git_log(dir.path(), 5, None, None).data.unwrap();
+        assert_eq!(log["count"], 1);
+        assert_eq!(log["commits"][0]["subject"], "Add a.txt");
+    }
+
+    #[test]
+    fn test_branch_create_and_switch() {
+        let dir = tempdir().unwrap();
+        init_repo(dir.path());
+        fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        git_add(dir.path(), &[]);
+        git_commit(dir.path(), "Initial", false, &[]);
+
+        let result = git_branch_create(dir.path(), "feature", None, true);
+        assert!(result.success);
+        assert_eq!(current_branch(dir.path()).as_deref(), Some("feature"));
+
+        let result = git_switch(dir.path(), "main");
+        assert!(result.success);
+        assert_eq!(result.data.unwrap()["previous_branch"], "feature");
+        assert!(!git_switch(dir.path(), "missing").success);
+    }
+
+    #[test]
+    fn test_option_like_revisions_refused() {
+        let dir = tempdir().unwrap();
+        init_repo(dir.path());
+        fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        git_add(dir.path(), &[]);
+        git_commit(dir.path(), "Initial", false, &[]);
+        let out = dir.path().join("out.txt");
+        let option = format!("--output={}", out.display());
+
+        let show = git_show(dir.path(), &option);
+        assert!(!show.success);
+        assert!(show.error.unwrap().contains("must not start with '-'"));
+        assert!(!git_log(dir.path(), 1, Some(&option), None).success);
+        assert!(!git_branch_create(dir.path(), &option, None, false).success);
+        assert!(!git_branch_create(dir.path(), "b", Some("--orphan"), true).success);
+        assert!(!git_switch(dir.path(), "-").success);
+        assert!(!out.exists());
+        assert_eq!(current_branch(dir.path()).as_deref(), Some("main"));
+    }
+}
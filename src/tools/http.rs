@@ -0,0 +1,242 @@
+//! HTTP request tool for verifying locally running services
+//!
+//! Requests are restricted to loopback hosts unless remote access is
+//! explicitly allowed, and redirects are held to the same rule.
+
+use crate::tools::file_system::{FileSystemTool, ToolFunction, ToolParameters, ToolResult};
+use reqwest::{Method, Url};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Body bytes returned before truncation
+pub const DEFAULT_MAX_BODY_BYTES: usize = 16 * 1024;
+
+/// Parameters of a single HTTP request
+#[derive(Debug, Clone)]
+pub struct HttpRequestOptions {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+    pub timeout: Duration,
+    pub max_body_bytes: usize,
+    /// Allow hosts other than localhost / loopback addresses
+    pub allow_remote: bool,
+}
+
+impl HttpRequestOptions {
+    pub fn new(method: &str, url: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: HashMap::new(),
+            body: None,
+            timeout: Duration::from_secs(10),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            allow_remote: false,
+        }
+    }
+}
+
+fn error_result(message: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        data: None,
+        error: Some(message),
+    }
+}
+
+/// Whether a URL points at this machine (`localhost`, `127.0.0.0/8`, `::1`)
+pub fn is_loopback_url(url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => {
+            let domain = host.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+    }
+}
+
+/// Perform an HTTP request and return status, headers and a (truncated) body
+pub async fn http_request(options: &HttpRequestOptions) -> ToolResult {
+    let url = match Url::parse(&options.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        Ok(url) => return error_result(format!("Unsupported URL scheme: {}", url.scheme())),
+        Err(e) => return error_result(format!("Invalid URL '{}': {}", options.url, e)),
+    };
+    if !options.allow_remote && !is_loopback_url(&url) {
+        return error_result(format!(
+            "Refusing request to non-loopback host '{}': only localhost is allowed",
+            url.host_str().unwrap_or_default()
+        ));
+    }
+    let method = match Method::from_bytes(options.method.to_ascii_uppercase().as_bytes()) {
+        Ok(method) => method,
+        Err(_) => return error_result(format!("Invalid HTTP method: {}", options.method)),
+    };
+
+    let allow_remote = options.allow_remote;
+    let redirect_policy = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 5 {
+            attempt.error("too many redirects")
+        } else if !allow_remote && !is_loopback_url(attempt.url()) {
+            attempt.error("redirect to non-loopback host")
+        } else {
+            attempt.follow()
+        }
+    });
+    let mut builder = reqwest::Client::builder()
+        .timeout(options.timeout)
+        .redirect(redirect_policy);
+    if !allow_remote {
+        // Loopback traffic must never be routed through a configured proxy
+        builder = builder.no_proxy();
+    }
+    let client = match builder.build() {
+        Ok(client) => client,
+        Err(e) => return error_result(format!("Failed to create HTTP client: {}", e)),
+    };
+
+    let mut request = client.request(method.clone(), url.clone());
+    for (name, value) in &options.headers {
+        request = request.header(name, value);
+    }
+    if let Some(body) = &options.body {
+        request = request.body(body.clone());
+    }
+
+    let started = Instant::now();
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => return error_result(format!("{} {} failed: {}", method, url, e)),
+    };
+
+    let status = response.status();
+    let final_url = response.url().to_string();
+    let headers: serde_json::Map<String, serde_json::Value> = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                json!(String::from_utf8_lossy(value.as_bytes())),
+            )
+        })
+        .collect();
+    let bytes = match response.bytes().await {
+        Ok(bytes) => bytes,
+        Err(e) => return error_result(format!("Failed to read response body: {}", e)),
+    };
+    let truncated = bytes.len() > options.max_body_bytes;
+    let body = String::from_utf8_lossy(&bytes[..bytes.len().min(options.max_body_bytes)]);
+
+    ToolResult {
+        success: true,
+        data: Some(json!({
+            "method": method.as_str(),
+            "url": final_url,
+            "status": status.as_u16(),
+            "status_text": status.canonical_reason(),
+            "ok": status.is_success(),
+            "headers": headers,
+            "body": body,
+            "body_bytes": bytes.len(),
+            "truncated": truncated,
+            "elapsed_ms": started.elapsed().as_millis() as u64,
+        })),
+        error: None,
+    }
+}
+
+/// Generate the OpenRouter-compatible definition for the HTTP request tool
+pub fn get_http_tool() -> FileSystemTool {
+    FileSystemTool {
+        tool_type: "function".to_string(),
+        function: ToolFunction {
+            name: "http_request".to_string(),
+            description: "Send an HTTP request to a local service (localhost only) and return status, headers and a truncated body.".to_string(),
+            parameters: ToolParameters {
+                param_type: "object".to_string(),
+                properties: json!({
+                    "url": {
+                        "type": "string",
+                        "description": "Request URL, e.g. http://localhost:8080/health"
+                    },
+                    "method": {
+                        "type": "string",
+                        "description": "HTTP method.",
+                        "default": "GET"
+                    },
+                    "headers": {
+                        "type": "object",
+                        "description": "Request headers as name/value pairs."
+                    },
+                    "body": {
+                        "type": "string",
+                        "description": "Optional request body."
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "Request timeout in seconds.",
+                        "default": 10
+                    }
+                }),
+                required: vec!["url".to_string()],
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_request_to_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let body = "x".repeat(100);
+            let response = format!(
+                "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let mut options =
+            HttpRequestOptions::new("post", &format!("http://127.0.0.1:{}/items", port));
+        options.body = Some("{}".to_string());
+        options.max_body_bytes = 10;
+        let result = http_request(&options).await;
+        assert!(result.success, "{:?}", result.error);
+        let data = result.data.unwrap();
+        assert_eq!(data["status"], 201);
+        assert_eq!(data["headers"]["content-type"], "text/plain");
+        assert_eq!(data["body"], "xxxxxxxxxx");
+        assert_eq!(data["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_remote_hosts_rejected_by_default() {
+        let result = http_request(&HttpRequestOptions::new("GET", "http://example.com/")).await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("non-loopback"));
+        assert!(is_loopback_url(&Url::parse("http://[::1]:3000/").unwrap()));
+        assert!(is_loopback_url(
+            &Url::parse("http://api.localhost/").unwrap()
+        ));
+    }
+}
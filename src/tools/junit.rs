@@ -0,0 +1,127 @@
+//! Minimal JUnit XML reader shared by the diagnostics and test runner tools
+//!
+//! Only what pytest, jest-junit and friends emit is understood: `<testcase>`
+//! elements with their attributes and an optional `<failure>`, `<error>` or
+//! `<skipped>` child.
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Outcome of a single test case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// One `<testcase>` element
+#[derive(Debug, Clone, Serialize)]
+pub struct JunitCase {
+    pub classname: String,
+    pub name: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub duration_secs: Option<f64>,
+    pub outcome: TestOutcome,
+    /// Failure or skip message
+    pub message: Option<String>,
+    /// Full failure text (traceback, assertion diff)
+    pub details: Option<String>,
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#10;", "\n")
+        .replace("&amp;", "&")
+}
+
+fn attributes(tag: &str) -> HashMap<String, String> {
+    let attr_re = Regex::new(r#"([\w:-]+)="([^"]*)""#).unwrap();
+    attr_re
+        .captures_iter(tag)
+        .map(|c| (c[1].to_string(), unescape(&c[2])))
+        .collect()
+}
+
+/// Parse every test case in a JUnit XML report
+pub fn parse_junit(xml: &str) -> Vec<JunitCase> {
+    let case_re = Regex::new(r"(?s)<testcase\b([^>]*?)(?:/>|>(.*?)</testcase>)").unwrap();
+    let child_re = Regex::new(
+        r"(?s)<(failure|error|skipped)\b([^>]*?)(?:/>|>(.*?)</(?:failure|error|skipped)>)",
+    )
+    .unwrap();
+
+    case_re
+        .captures_iter(xml)
+        .map(|case| {
+            let attrs = attributes(&case[1]);
+            let body = case.get(2).map_or("", |m| m.as_str());
+            let child = child_re.captures(body);
+
+            let (outcome, message, details) = match &child {
+                Some(c) => {
+                    let outcome = if &c[1] == "skipped" {
+                        TestOutcome::Skipped
+                    } else {
+                        TestOutcome::Failed
+                    };
+                    let message = attributes(&c[2]).get("message").cloned();
+                    let details = c
+                        .get(3)
+                        .map(|m| unescape(m.as_str().trim()))
+                        .filter(|d| !d.is_empty());
+                    (outcome, message, details)
+                }
+                None => (TestOutcome::Passed, None, None),
+            };
+
+            JunitCase {
+                classname: attrs.get("classname").cloned().unwrap_or_default(),
+                name: attrs.get("name").cloned().unwrap_or_default(),
+                file: attrs.get("file").cloned(),
+                line: attrs.get("line").and_then(|l| l.parse().ok()),
+                duration_secs: attrs.get("time").and_then(|t| t.parse().ok()),
+                outcome,
+                message,
+                details,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_junit() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<testsuites><testsuite name="pytest" tests="3">
+<testcase classname="tests.test_math" name="test_add" file="tests/test_math.py" line="3" time="0.001" />
+<testcase classname="tests.test_math" name="test_div" file="tests/test_math.py" line="7" time="0.002"><failure message="assert 1 == 2">def test_div():
+&gt;       assert 1 == 2
+E       assert 1 == 2</failure></testcase>
+<testcase classname="tests.test_math" name="test_skip" time="0"><skipped message="not ready" /></testcase>
+</testsuite></testsuites>"#;
+
+        let cases = parse_junit(xml);
+        assert_eq!(cases.len(), 3);
+        assert_eq!(cases[0].outcome, TestOutcome::Passed);
+        assert_eq!(cases[0].line, Some(3));
+        assert_eq!(cases[1].outcome, TestOutcome::Failed);
+        assert_eq!(cases[1].message.as_deref(), Some("assert 1 == 2"));
+        assert!(cases[1]
+            .details
+            .as_ref()
+            .unwrap()
+            .contains(">       assert"));
+        assert_eq!(cases[2].outcome, TestOutcome::Skipped);
+        assert_eq!(cases[2].message.as_deref(), Some("not ready"));
+    }
+}
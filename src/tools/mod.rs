@@ -1,5 +1,15 @@
+pub mod diagnostics;
+pub mod diff;
 pub mod exec;
 pub mod file_system;
+pub mod git;
+pub mod http;
+pub mod junit;
+pub mod process;
+pub mod reduce;
+pub mod symbols;
+pub mod test_runner;
+pub mod walk;
 
 use file_system::FileSystemTool;
 
@@ -7,5 +17,11 @@ use file_system::FileSystemTool;
 pub fn get_all_tools() -> Vec<FileSystemTool> {
     let mut tools = file_system::get_file_system_tools();
     tools.push(exec::get_shell_tool());
+    tools.extend(git::get_git_tools());
+    tools.extend(process::get_process_tools());
+    tools.push(http::get_http_tool());
+    tools.push(diagnostics::get_diagnostics_tool());
+    tools.push(test_runner::get_test_runner_tool());
+    tools.extend(symbols::get_symbol_tools());
     tools
 }
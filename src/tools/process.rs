@@ -0,0 +1,660 @@
+//! Background process management
+//!
+//! Lets plans start long-running commands (dev servers, watchers), read their
+//! output incrementally, wait for a line matching a regex, write to their
+//! stdin and stop them. Every process belongs to a plan and is killed when the
+//! plan finishes or the manager is dropped; outside a plan nothing would stop
+//! them, so they can't be started there.
+
+use crate::tools::file_system::{FileSystemTool, ToolFunction, ToolParameters, ToolResult};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Output kept per process; older output is dropped once exceeded
+const MAX_BUFFERED_OUTPUT: usize = 1024 * 1024;
+
+/// How long a stopped process gets to exit after SIGTERM before it is killed
+const TERM_GRACE: Duration = Duration::from_millis(500);
+
+/// How long to let output readers drain after a process exits; children that
+/// inherited its pipes can keep them open indefinitely
+const DRAIN_GRACE: Duration = Duration::from_millis(500);
+
+/// Combined stdout/stderr of a process, addressed by absolute byte offsets
+#[derive(Debug, Default)]
+struct OutputBuffer {
+    text: String,
+    /// Bytes discarded from the front of `text`
+    dropped: usize,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        if self.text.len() > MAX_BUFFERED_OUTPUT {
+            let mut cut = self.text.len() - MAX_BUFFERED_OUTPUT;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+            self.dropped += cut;
+        }
+    }
+
+    fn end_offset(&self) -> usize {
+        self.dropped + self.text.len()
+    }
+
+    /// Output from `offset` to the end (clamped to what is still buffered)
+    fn since(&self, offset: usize) -> &str {
+        let mut start = offset.saturating_sub(self.dropped).min(self.text.len());
+        while !self.text.is_char_boundary(start) {
+            start += 1;
+        }
+        &self.text[start..]
+    }
+}
+
+struct ManagedProcess {
+    plan_id: String,
+    command: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    output: Arc<Mutex<OutputBuffer>>,
+    /// Threads copying stdout and stderr into `output`
+    readers: Vec<JoinHandle<()>>,
+    read_offset: usize,
+    started_at: DateTime<Utc>,
+}
+
+impl ManagedProcess {
+    fn exit_code(&mut self) -> Option<Option<i32>> {
+        self.child
+            .try_wait()
+            .ok()
+            .flatten()
+            .map(|status| status.code())
+    }
+
+    /// Whether all output has been read into the buffer
+    fn output_drained(&self) -> bool {
+        self.readers.iter().all(|reader| reader.is_finished())
+    }
+
+    fn has_exited(&mut self) -> bool {
+        self.child.try_wait().ok().flatten().is_some()
+    }
+
+    /// Signal the whole process group, so children of `sh -c` get it too
+    #[cfg(unix)]
+    fn signal_group(&self, signal: libc::c_int) {
+        // SAFETY: killpg only sends a signal; the group is the child's own
+        unsafe {
+            libc::killpg(self.child.id() as libc::pid_t, signal);
+        }
+    }
+
+    /// Kill the process group without waiting for a graceful exit
+    fn kill_now(&mut self) {
+        if self.has_exited() {
+            return;
+        }
+        #[cfg(unix)]
+        self.signal_group(libc::SIGKILL);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Ask `processes` to terminate, give them `TERM_GRACE` to exit and kill the
+/// ones that are still running
+async fn terminate(processes: &mut [ManagedProcess]) {
+    #[cfg(unix)]
+    {
+        for process in processes.iter_mut() {
+            if !process.has_exited() {
+                process.signal_group(libc::SIGTERM);
+            }
+        }
+        let deadline = Instant::now() + TERM_GRACE;
+        while Instant::now() < deadline && processes.iter_mut().any(|p| !p.has_exited()) {
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+    }
+    for process in processes.iter_mut() {
+        process.kill_now();
+    }
+}
+
+#[derive(Default)]
+struct ProcessTable {
+    processes: HashMap<String, ManagedProcess>,
+}
+
+impl Drop for ProcessTable {
+    fn drop(&mut self) {
+        for process in self.processes.values_mut() {
+            process.kill_now();
+        }
+    }
+}
+
+/// Tracks background processes started by plans.
+///
+/// Clones share the same process table.
+#[derive(Clone, Default)]
+pub struct ProcessManager {
+    table: Arc<Mutex<ProcessTable>>,
+    next_id: Arc<AtomicUsize>,
+}
+
+impl std::fmt::Debug for ProcessManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let handles: Vec<String> = self
+            .table
+            .lock()
+            .map(|t| t.processes.keys().cloned().collect())
+            .unwrap_or_default();
+        f.debug_struct("ProcessManager")
+            .field("processes", &handles)
+            .finish()
+    }
+}
+
+fn error_result(message: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        data: None,
+        error: Some(message),
+    }
+}
+
+fn pump<R: Read + Send + 'static>(
+    mut reader: R,
+    output: Arc<Mutex<OutputBuffer>>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => output
+                    .lock()
+                    .unwrap()
+                    .push(&String::from_utf8_lossy(&buf[..n])),
+            }
+        }
+    })
+}
+
+impl ProcessManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start `command` through `sh -c` in `workdir`, owned by `plan_id`
+    pub fn start(&self, plan_id: &str, command: &str, workdir: &Path) -> ToolResult {
+        if command.trim().is_empty() {
+            return error_result("Command cannot be empty".to_string());
+        }
+        if plan_id.is_empty() {
+            return error_result(
+                "Background processes can only be started by a plan task".to_string(),
+            );
+        }
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .current_dir(workdir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => return error_result(format!("Failed to start '{}': {}", command, e)),
+        };
+
+        let output = Arc::new(Mutex::new(OutputBuffer::default()));
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(pump(stdout, output.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(pump(stderr, output.clone()));
+        }
+
+        let handle = format!("proc_{}", self.next_id.fetch_add(1, Ordering::SeqCst) + 1);
+        let pid = child.id();
+        let process = ManagedProcess {
+            plan_id: plan_id.to_string(),
+            command: command.to_string(),
+            stdin: child.stdin.take(),
+            child,
+            output,
+            readers,
+            read_offset: 0,
+            started_at: Utc::now(),
+        };
+        self.table
+            .lock()
+            .unwrap()
+            .processes
+            .insert(handle.clone(), process);
+
+        ToolResult {
+            success: true,
+            data: Some(json!({
+                "handle": handle,
+                "pid": pid,
+                "command": command,
+            })),
+            error: None,
+        }
+    }
+
+    /// Output produced since `since` (default: since the previous read)
+    pub fn read(&self, handle: &str, since: Option<usize>) -> ToolResult {
+        let mut table = self.table.lock().unwrap();
+        let Some(process) = table.processes.get_mut(handle) else {
+            return error_result(format!("Unknown process handle: {}", handle));
+        };
+
+        let exit_code = process.exit_code();
+        let (output, offset) = {
+            let buffer = process.output.lock().unwrap();
+            let from = since.unwrap_or(process.read_offset);
+            (buffer.since(from).to_string(), buffer.end_offset())
+        };
+        process.read_offset = offset;
+
+        ToolResult {
+            success: true,
+            data: Some(json!({
+                "handle": handle,
+                "output": output,
+                "offset": offset,
+                "running": exit_code.is_none(),
+                "exit_code": exit_code.flatten(),
+            })),
+            error: None,
+        }
+    }
+
+    /// Wait until the process output matches `pattern`, it exits, or `timeout` passes
+    pub async fn wait_for(&self, handle: &str, pattern: &str, timeout: Duration) -> ToolResult {
+        let regex = match Regex::new(pattern) {
+            Ok(regex) => regex,
+            Err(e) => return error_result(format!("Invalid regex '{}': {}", pattern, e)),
+        };
+        let start_offset = match self.table.lock().unwrap().processes.get(handle) {
+            Some(process) => process.read_offset,
+            None => return error_result(format!("Unknown process handle: {}", handle)),
+        };
+
+        let started = Instant::now();
+        let mut exited_at: Option<Instant> = None;
+        loop {
+            let (matched, output, running, drained) = {
+                let mut table = self.table.lock().unwrap();
+                let Some(process) = table.processes.get_mut(handle) else {
+                    return error_result(format!("Process {} was stopped", handle));
+                };
+                // Checked before matching, so the match sees everything read so far
+                let running = process.exit_code().is_none();
+                let drained = process.output_drained();
+                let buffer = process.output.lock().unwrap();
+                let output = buffer.since(start_offset).to_string();
+                let matched = regex.find(&output).map(|m| m.as_str().to_string());
+                (matched, output, running, drained)
+            };
+
+            if let Some(matched) = matched {
+                return ToolResult {
+                    success: true,
+                    data: Some(json!({
+                        "handle": handle,
+                        "matched": matched,
+                        "output": output,
+                        "elapsed_ms": started.elapsed().as_millis() as u64,
+                    })),
+                    error: None,
+                };
+            }
+            // An exited process only fails the wait once its output is drained
+            let exited = !running
+                && (drained || exited_at.get_or_insert_with(Instant::now).elapsed() >= DRAIN_GRACE);
+            if exited || started.elapsed() >= timeout {
+                let reason = if running {
+                    format!("Timed out after {:?} waiting for /{}/", timeout, pattern)
+                } else {
+                    format!("Process exited before /{}/ appeared", pattern)
+                };
+                return ToolResult {
+                    success: false,
+                    data: Some(json!({ "handle": handle, "output": output, "running": running })),
+                    error: Some(reason),
+                };
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Write `input` to the process stdin
+    pub fn send(&self, handle: &str, input: &str) -> ToolResult {
+        let mut table = self.table.lock().unwrap();
+        let Some(process) = table.processes.get_mut(handle) else {
+            return error_result(format!("Unknown process handle: {}", handle));
+        };
+        let Some(stdin) = process.stdin.as_mut() else {
+            return error_result(format!("Stdin of {} is closed", handle));
+        };
+
+        match stdin
+            .write_all(input.as_bytes())
+            .and_then(|_| stdin.flush())
+        {
+            Ok(()) => ToolResult {
+                success: true,
+                data: Some(json!({ "handle": handle, "bytes_written": input.len() })),
+                error: None,
+            },
+            Err(e) => error_result(format!("Failed to write to {}: {}", handle, e)),
+        }
+    }
+
+    /// Kill a process and return its remaining output
+    pub async fn stop(&self, handle: &str) -> ToolResult {
+        let Some(process) = self.table.lock().unwrap().processes.remove(handle) else {
+            return error_result(format!("Unknown process handle: {}", handle));
+        };
+        let mut stopped = [process];
+        terminate(&mut stopped).await;
+        let [mut process] = stopped;
+        let output = process
+            .output
+            .lock()
+            .unwrap()
+            .since(process.read_offset)
+            .to_string();
+
+        ToolResult {
+            success: true,
+            data: Some(json!({
+                "handle": handle,
+                "command": process.command,
+                "exit_code": process.exit_code().flatten(),
+                "output": output,
+            })),
+            error: None,
+        }
+    }
+
+    /// Processes owned by `plan_id` (all processes when `None`)
+    pub fn list(&self, plan_id: Option<&str>) -> ToolResult {
+        let mut table = self.table.lock().unwrap();
+        let mut processes: Vec<serde_json::Value> = table
+            .processes
+            .iter_mut()
+            .filter(|(_, p)| plan_id.is_none_or(|id| p.plan_id == id))
+            .map(|(handle, p)| {
+                let exit_code = p.exit_code();
+                json!({
+                    "handle": handle,
+                    "plan_id": p.plan_id,
+                    "command": p.command,
+                    "pid": p.child.id(),
+                    "running": exit_code.is_none(),
+                    "exit_code": exit_code.flatten(),
+                    "started_at": p.started_at.to_rfc3339(),
+                })
+            })
+            .collect();
+        processes.sort_by_key(|p| p["started_at"].as_str().unwrap_or_default().to_string());
+
+        ToolResult {
+            success: true,
+            data: Some(json!({ "processes": processes })),
+            error: None,
+        }
+    }
+
+    /// Kill every process started by `plan_id`, returning how many were stopped
+    pub async fn stop_plan(&self, plan_id: &str) -> usize {
+        // Taken out of the table first, so other calls don't wait on the kills
+        let mut processes: Vec<ManagedProcess> = {
+            let mut table = self.table.lock().unwrap();
+            let handles: Vec<String> = table
+                .processes
+                .iter()
+                .filter(|(_, p)| p.plan_id == plan_id)
+                .map(|(handle, _)| handle.clone())
+                .collect();
+            handles
+                .iter()
+                .filter_map(|handle| table.processes.remove(handle))
+                .collect()
+        };
+        terminate(&mut processes).await;
+        processes.len()
+    }
+}
+
+/// Generate OpenRouter-compatible definitions for the background process tools
+pub fn get_process_tools() -> Vec<FileSystemTool> {
+    let tool = |name: &str, description: &str, properties: serde_json::Value, required: &[&str]| {
+        FileSystemTool {
+            tool_type: "function".to_string(),
+            function: ToolFunction {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: ToolParameters {
+                    param_type: "object".to_string(),
+                    properties,
+                    required: required.iter().map(|r| r.to_string()).collect(),
+                },
+            },
+        }
+    };
+    let handle = json!({
+        "type": "string",
+        "description": "Process handle returned by start_process."
+    });
+
+    vec![
+        tool(
+            "start_process",
+            "Start a long-running shell command (server, watcher) in the background and return a handle. The process is stopped when the plan ends.",
+            json!({
+                "command": {
+                    "type": "string",
+                    "description": "The shell command to run."
+                }
+            }),
+            &["command"],
+        ),
+        tool(
+            "read_process_output",
+            "Read the output a background process produced since the last read.",
+            json!({
+                "handle": handle,
+                "since": {
+                    "type": "integer",
+                    "description": "Optional byte offset to read from instead of the last read position."
+                }
+            }),
+            &["handle"],
+        ),
+        tool(
+            "wait_for_output",
+            "Wait until a background process prints a line matching a regex, e.g. 'listening on'.",
+            json!({
+                "handle": handle,
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression to wait for."
+                },
+                "timeout_secs": {
+                    "type": "integer",
+                    "description": "Maximum seconds to wait.",
+                    "default": 30
+                }
+            }),
+            &["handle", "pattern"],
+        ),
+        tool(
+            "send_process_input",
+            "Write text to the stdin of a background process.",
+            json!({
+                "handle": handle,
+                "input": {
+                    "type": "string",
+                    "description": "Text to send; include a trailing newline for line-based programs."
+                }
+            }),
+            &["handle", "input"],
+        ),
+        tool(
+            "stop_process",
+            "Stop a background process and return its remaining output.",
+            json!({ "handle": handle }),
+            &["handle"],
+        ),
+        tool(
+            "list_processes",
+            "List background processes with their status.",
+            json!({}),
+            &[],
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_start_wait_send_stop() {
+        let dir = tempdir().unwrap();
+        let manager = ProcessManager::new();
+
+        let started = manager.start(
+            "plan_a",
+            "echo ready; while read line; do echo \"got $line\"; done",
+            dir.path(),
+        );
+        assert!(started.success);
+        let handle = started.data.unwrap()["handle"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let ready = manager
+            .wait_for(&handle, "^ready", Duration::from_secs(5))
+            .await;
+        assert!(ready.success, "{:?}", ready.error);
+
+        assert!(manager.send(&handle, "ping\n").success);
+        let echoed = manager
+            .wait_for(&handle, "got ping", Duration::from_secs(5))
+            .await;
+        assert!(echoed.success, "{:?}", echoed.error);
+
+        let read = manager.read(&handle, Some(0)).data.unwrap();
+        assert!(read["output"].as_str().unwrap().contains("ready"));
+        assert_eq!(read["running"], true);
+
+        assert!(manager.stop(&handle).await.success);
+        assert!(!manager.read(&handle, None).success);
+    }
+
+    #[tokio::test]
+    async fn test_stop_plan_kills_only_its_processes() {
+        let dir = tempdir().unwrap();
+        let manager = ProcessManager::new();
+        manager.start("plan_a", "sleep 30", dir.path());
+        manager.start("plan_a", "sleep 30", dir.path());
+        manager.start("plan_b", "sleep 30", dir.path());
+
+        assert_eq!(manager.stop_plan("plan_a").await, 2);
+        let remaining = manager.list(None).data.unwrap();
+        assert_eq!(remaining["processes"].as_array().unwrap().len(), 1);
+
+        let timeout = manager
+            .wait_for("proc_3", "never", Duration::from_millis(100))
+            .await;
+        assert!(!timeout.success);
+        assert_eq!(manager.stop_plan("plan_b").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_output_of_exited_process() {
+        let dir = tempdir().unwrap();
+        let manager = ProcessManager::new();
+        // Exits immediately; its output may still be in the pipe when it does
+        for i in 0..20 {
+            let started = manager.start("plan_a", &format!("echo done {}", i), dir.path());
+            let handle = started.data.unwrap()["handle"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            let result = manager
+                .wait_for(&handle, &format!("done {}", i), Duration::from_secs(5))
+                .await;
+            assert!(result.success, "{:?}", result.error);
+        }
+
+        let started = manager.start("plan_a", "true", dir.path());
+        let handle = started.data.unwrap()["handle"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let result = manager
+            .wait_for(&handle, "never", Duration::from_secs(5))
+            .await;
+        assert!(result.error.unwrap().contains("exited before"));
+    }
+
+    #[tokio::test]
+    async fn test_stop_plan_does_not_block_other_calls() {
+        let dir = tempdir().unwrap();
+        let manager = ProcessManager::new();
+        assert!(!manager.start("", "sleep 30", dir.path()).success);
+        // Ignores SIGTERM, so it is only killed once the grace period is over
+        manager.start("plan_a", "trap '' TERM; sleep 30", dir.path());
+        manager.start("plan_b", "sleep 30", dir.path());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let started = Instant::now();
+        let list = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let listed = manager.list(None);
+            (started.elapsed(), listed)
+        };
+        let (stopped, (listed_after, listed)) = tokio::join!(manager.stop_plan("plan_a"), list);
+        assert_eq!(stopped, 1);
+        assert!(started.elapsed() >= TERM_GRACE);
+        assert!(listed_after < TERM_GRACE, "{:?}", listed_after);
+        assert_eq!(
+            listed.data.unwrap()["processes"].as_array().unwrap().len(),
+            1
+        );
+        assert_eq!(manager.stop_plan("plan_b").await, 1);
+    }
+}
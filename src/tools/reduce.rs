@@ -0,0 +1,537 @@
+//! Tool output reducers
+//!
+//! Tool results are often far larger than what fits in a prompt. Instead of
+//! cutting the serialized JSON at an arbitrary byte, each tool's output is
+//! reduced in a way that keeps what matters for it: the head and tail of logs,
+//! grep hits grouped by file, repeated compiler errors collapsed, directory
+//! listings summarized. The full output can be spilled to a file the model can
+//! page through with `read_file`.
+
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Default prompt budget for a single tool result, in bytes
+pub const DEFAULT_OUTPUT_BUDGET: usize = 4000;
+
+/// Where full outputs are kept, relative to the working directory
+pub const SPILL_DIR: &str = ".context/spill";
+
+/// Grep hits shown per file
+const MAX_MATCHES_PER_FILE: usize = 5;
+
+/// Entries named in a directory summary
+const MAX_LISTED_ENTRIES: usize = 40;
+
+/// Lines kept from the omitted middle of a log because they look like errors
+const MAX_HIGHLIGHTED_LINES: usize = 20;
+
+static DIAGNOSTIC_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:error|warning)(?:\[[A-Za-z0-9_-]+\])?:\s|^\S+:\d+:\d+: (?:error|warning)")
+        .unwrap()
+});
+static LOCATION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*--> (\S+)|^(\S+:\d+:\d+):").unwrap());
+static HIGHLIGHT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(error|failed|failure|panicked|exception|fatal)\b").unwrap()
+});
+
+/// Reduce a tool's raw (serialized `ToolResult`) output to roughly `budget`
+/// bytes. Output within budget is returned unchanged; the result may still
+/// exceed the budget when the reducer cannot shrink it further.
+pub fn reduce(tool: &str, raw: &str, budget: usize) -> String {
+    if raw.len() <= budget {
+        return raw.to_string();
+    }
+    let Ok(result) = serde_json::from_str::<Value>(raw) else {
+        return reduce_log(raw, budget);
+    };
+    let data = &result["data"];
+
+    let mut sections = vec![format!(
+        "success: {}",
+        result["success"].as_bool().unwrap_or(false)
+    )];
+    if let Some(error) = result["error"].as_str() {
+        sections.push(format!("error: {}", head_tail(error, budget / 4)));
+    }
+
+    let body = match tool {
+        "bash" | "run_shell" => {
+            let mut parts = Vec::new();
+            if let Some(code) = data["exit_code"].as_i64() {
+                parts.push(format!("exit_code: {}", code));
+            }
+            for stream in ["stdout", "stderr"] {
+                let text = data[stream].as_str().unwrap_or("");
+                if !text.trim().is_empty() {
+                    parts.push(format!("{}:\n{}", stream, reduce_log(text, budget / 2)));
+                }
+            }
+            parts.join("\n")
+        }
+        "read_process_output" | "wait_for_output" => {
+            reduce_log(data["output"].as_str().unwrap_or(""), budget)
+        }
+        "read" | "read_file" => format!(
+            "{} ({} lines)\n{}",
+            data["path"].as_str().unwrap_or(""),
+            data["total_lines"],
+            head_tail_lines(data["content"].as_str().unwrap_or(""), budget)
+        ),
+        "grep_files" => reduce_grep(data),
+        "ls" | "list_directory" => {
+            let entries: Vec<&Value> = data["directories"]
+                .as_array()
+                .into_iter()
+                .chain(data["files"].as_array())
+                .flatten()
+                .collect();
+            summarize_entries(&entries)
+        }
+        "find_files" => {
+            let entries: Vec<&Value> = data["results"].as_array().into_iter().flatten().collect();
+            summarize_entries(&entries)
+        }
+        "build_diagnostics" => reduce_diagnostics(data),
+        "run_tests" => reduce_tests(data),
+        _ => {
+            let compact = serde_json::to_string(data).unwrap_or_default();
+            head_tail(&compact, budget)
+        }
+    };
+    sections.push(body);
+    sections.join("\n")
+}
+
+/// Keep the beginning and end of `text` within `budget` bytes
+pub fn head_tail(text: &str, budget: usize) -> String {
+    if text.len() <= budget {
+        return text.to_string();
+    }
+    let head_len = floor_char_boundary(text, budget * 2 / 5);
+    let tail_start = ceil_char_boundary(text, text.len() - budget * 3 / 5);
+    format!(
+        "{}\n... [{} bytes omitted] ...\n{}",
+        &text[..head_len],
+        tail_start - head_len,
+        &text[tail_start..]
+    )
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+/// Keep whole lines from the head and tail of `text` within `budget` bytes
+fn head_tail_lines(text: &str, budget: usize) -> String {
+    if text.len() <= budget {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let (mut head, mut head_bytes) = (0, 0);
+    while head < lines.len() && head_bytes + lines[head].len() < budget * 2 / 5 {
+        head_bytes += lines[head].len() + 1;
+        head += 1;
+    }
+    let (mut tail, mut tail_bytes) = (lines.len(), 0);
+    while tail > head && tail_bytes + lines[tail - 1].len() < budget * 3 / 5 {
+        tail -= 1;
+        tail_bytes += lines[tail].len() + 1;
+    }
+    if head == 0 && tail == lines.len() {
+        // A few huge lines: fall back to a byte cut
+        return head_tail(text, budget);
+    }
+
+    let middle = &lines[head..tail];
+    let highlighted: Vec<&str> = middle
+        .iter()
+        .filter(|line| HIGHLIGHT_RE.is_match(line))
+        .take(MAX_HIGHLIGHTED_LINES)
+        .map(|line| line.trim_end())
+        .collect();
+    let mut out = lines[..head].join("\n");
+    out.push_str(&format!("\n... [{} lines omitted", middle.len()));
+    if highlighted.is_empty() {
+        out.push_str("] ...\n");
+    } else {
+        out.push_str(", error lines among them:]\n");
+        for line in highlighted {
+            out.push_str(&format!("  {}\n", head_tail(line, 200)));
+        }
+        out.push_str("...\n");
+    }
+    out.push_str(&lines[tail..].join("\n"));
+    out
+}
+
+/// Collapse repeated compiler diagnostics and repeated lines, then keep the
+/// head and tail of what remains
+pub fn reduce_log(text: &str, budget: usize) -> String {
+    let deduped = dedupe_diagnostics(text);
+    head_tail_lines(&deduped, budget)
+}
+
+/// A diagnostic starts at an `error: ...` / `warning[..]: ...` / `file:1:2: error` line
+/// and runs until the next blank line or diagnostic. Later copies of the same
+/// headline are dropped and their locations listed under the first one.
+fn dedupe_diagnostics(text: &str) -> String {
+    let mut blocks: Vec<(Option<String>, Vec<&str>)> = Vec::new();
+    for line in text.lines() {
+        if DIAGNOSTIC_RE.is_match(line) {
+            let headline = LOCATION_RE.replace(line, "").trim().to_string();
+            blocks.push((Some(headline), vec![line]));
+        } else if line.trim().is_empty() {
+            blocks.push((None, vec![line]));
+        } else {
+            match blocks.last_mut() {
+                Some((Some(_), lines)) if !lines.last().is_some_and(|l| l.trim().is_empty()) => {
+                    lines.push(line)
+                }
+                _ => blocks.push((None, vec![line])),
+            }
+        }
+    }
+
+    let location = |lines: &[&str]| {
+        lines.iter().find_map(|line| {
+            LOCATION_RE
+                .captures(line)
+                .and_then(|c| c.get(1).or_else(|| c.get(2)))
+                .map(|m| m.as_str().to_string())
+        })
+    };
+    let mut first_index: HashMap<String, usize> = HashMap::new();
+    let mut repeats: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut kept: Vec<(usize, &Vec<&str>)> = Vec::new();
+    for (index, (headline, lines)) in blocks.iter().enumerate() {
+        match headline {
+            Some(headline) => match first_index.get(headline) {
+                Some(&first) => repeats
+                    .entry(first)
+                    .or_default()
+                    .push(location(lines).unwrap_or_default()),
+                None => {
+                    first_index.insert(headline.clone(), index);
+                    kept.push((index, lines));
+                }
+            },
+            None => kept.push((index, lines)),
+        }
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    let mut repeated = 0;
+    let flush_repeats = |out: &mut Vec<String>, repeated: &mut usize| {
+        if *repeated > 0 {
+            out.push(format!(
+                "  (previous line repeated {} more time(s))",
+                repeated
+            ));
+            *repeated = 0;
+        }
+    };
+    for (index, lines) in kept {
+        for line in lines {
+            // Collapse runs of identical lines
+            let last = out.last();
+            if line.trim().is_empty() && last.is_some_and(|last| last.trim().is_empty()) {
+                continue;
+            }
+            if !line.trim().is_empty() && last.is_some_and(|last| last == line) {
+                repeated += 1;
+                continue;
+            }
+            flush_repeats(&mut out, &mut repeated);
+            out.push(line.to_string());
+        }
+        if let Some(locations) = repeats.get(&index) {
+            let named: Vec<&str> = locations
+                .iter()
+                .filter(|l| !l.is_empty())
+                .map(|l| l.as_str())
+                .take(10)
+                .collect();
+            let mut note = format!("  (same diagnostic {} more time(s)", locations.len());
+            if !named.is_empty() {
+                note.push_str(&format!(" at {}", named.join(", ")));
+            }
+            note.push(')');
+            flush_repeats(&mut out, &mut repeated);
+            out.push(note);
+        }
+    }
+    flush_repeats(&mut out, &mut repeated);
+    out.join("\n")
+}
+
+/// Matches grouped per file, a few lines each
+fn reduce_grep(data: &Value) -> String {
+    let results = data["results"].as_array().cloned().unwrap_or_default();
+    let mut out = vec![format!(
+        "{} matches in {} files for '{}'",
+        data["total_matches"],
+        results.len(),
+        data["pattern"].as_str().unwrap_or("")
+    )];
+    for file in &results {
+        let matches = file["matches"].as_array().cloned().unwrap_or_default();
+        out.push(format!(
+            "{} ({} matches)",
+            file["file"].as_str().unwrap_or(""),
+            matches.len()
+        ));
+        for m in matches.iter().take(MAX_MATCHES_PER_FILE) {
+            out.push(format!(
+                "  {}: {}",
+                m["line_number"],
+                head_tail(m["line"].as_str().unwrap_or("").trim(), 160)
+            ));
+        }
+        if matches.len() > MAX_MATCHES_PER_FILE {
+            out.push(format!(
+                "  ... {} more",
+                matches.len() - MAX_MATCHES_PER_FILE
+            ));
+        }
+    }
+    out.join("\n")
+}
+
+/// Counts by directory and extension plus the first entries
+fn summarize_entries(entries: &[&Value]) -> String {
+    let path_of = |entry: &Value| entry["path"].as_str().unwrap_or("").to_string();
+    let dirs = entries.iter().filter(|e| e["is_dir"] == true).count();
+    let mut by_extension: BTreeMap<String, usize> = BTreeMap::new();
+    let mut by_parent: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e["is_dir"] != true) {
+        let path = PathBuf::from(path_of(entry));
+        let extension = path
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_else(|| "(none)".to_string());
+        *by_extension.entry(extension).or_default() += 1;
+        let parent = path
+            .parent()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        *by_parent.entry(parent).or_default() += 1;
+    }
+
+    let mut out = vec![format!(
+        "{} entries: {} files, {} directories",
+        entries.len(),
+        entries.len() - dirs,
+        dirs
+    )];
+    let mut extensions: Vec<(String, usize)> = by_extension.into_iter().collect();
+    extensions.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    out.push(format!(
+        "by extension: {}",
+        extensions
+            .iter()
+            .map(|(ext, count)| format!("{} {}", ext, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+    if by_parent.len() > 1 {
+        let mut parents: Vec<(String, usize)> = by_parent.into_iter().collect();
+        parents.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        out.push("largest directories:".to_string());
+        for (parent, count) in parents.iter().take(10) {
+            out.push(format!("  {} ({} files)", parent, count));
+        }
+    }
+    out.push("entries:".to_string());
+    for entry in entries.iter().take(MAX_LISTED_ENTRIES) {
+        let suffix = if entry["is_dir"] == true { "/" } else { "" };
+        out.push(format!("  {}{}", path_of(entry), suffix));
+    }
+    if entries.len() > MAX_LISTED_ENTRIES {
+        out.push(format!("  ... {} more", entries.len() - MAX_LISTED_ENTRIES));
+    }
+    out.join("\n")
+}
+
+/// Diagnostics grouped by severity, code and message with their locations
+fn reduce_diagnostics(data: &Value) -> String {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for diagnostic in data["diagnostics"].as_array().into_iter().flatten() {
+        let code = diagnostic["code"]
+            .as_str()
+            .map(|c| format!("[{}]", c))
+            .unwrap_or_default();
+        let key = format!(
+            "{}{}: {}",
+            diagnostic["severity"].as_str().unwrap_or("error"),
+            code,
+            diagnostic["message"].as_str().unwrap_or("")
+        );
+        let location = match (diagnostic["file"].as_str(), diagnostic["line"].as_u64()) {
+            (Some(file), Some(line)) => format!("{}:{}", file, line),
+            (Some(file), None) => file.to_string(),
+            _ => String::new(),
+        };
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, locations)) => locations.push(location),
+            None => groups.push((key, vec![location])),
+        }
+    }
+
+    let mut out = vec![format!(
+        "{} ({}): {} errors, {} warnings, exit code {}",
+        data["provider"].as_str().unwrap_or(""),
+        data["command"].as_str().unwrap_or(""),
+        data["errors"],
+        data["warnings"],
+        data["exit_code"]
+    )];
+    for (key, locations) in &groups {
+        let shown: Vec<&str> = locations
+            .iter()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.as_str())
+            .take(5)
+            .collect();
+        let mut line = format!("{} ({}x)", head_tail(key, 300), locations.len());
+        if !shown.is_empty() {
+            line.push_str(&format!(" at {}", shown.join(", ")));
+            if locations.len() > shown.len() {
+                line.push_str(", ...");
+            }
+        }
+        out.push(line);
+    }
+    if let Some(tail) = data["stderr_tail"]
+        .as_str()
+        .filter(|t| !t.trim().is_empty())
+    {
+        out.push(format!("stderr tail:\n{}", head_tail(tail, 1000)));
+    }
+    out.join("\n")
+}
+
+/// Test counts plus the failures; passing tests are not listed
+fn reduce_tests(data: &Value) -> String {
+    let mut out = vec![format!(
+        "{} ({}): {} passed, {} failed, {} skipped of {}",
+        data["framework"].as_str().unwrap_or(""),
+        data["command"].as_str().unwrap_or(""),
+        data["passed"],
+        data["failed"],
+        data["skipped"],
+        data["total"]
+    )];
+    for test in data["tests"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|t| t["outcome"] == "failed")
+    {
+        out.push(format!("FAILED {}", test["name"].as_str().unwrap_or("")));
+        if let Some(message) = test["message"].as_str() {
+            out.push(format!("  {}", head_tail(message.trim(), 600)));
+        }
+    }
+    if let Some(tail) = data["stderr_tail"]
+        .as_str()
+        .filter(|t| !t.trim().is_empty())
+    {
+        out.push(format!("stderr tail:\n{}", head_tail(tail, 1000)));
+    }
+    out.join("\n")
+}
+
+/// Save a full tool output under `<workdir>/.context/spill/<name>.txt`
+pub fn spill(workdir: &Path, name: &str, output: &str) -> std::io::Result<PathBuf> {
+    let dir = workdir.join(SPILL_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let file_name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = dir.join(format!("{}.txt", file_name));
+    std::fs::write(&path, output)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reducers_keep_the_important_parts() {
+        // Repeated compiler errors collapse; the head and tail of the log survive
+        let mut stderr = String::from("   Compiling app v0.1.0\n");
+        for i in 0..30 {
+            stderr.push_str(&format!(
+                "error[E0425]: cannot find value `y` in this scope\n  --> src/main.rs:{}:5\n   |\n\n",
+                i + 1
+            ));
+        }
+        for i in 0..200 {
+            stderr.push_str(&format!("note: filler line {}\n", i));
+        }
+        stderr.push_str("error: could not compile `app` due to 30 previous errors\n");
+        let raw = json!({
+            "success": false,
+            "data": { "stdout": "", "stderr": stderr, "exit_code": 101 },
+            "error": "build failed"
+        })
+        .to_string();
+        let reduced = reduce("bash", &raw, 2000);
+        assert!(reduced.len() < 2600, "{}", reduced.len());
+        assert!(reduced.contains("exit_code: 101"));
+        assert!(reduced.contains("same diagnostic 29 more time(s) at src/main.rs:2:5"));
+        assert!(reduced.contains("could not compile"));
+        assert_eq!(reduced.matches("cannot find value").count(), 1);
+
+        // Grep hits are grouped by file
+        let matches: Vec<Value> = (1..=50)
+            .map(|n| json!({ "line_number": n, "line": format!("let value_{} = compute();", n) }))
+            .collect();
+        let raw = json!({
+            "success": true,
+            "data": {
+                "pattern": "compute",
+                "total_matches": 51,
+                "results": [
+                    { "file": "src/a.rs", "matches": matches, "match_count": 50 },
+                    { "file": "src/b.rs", "matches": [{ "line_number": 7, "line": "compute()" }], "match_count": 1 }
+                ]
+            }
+        })
+        .to_string();
+        let reduced = reduce("grep_files", &raw, 500);
+        assert!(reduced.contains("src/a.rs (50 matches)"));
+        assert!(reduced.contains("... 45 more"));
+        assert!(reduced.contains("src/b.rs (1 matches)\n  7: compute()"));
+
+        // Small outputs pass through untouched
+        assert_eq!(
+            reduce("bash", "{\"success\":true}", 100),
+            "{\"success\":true}"
+        );
+        assert!(head_tail("ééééééééééé", 8).contains("omitted"));
+    }
+}
@@ -0,0 +1,862 @@
+//! Code-aware symbol search
+//!
+//! A lightweight structural parser for Rust, Python, TypeScript/JavaScript and
+//! Go. Comments and string literals are masked out first so braces can be
+//! matched reliably; definitions are then recognised per language and given
+//! line spans (brace matching, or indentation for Python). This powers the
+//! `outline` and `find_symbol` tools and the harvester's file structure data.
+
+use crate::tools::file_system::{FileSystemTool, ToolFunction, ToolParameters, ToolResult};
+use crate::tools::walk;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Body lines returned per match before truncation
+const MAX_BODY_LINES: usize = 400;
+/// Matches returned by one `find_symbol` call
+const MAX_MATCHES: usize = 20;
+/// Languages the symbol parser understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    TypeScript,
+    Go,
+}
+
+impl Language {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "rs" => Some(Language::Rust),
+            "py" => Some(Language::Python),
+            "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(Language::TypeScript),
+            "go" => Some(Language::Go),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Struct,
+    Enum,
+    Trait,
+    Impl,
+    Class,
+    Interface,
+    Type,
+    Module,
+    Const,
+    Macro,
+}
+
+impl SymbolKind {
+    /// Kinds whose members are reported as children
+    fn is_container(&self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Impl
+                | SymbolKind::Trait
+                | SymbolKind::Class
+                | SymbolKind::Interface
+                | SymbolKind::Module
+        )
+    }
+}
+
+/// A definition found in a source file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    /// Name including its container, e.g. `TaskExecutor::dispatch_tool`
+    pub qualified_name: String,
+    pub kind: SymbolKind,
+    /// 1-based, inclusive
+    pub start_line: usize,
+    pub end_line: usize,
+    /// The definition's first line, trimmed
+    pub signature: String,
+    /// Nesting depth (0 for top-level definitions)
+    pub depth: usize,
+}
+
+/// Replace comments and string/char literal contents with spaces, keeping
+/// newlines and byte offsets intact
+fn mask_source(source: &str, language: Language) -> Vec<u8> {
+    let bytes = source.as_bytes();
+    let mut out = bytes.to_vec();
+    let blank = |out: &mut Vec<u8>, from: usize, to: usize| {
+        for b in &mut out[from..to.min(bytes.len())] {
+            if *b != b'\n' {
+                *b = b' ';
+            }
+        }
+    };
+    let line_comment: &[u8] = if language == Language::Python {
+        b"#"
+    } else {
+        b"//"
+    };
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(line_comment) {
+            let end = rest
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |p| i + p);
+            blank(&mut out, i, end);
+            i = end;
+        } else if language != Language::Python && rest.starts_with(b"/*") {
+            let end = find(bytes, i + 2, b"*/").map_or(bytes.len(), |p| p + 2);
+            blank(&mut out, i, end);
+            i = end;
+        } else if language == Language::Python
+            && (rest.starts_with(b"\"\"\"") || rest.starts_with(b"'''"))
+        {
+            let end = find(bytes, i + 3, &rest[..3]).map_or(bytes.len(), |p| p + 3);
+            blank(&mut out, i + 3, end.saturating_sub(3).max(i + 3));
+            i = end;
+        } else if language == Language::Rust
+            && rest.starts_with(b"r")
+            && (rest.get(1) == Some(&b'"') || rest.get(1) == Some(&b'#'))
+            && (i == 0 || !is_ident_byte(bytes[i - 1]))
+        {
+            // Raw string r"..." / r#"..."#
+            let hashes = rest[1..].iter().take_while(|&&b| b == b'#').count();
+            if rest.get(1 + hashes) != Some(&b'"') {
+                i += 1;
+                continue;
+            }
+            let mut closing = vec![b'"'];
+            closing.extend(std::iter::repeat_n(b'#', hashes));
+            let start = i + 2 + hashes;
+            let end = find(bytes, start, &closing).map_or(bytes.len(), |p| p + closing.len());
+            blank(
+                &mut out,
+                start,
+                end.saturating_sub(closing.len()).max(start),
+            );
+            i = end;
+        } else if rest[0] == b'"'
+            || (rest[0] == b'`' && language != Language::Rust)
+            || (rest[0] == b'\'' && language != Language::Rust)
+        {
+            let quote = rest[0];
+            let mut j = i + 1;
+            while j < bytes.len() && bytes[j] != quote {
+                if bytes[j] == b'\\' {
+                    j += 1;
+                } else if bytes[j] == b'\n' && quote != b'`' && language != Language::Rust {
+                    break;
+                }
+                j += 1;
+            }
+            blank(&mut out, i + 1, j);
+            i = j + 1;
+        } else if rest[0] == b'\'' {
+            // Rust char literal ('x', '\n', '\u{1F600}'); anything else is a lifetime
+            let len = if rest.get(1) == Some(&b'\\') {
+                rest.iter().skip(2).position(|&b| b == b'\'').map(|p| p + 3)
+            } else {
+                std::str::from_utf8(&rest[1..rest.len().min(6)])
+                    .ok()
+                    .or_else(|| {
+                        (1..rest.len().min(6))
+                            .rev()
+                            .find_map(|n| std::str::from_utf8(&rest[1..n]).ok())
+                    })
+                    .and_then(|s| s.chars().next())
+                    .filter(|c| rest.get(1 + c.len_utf8()) == Some(&b'\''))
+                    .map(|c| c.len_utf8() + 2)
+            };
+            match len {
+                Some(len) if len <= 12 => {
+                    blank(&mut out, i + 1, i + len - 1);
+                    i += len;
+                }
+                _ => i += 1,
+            }
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+fn find(haystack: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| p + from)
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Byte offset of the start of each line
+fn line_starts(text: &[u8]) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(
+            text.iter()
+                .enumerate()
+                .filter(|(_, &b)| b == b'\n')
+                .map(|(i, _)| i + 1),
+        )
+        .collect()
+}
+
+fn line_of(starts: &[usize], offset: usize) -> usize {
+    starts.partition_point(|&s| s <= offset)
+}
+
+/// End offset of a definition starting at `from`: its matching `}` or the
+/// first `;` outside parentheses and brackets
+fn block_end(masked: &[u8], from: usize) -> usize {
+    let mut nesting = 0i32;
+    let mut i = from;
+    while i < masked.len() {
+        match masked[i] {
+            b'(' | b'[' => nesting += 1,
+            b')' | b']' => nesting -= 1,
+            b';' if nesting <= 0 => return i,
+            b'{' if nesting <= 0 => {
+                let mut depth = 0;
+                for (j, &b) in masked.iter().enumerate().skip(i) {
+                    match b {
+                        b'{' => depth += 1,
+                        b'}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                return j;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                return masked.len().saturating_sub(1);
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    masked.len().saturating_sub(1)
+}
+
+struct Head {
+    kind: SymbolKind,
+    name: String,
+    /// Explicit container name (Go receivers)
+    parent: Option<String>,
+}
+
+fn rust_head(line: &str) -> Option<Head> {
+    static ITEM_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+        r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:default|async|const|unsafe|extern(?:\s+"[^"]*")?)\s+)*(fn|struct|enum|trait|union|type|mod|const|static|macro_rules!)\s*([A-Za-z_][A-Za-z0-9_]*)"#,
+    ).unwrap()
+    });
+    if let Some(c) = ITEM_RE.captures(line) {
+        let kind = match &c[1] {
+            "fn" => SymbolKind::Function,
+            "struct" | "union" => SymbolKind::Struct,
+            "enum" => SymbolKind::Enum,
+            "trait" => SymbolKind::Trait,
+            "type" => SymbolKind::Type,
+            "mod" => SymbolKind::Module,
+            "macro_rules!" => SymbolKind::Macro,
+            _ => SymbolKind::Const,
+        };
+        return Some(Head {
+            kind,
+            name: c[2].to_string(),
+            parent: None,
+        });
+    }
+
+    let trimmed = line.trim_start();
+    let rest = trimmed
+        .strip_prefix("unsafe ")
+        .unwrap_or(trimmed)
+        .strip_prefix("impl")?;
+    if !(rest.starts_with('<') || rest.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    // Skip generic parameters, then take the implementing type
+    let mut depth = 0;
+    let mut body_start = 0;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ if depth == 0 => {
+                body_start = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let header = rest[body_start..]
+        .split(" where")
+        .next()
+        .unwrap_or("")
+        .trim_end_matches('{')
+        .trim();
+    let target = header.rsplit(" for ").next().unwrap_or(header).trim();
+    let name: String = target
+        .trim_start_matches('&')
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == ':')
+        .collect();
+    let name = name.rsplit("::").next().unwrap_or(&name).to_string();
+    (!name.is_empty()).then_some(Head {
+        kind: SymbolKind::Impl,
+        name,
+        parent: None,
+    })
+}
+
+fn typescript_head(line: &str, in_class: bool) -> Option<Head> {
+    static DECL_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+        r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(class|interface|enum|type|function\*?|namespace)\s+([A-Za-z_$][\w$]*)",
+    ).unwrap()
+    });
+    static ARROW_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+        r"^\s*(?:export\s+)?(?:const|let|var)\s+([A-Za-z_$][\w$]*)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function\b|\([^)]*\)\s*(?::[^=]+)?=>|[A-Za-z_$][\w$]*\s*=>)",
+    ).unwrap()
+    });
+    static METHOD_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+        r"^\s*(?:(?:public|private|protected|static|async|readonly|abstract|override|get|set)\s+)*\*?([A-Za-z_$#][\w$]*)\s*(?:<[^>]*>)?\s*\(",
+    ).unwrap()
+    });
+
+    if let Some(c) = DECL_RE.captures(line) {
+        let kind = match c[1].trim_end_matches('*') {
+            "class" => SymbolKind::Class,
+            "interface" => SymbolKind::Interface,
+            "enum" => SymbolKind::Enum,
+            "type" => SymbolKind::Type,
+            "namespace" => SymbolKind::Module,
+            _ => SymbolKind::Function,
+        };
+        return Some(Head {
+            kind,
+            name: c[2].to_string(),
+            parent: None,
+        });
+    }
+    if let Some(c) = ARROW_RE.captures(line) {
+        return Some(Head {
+            kind: SymbolKind::Function,
+            name: c[1].to_string(),
+            parent: None,
+        });
+    }
+    if in_class {
+        let keywords = [
+            "if", "for", "while", "switch", "return", "catch", "function", "new", "await",
+        ];
+        if let Some(c) = METHOD_RE.captures(line) {
+            if !keywords.contains(&&c[1]) && !line.trim_end().ends_with(';') {
+                return Some(Head {
+                    kind: SymbolKind::Method,
+                    name: c[1].to_string(),
+                    parent: None,
+                });
+            }
+        }
+    }
+    None
+}
+
+fn go_head(line: &str) -> Option<Head> {
+    static FUNC_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"^func\s+(?:\(\s*(?:[A-Za-z_]\w*\s+)?\*?\s*([A-Za-z_]\w*)[^)]*\)\s*)?([A-Za-z_]\w*)",
+        )
+        .unwrap()
+    });
+    static TYPE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^type\s+([A-Za-z_]\w*)(?:\[[^\]]*\])?\s+(struct|interface)?").unwrap()
+    });
+    if let Some(c) = FUNC_RE.captures(line) {
+        let receiver = c.get(1).map(|m| m.as_str().to_string());
+        return Some(Head {
+            kind: if receiver.is_some() {
+                SymbolKind::Method
+            } else {
+                SymbolKind::Function
+            },
+            name: c[2].to_string(),
+            parent: receiver,
+        });
+    }
+    if let Some(c) = TYPE_RE.captures(line) {
+        let kind = match c.get(2).map(|m| m.as_str()) {
+            Some("struct") => SymbolKind::Struct,
+            Some("interface") => SymbolKind::Interface,
+            _ => SymbolKind::Type,
+        };
+        return Some(Head {
+            kind,
+            name: c[1].to_string(),
+            parent: None,
+        });
+    }
+    None
+}
+
+/// Outline brace-delimited languages (Rust, TypeScript, Go)
+fn outline_braced(source: &str, masked: &[u8], language: Language) -> Vec<Symbol> {
+    let starts = line_starts(masked);
+    let masked_text = String::from_utf8_lossy(masked);
+    let source_lines: Vec<&str> = source.lines().collect();
+    // Open containers: (qualified name, kind, end offset)
+    let mut containers: Vec<(String, SymbolKind, usize)> = Vec::new();
+    let mut symbols = Vec::new();
+
+    for (index, line) in masked_text.split('\n').enumerate() {
+        let offset = starts[index];
+        containers.retain(|(_, _, end)| *end > offset);
+        let in_class = containers
+            .last()
+            .is_some_and(|(_, kind, _)| matches!(kind, SymbolKind::Class | SymbolKind::Interface));
+
+        let head = match language {
+            Language::Rust => rust_head(line),
+            Language::TypeScript => typescript_head(line, in_class),
+            Language::Go => go_head(line),
+            Language::Python => None,
+        };
+        let Some(mut head) = head else {
+            continue;
+        };
+
+        let end = block_end(masked, offset);
+        let parent = head
+            .parent
+            .clone()
+            .or_else(|| containers.last().map(|(name, _, _)| name.clone()));
+        if head.kind == SymbolKind::Function
+            && containers
+                .last()
+                .is_some_and(|(_, kind, _)| matches!(kind, SymbolKind::Impl | SymbolKind::Trait))
+        {
+            head.kind = SymbolKind::Method;
+        }
+        let qualified_name = match &parent {
+            Some(parent) if head.kind != SymbolKind::Impl => format!("{}::{}", parent, head.name),
+            _ => head.name.clone(),
+        };
+
+        symbols.push(Symbol {
+            name: head.name.clone(),
+            qualified_name: qualified_name.clone(),
+            kind: head.kind,
+            start_line: index + 1,
+            end_line: line_of(&starts, end).max(index + 1),
+            signature: source_lines.get(index).map_or("", |l| l.trim()).to_string(),
+            depth: containers.len(),
+        });
+        if head.kind.is_container() {
+            containers.push((qualified_name, head.kind, end));
+        }
+    }
+    symbols
+}
+
+/// Outline Python using indentation
+fn outline_python(source: &str, masked: &[u8]) -> Vec<Symbol> {
+    static DEF_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\s*)(?:async\s+)?(def|class)\s+([A-Za-z_]\w*)").unwrap());
+    let masked_text = String::from_utf8_lossy(masked);
+    let lines: Vec<&str> = masked_text.split('\n').collect();
+    let source_lines: Vec<&str> = source.lines().collect();
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+
+    // Lines that start inside a triple-quoted string belong to the enclosing
+    // block whatever their indentation. Only delimiters survive masking, so
+    // the remaining quotes pair up as open/close.
+    let starts = line_starts(masked);
+    let delimiters: Vec<usize> = (0..masked.len().saturating_sub(2))
+        .filter(|&i| matches!(&masked[i..i + 3], b"\"\"\"" | b"'''"))
+        .collect();
+    let in_string = |line: usize| {
+        delimiters
+            .chunks(2)
+            .any(|pair| pair[0] < starts[line] && pair.get(1).is_none_or(|&e| starts[line] <= e))
+    };
+    // Open classes: (qualified name, indent)
+    let mut classes: Vec<(String, usize)> = Vec::new();
+    let mut symbols = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let Some(c) = DEF_RE.captures(line) else {
+            continue;
+        };
+        let indent = c[1].len();
+        classes.retain(|(_, class_indent)| *class_indent < indent);
+
+        let mut end = index;
+        for (j, next) in lines.iter().enumerate().skip(index + 1) {
+            if next.trim().is_empty() {
+                continue;
+            }
+            if !in_string(j) && indent_of(next) <= indent {
+                break;
+            }
+            end = j;
+        }
+
+        let name = c[3].to_string();
+        let is_class = &c[2] == "class";
+        let kind = match (is_class, classes.is_empty()) {
+            (true, _) => SymbolKind::Class,
+            (false, false) => SymbolKind::Method,
+            (false, true) => SymbolKind::Function,
+        };
+        let qualified_name = match classes.last() {
+            Some((parent, _)) => format!("{}.{}", parent, name),
+            None => name.clone(),
+        };
+        symbols.push(Symbol {
+            name,
+            qualified_name: qualified_name.clone(),
+            kind,
+            start_line: index + 1,
+            end_line: end + 1,
+            signature: source_lines.get(index).map_or("", |l| l.trim()).to_string(),
+            depth: classes.len(),
+        });
+        if is_class {
+            classes.push((qualified_name, indent));
+        }
+    }
+    symbols
+}
+
+/// Outline source text written in `language`
+pub fn outline_source(source: &str, language: Language) -> Vec<Symbol> {
+    let masked = mask_source(source, language);
+    match language {
+        Language::Python => outline_python(source, &masked),
+        _ => outline_braced(source, &masked, language),
+    }
+}
+
+/// Outline a file, or `None` when its language is not supported
+pub fn outline_file(path: &Path) -> Option<Vec<Symbol>> {
+    let language = Language::from_path(path)?;
+    let source = std::fs::read_to_string(path).ok()?;
+    Some(outline_source(&source, language))
+}
+
+/// Compact one-line-per-symbol rendering used in prompts
+pub fn format_outline(symbols: &[Symbol]) -> String {
+    symbols
+        .iter()
+        .map(|s| {
+            format!(
+                "{}{:?} {} (lines {}-{})",
+                "  ".repeat(s.depth),
+                s.kind,
+                s.name,
+                s.start_line,
+                s.end_line
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn error_result(message: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        data: None,
+        error: Some(message),
+    }
+}
+
+/// `outline` tool: the definitions of one file
+pub fn outline(path: &Path) -> ToolResult {
+    if Language::from_path(path).is_none() {
+        return error_result(format!(
+            "Unsupported language for '{}': outline supports .rs, .py, .ts/.js and .go files",
+            path.display()
+        ));
+    }
+    match outline_file(path) {
+        Some(symbols) => ToolResult {
+            success: true,
+            data: Some(json!({
+                "path": path.display().to_string(),
+                "symbols": symbols,
+                "count": symbols.len(),
+            })),
+            error: None,
+        },
+        None => error_result(format!("Failed to read '{}'", path.display())),
+    }
+}
+
+fn matches_query(symbol: &Symbol, query: &str) -> bool {
+    if query.contains("::") || query.contains('.') {
+        let normalized = query.replace('.', "::");
+        let qualified = symbol.qualified_name.replace('.', "::");
+        qualified == normalized || qualified.ends_with(&format!("::{}", normalized))
+    } else {
+        symbol.name == query
+    }
+}
+
+/// `find_symbol` tool: definitions named `query` (`name` or `Type::name`)
+/// under `root`, optionally with their source. Ignored files are skipped
+/// when `respect_ignore` is set.
+pub fn find_symbol(
+    root: &Path,
+    query: &str,
+    kind: Option<&str>,
+    include_body: bool,
+    respect_ignore: bool,
+) -> ToolResult {
+    let query = query.trim();
+    if query.is_empty() {
+        return error_result("Symbol name cannot be empty".to_string());
+    }
+
+    let files = if root.is_file() {
+        vec![root.to_path_buf()]
+    } else {
+        walk::walk_files(root, respect_ignore)
+    };
+
+    let mut matches = Vec::new();
+    let mut total = 0;
+    for file in files {
+        let Some(language) = Language::from_path(&file) else {
+            continue;
+        };
+        let Ok(source) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let lines: Vec<&str> = source.lines().collect();
+        for symbol in outline_source(&source, language) {
+            let kind_matches = kind.is_none_or(|k| {
+                serde_json::to_value(symbol.kind)
+                    .ok()
+                    .and_then(|v| v.as_str().map(|s| s == k))
+                    == Some(true)
+            });
+            if !kind_matches || !matches_query(&symbol, query) {
+                continue;
+            }
+            total += 1;
+            if matches.len() >= MAX_MATCHES {
+                continue;
+            }
+
+            let path = file
+                .strip_prefix(root)
+                .unwrap_or(&file)
+                .display()
+                .to_string();
+            let mut entry = json!({ "path": path, "symbol": symbol });
+            if include_body {
+                let end = symbol.end_line.min(lines.len());
+                let shown_end = end.min(symbol.start_line - 1 + MAX_BODY_LINES);
+                entry["body"] = json!(lines[symbol.start_line - 1..shown_end].join("\n"));
+                entry["body_truncated"] = json!(shown_end < end);
+            }
+            matches.push(entry);
+        }
+    }
+
+    if total == 0 {
+        return error_result(format!("No definition of '{}' found", query));
+    }
+    ToolResult {
+        success: true,
+        data: Some(json!({
+            "query": query,
+            "total_matches": total,
+            "matches": matches,
+        })),
+        error: None,
+    }
+}
+
+/// Generate OpenRouter-compatible definitions for the symbol tools
+pub fn get_symbol_tools() -> Vec<FileSystemTool> {
+    vec![
+        FileSystemTool {
+            tool_type: "function".to_string(),
+            function: ToolFunction {
+                name: "outline".to_string(),
+                description: "List the functions, structs, classes, traits, impls and other definitions of a source file (Rust, Python, TypeScript/JavaScript, Go) with line spans.".to_string(),
+                parameters: ToolParameters {
+                    param_type: "object".to_string(),
+                    properties: json!({
+                        "path": {
+                            "type": "string",
+                            "description": "Source file to outline."
+                        }
+                    }),
+                    required: vec!["path".to_string()],
+                },
+            },
+        },
+        FileSystemTool {
+            tool_type: "function".to_string(),
+            function: ToolFunction {
+                name: "find_symbol".to_string(),
+                description: "Find where a symbol is defined and return its source, e.g. 'TaskExecutor::dispatch_tool' or 'parse_config'. Much cheaper than reading whole files.".to_string(),
+                parameters: ToolParameters {
+                    param_type: "object".to_string(),
+                    properties: json!({
+                        "name": {
+                            "type": "string",
+                            "description": "Symbol name, optionally qualified with its type or class (Type::method)."
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "File or directory to search (default: working directory)."
+                        },
+                        "kind": {
+                            "type": "string",
+                            "description": "Optional kind filter: function, method, struct, enum, trait, impl, class, interface, type, module, const, macro."
+                        },
+                        "no_ignore": {
+                            "type": "boolean",
+                            "description": "If true, also search files excluded by .gitignore, .ignore and .kaiignore.",
+                            "default": false
+                        },
+                        "include_body": {
+                            "type": "boolean",
+                            "description": "Include the definition's source code.",
+                            "default": true
+                        }
+                    }),
+                    required: vec!["name".to_string()],
+                },
+            },
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_outline_rust() {
+        let source = r#"
+/// Executor { with braces in docs }
+pub struct TaskExecutor {
+    verbose: bool,
+}
+
+impl<T: Clone> Default for Wrapper<T> {
+    fn default() -> Self { todo!() }
+}
+
+impl TaskExecutor {
+    pub async fn dispatch_tool(&self, call: &str) -> String {
+        let s = "}";
+        let c = '{';
+        format!("{}{}", s, c)
+    }
+
+    fn helper<'a>(x: &'a str) -> &'a str { x }
+}
+
+pub const LIMIT: [u8; 2] = [1, 2];
+"#;
+        let symbols = outline_source(source, Language::Rust);
+        let find = |q: &str| symbols.iter().find(|s| s.qualified_name == q).unwrap();
+
+        assert_eq!(find("TaskExecutor").kind, SymbolKind::Struct);
+        assert_eq!(find("Wrapper::default").kind, SymbolKind::Method);
+        let dispatch = find("TaskExecutor::dispatch_tool");
+        assert_eq!((dispatch.start_line, dispatch.end_line), (12, 16));
+        assert_eq!(dispatch.depth, 1);
+        let helper = find("TaskExecutor::helper");
+        assert_eq!((helper.start_line, helper.end_line), (18, 18));
+        assert_eq!(find("LIMIT").end_line, 21);
+    }
+
+    #[test]
+    fn test_outline_python_typescript_go() {
+        let python = "class Server:\n    def start(self):\n        return '''\nnot: a def\n'''\n\n    def stop(self):\n        pass\n\ndef main():\n    Server().start()\n";
+        let symbols = outline_source(python, Language::Python);
+        let names: Vec<(&str, usize, usize)> = symbols
+            .iter()
+            .map(|s| (s.qualified_name.as_str(), s.start_line, s.end_line))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Server", 1, 8),
+                ("Server.start", 2, 5),
+                ("Server.stop", 7, 8),
+                ("main", 10, 11)
+            ]
+        );
+
+        let ts = "export class Api {\n  private url = `${base}/}`;\n  async fetch(id: string): Promise<Item> {\n    return get(id);\n  }\n}\nexport const handler = async (req) => {\n  return 1;\n};\n";
+        let symbols = outline_source(ts, Language::TypeScript);
+        assert_eq!(symbols[0].qualified_name, "Api");
+        assert_eq!(symbols[0].end_line, 6);
+        assert_eq!(symbols[1].qualified_name, "Api::fetch");
+        assert_eq!(symbols[1].kind, SymbolKind::Method);
+        assert_eq!(
+            (symbols[2].name.as_str(), symbols[2].end_line),
+            ("handler", 9)
+        );
+
+        let go = "type Server struct {\n\tport int\n}\n\nfunc (s *Server) Start() error {\n\treturn nil\n}\n";
+        let symbols = outline_source(go, Language::Go);
+        assert_eq!(symbols[0].kind, SymbolKind::Struct);
+        assert_eq!(symbols[1].qualified_name, "Server::Start");
+        assert_eq!((symbols[1].start_line, symbols[1].end_line), (5, 7));
+    }
+
+    #[test]
+    fn test_find_symbol_returns_body() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "struct A;\n\nimpl A {\n    fn run(&self) -> u8 {\n        1\n    }\n}\n\nfn run() {}\n",
+        )
+        .unwrap();
+
+        let result = find_symbol(dir.path(), "A::run", None, true, true);
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["total_matches"], 1);
+        assert_eq!(data["matches"][0]["path"], "src/lib.rs");
+        assert_eq!(
+            data["matches"][0]["body"],
+            "    fn run(&self) -> u8 {\n        1\n    }"
+        );
+
+        let all = find_symbol(dir.path(), "run", Some("function"), false, true)
+            .data
+            .unwrap();
+        assert_eq!(all["total_matches"], 1);
+        assert!(!find_symbol(dir.path(), "missing", None, false, true).success);
+    }
+}
@@ -0,0 +1,524 @@
+//! Test runner with per-test outcomes
+//!
+//! Detects the project's test framework, runs all tests or a filtered subset
+//! and reports each test's outcome, duration and failure message instead of a
+//! blob of stdout. Supported: cargo test, pytest, jest and go test.
+
+use crate::tools::exec;
+use crate::tools::file_system::{FileSystemTool, ToolFunction, ToolParameters, ToolResult};
+use crate::tools::junit::{self, TestOutcome};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Passing tests listed individually before only failures are kept
+const MAX_LISTED_TESTS: usize = 500;
+
+/// Result of one test
+#[derive(Debug, Clone, Serialize)]
+pub struct TestCaseResult {
+    pub name: String,
+    pub file: Option<String>,
+    pub outcome: TestOutcome,
+    /// Not reported by stable `cargo test`
+    pub duration_ms: Option<f64>,
+    pub message: Option<String>,
+}
+
+/// Captured output of a test command
+#[derive(Debug, Clone)]
+pub struct TestCommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+}
+
+/// A test framework the runner knows how to drive
+pub trait TestFramework: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn detect(&self, project: &Path) -> bool;
+    /// Program and arguments; `report` is a scratch file path for report output
+    fn command(&self, filter: Option<&str>, report: &Path) -> Vec<String>;
+    fn parse(&self, output: &TestCommandOutput, report: &Path) -> Vec<TestCaseResult>;
+}
+
+fn args(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|s| s.to_string()).collect()
+}
+
+/// `cargo test`, parsed from the libtest text output
+pub struct CargoTest;
+
+impl CargoTest {
+    fn parse_text(stdout: &str) -> Vec<TestCaseResult> {
+        let result_re = Regex::new(r"^test (\S+) \.\.\. (ok|FAILED|ignored)").unwrap();
+        let section_re = Regex::new(r"^---- (\S+) stdout ----$").unwrap();
+
+        // Failure output sections: "---- name stdout ----" up to the next section
+        let mut failure_output: HashMap<String, String> = HashMap::new();
+        let mut current: Option<(String, Vec<&str>)> = None;
+        for line in stdout.lines() {
+            if let Some(c) = section_re.captures(line) {
+                if let Some((name, lines)) = current.take() {
+                    failure_output.insert(name, lines.join("\n").trim().to_string());
+                }
+                current = Some((c[1].to_string(), Vec::new()));
+            } else if line == "failures:" || line.starts_with("test result:") {
+                if let Some((name, lines)) = current.take() {
+                    failure_output.insert(name, lines.join("\n").trim().to_string());
+                }
+            } else if let Some((_, lines)) = current.as_mut() {
+                lines.push(line);
+            }
+        }
+
+        stdout
+            .lines()
+            .filter_map(|line| result_re.captures(line))
+            .map(|c| {
+                let name = c[1].to_string();
+                let outcome = match &c[2] {
+                    "ok" => TestOutcome::Passed,
+                    "ignored" => TestOutcome::Skipped,
+                    _ => TestOutcome::Failed,
+                };
+                TestCaseResult {
+                    message: (outcome == TestOutcome::Failed)
+                        .then(|| failure_output.get(&name).cloned())
+                        .flatten(),
+                    name,
+                    file: None,
+                    outcome,
+                    duration_ms: None,
+                }
+            })
+            .collect()
+    }
+}
+
+impl TestFramework for CargoTest {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn detect(&self, project: &Path) -> bool {
+        project.join("Cargo.toml").exists()
+    }
+
+    fn command(&self, filter: Option<&str>, _report: &Path) -> Vec<String> {
+        let mut command = args(&["cargo", "test"]);
+        command.extend(filter.map(|f| f.to_string()));
+        command
+    }
+
+    fn parse(&self, output: &TestCommandOutput, _report: &Path) -> Vec<TestCaseResult> {
+        Self::parse_text(&output.stdout)
+    }
+}
+
+/// `pytest` with a JUnit XML report
+pub struct Pytest;
+
+impl TestFramework for Pytest {
+    fn name(&self) -> &'static str {
+        "pytest"
+    }
+
+    fn detect(&self, project: &Path) -> bool {
+        [
+            "pytest.ini",
+            "conftest.py",
+            "setup.cfg",
+            "tox.ini",
+            "pyproject.toml",
+        ]
+        .iter()
+        .any(|f| project.join(f).exists())
+            || glob::glob(&format!("{}/**/test_*.py", project.display()))
+                .map(|mut paths| paths.next().is_some())
+                .unwrap_or(false)
+    }
+
+    fn command(&self, filter: Option<&str>, report: &Path) -> Vec<String> {
+        let mut command = args(&["python3", "-m", "pytest", "-q", "-o", "junit_family=xunit1"]);
+        command.push(format!("--junitxml={}", report.display()));
+        if let Some(filter) = filter {
+            command.extend(args(&["-k", filter]));
+        }
+        command
+    }
+
+    fn parse(&self, _output: &TestCommandOutput, report: &Path) -> Vec<TestCaseResult> {
+        let xml = std::fs::read_to_string(report).unwrap_or_default();
+        junit::parse_junit(&xml)
+            .into_iter()
+            .map(|case| TestCaseResult {
+                name: if case.classname.is_empty() {
+                    case.name
+                } else {
+                    format!("{}::{}", case.classname, case.name)
+                },
+                file: case.file,
+                outcome: case.outcome,
+                duration_ms: case.duration_secs.map(|s| s * 1000.0),
+                message: case.details.or(case.message),
+            })
+            .collect()
+    }
+}
+
+/// `jest --json`
+pub struct Jest;
+
+impl Jest {
+    fn parse_report(report: &serde_json::Value) -> Vec<TestCaseResult> {
+        let mut results = Vec::new();
+        for suite in report["testResults"].as_array().into_iter().flatten() {
+            let file = suite["name"].as_str().map(|s| s.to_string());
+            for test in suite["assertionResults"].as_array().into_iter().flatten() {
+                let outcome = match test["status"].as_str() {
+                    Some("passed") => TestOutcome::Passed,
+                    Some("failed") => TestOutcome::Failed,
+                    _ => TestOutcome::Skipped,
+                };
+                let failures: Vec<&str> = test["failureMessages"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|m| m.as_str())
+                    .collect();
+                results.push(TestCaseResult {
+                    name: test["fullName"]
+                        .as_str()
+                        .or(test["title"].as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    file: file.clone(),
+                    outcome,
+                    duration_ms: test["duration"].as_f64(),
+                    message: (!failures.is_empty()).then(|| failures.join("\n")),
+                });
+            }
+        }
+        results
+    }
+}
+
+impl TestFramework for Jest {
+    fn name(&self) -> &'static str {
+        "jest"
+    }
+
+    fn detect(&self, project: &Path) -> bool {
+        std::fs::read_to_string(project.join("package.json"))
+            .map(|package| package.contains("\"jest\""))
+            .unwrap_or(false)
+    }
+
+    fn command(&self, filter: Option<&str>, report: &Path) -> Vec<String> {
+        let mut command = args(&["npx", "--no-install", "jest", "--json"]);
+        command.push(format!("--outputFile={}", report.display()));
+        if let Some(filter) = filter {
+            command.extend(args(&["-t", filter]));
+        }
+        command
+    }
+
+    fn parse(&self, _output: &TestCommandOutput, report: &Path) -> Vec<TestCaseResult> {
+        std::fs::read_to_string(report)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .map(|report| Self::parse_report(&report))
+            .unwrap_or_default()
+    }
+}
+
+/// `go test -json`
+pub struct GoTest;
+
+impl GoTest {
+    fn parse_events(stdout: &str) -> Vec<TestCaseResult> {
+        let mut output: HashMap<String, String> = HashMap::new();
+        let mut results = Vec::new();
+        for line in stdout.lines() {
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            let Some(test) = event["Test"].as_str() else {
+                continue;
+            };
+            let name = format!("{}/{}", event["Package"].as_str().unwrap_or_default(), test);
+            let outcome = match event["Action"].as_str() {
+                Some("output") => {
+                    output
+                        .entry(name)
+                        .or_default()
+                        .push_str(event["Output"].as_str().unwrap_or_default());
+                    continue;
+                }
+                Some("pass") => TestOutcome::Passed,
+                Some("fail") => TestOutcome::Failed,
+                Some("skip") => TestOutcome::Skipped,
+                _ => continue,
+            };
+            results.push(TestCaseResult {
+                message: (outcome != TestOutcome::Passed)
+                    .then(|| output.get(&name).map(|o| o.trim().to_string()))
+                    .flatten(),
+                name,
+                file: None,
+                outcome,
+                duration_ms: event["Elapsed"].as_f64().map(|s| s * 1000.0),
+            });
+        }
+        results
+    }
+}
+
+impl TestFramework for GoTest {
+    fn name(&self) -> &'static str {
+        "go"
+    }
+
+    fn detect(&self, project: &Path) -> bool {
+        project.join("go.mod").exists()
+    }
+
+    fn command(&self, filter: Option<&str>, _report: &Path) -> Vec<String> {
+        let mut command = args(&["go", "test", "-json"]);
+        if let Some(filter) = filter {
+            command.extend(args(&["-run", filter]));
+        }
+        command.push("./...".to_string());
+        command
+    }
+
+    fn parse(&self, output: &TestCommandOutput, _report: &Path) -> Vec<TestCaseResult> {
+        Self::parse_events(&output.stdout)
+    }
+}
+
+/// Built-in frameworks in detection order
+pub fn frameworks() -> Vec<Box<dyn TestFramework>> {
+    vec![
+        Box::new(CargoTest),
+        Box::new(GoTest),
+        Box::new(Jest),
+        Box::new(Pytest),
+    ]
+}
+
+fn error_result(message: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        data: None,
+        error: Some(message),
+    }
+}
+
+fn report_path(framework: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "kai-tests-{}-{}-{}",
+        framework,
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ))
+}
+
+/// Run the tests of `project`, optionally filtered, with the named framework
+/// (auto-detected when `None`). Dropping the future kills the test command.
+pub async fn run_tests(
+    project: &Path,
+    framework: Option<&str>,
+    filter: Option<&str>,
+) -> ToolResult {
+    let all = frameworks();
+    let selected = match framework {
+        Some(name) => all.iter().find(|f| f.name() == name),
+        None => all.iter().find(|f| f.detect(project)),
+    };
+    let Some(framework) = selected else {
+        return error_result(match framework {
+            Some(name) => format!(
+                "Unknown test framework '{}'. Available: {}",
+                name,
+                all.iter().map(|f| f.name()).collect::<Vec<_>>().join(", ")
+            ),
+            None => format!(
+                "No supported test framework detected in {}",
+                project.display()
+            ),
+        });
+    };
+
+    let report = report_path(framework.name());
+    let command = framework.command(filter, &report);
+    let started = Instant::now();
+    let output = match exec::run_command(&command, project).await {
+        Ok(output) => TestCommandOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            exit_code: output.status.code(),
+        },
+        Err(e) => return error_result(format!("Failed to run '{}': {}", command.join(" "), e)),
+    };
+    let duration_secs = started.elapsed().as_secs_f64();
+    let mut tests = framework.parse(&output, &report);
+    let _ = std::fs::remove_file(&report);
+
+    let count = |outcome: TestOutcome| tests.iter().filter(|t| t.outcome == outcome).count();
+    let (passed, failed, skipped) = (
+        count(TestOutcome::Passed),
+        count(TestOutcome::Failed),
+        count(TestOutcome::Skipped),
+    );
+    let failed_tests: Vec<String> = tests
+        .iter()
+        .filter(|t| t.outcome == TestOutcome::Failed)
+        .map(|t| t.name.clone())
+        .collect();
+    let total = tests.len();
+    if total > MAX_LISTED_TESTS {
+        tests.retain(|t| t.outcome != TestOutcome::Passed);
+    }
+    let succeeded = output.exit_code == Some(0) && failed == 0;
+
+    // A failing command without parsed failures is a build or collection error
+    let stderr_tail = if !succeeded && failed == 0 {
+        let lines: Vec<&str> = output.stderr.lines().collect();
+        Some(lines[lines.len().saturating_sub(30)..].join("\n"))
+    } else {
+        None
+    };
+
+    ToolResult {
+        success: succeeded,
+        data: Some(json!({
+            "framework": framework.name(),
+            "command": command.join(" "),
+            "exit_code": output.exit_code,
+            "total": total,
+            "passed": passed,
+            "failed": failed,
+            "skipped": skipped,
+            "duration_secs": duration_secs,
+            "failed_tests": failed_tests,
+            "tests": tests,
+            "stderr_tail": stderr_tail,
+        })),
+        error: if succeeded {
+            None
+        } else if failed > 0 {
+            Some(format!("{} of {} tests failed", failed, total))
+        } else {
+            Some(format!(
+                "{} exited with code {:?}",
+                framework.name(),
+                output.exit_code
+            ))
+        },
+    }
+}
+
+/// Generate the OpenRouter-compatible definition for the test runner tool
+pub fn get_test_runner_tool() -> FileSystemTool {
+    FileSystemTool {
+        tool_type: "function".to_string(),
+        function: ToolFunction {
+            name: "run_tests".to_string(),
+            description: "Run the project's tests (cargo test, pytest, jest, go test) and return per-test pass/fail/skip with failure messages and durations.".to_string(),
+            parameters: ToolParameters {
+                param_type: "object".to_string(),
+                properties: json!({
+                    "path": {
+                        "type": "string",
+                        "description": "Project directory (default: working directory).",
+                        "default": "."
+                    },
+                    "framework": {
+                        "type": "string",
+                        "enum": ["cargo", "pytest", "jest", "go"],
+                        "description": "Test framework; detected from project files when omitted."
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Only run tests matching this name filter."
+                    }
+                }),
+                required: vec![],
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo_output() {
+        let stdout = "
+running 3 tests
+test math::tests::adds ... ok
+test math::tests::divides ... FAILED
+test math::tests::slow ... ignored
+
+failures:
+
+---- math::tests::divides stdout ----
+thread 'math::tests::divides' panicked at src/math.rs:12:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+
+failures:
+    math::tests::divides
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.00s
+";
+        let tests = CargoTest::parse_text(stdout);
+        assert_eq!(tests.len(), 3);
+        assert_eq!(tests[0].outcome, TestOutcome::Passed);
+        assert_eq!(tests[1].outcome, TestOutcome::Failed);
+        assert!(tests[1]
+            .message
+            .as_ref()
+            .unwrap()
+            .contains("panicked at src/math.rs:12:9"));
+        assert_eq!(tests[2].outcome, TestOutcome::Skipped);
+    }
+
+    #[test]
+    fn test_parse_go_and_jest_reports() {
+        let go = r#"{"Action":"run","Package":"example.com/m","Test":"TestAdd"}
+{"Action":"output","Package":"example.com/m","Test":"TestAdd","Output":"    add_test.go:9: got 3, want 4\n"}
+{"Action":"fail","Package":"example.com/m","Test":"TestAdd","Elapsed":0.01}
+{"Action":"pass","Package":"example.com/m","Test":"TestSub","Elapsed":0}
+{"Action":"fail","Package":"example.com/m","Elapsed":0.02}"#;
+        let tests = GoTest::parse_events(go);
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[0].name, "example.com/m/TestAdd");
+        assert_eq!(tests[0].outcome, TestOutcome::Failed);
+        assert_eq!(tests[0].duration_ms, Some(10.0));
+        assert!(tests[0].message.as_ref().unwrap().contains("want 4"));
+
+        let jest = json!({
+            "testResults": [{
+                "name": "/app/sum.test.js",
+                "assertionResults": [
+                    {"fullName": "sum adds", "status": "passed", "duration": 3, "failureMessages": []},
+                    {"fullName": "sum todo", "status": "pending", "failureMessages": []},
+                    {"fullName": "sum fails", "status": "failed", "duration": 5, "failureMessages": ["Expected 3"]}
+                ]
+            }]
+        });
+        let tests = Jest::parse_report(&jest);
+        assert_eq!(tests.len(), 3);
+        assert_eq!(tests[1].outcome, TestOutcome::Skipped);
+        assert_eq!(tests[2].message.as_deref(), Some("Expected 3"));
+        assert_eq!(tests[2].file.as_deref(), Some("/app/sum.test.js"));
+    }
+}
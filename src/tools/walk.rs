@@ -0,0 +1,180 @@
+//! Ignore-aware file walking
+//!
+//! Every tool that walks the tree (file search, grep, recursive listings,
+//! symbol search and the harvester) goes through here so `.gitignore`,
+//! `.ignore` and the project's `.kaiignore` are honored the same way
+//! everywhere. Passing `respect_ignore = false` restores a plain walk.
+//! KAI's own `.context/` directory (checkpoints, spill files, plan journals)
+//! is always skipped unless a path names it explicitly.
+
+use glob::{MatchOptions, Pattern};
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/// Project-specific ignore file, same syntax as `.gitignore`
+pub const KAI_IGNORE_FILE: &str = ".kaiignore";
+
+/// KAI's bookkeeping directory in the working directory
+const CONTEXT_DIR: &str = ".context";
+
+/// A walker rooted at `root`, sorted by file name, that never enters `.git`
+/// or `.context`
+pub fn walker(root: &Path, respect_ignore: bool) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(false)
+        .parents(respect_ignore)
+        .ignore(respect_ignore)
+        .git_ignore(respect_ignore)
+        .git_global(respect_ignore)
+        .git_exclude(respect_ignore)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(|entry| {
+            entry.depth() == 0 || (entry.file_name() != ".git" && entry.file_name() != CONTEXT_DIR)
+        });
+    if respect_ignore {
+        builder.add_custom_ignore_filename(KAI_IGNORE_FILE);
+    }
+    builder
+}
+
+/// Every file under `root` that is not ignored
+pub fn walk_files(root: &Path, respect_ignore: bool) -> Vec<PathBuf> {
+    walker(root, respect_ignore)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Expand a glob pattern (`src/**/*.rs`) like `glob::glob`, skipping ignored
+/// paths. Only the pattern's literal prefix is walked, so explicitly naming
+/// an ignored directory (`target/debug/*.d`) still works.
+pub fn glob_paths(pattern: &str, respect_ignore: bool) -> Result<Vec<PathBuf>, String> {
+    let matcher =
+        Pattern::new(pattern).map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e))?;
+    if !respect_ignore {
+        let names_context = Path::new(pattern)
+            .components()
+            .any(|c| c.as_os_str() == CONTEXT_DIR);
+        return glob::glob(pattern)
+            .map(|paths| {
+                paths
+                    .filter_map(|p| p.ok())
+                    .filter(|p| {
+                        names_context || !p.components().any(|c| c.as_os_str() == CONTEXT_DIR)
+                    })
+                    .collect()
+            })
+            .map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e));
+    }
+
+    let components: Vec<&str> = pattern.split('/').collect();
+    let Some(first_wild) = components.iter().position(|c| c.contains(['*', '?', '['])) else {
+        // No wildcards: the pattern names a single path
+        let path = PathBuf::from(pattern);
+        return Ok(if path.exists() {
+            vec![path]
+        } else {
+            Vec::new()
+        });
+    };
+
+    let base = components[..first_wild].join("/");
+    let (root, strip_dot) = match base.as_str() {
+        "" if pattern.starts_with('/') => (PathBuf::from("/"), false),
+        "" => (PathBuf::from("."), true),
+        _ => (PathBuf::from(&base), false),
+    };
+    let rest = &components[first_wild..];
+    let max_depth = if rest.iter().any(|c| c.contains("**")) {
+        None
+    } else {
+        Some(rest.len())
+    };
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    let mut builder = walker(&root, true);
+    builder.max_depth(max_depth);
+    Ok(builder
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.depth() > 0)
+        .map(|entry| {
+            let path = entry.into_path();
+            match path.strip_prefix(".") {
+                Ok(stripped) if strip_dot => stripped.to_path_buf(),
+                _ => path,
+            }
+        })
+        .filter(|path| matcher.matches_path_with(path, options))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_ignore_files_respected() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join("vendor")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join(".kaiignore"), "vendor/\n*.gen.rs\n").unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src/schema.gen.rs"), "").unwrap();
+        fs::write(root.join("target/debug/out.rs"), "").unwrap();
+        fs::write(root.join("vendor/dep.rs"), "").unwrap();
+        fs::write(root.join(".git/HEAD"), "").unwrap();
+
+        let pattern = format!("{}/**/*.rs", root.display());
+        let found = glob_paths(&pattern, true).unwrap();
+        assert_eq!(found, vec![root.join("src/main.rs")]);
+
+        let all = glob_paths(&pattern, false).unwrap();
+        assert_eq!(all.len(), 4);
+
+        // Naming an ignored directory explicitly still reaches its files
+        let explicit = glob_paths(&format!("{}/target/debug/*.rs", root.display()), true).unwrap();
+        assert_eq!(explicit, vec![root.join("target/debug/out.rs")]);
+
+        let files = walk_files(root, true);
+        assert!(files.contains(&root.join(".gitignore")));
+        assert!(!files.iter().any(|f| f.starts_with(root.join(".git"))));
+        assert!(!files.contains(&root.join("vendor/dep.rs")));
+    }
+
+    #[test]
+    fn test_context_dir_skipped() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".context/checkpoints/plan_a")).unwrap();
+        fs::write(root.join(".context/checkpoints/plan_a/0.snap"), "").unwrap();
+        fs::write(root.join("main.rs"), "").unwrap();
+
+        assert_eq!(walk_files(root, true), vec![root.join("main.rs")]);
+        assert_eq!(walk_files(root, false), vec![root.join("main.rs")]);
+        let pattern = format!("{}/**/*", root.display());
+        for respect_ignore in [true, false] {
+            let found = glob_paths(&pattern, respect_ignore).unwrap();
+            assert_eq!(found, vec![root.join("main.rs")]);
+        }
+
+        // Naming it explicitly still works
+        let snapshots = format!("{}/.context/checkpoints/*/*.snap", root.display());
+        assert_eq!(glob_paths(&snapshots, true).unwrap().len(), 1);
+        assert_eq!(glob_paths(&snapshots, false).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,66 @@
+#!/usr/bin/env python3
+"""Minimal stdio MCP server used by the MCP client tests."""
+import json
+import sys
+
+TOOLS = [
+    {
+        "name": "echo",
+        "description": "Echo the given text",
+        "inputSchema": {
+            "type": "object",
+            "properties": {"text": {"type": "string"}},
+            "required": ["text"],
+        },
+    },
+    {"name": "fail", "description": "Always fails"},
+]
+RESOURCES = [{"uri": "docs://readme", "name": "README", "mimeType": "text/markdown"}]
+
+
+def send(message):
+    sys.stdout.write(json.dumps(message) + "\n")
+    sys.stdout.flush()
+
+
+def result(request_id, value):
+    send({"jsonrpc": "2.0", "id": request_id, "result": value})
+
+
+for line in sys.stdin:
+    if not line.strip():
+        continue
+    request = json.loads(line)
+    method = request.get("method")
+    request_id = request.get("id")
+    params = request.get("params") or {}
+
+    if method == "initialize":
+        # Exercise the client's handling of interleaved notifications and requests
+        send({"jsonrpc": "2.0", "method": "notifications/message", "params": {"level": "info"}})
+        send({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"})
+        result(request_id, {
+            "protocolVersion": params.get("protocolVersion"),
+            "capabilities": {"tools": {}, "resources": {}},
+            "serverInfo": {"name": "fixture", "version": "1.0"},
+        })
+    elif method == "tools/list":
+        # Paginate to exercise cursor handling
+        if params.get("cursor"):
+            result(request_id, {"tools": TOOLS[1:]})
+        else:
+            result(request_id, {"tools": TOOLS[:1], "nextCursor": "page-2"})
+    elif method == "tools/call":
+        if params["name"] == "echo":
+            text = "echo: " + params["arguments"]["text"]
+            result(request_id, {"content": [{"type": "text", "text": text}]})
+        else:
+            result(request_id, {"content": [{"type": "text", "text": "boom"}], "isError": True})
+    elif method == "resources/list":
+        result(request_id, {"resources": RESOURCES})
+    elif method == "resources/read":
+        result(request_id, {"contents": [
+            {"uri": params["uri"], "mimeType": "text/markdown", "text": "# Fixture docs"}
+        ]})
+    elif request_id is not None and method is not None:
+        send({"jsonrpc": "2.0", "id": request_id, "error": {"code": -32601, "message": "Method not found"}})